@@ -0,0 +1,118 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use text_editor::{render, Document, Position, SearchDirection, Theme};
+
+/// The synthetic documents every benchmark runs on, by name and number of lines.
+const SIZES: [(&str, usize); 3] = [("small", 100), ("medium", 10_000), ("huge", 200_000)];
+
+/// Rows drawn in a frame, about the height of a terminal.
+const FRAME_HEIGHT: usize = 50;
+
+/// Rust-looking text with comments, strings and keywords, so highlighting has
+/// something to do on every line.
+fn source(lines: usize) -> String {
+    (0..lines)
+        .map(|line| match line % 4 {
+            0 => format!("fn function_{line}(value: usize) -> usize {{"),
+            1 => format!("    // Adds {line} to the value, unless it would overflow"),
+            2 => format!("    let text = \"line {line}\"; value.saturating_add({line})"),
+            _ => "}".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn document(text: &str) -> Document {
+    let mut document = Document::from_text(text);
+    document.set_file_name("bench.rs".to_string());
+    document
+}
+
+fn middle(lines: usize) -> Position {
+    Position { x: 4, y: lines / 2 }
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for (name, lines) in SIZES {
+        let text = source(lines);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || document(&text),
+                |document| document.insert(&middle(lines), 'x'),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn delete(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delete");
+    for (name, lines) in SIZES {
+        let text = source(lines);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || document(&text),
+                |document| document.delete(&middle(lines)),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn find(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find");
+    for (name, lines) in SIZES {
+        let document = document(&source(lines));
+        // The query only matches on the last line, so the whole document is searched
+        let query = format!("function_{}", lines.saturating_sub(4));
+        group.bench_function(name, |b| {
+            b.iter(|| document.find(&query, &Position::default(), SearchDirection::Forward));
+        });
+    }
+    group.finish();
+}
+
+fn highlight(c: &mut Criterion) {
+    let mut group = c.benchmark_group("highlight");
+    for (name, lines) in SIZES {
+        let text = source(lines);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || document(&text),
+                |document| document.highlight(&None, None),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Highlights and renders a screenful of rows from the middle of the document,
+/// the work of a frame without writing it to a terminal.
+fn render_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_frame");
+    let theme = Theme::default();
+    for (name, lines) in SIZES {
+        let text = source(lines);
+        let top = lines.saturating_sub(FRAME_HEIGHT) / 2;
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || document(&text),
+                |document| {
+                    document.highlight(&None, Some(top.saturating_add(FRAME_HEIGHT)));
+                    (top..top.saturating_add(FRAME_HEIGHT))
+                        .filter_map(|index| document.row(index))
+                        .map(|row| render::row(row, 0, 80, &(0..0), &theme, false))
+                        .collect::<Vec<_>>()
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, delete, find, highlight, render_frame);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+/// A color set by an SGR sequence.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// One of the 8 basic colors in SGR order, from black to white, and whether
+    /// it's the bright variant.
+    Basic(u8, bool),
+    /// A color of the 256-color palette.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// The look of text as set by ANSI SGR escape sequences, such as `ESC[1;31m`.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+}
+
+impl Style {
+    /// Applies the parameters of an SGR sequence, the part between `ESC[` and `m`.
+    fn apply(&mut self, parameters: &str) {
+        let mut codes = parameters
+            .split(';')
+            .map(|code| code.parse::<u8>().unwrap_or(0));
+
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.fg = Some(Color::Basic(code.saturating_sub(30), false)),
+                90..=97 => self.fg = Some(Color::Basic(code.saturating_sub(90), true)),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Basic(code.saturating_sub(40), false)),
+                100..=107 => self.bg = Some(Color::Basic(code.saturating_sub(100), true)),
+                49 => self.bg = None,
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(Color::Indexed),
+                        Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Works out the style of every grapheme of a row that contains ANSI escape
+/// sequences. The graphemes of the sequences themselves get `None`, since they're
+/// hidden rather than drawn. Styles start over on every row.
+#[must_use]
+pub fn styles(graphemes: &[&str]) -> Vec<Option<Style>> {
+    let mut styles = Vec::with_capacity(graphemes.len());
+    let mut style = Style::default();
+    let mut index = 0;
+
+    while let Some(grapheme) = graphemes.get(index) {
+        if *grapheme == "\x1b" && graphemes.get(index.saturating_add(1)) == Some(&"[") {
+            // A CSI sequence runs up to its final byte, a character from @ to ~
+            let start = index.saturating_add(2);
+            let end = graphemes
+                .iter()
+                .skip(start)
+                .position(|grapheme| {
+                    grapheme
+                        .chars()
+                        .next()
+                        .is_some_and(|c| ('@'..='~').contains(&c))
+                })
+                .map_or(graphemes.len(), |offset| start.saturating_add(offset));
+
+            if graphemes.get(end) == Some(&"m") {
+                style.apply(&graphemes.get(start..end).unwrap_or_default().concat());
+            }
+
+            let hidden = end.saturating_add(1).min(graphemes.len());
+            styles.resize(hidden, None);
+            index = hidden;
+        } else {
+            styles.push(Some(style));
+            index = index.saturating_add(1);
+        }
+    }
+
+    styles
+}
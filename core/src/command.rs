@@ -0,0 +1,305 @@
+use crate::Position;
+
+/// Cursor movements over structure rather than single characters or lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    /// The blank line before the current paragraph.
+    PreviousParagraph,
+    /// The blank line after the current paragraph.
+    NextParagraph,
+    /// The first line of the indentation block, or the line opening it when
+    /// already there.
+    BlockStart,
+    /// The last line of the indentation block, or the line after it when already
+    /// there.
+    BlockEnd,
+}
+
+/// The cursor movements of the arrow and navigation keys, and of scrolling by half
+/// a screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Movement {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    /// Scrolls the view and the cursor half a screen up.
+    HalfPageUp,
+    HalfPageDown,
+    Home,
+    End,
+}
+
+/// The project commands that can be run from the editor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Job {
+    Build,
+    Test,
+    Run,
+}
+
+impl Job {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Build => "build",
+            Self::Test => "test",
+            Self::Run => "run",
+        }
+    }
+}
+
+/// Which side of a merge conflict to keep.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs.
+    Both,
+}
+
+/// How far a program paused in the debugger runs before pausing again.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Step {
+    /// To the next line of the same function.
+    Over,
+    /// Into the function called on the line, if there is one.
+    Into,
+    /// Out of the current function, to the line that called it.
+    Out,
+}
+
+/// Everything the editor can be asked to do, independent of the key that asked for it.
+///
+/// The `Keymap` turns key presses into commands and `Editor::execute` carries them out,
+/// so anything able to produce commands can drive the editor.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EditorCommand {
+    InsertChar(char),
+    InsertNewline,
+    DeleteBackward,
+    DeleteForward,
+    ToggleOverwrite,
+    /// Moves the cursor like an arrow or navigation key, extending the selection
+    /// when `select` is set.
+    Move {
+        movement: Movement,
+        select: bool,
+    },
+    /// Moves the cursor by a `Motion`, extending the selection when `select` is set.
+    Jump {
+        motion: Motion,
+        select: bool,
+    },
+    Undo,
+    Redo,
+    /// Does the last edit again at the cursor, such as the text typed since the
+    /// cursor last moved.
+    RepeatEdit,
+    /// Starts recording the commands of the keys pressed next as a macro, or stops
+    /// when one is being recorded.
+    RecordMacro,
+    /// Carries out the commands of the last recorded macro, as a single undo step.
+    PlayMacro,
+    /// Carries out a command `count` times in a row, as a single undo step, like
+    /// after typing a count before a key.
+    Repeat {
+        command: Box<EditorCommand>,
+        count: usize,
+    },
+    /// Shows every state of the undo history, including undone branches, to pick one.
+    UndoTree,
+    SelectAll,
+    /// Asks for a key naming a text object and selects the inside of it.
+    SelectInside,
+    /// Asks for a key naming a text object and selects it with its delimiters.
+    SelectAround,
+    Copy,
+    Cut,
+    Paste,
+    PasteReindented,
+    Complete,
+    InsertCodepoint,
+    Search,
+    Replace,
+    /// Searches the project files, filling the quickfix list with the matches.
+    ProjectSearch,
+    ProjectReplace,
+    /// Runs one of the project commands, `build`, `test` or `run`, showing its output
+    /// in a pane.
+    RunJob(Job),
+    /// Kills the project commands that are running.
+    StopJobs,
+    /// Shows the changed files of the git repository, staging or unstaging them.
+    GitStatus,
+    /// Opens the commit message in a buffer that commits when saved.
+    GitCommit,
+    /// Replaces the merge conflict under the cursor with the side it names.
+    ResolveConflict(Resolution),
+    /// Opens the outline panel to jump to a symbol, or closes it.
+    Outline,
+    /// Shows the differences between the buffer and its file on disk.
+    CompareSaved,
+    /// Stages the change under the cursor, as git diff shows it against the index.
+    StageHunk,
+    /// Replaces the change under the cursor with its staged version.
+    RevertHunk,
+    /// Jumps to the next location found in the output of the last project command.
+    NextLocation,
+    PreviousLocation,
+    Surround,
+    ChangeSurround,
+    SortLines,
+    Uppercase,
+    Lowercase,
+    TitleCase,
+    ReflowParagraph,
+    ShowStatistics,
+    InspectCharacter,
+    NextBuffer,
+    PreviousBuffer,
+    Save,
+    Revert,
+    /// Starts or stops loading what gets appended to the file, like `tail -f`.
+    ToggleFollow,
+    /// Switches between showing ANSI escape sequences and the colors they set.
+    ToggleAnsiColors,
+    /// Shows or hides how long the last frame took and how big the document is.
+    ToggleDebugOverlay,
+    /// Runs the command a user script registered under this name.
+    RunScript(String),
+    /// Runs the command a WebAssembly plugin registered under this name.
+    RunPlugin(String),
+    Open,
+    /// Opens a file of the project picked by typing part of its path.
+    FindFile,
+    Rename,
+    /// Renames the symbol under the cursor wherever the language server finds it.
+    RenameSymbol,
+    /// Shows what the language server knows about the symbol under the cursor.
+    Hover,
+    /// Offers the language server's fixes and refactorings for the cursor to pick one.
+    CodeActions,
+    /// Formats only the selected lines, with the formatter or the language server.
+    FormatSelection,
+    /// Finds a definition in any file of the project by name and jumps to it.
+    WorkspaceSymbols,
+    /// Sets or clears the breakpoint on the cursor's line.
+    ToggleBreakpoint,
+    /// Starts debugging the program, or lets it run on when it's paused.
+    DebugContinue,
+    DebugStep(Step),
+    /// Ends the debug session, stopping the program.
+    DebugStop,
+    /// Sends the selection, or the cursor's line, to the REPL.
+    SendToRepl,
+    /// Checks or unchecks the markdown checkboxes of the selected lines.
+    ToggleCheckbox,
+    /// Lines up the pipes of the markdown table under the cursor.
+    FormatTable,
+    /// Opens the URL under the cursor with the system's opener.
+    OpenUrl,
+    /// Puts the cursor at a place in the document, such as where the mouse clicked,
+    /// extending the selection when `select` is set.
+    MoveTo {
+        position: Position,
+        select: bool,
+    },
+    /// Selects the word under the cursor.
+    SelectWord,
+    /// Selects the cursor's line, with its line break.
+    SelectLine,
+    /// Shows another buffer in a pane beside the active one, or closes that pane.
+    SplitPane,
+    /// Makes the buffer in the other pane the active one.
+    FocusPane,
+    SaveAll,
+    CloseAll,
+    SaveAllAndQuit,
+    ShowHelp,
+    ShowMessageLog,
+    ReloadConfig,
+    Quit,
+}
+
+impl EditorCommand {
+    /// Whether the command changes the document or its file, which a read-only
+    /// document refuses. Scripts and plugins aren't counted, as they may only read
+    /// the document; their functions that edit refuse a read-only one instead.
+    #[must_use]
+    pub fn edits(&self) -> bool {
+        if let Self::Repeat { command, .. } = self {
+            return command.edits();
+        }
+        matches!(
+            self,
+            Self::InsertChar(_)
+                | Self::InsertNewline
+                | Self::DeleteBackward
+                | Self::DeleteForward
+                | Self::Undo
+                | Self::Redo
+                | Self::UndoTree
+                | Self::RepeatEdit
+                | Self::Cut
+                | Self::Paste
+                | Self::PasteReindented
+                | Self::Complete
+                | Self::InsertCodepoint
+                | Self::Replace
+                | Self::Surround
+                | Self::ChangeSurround
+                | Self::SortLines
+                | Self::Uppercase
+                | Self::Lowercase
+                | Self::TitleCase
+                | Self::ReflowParagraph
+                | Self::Save
+                | Self::Rename
+                | Self::RenameSymbol
+                | Self::CodeActions
+                | Self::FormatSelection
+                | Self::ToggleCheckbox
+                | Self::FormatTable
+                | Self::RevertHunk
+                | Self::ResolveConflict(_)
+        )
+    }
+
+    /// Whether the command is an edit `RepeatEdit` can do again, without asking
+    /// anything, wherever the cursor is.
+    #[must_use]
+    pub fn is_repeatable(&self) -> bool {
+        if let Self::Repeat { command, .. } = self {
+            return command.is_repeatable();
+        }
+        matches!(
+            self,
+            Self::InsertChar(_)
+                | Self::InsertNewline
+                | Self::DeleteBackward
+                | Self::DeleteForward
+                | Self::Cut
+                | Self::Paste
+                | Self::PasteReindented
+                | Self::Uppercase
+                | Self::Lowercase
+                | Self::TitleCase
+                | Self::ReflowParagraph
+                | Self::ToggleCheckbox
+                | Self::FormatTable
+        )
+    }
+
+    /// Whether a count typed before the command repeats it, which only moving the
+    /// cursor, playing a macro and repeatable edits do.
+    #[must_use]
+    pub fn takes_count(&self) -> bool {
+        matches!(
+            self,
+            Self::Move { .. } | Self::Jump { .. } | Self::PlayMacro
+        ) || self.is_repeatable()
+    }
+}
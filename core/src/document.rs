@@ -0,0 +1,1477 @@
+use crate::filetype::FileType;
+use crate::highlighting;
+use crate::history::{Change, History};
+use crate::LineRange;
+use crate::Position;
+use crate::Row;
+use crate::SearchDirection;
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::time::SystemTime;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A piece of text around the cursor that can be selected as a whole.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObject {
+    Word,
+    /// Text between an opening and a closing bracket, which may span rows and nest.
+    Pair(char, char),
+    /// Text between two of the same quote on one row.
+    Quote(char),
+}
+
+impl TextObject {
+    /// The object a key names, as in vim: `w`, a bracket of either side (or `b` and
+    /// `B` for parentheses and braces), or a quote.
+    #[must_use]
+    pub fn from_key(c: char) -> Option<Self> {
+        let object = match c {
+            'w' => Self::Word,
+            '(' | ')' | 'b' => Self::Pair('(', ')'),
+            '[' | ']' => Self::Pair('[', ']'),
+            '{' | '}' | 'B' => Self::Pair('{', '}'),
+            '<' | '>' => Self::Pair('<', '>'),
+            '"' | '\'' | '`' => Self::Quote(c),
+            _ => return None,
+        };
+        Some(object)
+    }
+}
+
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Document {
+    rows: Vec<Row>,
+    pub file_name: Option<String>,
+    dirty: bool,
+    file_type: FileType,
+    history: History,
+    read_only: bool,
+    follow: Option<Follow>,
+    ansi: bool,
+    binary: bool,
+    revision: usize,
+    /// The modification time and length of the file when it was last read or
+    /// written, to tell when another program changed it.
+    disk_stamp: Option<(SystemTime, u64)>,
+}
+
+/// How much of a followed file has been loaded.
+struct Follow {
+    /// The length of the file up to the last complete line loaded.
+    offset: u64,
+    /// Whether the last row has no newline yet, so the next text continues it.
+    partial: bool,
+}
+
+/// What `Document::load_appended` found in a followed file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Appended {
+    /// This many rows were added at the end, which may be none.
+    Rows(usize),
+    /// The file shrank, so it was loaded again from its start.
+    Reloaded,
+}
+
+impl Document {
+    /// Opens a file. Files that look binary, because they contain NUL bytes or
+    /// aren't valid UTF-8, are decoded lossily and opened read-only, since saving
+    /// them back would corrupt them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of reading the file, such as when it doesn't exist.
+    pub fn open(filename: &str) -> Result<Self, std::io::Error> {
+        let bytes = fs::read(filename)?;
+        let binary = bytes.contains(&0) || std::str::from_utf8(&bytes).is_err();
+        let contents = String::from_utf8_lossy(&bytes);
+        let mut rows = Vec::new();
+
+        for value in contents.lines() {
+            rows.push(Row::from(value));
+        }
+
+        let file_type = Self::detect_file_type(filename, &rows);
+
+        Ok(Self {
+            rows,
+            file_name: Some(filename.to_string()),
+            dirty: false,
+            file_type,
+            history: History::default(),
+            read_only: binary,
+            follow: None,
+            ansi: false,
+            binary,
+            revision: 0,
+            disk_stamp: stamp(filename),
+        })
+    }
+
+    /// Creates an unnamed document holding `text`, such as a generated scratch buffer.
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            rows: text.lines().map(Row::from).collect(),
+            ..Self::default()
+        }
+    }
+
+    fn detect_file_type(file_name: &str, rows: &[Row]) -> FileType {
+        let file_type = FileType::from(file_name);
+        if file_type.name() != FileType::default().name() {
+            return file_type;
+        }
+
+        rows.first()
+            .and_then(|row| FileType::from_shebang(row.as_str()))
+            .unwrap_or(file_type)
+    }
+
+    #[must_use]
+    pub fn line_comment(&self) -> Option<&'static str> {
+        self.file_type.line_comment()
+    }
+
+    #[must_use]
+    pub fn file_type(&self) -> String {
+        self.file_type.name()
+    }
+
+    #[must_use]
+    pub fn row(&self, index: usize) -> Option<&Row> {
+        self.rows.get(index)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The size of the text in bytes, counting a newline after every row but the last.
+    #[must_use]
+    pub fn byte_len(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| row.as_bytes().len())
+            .sum::<usize>()
+            .saturating_add(self.rows.len().saturating_sub(1))
+    }
+
+    fn insert_newline(&mut self, at: &Position) {
+        if at.y > self.rows.len() {
+            return;
+        }
+
+        if at.y == self.rows.len() {
+            self.rows.push(Row::default());
+            return;
+        }
+
+        let Some(current_row) = self.rows.get_mut(at.y) else {
+            return;
+        };
+        let new_row = current_row.split(at.x);
+
+        #[allow(clippy::arithmetic_side_effects)]
+        self.rows.insert(at.y + 1, new_row);
+    }
+
+    pub fn insert(&mut self, at: &Position, c: char) {
+        if at.y > self.rows.len() {
+            return;
+        }
+
+        self.dirty = true;
+        if c != '\n' {
+            self.record_insert(at, &c.to_string());
+        } else if at.y < self.rows.len() {
+            self.record_insert(at, "\n");
+        } else if !self.rows.is_empty() {
+            // A newline past the last row only adds an empty row
+            self.record_insert(at, "");
+        }
+
+        if c == '\n' {
+            self.insert_newline(at);
+        } else if at.y == self.rows.len() {
+            let mut row = Row::default();
+            row.insert(0, c);
+            self.rows.push(row);
+        } else {
+            #[allow(clippy::indexing_slicing)]
+            let row = &mut self.rows[at.y];
+            row.insert(at.x, c);
+        }
+
+        let last = if c == '\n' {
+            at.y.saturating_add(1)
+        } else {
+            at.y
+        };
+        self.mark_modified(at.y..=last);
+        self.unhighlight_row(at.y);
+    }
+
+    /// Returns the text between two positions, with rows joined by `\n`.
+    #[must_use]
+    pub fn text(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+
+        for (y, row) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(start.y)
+            .take(end.y.saturating_sub(start.y).saturating_add(1))
+        {
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+
+            if y > start.y {
+                text.push('\n');
+            }
+            text.push_str(&row.substring(from, to));
+        }
+
+        text
+    }
+
+    /// Replaces the rows in `range` with the lines returned by `transform`, which
+    /// receives the current contents of those rows. Returns how many rows replaced them.
+    /// The rows are recorded as one block, so this is for edits that can change how
+    /// many there are, such as reflowing; edits that only change the text of each
+    /// row use `edit_lines`, which records just the rows that changed.
+    pub fn transform_rows<F>(&mut self, range: Range<usize>, transform: F) -> usize
+    where
+        F: FnOnce(Vec<String>) -> Vec<String>,
+    {
+        let end = range.end.min(self.rows.len());
+        let start = range.start.min(end);
+
+        #[allow(clippy::indexing_slicing)]
+        let lines: Vec<String> = self.rows[start..end]
+            .iter()
+            .map(|row| row.as_str().to_string())
+            .collect();
+        let new_lines = transform(lines.clone());
+        let new_rows: Vec<Row> = new_lines
+            .iter()
+            .map(|line| Row::from(line.as_str()))
+            .collect();
+        let count = new_rows.len();
+        if new_lines == lines {
+            return count;
+        }
+
+        // Whole rows are recorded along with the newline that separates them from
+        // the row after, or before when they reach the end of the document
+        let (at, old, new) = if end < self.rows.len() || start == 0 {
+            let suffix = if end < self.rows.len() { "\n" } else { "" };
+            let join = |lines: &[String]| {
+                if lines.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{suffix}", lines.join("\n"))
+                }
+            };
+            (Position { x: 0, y: start }, join(&lines), join(&new_lines))
+        } else {
+            let y = start.saturating_sub(1);
+            let x = self.rows.get(y).map_or(0, Row::len);
+            let join = |lines: &[String]| {
+                lines
+                    .iter()
+                    .map(|line| format!("\n{line}"))
+                    .collect::<String>()
+            };
+            (Position { x, y }, join(&lines), join(&new_lines))
+        };
+        if !old.is_empty() {
+            self.history.record(Change::Delete {
+                at: at.clone(),
+                text: old,
+            });
+        }
+        if !new.is_empty() {
+            self.history.record(Change::Insert { at, text: new });
+        }
+
+        self.rows.splice(start..end, new_rows);
+        if count > 0 {
+            self.mark_modified(start..=start.saturating_add(count).saturating_sub(1));
+        } else if start > 0 {
+            self.mark_modified(start.saturating_sub(1)..=start.saturating_sub(1));
+        }
+        self.dirty = true;
+        self.unhighlight_row(start);
+        count
+    }
+
+    /// Lets `edit` change each row of `lines` in place, recording the rows it changed
+    /// for undo and marking them modified. Rows past the end are left out. Returns
+    /// how many rows changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use voider_core::{Document, LineRange};
+    ///
+    /// let mut document = Document::from_text("a  \nb\nc\t");
+    /// let trimmed = document.edit_lines(LineRange::from(0..3), |row| {
+    ///     row.trim_end();
+    /// });
+    /// assert_eq!(trimmed, 2);
+    /// assert!(document.is_dirty());
+    /// ```
+    pub fn edit_lines<F>(&mut self, lines: LineRange, mut edit: F) -> usize
+    where
+        F: FnMut(&mut Row),
+    {
+        let mut changed = 0_usize;
+        for y in lines.rows() {
+            let Some(row) = self.rows.get_mut(y) else {
+                break;
+            };
+            let before = row.as_str().to_string();
+            edit(row);
+            if row.as_str() == before {
+                continue;
+            }
+
+            // The row is recorded as a whole, so undoing puts back its old text
+            let at = Position { x: 0, y };
+            let after = row.as_str().to_string();
+            row.is_modified = true;
+            row.is_highlighted = false;
+            if !before.is_empty() {
+                self.history.record(Change::Delete {
+                    at: at.clone(),
+                    text: before,
+                });
+            }
+            if !after.is_empty() {
+                self.history.record(Change::Insert { at, text: after });
+            }
+            changed = changed.saturating_add(1);
+        }
+
+        if changed > 0 {
+            self.dirty = true;
+            self.revision = self.revision.wrapping_add(1);
+        }
+        changed
+    }
+
+    /// Returns the start and end of the word touching a position, if there is one.
+    #[must_use]
+    pub fn word_at(&self, at: &Position) -> Option<(Position, Position)> {
+        let row = self.rows.get(at.y)?;
+        let graphemes: Vec<&str> = row.as_str().graphemes(true).collect();
+        // A letter with combining marks is one grapheme, counted by its base
+        let is_word = |index: usize| {
+            graphemes
+                .get(index)
+                .and_then(|grapheme| grapheme.chars().next())
+                .is_some_and(is_word_char)
+        };
+
+        let mut start = at.x;
+        if !is_word(start) {
+            start = start.checked_sub(1).filter(|index| is_word(*index))?;
+        }
+        while start > 0 && is_word(start.saturating_sub(1)) {
+            start = start.saturating_sub(1);
+        }
+
+        let mut end = start;
+        while is_word(end) {
+            end = end.saturating_add(1);
+        }
+
+        Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+    }
+
+    /// Returns the start and end of a text object around a position. `around`
+    /// includes the delimiters, or for a word the whitespace after it.
+    #[must_use]
+    pub fn text_object(
+        &self,
+        at: &Position,
+        object: TextObject,
+        around: bool,
+    ) -> Option<(Position, Position)> {
+        match object {
+            TextObject::Word => self.word_object(at, around),
+            TextObject::Pair(open, close) => {
+                let (start, end) = self.enclosing_pair(at, open, close)?;
+                Some(Self::delimited(start, end, around))
+            }
+            TextObject::Quote(quote) => {
+                let (start, end) = self.enclosing_quotes(at, quote)?;
+                Some(Self::delimited(start, end, around))
+            }
+        }
+    }
+
+    /// Turns the positions of two delimiters into the range between them, or
+    /// including them when `around` is set.
+    fn delimited(start: Position, end: Position, around: bool) -> (Position, Position) {
+        if around {
+            (
+                start,
+                Position {
+                    x: end.x.saturating_add(1),
+                    y: end.y,
+                },
+            )
+        } else {
+            (
+                Position {
+                    x: start.x.saturating_add(1),
+                    y: start.y,
+                },
+                end,
+            )
+        }
+    }
+
+    fn word_object(&self, at: &Position, around: bool) -> Option<(Position, Position)> {
+        let (mut start, mut end) = self.word_at(at)?;
+        if !around {
+            return Some((start, end));
+        }
+
+        let row = self.rows.get(at.y)?;
+        let graphemes: Vec<&str> = row.as_str().graphemes(true).collect();
+        let is_space = |index: usize| {
+            graphemes
+                .get(index)
+                .is_some_and(|grapheme| grapheme.trim().is_empty())
+        };
+
+        // Takes the whitespace after the word, or before it when there is none
+        if is_space(end.x) {
+            while is_space(end.x) {
+                end.x = end.x.saturating_add(1);
+            }
+        } else {
+            while start.x > 0 && is_space(start.x.saturating_sub(1)) {
+                start.x = start.x.saturating_sub(1);
+            }
+        }
+
+        Some((start, end))
+    }
+
+    /// Finds the brackets enclosing a position, skipping nested pairs. A position
+    /// on a bracket counts as inside the pair that bracket belongs to.
+    fn enclosing_pair(
+        &self,
+        at: &Position,
+        open: char,
+        close: char,
+    ) -> Option<(Position, Position)> {
+        let mut open_buffer = [0; 4];
+        let mut close_buffer = [0; 4];
+        let open: &str = open.encode_utf8(&mut open_buffer);
+        let close: &str = close.encode_utf8(&mut close_buffer);
+
+        let graphemes: Vec<(Position, &str)> = self
+            .rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.as_str()
+                    .graphemes(true)
+                    .enumerate()
+                    .map(move |(x, grapheme)| (Position { x, y }, grapheme))
+            })
+            .collect();
+        let cursor = graphemes
+            .iter()
+            .position(|(position, _)| (position.y, position.x) >= (at.y, at.x))
+            .unwrap_or(graphemes.len());
+
+        let start = if graphemes.get(cursor).map(|(_, grapheme)| *grapheme) == Some(open) {
+            cursor
+        } else {
+            let mut depth: usize = 0;
+            let mut found = None;
+            for index in (0..cursor).rev() {
+                let grapheme = graphemes.get(index)?.1;
+                if grapheme == close {
+                    depth = depth.saturating_add(1);
+                } else if grapheme == open {
+                    if depth == 0 {
+                        found = Some(index);
+                        break;
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+            }
+            found?
+        };
+
+        let mut depth: usize = 0;
+        for (index, (_, grapheme)) in graphemes.iter().enumerate().skip(start.saturating_add(1)) {
+            if *grapheme == open {
+                depth = depth.saturating_add(1);
+            } else if *grapheme == close {
+                if depth == 0 {
+                    return Some((
+                        graphemes.get(start)?.0.clone(),
+                        graphemes.get(index)?.0.clone(),
+                    ));
+                }
+                depth = depth.saturating_sub(1);
+            }
+        }
+        None
+    }
+
+    /// Finds the quotes enclosing a position on its row, pairing unescaped quotes
+    /// from the start of the row. When the position isn't inside a pair, the next
+    /// pair on the row is used.
+    fn enclosing_quotes(&self, at: &Position, quote: char) -> Option<(Position, Position)> {
+        let row = self.rows.get(at.y)?;
+        let mut buffer = [0; 4];
+        let quote: &str = quote.encode_utf8(&mut buffer);
+
+        let graphemes: Vec<&str> = row.as_str().graphemes(true).collect();
+        let quotes: Vec<usize> = graphemes
+            .iter()
+            .enumerate()
+            .filter(|(index, grapheme)| {
+                **grapheme == quote
+                    && (*index == 0 || graphemes.get(index.saturating_sub(1)) != Some(&"\\"))
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        let pairs = || {
+            quotes.chunks_exact(2).filter_map(|pair| match *pair {
+                [start, end] => Some((start, end)),
+                _ => None,
+            })
+        };
+        let (start, end) = pairs()
+            .find(|(start, end)| *start <= at.x && at.x <= *end)
+            .or_else(|| pairs().find(|(start, _)| *start > at.x))?;
+
+        Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+    }
+
+    /// Returns the word characters right before a position, used as a completion prefix.
+    #[must_use]
+    pub fn word_prefix(&self, at: &Position) -> String {
+        let Some(row) = self.rows.get(at.y) else {
+            return String::new();
+        };
+
+        let before: Vec<char> = row.substring(0, at.x).chars().collect();
+        let start = before
+            .iter()
+            .rposition(|c| !is_word_char(*c))
+            .map_or(0, |index| index.saturating_add(1));
+
+        before.get(start..).unwrap_or_default().iter().collect()
+    }
+
+    /// Collects the distinct words starting with `prefix`, nearest to `near` first.
+    #[must_use]
+    pub fn completions(&self, prefix: &str, near: &Position) -> Vec<String> {
+        let mut rows: Vec<(usize, &Row)> = self.rows.iter().enumerate().collect();
+        rows.sort_by_key(|(y, _)| y.abs_diff(near.y));
+
+        let mut words: Vec<String> = Vec::new();
+        for (_, row) in rows {
+            for word in row.as_str().split(|c: char| !is_word_char(c)) {
+                if word.len() > prefix.len()
+                    && word.starts_with(prefix)
+                    && !words.iter().any(|known| known == word)
+                {
+                    words.push(word.to_string());
+                }
+            }
+        }
+
+        words
+    }
+
+    /// Inserts a whole piece of text, which may span several lines, and returns
+    /// the position right after it.
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        if at.y > self.rows.len() || text.is_empty() {
+            return at.clone();
+        }
+
+        self.dirty = true;
+        self.record_insert(at, text);
+
+        if at.y == self.rows.len() {
+            self.rows.push(Row::default());
+        }
+
+        #[allow(clippy::indexing_slicing)]
+        let tail = self.rows[at.y].split(at.x);
+        let mut position = at.clone();
+
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                position.y = position.y.saturating_add(1);
+                position.x = 0;
+                self.rows.insert(position.y, Row::default());
+            }
+
+            let line = Row::from(line);
+            position.x = position.x.saturating_add(line.len());
+
+            #[allow(clippy::indexing_slicing)]
+            self.rows[position.y].append(&line);
+            self.unhighlight_row(position.y);
+        }
+
+        #[allow(clippy::indexing_slicing)]
+        self.rows[position.y].append(&tail);
+        self.mark_modified(at.y..=position.y);
+        self.unhighlight_row(at.y);
+
+        position
+    }
+
+    /// Deletes the text between two positions, joining the rows at both ends.
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        if start.y >= self.rows.len() {
+            return;
+        }
+
+        self.dirty = true;
+        let end_y = end.y.min(self.rows.len().saturating_sub(1));
+        let end_x = if end.y > end_y {
+            self.rows.get(end_y).map_or(0, Row::len)
+        } else {
+            end.x
+        };
+
+        let text = self.text(start, &Position { x: end_x, y: end_y });
+        if !text.is_empty() {
+            self.history.record(Change::Delete {
+                at: start.clone(),
+                text,
+            });
+        }
+
+        #[allow(clippy::indexing_slicing)]
+        let tail = self.rows[end_y].split(end_x);
+        #[allow(clippy::indexing_slicing)]
+        let _replaced = self.rows[start.y].split(start.x);
+        self.rows
+            .drain(start.y.saturating_add(1)..=end_y.max(start.y));
+        #[allow(clippy::indexing_slicing)]
+        self.rows[start.y].append(&tail);
+
+        self.mark_modified(start.y..=start.y);
+        self.unhighlight_row(start.y);
+    }
+
+    fn mark_modified(&mut self, rows: RangeInclusive<usize>) {
+        self.revision = self.revision.wrapping_add(1);
+        for row in self
+            .rows
+            .iter_mut()
+            .take(rows.end().saturating_add(1))
+            .skip(*rows.start())
+        {
+            row.is_modified = true;
+        }
+    }
+
+    fn clear_modified(&mut self) {
+        for row in &mut self.rows {
+            row.is_modified = false;
+        }
+    }
+
+    // Rows below the edited one are re-highlighted on their own when their
+    // starting state changes, so only the edited row needs to be invalidated
+    fn unhighlight_row(&mut self, index: usize) {
+        if let Some(row) = self.rows.get_mut(index) {
+            row.is_highlighted = false;
+        }
+    }
+
+    /// Drops every row's cached rendering, for when the theme changes.
+    pub fn invalidate_rendering(&mut self) {
+        for row in &mut self.rows {
+            row.invalidate_rendering();
+        }
+    }
+
+    fn unhighlight_rows(&mut self) {
+        for row in &mut self.rows {
+            row.is_highlighted = false;
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)]
+    pub fn delete(&mut self, at: &Position) {
+        let len = self.rows.len();
+        if at.y >= len {
+            return;
+        }
+        self.dirty = true;
+        if at.x == self.rows[at.y].len() && at.y + 1 < len {
+            self.history.record(Change::Delete {
+                at: at.clone(),
+                text: "\n".to_string(),
+            });
+            let next_row = self.rows.remove(at.y + 1);
+            let row = &mut self.rows[at.y];
+            row.append(&next_row);
+        } else {
+            let text = self.rows[at.y].substring(at.x, at.x + 1);
+            if !text.is_empty() {
+                self.history.record(Change::Delete {
+                    at: at.clone(),
+                    text,
+                });
+            }
+            let row = &mut self.rows[at.y];
+            row.delete(at.x);
+        }
+
+        self.mark_modified(at.y..=at.y);
+        self.unhighlight_row(at.y);
+    }
+
+    pub fn set_file_name(&mut self, file_name: String) {
+        self.file_type = Self::detect_file_type(&file_name, &self.rows);
+        self.disk_stamp = stamp(&file_name);
+        self.file_name = Some(file_name);
+        self.unhighlight_rows();
+    }
+
+    /// Strips trailing whitespace from every row, returning how many rows changed.
+    pub fn trim_trailing_whitespace(&mut self) -> usize {
+        self.edit_lines(LineRange::from(0..self.rows.len()), |row| {
+            row.trim_end();
+        })
+    }
+
+    /// Writes the document to its file. A symlink is written through to the file it
+    /// points to when `follow_symlinks` is set, and replaced by a regular file
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of writing the file, such as when its directory is gone.
+    pub fn save(
+        &mut self,
+        trim_trailing_whitespace: bool,
+        follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        if trim_trailing_whitespace {
+            self.trim_trailing_whitespace();
+        }
+
+        if let Some(file_name) = &self.file_name {
+            self.write_file(Path::new(file_name), follow_symlinks)?;
+            self.disk_stamp = stamp(file_name);
+            self.file_type = Self::detect_file_type(file_name, &self.rows);
+            self.unhighlight_rows();
+            self.dirty = false;
+            self.history.mark_saved();
+            self.clear_modified();
+        }
+        Ok(())
+    }
+
+    /// Writes the contents over `path` by renaming a new file next to it into its
+    /// place, so a failed write leaves the old contents whole. The new file takes
+    /// the permissions and owner of the old one. Files that can't be replaced that
+    /// way, such as hard links or files owned by someone else, are rewritten in place.
+    /// The new file is named `.name.voider-save`, and one a killed save left behind
+    /// is taken to be stale and replaced.
+    fn write_file(&self, path: &Path, follow_symlinks: bool) -> Result<(), Error> {
+        let target = if follow_symlinks {
+            fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        } else {
+            path.to_path_buf()
+        };
+        let Ok(metadata) = fs::metadata(&target) else {
+            return self.write_to(&target);
+        };
+        let Some(name) = target.file_name().and_then(|name| name.to_str()) else {
+            return self.write_to(&target);
+        };
+        #[cfg(unix)]
+        if std::os::unix::fs::MetadataExt::nlink(&metadata) > 1 {
+            return self.write_to(&target);
+        }
+
+        let temporary = target.with_file_name(format!(".{name}.voider-save"));
+        let created = match self.write_new(&temporary, &metadata) {
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                fs::remove_file(&temporary).and_then(|()| self.write_new(&temporary, &metadata))
+            }
+            created => created,
+        };
+        let replaced = created.and_then(|()| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                std::os::unix::fs::chown(&temporary, Some(metadata.uid()), Some(metadata.gid()))?;
+            }
+            fs::rename(&temporary, &target)
+        });
+        if replaced.is_err() {
+            // Leftovers go, and the file is still written, only without the rename
+            fs::remove_file(&temporary).ok();
+            return self.write_to(&target);
+        }
+        Ok(())
+    }
+
+    /// Creates the file `path`, which mustn't exist yet, and writes the contents to
+    /// it. It has the permissions in `metadata` from the start, so the contents are
+    /// never readable by more users than the file they're about to replace.
+    fn write_new(&self, path: &Path, metadata: &fs::Metadata) -> Result<(), Error> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(metadata.permissions().mode());
+        }
+        let mut file = options.open(path)?;
+        self.write_rows(&mut file)?;
+        // The mode a file is created with is narrowed by the umask
+        fs::set_permissions(path, metadata.permissions())
+    }
+
+    /// Writes the contents to any file, leaving the document's own file and dirty flag alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of creating or writing the file.
+    pub fn write_to(&self, path: &Path) -> Result<(), Error> {
+        self.write_rows(&mut fs::File::create(path)?)
+    }
+
+    fn write_rows(&self, file: &mut fs::File) -> Result<(), Error> {
+        for row in &self.rows {
+            file.write_all(row.as_bytes())?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn highlight(&mut self, word: &Option<String>, until: Option<usize>) {
+        let mut state = highlighting::State::default();
+
+        let until = if let Some(until) = until {
+            if until.saturating_add(1) < self.rows.len() {
+                until.saturating_add(1)
+            } else {
+                self.rows.len()
+            }
+        } else {
+            self.rows.len()
+        };
+
+        #[allow(clippy::indexing_slicing)]
+        for row in &mut self.rows[..until] {
+            state = row.highlight(self.file_type.highlighting_options(), word, &state);
+        }
+    }
+
+    #[must_use]
+    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+        if at.y >= self.rows.len() {
+            return None;
+        }
+
+        let mut position = Position { x: at.x, y: at.y };
+
+        let start = if direction == SearchDirection::Forward {
+            at.y
+        } else {
+            0
+        };
+
+        let end = if direction == SearchDirection::Forward {
+            self.rows.len()
+        } else {
+            at.y.saturating_add(1)
+        };
+
+        for _ in start..end {
+            if let Some(row) = self.rows.get(position.y) {
+                if let Some(x) = row.find(query, position.x, direction) {
+                    position.x = x;
+                    return Some(position);
+                }
+
+                if direction == SearchDirection::Forward {
+                    position.y = position.y.saturating_add(1);
+                    position.x = 0;
+                } else {
+                    position.y = position.y.saturating_sub(1);
+                    position.x = self.rows.get(position.y).map_or(0, Row::len);
+                }
+            } else {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    /// Like `find`, but continues from the other end of the document when there are
+    /// no matches left in `direction`. Also returns whether the search wrapped around.
+    pub fn find_wrapping(
+        &self,
+        query: &str,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<(Position, bool)> {
+        if let Some(position) = self.find(query, at, direction) {
+            return Some((position, false));
+        }
+
+        let other_end = if direction == SearchDirection::Forward {
+            Position::default()
+        } else {
+            let y = self.rows.len().saturating_sub(1);
+            Position {
+                x: self.rows.get(y).map_or(0, Row::len),
+                y,
+            }
+        };
+
+        self.find(query, &other_end, direction)
+            .map(|position| (position, true))
+    }
+
+    /// Adds a line at the end without counting it as a change, for output shown as
+    /// it arrives.
+    pub fn push_line(&mut self, line: &str) {
+        self.rows.push(Row::from(line));
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Replaces the whole text with `lines`, as an edit that can be undone. When
+    /// there are as many lines as rows, only the rows that changed are recorded.
+    pub fn replace_lines(&mut self, lines: Vec<String>) {
+        if lines.len() == self.rows.len() {
+            let mut lines = lines.into_iter();
+            self.edit_lines(LineRange::from(0..self.rows.len()), |row| {
+                if let Some(line) = lines.next() {
+                    row.set_text(&line);
+                }
+            });
+        } else {
+            self.transform_rows(0..self.rows.len(), |_| lines);
+        }
+    }
+
+    /// Takes over the contents of another document, such as the file rewritten by
+    /// a hook, as an edit that can be undone. The new contents count as saved.
+    pub fn replace_contents(&mut self, other: &Self) {
+        let lines = other
+            .rows
+            .iter()
+            .map(|row| row.as_str().to_string())
+            .collect();
+        self.replace_lines(lines);
+        self.history.mark_saved();
+        self.dirty = false;
+        self.clear_modified();
+        self.disk_stamp = other.disk_stamp;
+    }
+
+    /// Whether another program wrote or removed the file since the document last
+    /// read or wrote it, going by its modification time and length.
+    #[must_use]
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.file_name, &self.disk_stamp) {
+            (Some(file_name), Some(disk_stamp)) => stamp(file_name).as_ref() != Some(disk_stamp),
+            _ => false,
+        }
+    }
+
+    /// Takes the file as it is on disk now as the one the document knows, so
+    /// `changed_on_disk` only tells about later changes.
+    pub fn acknowledge_disk_changes(&mut self) {
+        self.disk_stamp = self.file_name.as_deref().and_then(stamp);
+    }
+
+    /// Records an insertion. Text inserted past the last row starts a new row, so
+    /// it's recorded as inserted after a newline at the end of the last row.
+    fn record_insert(&mut self, at: &Position, text: &str) {
+        let change = match self.rows.len().checked_sub(1) {
+            Some(last) if at.y > last => Change::Insert {
+                at: Position {
+                    x: self.rows.get(last).map_or(0, Row::len),
+                    y: last,
+                },
+                text: format!("\n{text}"),
+            },
+            _ => Change::Insert {
+                at: at.clone(),
+                text: text.to_string(),
+            },
+        };
+        self.history.record(change);
+    }
+
+    /// Groups the changes made since the last call into one undo step.
+    pub fn commit_changes(&mut self, before: &Position, after: &Position) {
+        self.history.commit(before, after);
+    }
+
+    #[must_use]
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Reverts the last undo step, returning where the cursor was before it.
+    pub fn undo(&mut self) -> Option<Position> {
+        let (changes, cursor) = self.history.undo()?;
+        self.apply(changes, true);
+        Some(cursor)
+    }
+
+    /// Applies the undo step that was last undone, returning where the cursor was
+    /// after it.
+    pub fn redo(&mut self) -> Option<Position> {
+        let (changes, cursor) = self.history.redo()?;
+        self.apply(changes, false);
+        Some(cursor)
+    }
+
+    /// Undoes and redoes changes until the document is in another state of its
+    /// history, returning where the cursor goes.
+    pub fn go_to_state(&mut self, state: usize) -> Option<Position> {
+        let (undos, redos) = self.history.path_to(state);
+        let mut cursor = None;
+
+        for _ in 0..undos {
+            cursor = self.undo().or(cursor);
+        }
+        for child in redos {
+            self.history.select_branch(child);
+            cursor = self.redo().or(cursor);
+        }
+        cursor
+    }
+
+    fn apply(&mut self, changes: Vec<Change>, revert: bool) {
+        // The edits made here are the history itself, so they aren't recorded again
+        let history = std::mem::take(&mut self.history);
+
+        for change in changes {
+            let end = change.end();
+            match (change, revert) {
+                (Change::Insert { at, .. }, true) | (Change::Delete { at, .. }, false) => {
+                    self.delete_range(&at, &end);
+                }
+                (Change::Insert { at, text }, false) | (Change::Delete { at, text }, true) => {
+                    self.insert_str(&at, &text);
+                }
+            }
+        }
+
+        self.history = history;
+        self.dirty = !self.history.is_saved();
+        if !self.dirty {
+            self.clear_modified();
+        }
+    }
+
+    #[must_use]
+    pub fn same_contents(&self, other: &Self) -> bool {
+        self.rows.len() == other.rows.len()
+            && self
+                .rows
+                .iter()
+                .zip(&other.rows)
+                .all(|(row, other_row)| row.as_str() == other_row.as_str())
+    }
+
+    /// Starts watching the file for appended lines, from its current end.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the document has no file or it can't be read.
+    pub fn start_following(&mut self) -> Result<(), Error> {
+        let file_name = self
+            .file_name
+            .as_ref()
+            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "the buffer has no file"))?;
+        let mut file = fs::File::open(file_name)?;
+        let offset = file.metadata()?.len();
+        let mut last = [b'\n'];
+        if offset > 0 {
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+        }
+
+        self.follow = Some(Follow {
+            offset,
+            partial: last != [b'\n'],
+        });
+        Ok(())
+    }
+
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+
+    #[must_use]
+    pub fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+
+    /// Loads the complete lines appended to a followed file since the last call.
+    /// A file that shrank was truncated or replaced, as logs are when rotated, so
+    /// it's loaded again from the start, with the undo history of the old text
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file can't be read.
+    pub fn load_appended(&mut self) -> Result<Appended, Error> {
+        let (Some(file_name), Some(follow)) = (&self.file_name, &mut self.follow) else {
+            return Ok(Appended::Rows(0));
+        };
+
+        let mut file = fs::File::open(file_name)?;
+        let len = file.metadata()?.len();
+        let reloaded = len < follow.offset;
+        if reloaded {
+            self.rows.clear();
+            self.history = History::default();
+            self.revision = self.revision.wrapping_add(1);
+            follow.offset = 0;
+            follow.partial = false;
+        }
+        let nothing = if reloaded {
+            Appended::Reloaded
+        } else {
+            Appended::Rows(0)
+        };
+        if len == follow.offset {
+            return Ok(nothing);
+        }
+
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(follow.offset))?;
+        file.take(len.saturating_sub(follow.offset))
+            .read_to_end(&mut bytes)?;
+
+        // A line still being written is left for the next call
+        let Some(end) = bytes.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(nothing);
+        };
+        let text = String::from_utf8_lossy(bytes.get(..end).unwrap_or_default()).into_owned();
+        let end_offset = u64::try_from(end).unwrap_or(u64::MAX);
+        follow.offset = follow.offset.saturating_add(end_offset).saturating_add(1);
+
+        let count = self.rows.len();
+        let mut lines = text.split('\n');
+        if std::mem::take(&mut follow.partial) {
+            if let (Some(row), Some(line)) = (self.rows.last_mut(), lines.next()) {
+                row.append(&Row::from(line));
+                row.is_highlighted = false;
+            }
+        }
+        self.rows.extend(lines.map(Row::from));
+        self.revision = self.revision.wrapping_add(1);
+        if reloaded {
+            return Ok(Appended::Reloaded);
+        }
+        Ok(Appended::Rows(self.rows.len().saturating_sub(count)))
+    }
+
+    /// Whether the document has ANSI escape sequences that could be shown as colors.
+    #[must_use]
+    pub fn has_ansi_escapes(&self) -> bool {
+        self.rows.iter().any(|row| row.as_str().contains("\x1b["))
+    }
+
+    /// Whether ANSI escape sequences are shown as the colors they set.
+    #[must_use]
+    pub fn shows_ansi(&self) -> bool {
+        self.ansi
+    }
+
+    pub fn set_ansi(&mut self, ansi: bool) {
+        self.ansi = ansi;
+    }
+
+    /// Whether the file looked binary when it was opened.
+    #[must_use]
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Replaces what a language server says the names are, as the row, graphemes and
+    /// highlighting type of each. Rows without any lose theirs.
+    pub fn set_semantic_tokens(&mut self, tokens: Vec<(usize, Range<usize>, highlighting::Type)>) {
+        let mut rows: Vec<Vec<(Range<usize>, highlighting::Type)>> = Vec::new();
+        rows.resize_with(self.rows.len(), Vec::new);
+        for (index, range, highlighting_type) in tokens {
+            if let Some(row) = rows.get_mut(index) {
+                row.push((range, highlighting_type));
+            }
+        }
+        for (row, semantic) in self.rows.iter_mut().zip(rows) {
+            row.set_semantic(semantic);
+        }
+    }
+
+    /// The indices of the rows with a breakpoint.
+    #[must_use]
+    pub fn breakpoints(&self) -> Vec<usize> {
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.has_breakpoint)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Whether any row has a breakpoint.
+    #[must_use]
+    pub fn has_breakpoints(&self) -> bool {
+        self.rows.iter().any(|row| row.has_breakpoint)
+    }
+
+    /// Sets or clears the breakpoint of a row, returning whether it has one now.
+    pub fn toggle_breakpoint(&mut self, index: usize) -> bool {
+        self.rows.get_mut(index).is_some_and(|row| {
+            row.has_breakpoint = !row.has_breakpoint;
+            row.has_breakpoint
+        })
+    }
+
+    /// Changes with every edit, so a caller can tell whether the text changed since
+    /// it last looked.
+    #[must_use]
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The modification time and length of a file, if it can be read.
+fn stamp(file_name: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(file_name).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(document: &Document) -> Vec<&str> {
+        (0..document.len())
+            .filter_map(|index| document.row(index).map(Row::as_str))
+            .collect()
+    }
+
+    #[test]
+    fn edits_lines_as_one_undo_step() {
+        let mut document = Document::from_text("b  \nkeep\na\t");
+        let edited = document.edit_lines(LineRange::from(0..3), |row| {
+            row.trim_end();
+        });
+        document.commit_changes(&Position::default(), &Position::default());
+
+        assert_eq!(edited, 2);
+        assert!(document.is_dirty());
+        assert_eq!(lines(&document), ["b", "keep", "a"]);
+
+        document.undo();
+        assert_eq!(lines(&document), ["b  ", "keep", "a\t"]);
+        document.redo();
+        assert_eq!(lines(&document), ["b", "keep", "a"]);
+    }
+
+    #[test]
+    fn leaves_unchanged_lines_alone() {
+        let mut document = Document::from_text("b  \nkeep\na\t");
+        document.edit_lines(LineRange::from(0..3), |row| {
+            row.trim_end();
+        });
+
+        let modified: Vec<bool> = (0..3)
+            .filter_map(|index| document.row(index).map(|row| row.is_modified))
+            .collect();
+        assert_eq!(modified, [true, false, true]);
+
+        // An edit that changes nothing isn't recorded at all
+        let mut document = Document::from_text("a\nb");
+        let edited = document.edit_lines(LineRange::from(0..2), |row| {
+            row.trim_end();
+        });
+        document.commit_changes(&Position::default(), &Position::default());
+        assert_eq!(edited, 0);
+        assert!(!document.is_dirty());
+        assert!(document.undo().is_none());
+    }
+
+    /// Whether each character of a row is highlighted as `kind`.
+    fn marked(document: &Document, y: usize, kind: highlighting::Type) -> Vec<bool> {
+        document.row(y).map_or_else(Vec::new, |row| {
+            row.highlighting()
+                .iter()
+                .map(|marked| *marked == kind)
+                .collect()
+        })
+    }
+
+    fn commented(document: &Document, y: usize) -> Vec<bool> {
+        marked(document, y, highlighting::Type::MultilineComment)
+    }
+
+    fn rust(text: &str) -> Document {
+        let mut document = Document::from_text(text);
+        document.set_file_name("nested.rs".to_string());
+        document.highlight(&None, None);
+        document
+    }
+
+    #[test]
+    fn nested_block_comments_carry_over_rows() {
+        let document = rust("/* a /* b */\nstill */ fn\nlet");
+
+        // Closing the inner comment leaves the outer one open to the next row
+        assert!(commented(&document, 0).iter().all(|commented| *commented));
+        assert_eq!(
+            commented(&document, 1),
+            [[true; 8].as_slice(), &[false; 3]].concat()
+        );
+        assert_eq!(
+            marked(&document, 2, highlighting::Type::PrimaryKeywords),
+            [true; 3]
+        );
+    }
+
+    #[test]
+    fn rehighlights_the_rows_after_an_edit_that_closes_a_comment() {
+        let mut document = rust("/* /* */ open\ncode\nlet");
+        assert_eq!(commented(&document, 1), [true; 4]);
+        assert_eq!(commented(&document, 2), [true; 3]);
+
+        // The rows below weren't edited, but the state they start in changed
+        document.insert_str(&Position { x: 13, y: 0 }, " */");
+        document.highlight(&None, None);
+        assert_eq!(commented(&document, 1), [false; 4]);
+        assert_eq!(
+            marked(&document, 2, highlighting::Type::PrimaryKeywords),
+            [true; 3]
+        );
+
+        // And opening it again carries the comment back down
+        document.delete(&Position { x: 14, y: 0 });
+        document.highlight(&None, None);
+        assert_eq!(commented(&document, 2), [true; 3]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_over_a_stale_new_file_keeping_the_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("voider-{}-save", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("secret.txt");
+        fs::write(&path, "old\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        // What a save that was killed midway left behind
+        fs::write(dir.join(".secret.txt.voider-save"), "stale\n").unwrap();
+
+        let mut document = Document::open(path.to_str().unwrap()).unwrap();
+        document.insert_str(&Position::default(), "new ");
+        document.save(false, false).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new old\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!dir.join(".secret.txt.voider-save").exists());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_through_a_symlink_keeping_the_link() {
+        let dir = std::env::temp_dir().join(format!("voider-{}-symlink", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.txt");
+        let link = dir.join("link.txt");
+        fs::write(&target, "old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let mut document = Document::open(link.to_str().unwrap()).unwrap();
+        document.insert_str(&Position::default(), "new ");
+        document.save(false, true).unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new old\n");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), target);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_hard_linked_files_in_place_keeping_the_inode() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = std::env::temp_dir().join(format!("voider-{}-in-place", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        let other = dir.join("other.txt");
+        fs::write(&path, "old\n").unwrap();
+        fs::hard_link(&path, &other).unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+
+        let mut document = Document::open(path.to_str().unwrap()).unwrap();
+        document.insert_str(&Position::default(), "new ");
+        document.save(false, false).unwrap();
+
+        // Renaming a new file into place would split the two names apart
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(fs::read_to_string(&other).unwrap(), "new old\n");
+        assert!(!dir.join(".file.txt.voider-save").exists());
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -4,12 +4,20 @@ pub struct FileType {
 }
 
 #[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct HighlightingOptions {
     numbers: bool,
     strings: bool,
     characters: bool,
     comments: bool,
     multiline_comments: bool,
+    nested_comments: bool,
+    hash_comments: bool,
+    raw_strings: bool,
+    lifetimes: bool,
+    attributes: bool,
+    variables: bool,
+    heredocs: bool,
     primary_keywords: Vec<String>,
     secondary_keywords: Vec<String>,
 }
@@ -24,14 +32,29 @@ impl Default for FileType {
 }
 
 impl FileType {
+    #[must_use]
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
+    /// The token that starts a single line comment in this file type, if it has one.
+    #[must_use]
+    pub fn line_comment(&self) -> Option<&'static str> {
+        if self.hl_opts.comments {
+            Some("//")
+        } else if self.hl_opts.hash_comments {
+            Some("#")
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
     pub fn highlighting_options(&self) -> &HighlightingOptions {
         &self.hl_opts
     }
 
+    #[must_use]
     pub fn from(file_name: &str) -> Self {
         if file_name.ends_with(".rs") {
             return Self {
@@ -42,6 +65,10 @@ impl FileType {
                     characters: true,
                     comments: true,
                     multiline_comments: true,
+                    nested_comments: true,
+                    raw_strings: true,
+                    lifetimes: true,
+                    attributes: true,
                     primary_keywords: vec![
                         "as".to_string(),
                         "break".to_string(),
@@ -111,40 +138,175 @@ impl FileType {
                         "f32".to_string(),
                         "f64".to_string(),
                     ],
+                    ..HighlightingOptions::default()
                 },
             };
         }
 
+        if file_name.ends_with(".sh")
+            || file_name.ends_with(".bash")
+            || file_name.ends_with(".bashrc")
+            || file_name.ends_with(".bash_profile")
+        {
+            return Self::shell();
+        }
+
+        // Markdown has no highlighting, but its lists are continued on Enter
+        if file_name.ends_with(".md") || file_name.ends_with(".markdown") {
+            return Self {
+                name: String::from("Markdown"),
+                hl_opts: HighlightingOptions::default(),
+            };
+        }
+
         Self::default()
     }
+
+    #[must_use]
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let interpreter = line.strip_prefix("#!")?.trim();
+        let mut parts = interpreter.split_whitespace();
+        let mut program = parts.next()?.rsplit('/').next()?;
+
+        if program == "env" {
+            program = parts.next()?;
+        }
+
+        match program {
+            "sh" | "bash" | "dash" | "ksh" => Some(Self::shell()),
+            _ => None,
+        }
+    }
+
+    fn shell() -> Self {
+        Self {
+            name: String::from("Shell"),
+            hl_opts: HighlightingOptions {
+                numbers: true,
+                strings: true,
+                hash_comments: true,
+                variables: true,
+                heredocs: true,
+                primary_keywords: vec![
+                    "if".to_string(),
+                    "then".to_string(),
+                    "else".to_string(),
+                    "elif".to_string(),
+                    "fi".to_string(),
+                    "case".to_string(),
+                    "esac".to_string(),
+                    "for".to_string(),
+                    "select".to_string(),
+                    "while".to_string(),
+                    "until".to_string(),
+                    "do".to_string(),
+                    "done".to_string(),
+                    "in".to_string(),
+                    "function".to_string(),
+                    "time".to_string(),
+                    "return".to_string(),
+                    "exit".to_string(),
+                    "break".to_string(),
+                    "continue".to_string(),
+                    "local".to_string(),
+                    "export".to_string(),
+                    "readonly".to_string(),
+                    "declare".to_string(),
+                    "unset".to_string(),
+                    "shift".to_string(),
+                    "source".to_string(),
+                ],
+                secondary_keywords: vec![
+                    "echo".to_string(),
+                    "printf".to_string(),
+                    "read".to_string(),
+                    "cd".to_string(),
+                    "pwd".to_string(),
+                    "test".to_string(),
+                    "eval".to_string(),
+                    "exec".to_string(),
+                    "set".to_string(),
+                    "trap".to_string(),
+                    "wait".to_string(),
+                    "kill".to_string(),
+                    "alias".to_string(),
+                    "true".to_string(),
+                    "false".to_string(),
+                ],
+                ..HighlightingOptions::default()
+            },
+        }
+    }
 }
 
 impl HighlightingOptions {
+    #[must_use]
     pub fn numbers(&self) -> bool {
         self.numbers
     }
 
+    #[must_use]
     pub fn strings(&self) -> bool {
         self.strings
     }
 
+    #[must_use]
     pub fn characters(&self) -> bool {
         self.characters
     }
 
+    #[must_use]
     pub fn comments(&self) -> bool {
         self.comments
     }
 
+    #[must_use]
     pub fn primary_keywords(&self) -> &Vec<String> {
         &self.primary_keywords
     }
 
+    #[must_use]
     pub fn secondary_keywords(&self) -> &Vec<String> {
         &self.secondary_keywords
     }
 
+    #[must_use]
     pub fn multiline_comments(&self) -> bool {
         self.multiline_comments
     }
+
+    #[must_use]
+    pub fn nested_comments(&self) -> bool {
+        self.nested_comments
+    }
+
+    #[must_use]
+    pub fn hash_comments(&self) -> bool {
+        self.hash_comments
+    }
+
+    #[must_use]
+    pub fn raw_strings(&self) -> bool {
+        self.raw_strings
+    }
+
+    #[must_use]
+    pub fn lifetimes(&self) -> bool {
+        self.lifetimes
+    }
+
+    #[must_use]
+    pub fn attributes(&self) -> bool {
+        self.attributes
+    }
+
+    #[must_use]
+    pub fn variables(&self) -> bool {
+        self.variables
+    }
+
+    #[must_use]
+    pub fn heredocs(&self) -> bool {
+        self.heredocs
+    }
 }
@@ -0,0 +1,37 @@
+/// The highlighter state at a row boundary, for constructs spanning several rows.
+///
+/// Each `Row` remembers the state it started and ended with, so a row only needs
+/// to be highlighted again when its own content or its starting state changes.
+#[derive(PartialEq, Eq, Clone, Default)]
+pub enum State {
+    #[default]
+    Normal,
+    InString,
+    InRawString(usize),
+    InComment(usize),
+    InHeredoc(String),
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Type {
+    None,
+    Number,
+    Match,
+    String,
+    Character,
+    Comment,
+    MultilineComment,
+    Variable,
+    Lifetime,
+    Attribute,
+    PrimaryKeywords,
+    SecondaryKeywords,
+    /// A web address, found in any kind of text.
+    Url,
+    // The kinds below only come from a language server, which knows what a name is
+    TypeName,
+    Function,
+    Macro,
+    Parameter,
+    Local,
+}
@@ -0,0 +1,358 @@
+use std::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::Position;
+
+/// A single edit, described well enough to be undone and redone.
+#[derive(Clone)]
+pub enum Change {
+    Insert { at: Position, text: String },
+    Delete { at: Position, text: String },
+}
+
+impl Change {
+    /// The position right after the text of the change, once it's in the document.
+    #[must_use]
+    pub fn end(&self) -> Position {
+        let (Self::Insert { at, text } | Self::Delete { at, text }) = self;
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default().graphemes(true).count();
+
+        match lines.enumerate().last() {
+            Some((index, last)) => Position {
+                x: last.graphemes(true).count(),
+                y: at.y.saturating_add(index).saturating_add(1),
+            },
+            None => Position {
+                x: at.x.saturating_add(first),
+                y: at.y,
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        let (action, text) = match self {
+            Self::Insert { text, .. } => ("inserted", text),
+            Self::Delete { text, .. } => ("deleted", text),
+        };
+
+        let mut shown: String = text.chars().take(20).collect();
+        if shown.len() < text.len() {
+            shown.push('…');
+        }
+        format!("{action} {shown:?}")
+    }
+}
+
+/// A group of changes undone together, one step in the history.
+struct Node {
+    parent: usize,
+    children: Vec<usize>,
+    /// The child redo follows, the most recently visited one.
+    redo: Option<usize>,
+    changes: Vec<Change>,
+    /// Where the cursor was before the changes.
+    before: Position,
+    /// Where the cursor was after the changes.
+    after: Position,
+    time: Instant,
+}
+
+/// Every state a document went through, kept as a tree so that editing after an
+/// undo starts a new branch instead of throwing the undone changes away.
+///
+/// The document records its changes as they happen, and the editor commits them
+/// as one step once a command is done.
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+    pending: Vec<Change>,
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                children: Vec::new(),
+                redo: None,
+                changes: Vec::new(),
+                before: Position::default(),
+                after: Position::default(),
+                time: Instant::now(),
+            }],
+            current: 0,
+            pending: Vec::new(),
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, change: Change) {
+        self.pending.push(change);
+    }
+
+    /// Turns the changes recorded since the last commit into a new step.
+    pub fn commit(&mut self, before: &Position, after: &Position) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            parent: self.current,
+            children: Vec::new(),
+            redo: None,
+            changes: std::mem::take(&mut self.pending),
+            before: before.clone(),
+            after: after.clone(),
+            time: Instant::now(),
+        });
+        if let Some(parent) = self.nodes.get_mut(self.current) {
+            parent.children.push(index);
+            parent.redo = Some(index);
+        }
+        self.current = index;
+    }
+
+    pub fn mark_saved(&mut self) {
+        // Changes made while saving, like trimmed whitespace, are still pending and
+        // the saved state is the step they're about to become
+        self.saved = Some(if self.pending.is_empty() {
+            self.current
+        } else {
+            self.nodes.len()
+        });
+    }
+
+    #[must_use]
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current) && self.pending.is_empty()
+    }
+
+    /// Steps back to the parent state, returning the changes to revert (newest
+    /// first) and where the cursor goes.
+    pub fn undo(&mut self) -> Option<(Vec<Change>, Position)> {
+        if self.current == 0 {
+            return None;
+        }
+
+        let node = self.nodes.get(self.current)?;
+        let changes = node.changes.iter().rev().cloned().collect();
+        let before = node.before.clone();
+        let (current, parent) = (self.current, node.parent);
+
+        if let Some(parent) = self.nodes.get_mut(parent) {
+            parent.redo = Some(current);
+        }
+        self.current = parent;
+        Some((changes, before))
+    }
+
+    /// Steps forward along the most recently visited branch, returning the changes
+    /// to apply again and where the cursor goes.
+    pub fn redo(&mut self) -> Option<(Vec<Change>, Position)> {
+        let child = self.nodes.get(self.current)?.redo?;
+        let node = self.nodes.get(child)?;
+        self.current = child;
+        Some((node.changes.clone(), node.after.clone()))
+    }
+
+    /// Returns how many undos and which redos lead from the current state to
+    /// another one, going through their closest common state.
+    #[must_use]
+    pub fn path_to(&self, target: usize) -> (usize, Vec<usize>) {
+        let ancestors = |mut index: usize| {
+            let mut path = vec![index];
+            while index != 0 {
+                index = self.nodes.get(index).map_or(0, |node| node.parent);
+                path.push(index);
+            }
+            path
+        };
+
+        let from = ancestors(self.current);
+        let mut to = ancestors(target);
+        let common = to
+            .iter()
+            .position(|index| from.contains(index))
+            .unwrap_or(to.len().saturating_sub(1));
+        let undos = from
+            .iter()
+            .position(|index| to.get(common) == Some(index))
+            .unwrap_or(0);
+
+        to.truncate(common);
+        to.reverse();
+        (undos, to)
+    }
+
+    /// Marks `child` as the branch redo follows from its parent.
+    pub fn select_branch(&mut self, child: usize) {
+        let Some(parent) = self.nodes.get(child).map(|node| node.parent) else {
+            return;
+        };
+        if let Some(parent) = self.nodes.get_mut(parent) {
+            parent.redo = Some(child);
+        }
+    }
+
+    /// Draws the tree one state per line, oldest first. A branch that isn't the
+    /// newest is indented under the state it split from. Returns the lines along
+    /// with the state each of them stands for.
+    #[must_use]
+    pub fn tree(&self) -> (Vec<String>, Vec<usize>) {
+        let mut lines = Vec::new();
+        let mut states = Vec::new();
+        let mut stack = vec![(0, 0)];
+
+        while let Some((index, depth)) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+
+            let marker = if index == self.current { '@' } else { 'o' };
+            let summary = match node.changes.as_slice() {
+                [] => "original".to_string(),
+                [change] => change.describe(),
+                [change, ..] => format!(
+                    "{} and {} more",
+                    change.describe(),
+                    node.changes.len().saturating_sub(1)
+                ),
+            };
+            lines.push(format!(
+                "{}{marker} {index}: {summary} ({})",
+                "  ".repeat(depth),
+                age(node.time)
+            ));
+            states.push(index);
+
+            // The newest child continues the line, older ones are pushed last so
+            // they come out first and get indented
+            if let Some((newest, older)) = node.children.split_last() {
+                stack.push((*newest, depth));
+                for child in older.iter().rev() {
+                    stack.push((*child, depth.saturating_add(1)));
+                }
+            }
+        }
+
+        (lines, states)
+    }
+
+    #[must_use]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+}
+
+/// How long ago something happened, roughly, like `5s ago` or `3h ago`.
+fn age(time: Instant) -> String {
+    let seconds = time.elapsed().as_secs();
+    match seconds {
+        0..=59 => format!("{seconds}s ago"),
+        60..=3599 => format!("{}m ago", seconds.checked_div(60).unwrap_or_default()),
+        _ => format!("{}h ago", seconds.checked_div(3600).unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Commits one insertion of `text` as its own step.
+    fn step(history: &mut History, text: &str) {
+        history.record(Change::Insert {
+            at: Position::default(),
+            text: text.to_string(),
+        });
+        history.commit(&Position::default(), &Position::default());
+    }
+
+    fn texts(changes: &[Change]) -> Vec<&str> {
+        changes
+            .iter()
+            .map(|(Change::Insert { text, .. } | Change::Delete { text, .. })| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn editing_after_an_undo_keeps_the_old_branch() {
+        let mut history = History::default();
+        step(&mut history, "a");
+        step(&mut history, "b");
+        history.undo();
+        step(&mut history, "c");
+
+        assert_eq!(history.current(), 3);
+        let (lines, states) = history.tree();
+        assert_eq!(states, [0, 1, 2, 3]);
+        assert!(lines.get(2).is_some_and(|line| line.starts_with("  o 2:")));
+        assert!(lines.get(3).is_some_and(|line| line.starts_with("@ 3:")));
+
+        // Redo follows the newest branch, the old one is still a state to go to
+        history.undo();
+        let (changes, _) = history.redo().unwrap();
+        assert_eq!(texts(&changes), ["c"]);
+        assert_eq!(history.path_to(2), (1, vec![2]));
+    }
+
+    #[test]
+    fn goes_to_a_state_on_another_branch_through_the_common_one() {
+        let mut history = History::default();
+        step(&mut history, "a");
+        step(&mut history, "b");
+        step(&mut history, "c");
+        history.undo();
+        history.undo();
+        step(&mut history, "d");
+        step(&mut history, "e");
+
+        assert_eq!(history.path_to(5), (0, vec![]));
+        assert_eq!(history.path_to(0), (3, vec![]));
+        let (undos, redos) = history.path_to(3);
+        assert_eq!((undos, redos.clone()), (2, vec![2, 3]));
+
+        // Replayed the way the document does it
+        let mut undone = Vec::new();
+        for _ in 0..undos {
+            undone.extend(history.undo().unwrap().0);
+        }
+        let mut redone = Vec::new();
+        for child in redos {
+            history.select_branch(child);
+            redone.extend(history.redo().unwrap().0);
+        }
+        assert_eq!(texts(&undone), ["e", "d"]);
+        assert_eq!(texts(&redone), ["b", "c"]);
+        assert_eq!(history.current(), 3);
+    }
+
+    #[test]
+    fn the_saved_state_survives_undo_and_redo() {
+        let mut history = History::default();
+        step(&mut history, "a");
+        history.mark_saved();
+        assert!(history.is_saved());
+
+        history.undo();
+        assert!(!history.is_saved());
+        history.redo();
+        assert!(history.is_saved());
+
+        // Changes still pending when saving become the saved step once committed
+        history.record(Change::Delete {
+            at: Position::default(),
+            text: "a".to_string(),
+        });
+        history.mark_saved();
+        assert!(!history.is_saved());
+        history.commit(&Position::default(), &Position::default());
+        assert!(history.is_saved());
+        history.undo();
+        assert!(!history.is_saved());
+    }
+}
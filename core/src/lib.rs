@@ -0,0 +1,94 @@
+//! The editing engine of voider: documents, rows and their highlighting, the undo
+//! history and the commands an editor carries out. Nothing here draws to or reads
+//! from a terminal, so it can be embedded in other front ends and tested on its own.
+#![warn(clippy::all, clippy::pedantic, clippy::restriction)]
+#![allow(
+    clippy::missing_docs_in_private_items,
+    clippy::implicit_return,
+    clippy::shadow_reuse,
+    clippy::print_stdout,
+    clippy::wildcard_enum_match_arm,
+    clippy::else_if_without_else
+)]
+pub mod ansi;
+pub mod command;
+mod document;
+mod filetype;
+pub mod highlighting;
+pub mod history;
+mod lines;
+mod row;
+pub use document::Appended;
+pub use document::Document;
+pub use document::TextObject;
+pub use filetype::FileType;
+pub use filetype::HighlightingOptions;
+pub use lines::LineRange;
+pub use row::Row;
+pub use row::HIGHLIGHT_LIMIT;
+
+/// An enum representing the search direction.
+///
+/// This enum is used to indicate the direction of a search operation,
+/// and it can have two possible values: `Forward` and `Backward`.
+///
+/// - `Forward`: Represents a forward search direction.
+/// - `Backward`: Represents a backward search direction.
+///
+/// # Examples
+///
+/// ```
+/// use voider_core::SearchDirection;
+///
+/// let direction = SearchDirection::Forward;
+/// assert!(direction == SearchDirection::Forward);
+///
+/// let opposite_direction = SearchDirection::Backward;
+/// assert!(opposite_direction == SearchDirection::Backward);
+/// ```
+#[derive(PartialEq, Clone, Copy)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+/// A struct representing a 2D position.
+///
+/// This struct holds the X and Y coordinates of a point in a 2D space.
+///
+/// # Fields
+///
+/// - `x`: The X coordinate, represented as a `usize`.
+/// - `y`: The Y coordinate, represented as a `usize`.
+///
+/// # Default
+///
+/// This struct implements the `Default` trait, allowing you to create instances
+/// with default values using `Position::default()`, which sets both `x` and `y` to 0.
+///
+/// # Clone
+///
+/// This struct implements the `Clone` trait, allowing you to create cloned copies
+/// of `Position` instances.
+///
+/// # Examples
+///
+/// ```
+/// use voider_core::Position;
+///
+/// let position = Position { x: 10, y: 20 };
+/// assert_eq!(position.x, 10);
+/// assert_eq!(position.y, 20);
+///
+/// let default_position = Position::default();
+/// assert_eq!(default_position.x, 0);
+/// assert_eq!(default_position.y, 0);
+///
+/// let cloned_position = position.clone();
+/// assert_eq!(cloned_position.x, position.x);
+/// ```
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
@@ -0,0 +1,75 @@
+//! The runs of whole rows that line-wise edits, such as sorting or toggling
+//! checkboxes, apply to.
+use std::ops::Range;
+
+use crate::Position;
+
+/// A run of whole rows of a document, from `start` up to but not including `end`.
+///
+/// # Examples
+///
+/// ```
+/// use voider_core::{LineRange, Position};
+///
+/// // A selection ending at the start of a row doesn't take that row along
+/// let start = Position { x: 3, y: 1 };
+/// let end = Position { x: 0, y: 4 };
+/// assert_eq!(LineRange::covering(&start, &end).rows(), 1..4);
+/// assert_eq!(LineRange::row(2).rows(), 2..3);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    start: usize,
+    end: usize,
+}
+
+impl LineRange {
+    /// Just the row `y`.
+    #[must_use]
+    pub fn row(y: usize) -> Self {
+        Self {
+            start: y,
+            end: y.saturating_add(1),
+        }
+    }
+
+    /// The rows a selection from `start` to `end` touches, leaving out the last one
+    /// when the selection ends at its start.
+    #[must_use]
+    pub fn covering(start: &Position, end: &Position) -> Self {
+        let end = if end.x == 0 && end.y > start.y {
+            end.y
+        } else {
+            end.y.saturating_add(1)
+        };
+        Self {
+            start: start.y,
+            end,
+        }
+    }
+
+    /// The indices of the rows.
+    #[must_use]
+    pub fn rows(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+impl From<Range<usize>> for LineRange {
+    fn from(rows: Range<usize>) -> Self {
+        Self {
+            start: rows.start,
+            end: rows.end.max(rows.start),
+        }
+    }
+}
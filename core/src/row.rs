@@ -0,0 +1,1171 @@
+use std::{cell::RefCell, char, ops::Range};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{ansi, highlighting, HighlightingOptions, SearchDirection};
+
+/// How many characters of a row get syntax highlighting. The rest of a longer
+/// row, like a line of minified code, is drawn as plain text so it doesn't slow
+/// down every redraw.
+pub const HIGHLIGHT_LIMIT: usize = 10_000;
+
+/// The last output of `Row::render_cached`, along with what it was rendered for.
+struct Rendered {
+    start: usize,
+    end: usize,
+    selection: Range<usize>,
+    ansi: bool,
+    text: String,
+}
+
+#[derive(Default)]
+pub struct Row {
+    string: String,
+    highlighting: Vec<highlighting::Type>,
+    /// What a language server says the names in the row are, by grapheme range,
+    /// drawn over the plain text the highlighter leaves.
+    semantic: Vec<(Range<usize>, highlighting::Type)>,
+    pub is_highlighted: bool,
+    /// Whether the row changed since the document was opened or last saved.
+    pub is_modified: bool,
+    /// Whether a debugger stops the program at the row.
+    pub has_breakpoint: bool,
+    start_state: highlighting::State,
+    end_state: highlighting::State,
+    len: usize,
+    /// Kept until the row or its highlighting changes, so redrawing an unchanged
+    /// row doesn't style it all over again.
+    rendered: RefCell<Option<Rendered>>,
+}
+
+impl From<&str> for Row {
+    fn from(slice: &str) -> Self {
+        Self {
+            string: String::from(slice),
+            highlighting: Vec::new(),
+            semantic: Vec::new(),
+            is_highlighted: false,
+            is_modified: false,
+            has_breakpoint: false,
+            start_state: highlighting::State::Normal,
+            end_state: highlighting::State::Normal,
+            len: slice.graphemes(true).count(),
+            rendered: RefCell::new(None),
+        }
+    }
+}
+
+impl Row {
+    /// Returns what `render` made of the screen columns from `start` to `end` the
+    /// last time it was called for the same selection and `ansi` setting. Otherwise
+    /// calls it and keeps the result until the row or its highlighting changes.
+    pub fn render_cached(
+        &self,
+        start: usize,
+        end: usize,
+        selection: &Range<usize>,
+        ansi: bool,
+        render: impl FnOnce() -> String,
+    ) -> String {
+        if let Some(rendered) = self.rendered.borrow().as_ref() {
+            if rendered.start == start
+                && rendered.end == end
+                && rendered.selection == *selection
+                && rendered.ansi == ansi
+            {
+                return rendered.text.clone();
+            }
+        }
+
+        let text = render();
+        *self.rendered.borrow_mut() = Some(Rendered {
+            start,
+            end,
+            selection: selection.clone(),
+            ansi,
+            text: text.clone(),
+        });
+        text
+    }
+
+    /// Forgets the cached rendering, for when the colors it used change.
+    pub fn invalidate_rendering(&mut self) {
+        *self.rendered.get_mut() = None;
+    }
+
+    /// Replaces what a language server says the names in the row are, to be drawn
+    /// the next time the row is highlighted.
+    pub fn set_semantic(&mut self, semantic: Vec<(Range<usize>, highlighting::Type)>) {
+        if self.semantic != semantic {
+            self.semantic = semantic;
+            self.is_highlighted = false;
+        }
+    }
+
+    /// The highlighting type of every highlighted character, in order.
+    pub fn highlighting(&self) -> &[highlighting::Type] {
+        &self.highlighting
+    }
+
+    /// The visible stand-in for a control character, or `None` for printable text.
+    #[must_use]
+    pub fn placeholder(grapheme: &str) -> Option<String> {
+        let mut chars = grapheme.chars();
+        let c = chars.next()?;
+        if chars.next().is_some() || !c.is_control() || c == '\t' {
+            return None;
+        }
+
+        let code = u32::from(c);
+        match char::from_u32(code ^ 0x40) {
+            // Caret notation for the C0 controls and DEL, except ESC which reads
+            // better as a byte since `^[` is easy to miss
+            Some(caret) if (code < 0x20 || code == 0x7f) && c != '\x1b' => {
+                Some(format!("^{caret}"))
+            }
+            _ => Some(format!("<0x{code:02X}>")),
+        }
+    }
+
+    /// The ANSI style of every grapheme, as worked out by `ansi::styles`.
+    pub fn ansi_styles(&self) -> Vec<Option<ansi::Style>> {
+        ansi::styles(&self.string.as_str().graphemes(true).collect::<Vec<&str>>())
+    }
+
+    /// The number of screen columns each grapheme takes, in order. With `ansi` set,
+    /// ANSI escape sequences are hidden and take no columns.
+    fn widths(&self, ansi: bool) -> impl Iterator<Item = usize> + '_ {
+        let ansi_styles = ansi.then(|| self.ansi_styles());
+
+        self.string
+            .as_str()
+            .graphemes(true)
+            .enumerate()
+            .map(move |(index, grapheme)| {
+                if ansi_styles
+                    .as_ref()
+                    .is_some_and(|styles| styles.get(index) == Some(&None))
+                {
+                    0
+                } else {
+                    Self::placeholder(grapheme).map_or(1, |text| text.chars().count())
+                }
+            })
+    }
+
+    /// Whether the row takes more than `columns` screen columns. Stops counting
+    /// as soon as it does, so it stays cheap on very long rows.
+    pub fn is_wider_than(&self, columns: usize, ansi: bool) -> bool {
+        let mut width: usize = 0;
+        self.widths(ansi).any(|columns_taken| {
+            width = width.saturating_add(columns_taken);
+            width > columns
+        })
+    }
+
+    /// The screen column where the grapheme at `x` starts.
+    pub fn column(&self, x: usize, ansi: bool) -> usize {
+        self.widths(ansi).take(x).sum()
+    }
+
+    /// The grapheme drawn at screen column `column`, or the length of the row when
+    /// the column is past its end.
+    pub fn x_at_column(&self, column: usize, ansi: bool) -> usize {
+        let mut start: usize = 0;
+        for (x, width) in self.widths(ansi).enumerate() {
+            let end = start.saturating_add(width);
+            if column < end {
+                return x;
+            }
+            start = end;
+        }
+        self.len
+    }
+
+    /// The character ranges of the `http://` and `https://` addresses in the row.
+    /// Punctuation right after an address, such as a closing parenthesis that
+    /// opens nowhere in it, is taken as part of the text around it.
+    pub fn urls(&self) -> Vec<Range<usize>> {
+        if !self.string.contains("://") {
+            return Vec::new();
+        }
+
+        let chars: Vec<char> = self.string.chars().collect();
+        let starts_with = |at: usize, prefix: &str| {
+            prefix
+                .chars()
+                .enumerate()
+                .all(|(offset, c)| chars.get(at.saturating_add(offset)) == Some(&c))
+        };
+        let mut urls = Vec::new();
+        let mut index = 0;
+        while index < chars.len() {
+            let after_word = index
+                .checked_sub(1)
+                .and_then(|before| chars.get(before))
+                .is_some_and(|c| c.is_alphanumeric());
+            let scheme = ["https://", "http://"]
+                .into_iter()
+                .find(|scheme| starts_with(index, scheme));
+            let Some(scheme) = scheme.filter(|_| !after_word) else {
+                index = index.saturating_add(1);
+                continue;
+            };
+
+            let mut end = chars
+                .iter()
+                .enumerate()
+                .skip(index)
+                .find(|(_, c)| c.is_whitespace() || c.is_control() || "<>\"'`".contains(**c))
+                .map_or(chars.len(), |(end, _)| end);
+            while let Some(last) = end.checked_sub(1).and_then(|last| chars.get(last)) {
+                let url = chars.get(index..end).unwrap_or_default();
+                let count = |c: char| url.iter().filter(|other| **other == c).count();
+                let unbalanced = match last {
+                    ')' => count('(') < count(')'),
+                    ']' => count('[') < count(']'),
+                    '}' => count('{') < count('}'),
+                    last => ".,;:!?*".contains(*last),
+                };
+                if !unbalanced {
+                    break;
+                }
+                end = end.saturating_sub(1);
+            }
+
+            if end > index.saturating_add(scheme.len()) {
+                urls.push(index..end);
+            }
+            index = end.max(index.saturating_add(1));
+        }
+        urls
+    }
+
+    /// Whether part of the row is past [`HIGHLIGHT_LIMIT`] and drawn without
+    /// syntax highlighting.
+    pub fn is_long(&self) -> bool {
+        self.string.chars().nth(HIGHLIGHT_LIMIT).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, at: usize, c: char) {
+        self.invalidate_rendering();
+        self.semantic.clear();
+        if at >= self.len() {
+            self.string.push(c);
+            self.len = self.len.saturating_add(1);
+            return;
+        }
+        let mut result: String = String::new();
+        let mut length = 0_usize;
+        for (index, grapheme) in self.string.as_str().graphemes(true).enumerate() {
+            length = length.saturating_add(1);
+            if index == at {
+                length = length.saturating_add(1);
+                result.push(c);
+            }
+            result.push_str(grapheme);
+        }
+        self.len = length;
+        self.string = result;
+    }
+
+    pub fn delete(&mut self, at: usize) {
+        self.invalidate_rendering();
+        self.semantic.clear();
+        if at >= self.len() {
+            return;
+        }
+        let mut result: String = String::new();
+        let mut length = 0_usize;
+        for (index, grapheme) in self.string.as_str().graphemes(true).enumerate() {
+            if index != at {
+                length = length.saturating_add(1);
+                result.push_str(grapheme);
+            }
+        }
+        self.len = length;
+        self.string = result;
+    }
+
+    pub fn append(&mut self, new: &Self) {
+        self.invalidate_rendering();
+        self.semantic.clear();
+        self.string = format!("{}{}", self.string, new.string);
+        self.len = self.len.saturating_add(new.len);
+    }
+
+    #[must_use]
+    pub fn split(&mut self, at: usize) -> Self {
+        self.invalidate_rendering();
+        self.semantic.clear();
+        let mut row: String = String::new();
+        let mut length = 0_usize;
+        let mut splitted_row: String = String::new();
+        let mut splitted_length = 0_usize;
+        for (index, grapheme) in self.string.as_str().graphemes(true).enumerate() {
+            if index < at {
+                length = length.saturating_add(1);
+                row.push_str(grapheme);
+            } else {
+                splitted_length = splitted_length.saturating_add(1);
+                splitted_row.push_str(grapheme);
+            }
+        }
+
+        self.string = row;
+        self.len = length;
+        Self {
+            string: splitted_row,
+            highlighting: Vec::new(),
+            semantic: Vec::new(),
+            is_highlighted: false,
+            is_modified: false,
+            has_breakpoint: false,
+            start_state: highlighting::State::Normal,
+            end_state: highlighting::State::Normal,
+            len: splitted_length,
+            rendered: RefCell::new(None),
+        }
+    }
+
+    /// Replaces the whole text of the row.
+    pub fn set_text(&mut self, text: &str) {
+        self.invalidate_rendering();
+        self.semantic.clear();
+        self.string = text.to_string();
+        self.len = text.graphemes(true).count();
+        self.is_highlighted = false;
+    }
+
+    pub fn indentation(&self) -> &str {
+        let text = self.string.trim_start_matches([' ', '\t']);
+        self.string
+            .get(..self.string.len().saturating_sub(text.len()))
+            .unwrap_or_default()
+    }
+
+    /// Removes trailing whitespace, returning whether there was any.
+    pub fn trim_end(&mut self) -> bool {
+        let trimmed_len = self.string.trim_end().len();
+        if trimmed_len == self.string.len() {
+            return false;
+        }
+
+        self.invalidate_rendering();
+        self.string.truncate(trimmed_len);
+        self.len = self.string.as_str().graphemes(true).count();
+        self.is_highlighted = false;
+        true
+    }
+
+    pub fn substring(&self, start: usize, end: usize) -> String {
+        self.string
+            .as_str()
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect()
+    }
+
+    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
+        if at > self.len || query.is_empty() {
+            return None;
+        }
+
+        let start = if direction == SearchDirection::Forward {
+            at
+        } else {
+            0
+        };
+
+        let end = if direction == SearchDirection::Forward {
+            self.len
+        } else {
+            at
+        };
+
+        let substring: String = self
+            .string
+            .as_str()
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect();
+
+        let matching_byte_index = if direction == SearchDirection::Forward {
+            substring.find(query)
+        } else {
+            substring.rfind(query)
+        };
+
+        if let Some(matching_byte_index) = matching_byte_index {
+            for (grapheme_index, (byte_index, _)) in substring.grapheme_indices(true).enumerate() {
+                if matching_byte_index == byte_index {
+                    return Some(start.saturating_add(grapheme_index));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn highlight_match(&mut self, word: Option<&str>) {
+        let Some(word) = word.filter(|word| !word.is_empty()) else {
+            return;
+        };
+
+        // One pass over the highlighted part of the row, marking the graphemes of
+        // every match that starts on a grapheme boundary
+        let highlighted = self
+            .string
+            .as_str()
+            .char_indices()
+            .nth(HIGHLIGHT_LIMIT)
+            .map_or(self.string.len(), |(byte_index, _)| byte_index);
+        let highlighted = self.string.get(..highlighted).unwrap_or_default();
+        let word_len = word.graphemes(true).count();
+        let mut matches = highlighted
+            .match_indices(word)
+            .map(|(byte_index, _)| byte_index)
+            .peekable();
+        let mut remaining: usize = 0;
+
+        for (index, (byte_index, _)) in highlighted.grapheme_indices(true).enumerate() {
+            while matches.next_if(|start| *start < byte_index).is_some() {}
+            if remaining == 0 && matches.next_if_eq(&byte_index).is_some() {
+                remaining = word_len;
+            }
+            if remaining > 0 {
+                if let Some(highlighting_type) = self.highlighting.get_mut(index) {
+                    *highlighting_type = highlighting::Type::Match;
+                }
+                remaining = remaining.saturating_sub(1);
+            }
+        }
+    }
+
+    fn char_literal_len(chars: &[char], index: usize) -> Option<usize> {
+        let next_char = chars.get(index.saturating_add(1))?;
+
+        let closing_index = if *next_char == '\\' {
+            // Escapes such as '\n', '\x7f' or '\u{1F600}' end at the next unescaped quote
+            chars
+                .iter()
+                .enumerate()
+                .skip(index.saturating_add(3))
+                .take(10)
+                .find(|(_, c)| **c == '\'')
+                .map(|(closing_index, _)| closing_index)?
+        } else {
+            index.saturating_add(2)
+        };
+
+        if chars.get(closing_index) == Some(&'\'') {
+            return Some(closing_index.saturating_sub(index).saturating_add(1));
+        }
+
+        None
+    }
+
+    fn lifetime_len(chars: &[char], index: usize) -> Option<usize> {
+        let next_char = chars.get(index.saturating_add(1))?;
+        if !next_char.is_alphabetic() && *next_char != '_' {
+            return None;
+        }
+
+        let len = chars
+            .iter()
+            .skip(index.saturating_add(1))
+            .take_while(|c| c.is_alphanumeric() || **c == '_')
+            .count();
+
+        Some(len.saturating_add(1))
+    }
+
+    fn highlight_char(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.characters() {
+            return false;
+        }
+
+        // Byte literals like b'x' are colored as a whole
+        let (start, prefix) = if c == 'b' && chars.get(index.saturating_add(1)) == Some(&'\'') {
+            if *index > 0 {
+                #[allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)]
+                let prev_char = chars[*index - 1];
+                if !Self::is_separator(prev_char) {
+                    return false;
+                }
+            }
+            (index.saturating_add(1), 1)
+        } else if c == '\'' {
+            (*index, 0)
+        } else {
+            return false;
+        };
+
+        if let Some(len) = Self::char_literal_len(chars, start) {
+            for _ in 0..len.saturating_add(prefix) {
+                self.highlighting.push(highlighting::Type::Character);
+                *index = index.saturating_add(1);
+            }
+
+            return true;
+        }
+
+        if opts.lifetimes() && prefix == 0 {
+            if let Some(len) = Self::lifetime_len(chars, start) {
+                for _ in 0..len {
+                    self.highlighting.push(highlighting::Type::Lifetime);
+                    *index = index.saturating_add(1);
+                }
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn highlight_attribute(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.attributes() || c != '#' {
+            return false;
+        }
+
+        let bracket_index = if chars.get(index.saturating_add(1)) == Some(&'!') {
+            index.saturating_add(2)
+        } else {
+            index.saturating_add(1)
+        };
+
+        if chars.get(bracket_index) != Some(&'[') {
+            return false;
+        }
+
+        let mut depth: usize = 0;
+        let mut end = chars.len();
+        for (position, next_char) in chars.iter().enumerate().skip(bracket_index) {
+            match next_char {
+                '[' => depth = depth.saturating_add(1),
+                ']' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        end = position.saturating_add(1);
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for _ in *index..end {
+            self.highlighting.push(highlighting::Type::Attribute);
+            *index = index.saturating_add(1);
+        }
+
+        true
+    }
+
+    fn highlight_raw_string(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.raw_strings() || (c != 'r' && c != 'b') {
+            return false;
+        }
+
+        if *index > 0 {
+            #[allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)]
+            let prev_char = chars[*index - 1];
+            if !Self::is_separator(prev_char) {
+                return false;
+            }
+        }
+
+        let r_index = if c == 'b' {
+            index.saturating_add(1)
+        } else {
+            *index
+        };
+
+        if chars.get(r_index) != Some(&'r') {
+            return false;
+        }
+
+        let hashes = chars
+            .iter()
+            .skip(r_index.saturating_add(1))
+            .take_while(|c| **c == '#')
+            .count();
+        let quote_index = r_index.saturating_add(1).saturating_add(hashes);
+
+        if chars.get(quote_index) != Some(&'"') {
+            return false;
+        }
+
+        for _ in *index..=quote_index {
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+        }
+
+        self.highlight_raw_string_body(index, chars, hashes);
+        true
+    }
+
+    fn highlight_raw_string_body(&mut self, index: &mut usize, chars: &[char], hashes: usize) {
+        while let Some(next_char) = chars.get(*index) {
+            let closing_hashes = chars
+                .iter()
+                .skip(index.saturating_add(1))
+                .take(hashes)
+                .filter(|c| **c == '#')
+                .count();
+
+            if *next_char == '"' && closing_hashes == hashes {
+                for _ in 0..=hashes {
+                    self.highlighting.push(highlighting::Type::String);
+                    *index = index.saturating_add(1);
+                }
+                return;
+            }
+
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+        }
+
+        self.end_state = highlighting::State::InRawString(hashes);
+    }
+
+    fn highlight_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.comments() && c == '/' && *index < chars.len() {
+            if let Some(next_char) = chars.get(index.saturating_add(1)) {
+                if *next_char == '/' {
+                    for _ in *index..chars.len() {
+                        self.highlighting.push(highlighting::Type::Comment);
+                        *index = index.saturating_add(1);
+                    }
+
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn highlight_hash_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.hash_comments() && c == '#' {
+            if *index > 0 {
+                #[allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)]
+                let prev_char = chars[*index - 1];
+                if !prev_char.is_ascii_whitespace() {
+                    return false;
+                }
+            }
+
+            for _ in *index..chars.len() {
+                self.highlighting.push(highlighting::Type::Comment);
+                *index = index.saturating_add(1);
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    fn variable_len(chars: &[char], index: usize) -> usize {
+        let Some(next_char) = chars.get(index.saturating_add(1)) else {
+            return 0;
+        };
+
+        match next_char {
+            '{' => chars
+                .iter()
+                .skip(index)
+                .position(|c| *c == '}')
+                .map_or(chars.len().saturating_sub(index), |len| len.saturating_add(1)),
+            '(' | '@' | '#' | '?' | '$' | '!' | '*' | '-' => 2,
+            c if c.is_ascii_digit() => 2,
+            c if c.is_alphabetic() || *c == '_' => chars
+                .iter()
+                .skip(index.saturating_add(1))
+                .take_while(|c| c.is_alphanumeric() || **c == '_')
+                .count()
+                .saturating_add(1),
+            _ => 0,
+        }
+    }
+
+    fn highlight_variable(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.variables() && c == '$' {
+            let len = Self::variable_len(chars, *index);
+            if len == 0 {
+                return false;
+            }
+
+            for _ in 0..len {
+                self.highlighting.push(highlighting::Type::Variable);
+                *index = index.saturating_add(1);
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    fn highlight_shell_string(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if !opts.variables() || (c != '"' && c != '\'') {
+            return false;
+        }
+
+        self.highlighting.push(highlighting::Type::String);
+        *index = index.saturating_add(1);
+
+        while let Some(next_char) = chars.get(*index) {
+            if *next_char == c {
+                self.highlighting.push(highlighting::Type::String);
+                *index = index.saturating_add(1);
+                break;
+            }
+
+            // Single quotes are fully literal, double quotes allow escapes and expansions
+            if c == '"' && *next_char == '\\' && index.saturating_add(1) < chars.len() {
+                self.highlighting.push(highlighting::Type::String);
+                self.highlighting.push(highlighting::Type::String);
+                *index = index.saturating_add(2);
+                continue;
+            }
+
+            if c == '"' && *next_char == '$' {
+                let len = Self::variable_len(chars, *index);
+                if len > 0 {
+                    for _ in 0..len {
+                        self.highlighting.push(highlighting::Type::Variable);
+                        *index = index.saturating_add(1);
+                    }
+                    continue;
+                }
+            }
+
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+        }
+
+        true
+    }
+
+    fn highlight_numbers(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.numbers() && c.is_ascii_digit() {
+            if *index > 0 {
+                #[allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)]
+                let prev_char = chars[*index - 1];
+                if !Self::is_separator(prev_char) {
+                    return false;
+                }
+            }
+
+            loop {
+                self.highlighting.push(highlighting::Type::Number);
+                *index = index.saturating_add(1);
+                if let Some(next_char) = chars.get(*index) {
+                    if *next_char != '.' && !next_char.is_ascii_digit() {
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    fn highlight_string(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.strings() && c == '"' {
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+            self.highlight_string_body(index, chars);
+            return true;
+        }
+
+        false
+    }
+
+    fn highlight_string_body(&mut self, index: &mut usize, chars: &[char]) {
+        while let Some(next_char) = chars.get(*index) {
+            // An escaped character never closes the string
+            if *next_char == '\\' && index.saturating_add(1) < chars.len() {
+                self.highlighting.push(highlighting::Type::String);
+                self.highlighting.push(highlighting::Type::String);
+                *index = index.saturating_add(2);
+                continue;
+            }
+
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+
+            if *next_char == '"' {
+                return;
+            }
+        }
+
+        self.end_state = highlighting::State::InString;
+    }
+
+    fn highlight_str(
+        &mut self,
+        index: &mut usize,
+        subtring: &str,
+        chars: &[char],
+        hl_type: highlighting::Type,
+    ) -> bool {
+        if subtring.is_empty() {
+            return false;
+        }
+
+        for (substring_index, c) in subtring.chars().enumerate() {
+            if let Some(next_char) = chars.get(index.saturating_add(substring_index)) {
+                if *next_char != c {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        for _ in 0..subtring.len() {
+            self.highlighting.push(hl_type);
+            *index = index.saturating_add(1);
+        }
+
+        true
+    }
+
+    fn highlight_keywords(
+        &mut self,
+        index: &mut usize,
+        chars: &[char],
+        keywords: &[String],
+        hl_type: highlighting::Type,
+    ) -> bool {
+        if *index > 0 {
+            #[allow(clippy::indexing_slicing, clippy::arithmetic_side_effects)]
+            let prev_char = chars[*index - 1];
+            if !Self::is_separator(prev_char) {
+                return false;
+            }
+        }
+
+        for word in keywords {
+            if let Some(next_char) = chars.get(index.saturating_add(word.len())) {
+                if !Self::is_separator(*next_char) {
+                    continue;
+                }
+            }
+
+            if self.highlight_str(index, word, chars, hl_type) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn highlight_multiline_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        c: char,
+        chars: &[char],
+    ) -> bool {
+        if opts.multiline_comments()
+            && c == '/'
+            && chars.get(index.saturating_add(1)) == Some(&'*')
+        {
+            self.highlighting.push(highlighting::Type::MultilineComment);
+            self.highlighting.push(highlighting::Type::MultilineComment);
+            *index = index.saturating_add(2);
+            self.highlight_multiline_comment_body(index, opts, chars, 1);
+            return true;
+        }
+
+        false
+    }
+
+    fn highlight_multiline_comment_body(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+        mut depth: usize,
+    ) {
+        while let Some(c) = chars.get(*index) {
+            let next_char = chars.get(index.saturating_add(1));
+
+            if *c == '*' && next_char == Some(&'/') {
+                depth = depth.saturating_sub(1);
+            } else if opts.nested_comments() && *c == '/' && next_char == Some(&'*') {
+                depth = depth.saturating_add(1);
+            } else {
+                self.highlighting.push(highlighting::Type::MultilineComment);
+                *index = index.saturating_add(1);
+                continue;
+            }
+
+            self.highlighting.push(highlighting::Type::MultilineComment);
+            self.highlighting.push(highlighting::Type::MultilineComment);
+            *index = index.saturating_add(2);
+
+            if depth == 0 {
+                return;
+            }
+        }
+
+        self.end_state = highlighting::State::InComment(depth);
+    }
+
+    fn highlight_primary_keywords(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        self.highlight_keywords(
+            index,
+            chars,
+            opts.primary_keywords(),
+            highlighting::Type::PrimaryKeywords,
+        )
+    }
+
+    fn highlight_secondary_keywords(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightingOptions,
+        chars: &[char],
+    ) -> bool {
+        self.highlight_keywords(
+            index,
+            chars,
+            opts.secondary_keywords(),
+            highlighting::Type::SecondaryKeywords,
+        )
+    }
+
+    pub fn highlight(
+        &mut self,
+        opts: &HighlightingOptions,
+        word: &Option<String>,
+        start_state: &highlighting::State,
+    ) -> highlighting::State {
+        if self.is_highlighted
+            && word.is_none()
+            && self.start_state == *start_state
+            && !self.highlighting.contains(&highlighting::Type::Match)
+        {
+            return self.end_state.clone();
+        }
+
+        let chars: Vec<char> = self.string.chars().take(HIGHLIGHT_LIMIT).collect();
+        self.invalidate_rendering();
+        self.highlighting = Vec::new();
+        self.start_state = start_state.clone();
+        self.end_state = highlighting::State::Normal;
+        let mut index = 0;
+
+        match start_state {
+            highlighting::State::Normal => (),
+            highlighting::State::InString => self.highlight_string_body(&mut index, &chars),
+            highlighting::State::InRawString(hashes) => {
+                self.highlight_raw_string_body(&mut index, &chars, *hashes);
+            }
+            highlighting::State::InComment(depth) => {
+                self.highlight_multiline_comment_body(&mut index, opts, &chars, *depth);
+            }
+            highlighting::State::InHeredoc(delimiter) => {
+                self.highlight_heredoc_body(&mut index, &chars, delimiter);
+            }
+        }
+
+        while let Some(c) = chars.get(index) {
+            if self.highlight_multiline_comment(&mut index, opts, *c, &chars)
+                || self.highlight_shell_string(&mut index, opts, *c, &chars)
+                || self.highlight_hash_comment(&mut index, opts, *c, &chars)
+                || self.highlight_variable(&mut index, opts, *c, &chars)
+                || self.highlight_attribute(&mut index, opts, *c, &chars)
+                || self.highlight_raw_string(&mut index, opts, *c, &chars)
+                || self.highlight_char(&mut index, opts, *c, &chars)
+                || self.highlight_comment(&mut index, opts, *c, &chars)
+                || self.highlight_numbers(&mut index, opts, *c, &chars)
+                || self.highlight_primary_keywords(&mut index, opts, &chars)
+                || self.highlight_secondary_keywords(&mut index, opts, &chars)
+                || self.highlight_string(&mut index, opts, *c, &chars)
+            {
+                continue;
+            }
+
+            self.highlighting.push(highlighting::Type::None);
+            index = index.saturating_add(1);
+        }
+
+        if self.is_long() {
+            // Wherever the highlighting stopped says nothing about how the row ends
+            self.end_state = highlighting::State::Normal;
+        } else if self.end_state == highlighting::State::Normal && opts.heredocs() {
+            if let Some(delimiter) = self.heredoc_delimiter() {
+                self.end_state = highlighting::State::InHeredoc(delimiter);
+            }
+        }
+
+        for range in self.urls() {
+            for slot in self
+                .highlighting
+                .iter_mut()
+                .take(range.end)
+                .skip(range.start)
+            {
+                *slot = highlighting::Type::Url;
+            }
+        }
+        for (range, highlighting_type) in &self.semantic {
+            for index in range.clone() {
+                if let Some(slot) = self
+                    .highlighting
+                    .get_mut(index)
+                    .filter(|slot| **slot == highlighting::Type::None)
+                {
+                    *slot = *highlighting_type;
+                }
+            }
+        }
+        self.highlight_match(word.as_deref());
+        self.is_highlighted = true;
+        self.end_state.clone()
+    }
+
+    fn heredoc_delimiter(&self) -> Option<String> {
+        let chars: Vec<char> = self.string.chars().take(HIGHLIGHT_LIMIT).collect();
+        let mut index = 0;
+
+        while let Some(c) = chars.get(index) {
+            let is_code = self.highlighting.get(index) == Some(&highlighting::Type::None);
+            let is_heredoc = *c == '<'
+                && chars.get(index.saturating_add(1)) == Some(&'<')
+                && chars.get(index.saturating_add(2)) != Some(&'<');
+
+            if !is_code || !is_heredoc {
+                index = index.saturating_add(1);
+                continue;
+            }
+
+            index = index.saturating_add(2);
+            if chars.get(index) == Some(&'-') {
+                index = index.saturating_add(1);
+            }
+            while chars.get(index).is_some_and(char::is_ascii_whitespace) {
+                index = index.saturating_add(1);
+            }
+
+            let quote = chars.get(index).copied().filter(|c| *c == '\'' || *c == '"');
+            if quote.is_some() {
+                index = index.saturating_add(1);
+            }
+
+            let delimiter: String = chars
+                .iter()
+                .skip(index)
+                .take_while(|c| c.is_alphanumeric() || **c == '_')
+                .collect();
+
+            if !delimiter.is_empty() {
+                return Some(delimiter);
+            }
+        }
+
+        None
+    }
+
+    fn highlight_heredoc_body(&mut self, index: &mut usize, chars: &[char], delimiter: &str) {
+        for _ in chars {
+            self.highlighting.push(highlighting::Type::String);
+            *index = index.saturating_add(1);
+        }
+
+        if self.string.trim_start_matches('\t') != delimiter {
+            self.end_state = highlighting::State::InHeredoc(delimiter.to_string());
+        }
+    }
+
+    fn is_separator(c: char) -> bool {
+        c.is_ascii_punctuation() || c.is_ascii_whitespace()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.string.as_bytes()
+    }
+}
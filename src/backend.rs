@@ -0,0 +1,104 @@
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{KeyEvent, MouseEvent},
+    style::Color,
+};
+use std::{io, time::Duration};
+
+use crate::Position;
+
+/// Something that happened in the terminal while waiting for input.
+pub enum Input {
+    Key(KeyEvent),
+    /// A mouse button was pressed, released or dragged, or the wheel turned.
+    Mouse(MouseEvent),
+    /// The terminal was resized to the given width and height.
+    Resize(u16, u16),
+}
+
+pub struct Size {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Where the editor draws and where its keys come from.
+///
+/// `Terminal` is the crossterm implementation. Another front end, such as a GUI
+/// window or a test double, implements this trait and hands it to
+/// `Editor::with_backend` to drive the same editor.
+///
+/// The trait is tied to crossterm for now: keys, mouse events, colors and cursor
+/// styles are crossterm's types, and text passed to `print` may carry its colors
+/// as the ANSI SGR sequences crossterm styles text with, so a backend that isn't
+/// a terminal has to read those. Neutral types for them are planned.
+///
+/// Drawing happens a frame at a time, between `begin_frame` and `end_frame`.
+pub trait Backend {
+    /// The size of the text area, without the status and message bars.
+    fn size(&self) -> &Size;
+
+    /// Takes the new size of the whole screen after a resize.
+    fn resize(&mut self, width: u16, height: u16);
+
+    /// Waits up to `timeout` for a key press, a mouse event or a resize, returning
+    /// `None` when none happened in time.
+    fn poll_input(&mut self, timeout: Duration) -> Option<Input>;
+
+    /// Waits for a key press, for prompts that can't go on without one.
+    fn read_key(&mut self) -> KeyEvent;
+
+    /// Waits up to `timeout` for a key press, for prompts that show what comes in
+    /// meanwhile. Returns `None` when none came in time. Backends that can't wait
+    /// for a while only wait for the key.
+    fn read_key_within(&mut self, _timeout: Duration) -> Option<KeyEvent> {
+        Some(self.read_key())
+    }
+
+    fn begin_frame(&mut self);
+
+    /// Shows everything drawn since `begin_frame`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the output can't be written.
+    fn end_frame(&mut self) -> Result<(), io::Error>;
+
+    fn move_cursor(&mut self, position: &Position);
+
+    fn show_cursor(&mut self);
+
+    fn hide_cursor(&mut self);
+
+    /// Sets the shape of the cursor and whether it blinks. Backends that can't
+    /// change it leave it as it is.
+    fn set_cursor_style(&mut self, _style: SetCursorStyle) {}
+
+    fn clear_screen(&mut self);
+
+    /// Clears the line the cursor is on.
+    fn clear_line(&mut self);
+
+    /// Draws text at the cursor, moving the cursor past it.
+    fn print(&mut self, text: &str);
+
+    fn set_fg_color(&mut self, color: Color);
+
+    fn set_bg_color(&mut self, color: Color);
+
+    fn reset_colors(&mut self);
+
+    fn set_title(&mut self, title: &str);
+
+    /// Puts copied text on the clipboard of the system. Backends without one keep
+    /// copied text inside the editor.
+    fn set_clipboard(&mut self, _text: &str) {}
+
+    /// The text on the clipboard of the system, or `None` to paste what was last
+    /// copied in the editor.
+    fn clipboard(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Puts things back the way they were before the editor started, when it quits.
+    fn restore(&mut self);
+}
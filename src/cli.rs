@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: voider [options] [+LINE] [FILE]...
+
+Options:
+  -R, --readonly       open the files without allowing changes
+  -c, --config PATH    read the config from PATH instead of the config directory
+  -t, --theme NAME     use the base16 theme NAME, or the scheme file at NAME
+      --tutor          open the interactive tutorial
+  +LINE                put the cursor on LINE of the first file
+  -h, --help           show this help
+  -V, --version        show the version
+";
+
+/// The options the editor was started with.
+#[derive(Default)]
+pub struct Args {
+    pub files: Vec<String>,
+    pub read_only: bool,
+    pub config: Option<PathBuf>,
+    pub theme: Option<String>,
+    /// The line to start on, counted from 1.
+    pub line: Option<usize>,
+    pub tutor: bool,
+}
+
+/// What the command line asks for: running the editor or just printing something.
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+/// Parses the command line arguments, without the program name.
+///
+/// # Errors
+///
+/// Returns a description of an unknown option or an option missing its value.
+pub fn parse(arguments: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = Args::default();
+    let mut arguments = arguments.into_iter();
+    let mut only_files = false;
+
+    while let Some(argument) = arguments.next() {
+        if only_files {
+            args.files.push(argument);
+            continue;
+        }
+
+        // Options with a value take it from the next argument or after `=`
+        let (option, inline_value) = match argument.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => {
+                (option.to_string(), Some(value.to_string()))
+            }
+            _ => (argument.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .clone()
+                .or_else(|| arguments.next())
+                .ok_or_else(|| format!("{name} needs a value"))
+        };
+
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-R" | "--readonly" => args.read_only = true,
+            "-c" | "--config" => args.config = Some(PathBuf::from(value(&option)?)),
+            "-t" | "--theme" => args.theme = Some(value(&option)?),
+            "--tutor" => args.tutor = true,
+            "--" => only_files = true,
+            _ => {
+                if let Some(line) = argument.strip_prefix('+') {
+                    let line = line
+                        .parse()
+                        .map_err(|err| format!("invalid line number {argument}: {err}"))?;
+                    args.line = Some(line);
+                } else if argument.starts_with('-') && argument != "-" {
+                    return Err(format!("unknown option {argument}"));
+                } else {
+                    args.files.push(argument);
+                }
+            }
+        }
+    }
+
+    Ok(Command::Run(args))
+}
@@ -0,0 +1,93 @@
+//! The clipboard of the system, reached through the commands each platform has
+//! for it, since terminals have no portable way to read it. Where none of them is
+//! installed, copied text stays inside the editor.
+use std::{
+    env,
+    io::Write as _,
+    process::{Command, Stdio},
+};
+
+/// Puts `text` on the system clipboard. Returns whether a command took it.
+pub fn copy(text: &str) -> bool {
+    copy_commands().iter().any(|&(program, args)| {
+        let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+        // Dropping stdin closes it, so the command knows the text is complete
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    })
+}
+
+/// The text on the system clipboard with its line endings made `\n`, or `None`
+/// when it can't be read.
+pub fn paste() -> Option<String> {
+    paste_commands().iter().find_map(|&(program, args)| {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"))
+    })
+}
+
+/// The commands that copy their input, in the order they're tried.
+fn copy_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(windows) {
+        // clip.exe reads the console code page, so non-ASCII text goes through PowerShell
+        vec![(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+            ][..],
+        )]
+    } else if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[][..])]
+    } else {
+        let mut commands = Vec::new();
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(("wl-copy", &[][..]));
+        }
+        commands.push(("xclip", &["-selection", "clipboard", "-in"][..]));
+        commands.push(("xsel", &["--clipboard", "--input"][..]));
+        commands
+    }
+}
+
+/// The commands that write out the clipboard, in the order they're tried.
+fn paste_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(windows) {
+        vec![(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "[Console]::OutputEncoding = [Text.Encoding]::UTF8; [Console]::Out.Write((Get-Clipboard -Raw))",
+            ][..],
+        )]
+    } else if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[][..])]
+    } else {
+        let mut commands = Vec::new();
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(("wl-paste", &["--no-newline"][..]));
+        }
+        commands.push(("xclip", &["-selection", "clipboard", "-out"][..]));
+        commands.push(("xsel", &["--clipboard", "--output"][..]));
+        commands
+    }
+}
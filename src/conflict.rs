@@ -0,0 +1,202 @@
+//! Merge conflicts left in a file by git, between `<<<<<<<` and `>>>>>>>` markers.
+use std::ops::Range;
+
+use voider_core::{Document, Row};
+
+/// The rows of one conflict block.
+///
+/// # Fields
+///
+/// - `start`: The `<<<<<<<` row, followed by our side.
+/// - `base`: The `|||||||` row that starts the common ancestor, in the diff3 style.
+/// - `separator`: The `=======` row, followed by their side.
+/// - `end`: The `>>>>>>>` row.
+pub struct Conflict {
+    pub start: usize,
+    pub base: Option<usize>,
+    pub separator: usize,
+    pub end: usize,
+}
+
+/// The part of a conflict a row belongs to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    Marker,
+    Ours,
+    Base,
+    Theirs,
+}
+
+impl Conflict {
+    /// Our side, the version of the branch being merged into.
+    pub fn ours(&self) -> Range<usize> {
+        self.start.saturating_add(1)..self.base.unwrap_or(self.separator)
+    }
+
+    /// Their side, the version being merged in.
+    pub fn theirs(&self) -> Range<usize> {
+        self.separator.saturating_add(1)..self.end
+    }
+
+    /// Every row of the block, markers included.
+    pub fn rows(&self) -> Range<usize> {
+        self.start..self.end.saturating_add(1)
+    }
+
+    fn region(&self, row: usize) -> Option<Region> {
+        if row == self.start || Some(row) == self.base || row == self.separator || row == self.end {
+            Some(Region::Marker)
+        } else if self.ours().contains(&row) {
+            Some(Region::Ours)
+        } else if self.theirs().contains(&row) {
+            Some(Region::Theirs)
+        } else {
+            self.rows().contains(&row).then_some(Region::Base)
+        }
+    }
+}
+
+/// The conflicts of a document, found again only when it changes.
+#[derive(Default)]
+pub struct Conflicts {
+    seen: Option<(Option<String>, usize)>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Conflicts {
+    /// Brings the conflicts up to date with `document`. Returns whether it's a file
+    /// that wasn't looked at just before and has conflicts, so they can be pointed out.
+    pub fn update(&mut self, document: &Document) -> bool {
+        let seen = (document.file_name.clone(), document.revision());
+        if self.seen.as_ref() == Some(&seen) {
+            return false;
+        }
+
+        let other_file = self
+            .seen
+            .as_ref()
+            .is_none_or(|(file_name, _)| *file_name != seen.0);
+        self.seen = Some(seen);
+        self.conflicts = find((0..document.len()).filter_map(|index| document.row(index)));
+        other_file && !self.conflicts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.conflicts.len()
+    }
+
+    /// The conflict a row is part of.
+    pub fn at(&self, row: usize) -> Option<&Conflict> {
+        self.conflicts
+            .iter()
+            .find(|conflict| conflict.rows().contains(&row))
+    }
+
+    /// The part of a conflict a row belongs to, if any.
+    pub fn region(&self, row: usize) -> Option<Region> {
+        self.at(row).and_then(|conflict| conflict.region(row))
+    }
+}
+
+/// Finds the complete conflict blocks among the rows. Markers that don't make up a
+/// block, such as a lone `=======` underlining a heading, are left alone.
+fn find<'a>(rows: impl Iterator<Item = &'a Row>) -> Vec<Conflict> {
+    let is_marker = |line: &str, marker: &str| {
+        line.strip_prefix(marker)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+
+    let mut conflicts = Vec::new();
+    let mut start = None;
+    let mut base = None;
+    let mut separator = None;
+    for (index, row) in rows.enumerate() {
+        let line = row.as_str().trim_end();
+        if is_marker(line, "<<<<<<<") {
+            start = Some(index);
+            base = None;
+            separator = None;
+        } else if start.is_some() && separator.is_none() && is_marker(line, "|||||||") {
+            base = Some(index);
+        } else if start.is_some() && separator.is_none() && line == "=======" {
+            separator = Some(index);
+        } else if is_marker(line, ">>>>>>>") {
+            if let (Some(start), Some(separator)) = (start, separator) {
+                conflicts.push(Conflict {
+                    start,
+                    base,
+                    separator,
+                    end: index,
+                });
+            }
+            start = None;
+            base = None;
+            separator = None;
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflicts(text: &str) -> Vec<(usize, Option<usize>, usize, usize)> {
+        let rows: Vec<Row> = text.lines().map(Row::from).collect();
+        find(rows.iter())
+            .iter()
+            .map(|conflict| {
+                (
+                    conflict.start,
+                    conflict.base,
+                    conflict.separator,
+                    conflict.end,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_two_way_and_diff3_blocks() {
+        let text = "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> branch\nb\n\
+                    <<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> branch";
+        assert_eq!(conflicts(text), [(1, None, 3, 5), (7, Some(9), 11, 13)]);
+
+        let rows: Vec<Row> = text.lines().map(Row::from).collect();
+        let found = find(rows.iter());
+        let regions: Vec<Option<Region>> = (7..=13)
+            .map(|row| found.get(1).and_then(|conflict| conflict.region(row)))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                Some(Region::Marker),
+                Some(Region::Ours),
+                Some(Region::Marker),
+                Some(Region::Base),
+                Some(Region::Marker),
+                Some(Region::Theirs),
+                Some(Region::Marker),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_nested_start_begins_the_block_again() {
+        let text = "<<<<<<< outer\nx\n<<<<<<< inner\nours\n=======\ntheirs\n>>>>>>> inner\n\
+                    =======\ny\n>>>>>>> outer";
+        assert_eq!(conflicts(text), [(2, None, 4, 6)]);
+    }
+
+    #[test]
+    fn leaves_out_unterminated_blocks_and_stray_markers() {
+        assert!(conflicts("Title\n=======\n>>>>>>> stray").is_empty());
+        assert!(conflicts("<<<<<<< HEAD\n>>>>>>> no separator").is_empty());
+        assert!(conflicts("a\n<<<<<<< HEAD\nours\n=======\ntheirs").is_empty());
+        assert_eq!(
+            conflicts("<<<<<<< HEAD\nours\n<<<<<<< HEAD\na\n=======\nb\n>>>>>>> x"),
+            [(2, None, 4, 6)]
+        );
+    }
+}
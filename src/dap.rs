@@ -0,0 +1,649 @@
+//! A client for debug adapters, which run a program under a debugger for the editor.
+//! An adapter runs as a child process speaking the Debug Adapter Protocol over its
+//! stdin and stdout, framed the way language servers frame their messages.
+use std::{
+    io::{BufReader, Write as _},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+use voider_core::command::Step;
+
+use crate::{hooks, lsp};
+
+/// How long an adapter may take to answer a request before the editor gives up on it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an adapter gets to stop the program and exit once the session ends.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The values shown for each scope at most, as some scopes hold hundreds.
+const VARIABLE_LIMIT: usize = 100;
+
+/// Where the program is paused.
+///
+/// # Fields
+///
+/// - `thread`: The thread that stopped, which the stepping commands move on.
+/// - `function`: The name of the function it stopped in.
+/// - `path`: The source file it stopped in, when the adapter knows it.
+/// - `row`: The index of the line it stopped at.
+/// - `variables`: The values in scope, as `name = value` lines.
+pub struct Pause {
+    pub thread: u64,
+    pub function: String,
+    pub path: Option<PathBuf>,
+    pub row: usize,
+    pub variables: Vec<String>,
+}
+
+/// What happened to the program since the session was last polled.
+pub enum Event {
+    /// The adapter set up the session and launched the program.
+    Started,
+    /// It paused for the reason given, see `Session::pause` for where.
+    Stopped(String),
+    /// It runs again after pausing.
+    Continued,
+    /// Text it or the adapter printed.
+    Output(String),
+    /// A request to the adapter failed, with the adapter's message.
+    Failed(String),
+    /// It exited, and with it the session.
+    Ended,
+}
+
+/// What the session asks of the thread talking to the adapter.
+enum Command {
+    SetBreakpoints(PathBuf, Vec<usize>),
+    /// Runs the paused thread with the request named, such as `continue`.
+    Go(&'static str, u64),
+    Stop,
+}
+
+/// What the thread talking to the adapter waits for: messages of the adapter and
+/// commands of the session, in the order they came.
+enum Incoming {
+    Message(Value),
+    Command(Command),
+    /// The adapter closed its output, so it exited.
+    Exited,
+}
+
+/// What the thread talking to the adapter tells the session.
+enum Report {
+    Event(Event),
+    /// The program paused at `pause`, for the reason given.
+    Paused(String, Pause),
+}
+
+/// A program being debugged, along with the adapter running it. The adapter is
+/// talked to on a thread of its own, since working out where the program paused
+/// takes several requests, so the session only passes commands on and collects
+/// what happened.
+///
+/// # Fields
+///
+/// - `commands`: Passes commands on to the thread talking to the adapter.
+/// - `reports`: What that thread found out, handed out by `poll`.
+/// - `started`: Whether the adapter set up the session.
+/// - `pause`: Where the program is paused, while it is.
+pub struct Session {
+    commands: Sender<Incoming>,
+    reports: Receiver<Report>,
+    worker: Option<JoinHandle<()>>,
+    started: bool,
+    pause: Option<Pause>,
+}
+
+/// The adapter of a session, talked to on a thread of its own.
+///
+/// # Fields
+///
+/// - `pending`: Messages that came in while waiting for an answer, handled once
+///   it's in.
+/// - `queued`: Commands that came in while waiting for an answer.
+/// - `stopping`: Whether the session asked to stop, so waiting gives up.
+/// - `exited`: Whether the adapter exited.
+struct Adapter {
+    child: Child,
+    stdin: ChildStdin,
+    incoming: Receiver<Incoming>,
+    reports: Sender<Report>,
+    seq: u64,
+    pending: Vec<Value>,
+    queued: Vec<Command>,
+    stopping: bool,
+    exited: bool,
+}
+
+impl Session {
+    /// Starts the adapter `command` in `root`, which then launches the program with
+    /// the adapter-specific `launch` settings, stopping at `breakpoints`, the row
+    /// indices for each file. Setting up the session goes on in the background,
+    /// `poll` hands out `Event::Started` once it's done or why it failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the adapter can't be started.
+    pub fn start(
+        command: &str,
+        root: &Path,
+        launch: &Value,
+        breakpoints: Vec<(PathBuf, Vec<usize>)>,
+    ) -> Result<Self, String> {
+        let mut child = hooks::shell(command)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("could not start {command}: {err}"))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(format!("could not talk to {command}"));
+        };
+
+        let (commands, incoming) = mpsc::channel();
+        let messages = commands.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(message) = lsp::read_message(&mut reader) {
+                if messages.send(Incoming::Message(message)).is_err() {
+                    return;
+                }
+            }
+            messages.send(Incoming::Exited).ok();
+        });
+
+        let (reports, received) = mpsc::channel();
+        let adapter = Adapter {
+            child,
+            stdin,
+            incoming,
+            reports,
+            seq: 0,
+            pending: Vec::new(),
+            queued: Vec::new(),
+            stopping: false,
+            exited: false,
+        };
+        let launch = launch.clone();
+        let worker = thread::spawn(move || adapter.run(&launch, &breakpoints));
+
+        Ok(Self {
+            commands,
+            reports: received,
+            worker: Some(worker),
+            started: false,
+            pause: None,
+        })
+    }
+
+    /// Whether the adapter set up the session, so a program is being debugged.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Where the program is paused, while it is.
+    pub fn pause(&self) -> Option<&Pause> {
+        self.pause.as_ref()
+    }
+
+    /// Replaces the breakpoints of the file at `path` with the ones on `rows`. When
+    /// the adapter refuses them, `poll` hands out the reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the session ended.
+    pub fn set_breakpoints(&mut self, path: &Path, rows: &[usize]) -> Result<(), String> {
+        self.send(Command::SetBreakpoints(path.to_path_buf(), rows.to_vec()))
+    }
+
+    /// Lets the paused program run until a breakpoint or its end.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when it isn't paused or the session
+    /// ended.
+    pub fn resume(&mut self) -> Result<(), String> {
+        self.go("continue")
+    }
+
+    /// Runs the paused program for one `step`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when it isn't paused or the session
+    /// ended.
+    pub fn step(&mut self, step: Step) -> Result<(), String> {
+        self.go(match step {
+            Step::Over => "next",
+            Step::Into => "stepIn",
+            Step::Out => "stepOut",
+        })
+    }
+
+    fn go(&mut self, command: &'static str) -> Result<(), String> {
+        let Some(thread) = self.pause.as_ref().map(|pause| pause.thread) else {
+            return Err("the program isn't paused".to_string());
+        };
+        self.send(Command::Go(command, thread))?;
+        self.pause = None;
+        Ok(())
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        if self.commands.send(Incoming::Command(command)).is_err() {
+            return Err("the debug session ended".to_string());
+        }
+        Ok(())
+    }
+
+    /// Takes what happened to the program since the last call, without waiting.
+    /// When it paused, `pause` is updated before the event is handed out.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        loop {
+            let event = match self.reports.try_recv() {
+                Ok(Report::Paused(reason, pause)) => {
+                    self.pause = Some(pause);
+                    Event::Stopped(reason)
+                }
+                Ok(Report::Event(event)) => event,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !events.iter().any(|event| matches!(event, Event::Ended)) {
+                        events.push(Event::Ended);
+                    }
+                    break;
+                }
+            };
+            match event {
+                Event::Started => self.started = true,
+                Event::Continued | Event::Ended => self.pause = None,
+                _ => {}
+            }
+            events.push(event);
+        }
+        events
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.commands.send(Incoming::Command(Command::Stop)).ok();
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+impl Adapter {
+    /// Sets up the session, then handles the adapter's messages and the session's
+    /// commands until either ends it.
+    fn run(mut self, launch: &Value, breakpoints: &[(PathBuf, Vec<usize>)]) {
+        match self.set_up(launch, breakpoints) {
+            Ok(()) => self.report(Event::Started),
+            Err(_) if self.stopping => {}
+            Err(err) => self.report(Event::Failed(format!("could not start: {err}"))),
+        }
+
+        while !self.stopping && !self.exited {
+            let incoming = if !self.pending.is_empty() {
+                Incoming::Message(self.pending.remove(0))
+            } else if !self.queued.is_empty() {
+                Incoming::Command(self.queued.remove(0))
+            } else {
+                match self.incoming.recv() {
+                    Ok(incoming) => incoming,
+                    Err(_) => break,
+                }
+            };
+            let result = match incoming {
+                Incoming::Message(message) => {
+                    self.handle(&message);
+                    Ok(())
+                }
+                Incoming::Command(Command::SetBreakpoints(path, rows)) => {
+                    self.set_breakpoints(&path, &rows)
+                }
+                Incoming::Command(Command::Go(command, thread)) => self
+                    .request(command, json!({ "threadId": thread }))
+                    .map(|_| ()),
+                Incoming::Command(Command::Stop) => {
+                    self.stopping = true;
+                    Ok(())
+                }
+                Incoming::Exited => {
+                    self.exited = true;
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                if !self.stopping {
+                    self.report(Event::Failed(err));
+                }
+            }
+        }
+
+        if !self.exited {
+            self.send_request("disconnect", json!({ "terminateDebuggee": true }))
+                .ok();
+        }
+        hooks::wait(&mut self.child, SHUTDOWN_TIMEOUT).ok();
+        self.report(Event::Ended);
+    }
+
+    /// Has the adapter launch the program, stopping at `breakpoints`.
+    fn set_up(
+        &mut self,
+        launch: &Value,
+        breakpoints: &[(PathBuf, Vec<usize>)],
+    ) -> Result<(), String> {
+        let capabilities = self.request(
+            "initialize",
+            json!({
+                "clientID": "voider",
+                "clientName": "voider",
+                "adapterID": "voider",
+                "pathFormat": "path",
+                "linesStartAt1": true,
+                "columnsStartAt1": true,
+                "supportsVariableType": true,
+            }),
+        )?;
+
+        // Adapters answer the launch request only once they're configured, which
+        // they ask for with the `initialized` event
+        let launch = match launch {
+            Value::Null => json!({}),
+            launch => launch.clone(),
+        };
+        self.send_request("launch", launch)?;
+        self.wait_for("the initialized event", |message| {
+            message.get("event").and_then(Value::as_str) == Some("initialized")
+        })?;
+        for (path, rows) in breakpoints {
+            self.set_breakpoints(path, rows)?;
+        }
+        if capabilities
+            .get("supportsConfigurationDoneRequest")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            self.request("configurationDone", json!({}))?;
+        }
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, path: &Path, rows: &[usize]) -> Result<(), String> {
+        let breakpoints: Vec<Value> = rows
+            .iter()
+            .map(|row| json!({ "line": row.saturating_add(1) }))
+            .collect();
+        self.request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": lsp::absolute(path) },
+                "breakpoints": breakpoints,
+            }),
+        )
+        .map(|_| ())
+    }
+
+    fn report(&self, event: Event) {
+        self.reports.send(Report::Event(event)).ok();
+    }
+
+    /// Handles a message of the adapter that isn't an answer being waited for.
+    fn handle(&mut self, message: &Value) {
+        match message.get("type").and_then(Value::as_str) {
+            Some("event") => self.event(message),
+            Some("request") => self.refuse(message),
+            Some("response") if !succeeded(message) => {
+                self.report(Event::Failed(failure(message)));
+            }
+            _ => {}
+        }
+    }
+
+    /// Tells the session what an event of the adapter means for it, if anything.
+    fn event(&mut self, message: &Value) {
+        let body = message.get("body").cloned().unwrap_or(Value::Null);
+        match message.get("event").and_then(Value::as_str) {
+            Some("stopped") => {
+                let reason = body
+                    .get("reason")
+                    .and_then(Value::as_str)
+                    .unwrap_or("paused")
+                    .to_string();
+                let thread = body.get("threadId").and_then(Value::as_u64);
+                match self.inspect(thread) {
+                    Ok(pause) => {
+                        self.reports.send(Report::Paused(reason, pause)).ok();
+                    }
+                    Err(_) if self.stopping || self.exited => {}
+                    Err(err) => {
+                        self.report(Event::Failed(format!(
+                            "could not see where it stopped: {err}"
+                        )));
+                    }
+                }
+            }
+            Some("continued") => self.report(Event::Continued),
+            Some("output") if body.get("category").and_then(Value::as_str) != Some("telemetry") => {
+                if let Some(output) = body.get("output").and_then(Value::as_str) {
+                    self.report(Event::Output(output.to_string()));
+                }
+            }
+            Some("terminated" | "exited") => self.stopping = true,
+            _ => {}
+        }
+    }
+
+    /// Finds out where a stopped thread is and what the values in scope there are.
+    /// Without a thread, the first one the adapter lists is taken.
+    fn inspect(&mut self, thread: Option<u64>) -> Result<Pause, String> {
+        let thread = match thread {
+            Some(thread) => thread,
+            None => self
+                .request("threads", json!({}))?
+                .pointer("/threads/0/id")
+                .and_then(Value::as_u64)
+                .ok_or("no thread is running")?,
+        };
+        let trace = self.request(
+            "stackTrace",
+            json!({ "threadId": thread, "startFrame": 0, "levels": 1 }),
+        )?;
+        let frame = trace
+            .pointer("/stackFrames/0")
+            .cloned()
+            .ok_or("the thread has no stack")?;
+
+        let mut variables = Vec::new();
+        if let Some(id) = frame.get("id").and_then(Value::as_u64) {
+            let scopes = self.request("scopes", json!({ "frameId": id }))?;
+            let scopes = scopes
+                .get("scopes")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            // Scopes the adapter calls expensive, such as globals, aren't asked for
+            let scopes: Vec<&Value> = scopes
+                .iter()
+                .filter(|scope| {
+                    !scope
+                        .get("expensive")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false)
+                })
+                .collect();
+            for scope in &scopes {
+                let Some(reference) = scope.get("variablesReference").and_then(Value::as_u64)
+                else {
+                    continue;
+                };
+                if scopes.len() > 1 {
+                    variables.push(
+                        scope
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .unwrap_or("Scope")
+                            .to_string(),
+                    );
+                }
+                let indent = if scopes.len() > 1 { "  " } else { "" };
+                let values =
+                    self.request("variables", json!({ "variablesReference": reference }))?;
+                variables.extend(
+                    values
+                        .get("variables")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .take(VARIABLE_LIMIT)
+                        .map(|variable| {
+                            let text =
+                                |key: &str| variable.get(key).and_then(Value::as_str).unwrap_or("");
+                            // Values such as structs can span lines, only the first is kept
+                            let value = text("value").lines().next().unwrap_or("");
+                            format!("{indent}{} = {value}", text("name"))
+                        }),
+                );
+            }
+        }
+
+        Ok(Pause {
+            thread,
+            function: frame
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string(),
+            path: frame
+                .pointer("/source/path")
+                .and_then(Value::as_str)
+                .map(PathBuf::from),
+            row: frame
+                .get("line")
+                .and_then(Value::as_u64)
+                .and_then(|line| usize::try_from(line).ok())
+                .unwrap_or(1)
+                .saturating_sub(1),
+            variables,
+        })
+    }
+
+    /// Sends a request and waits for its answer, returning its body.
+    ///
+    /// # Errors
+    ///
+    /// Returns the adapter's message when the request failed, or a description of
+    /// the problem when there's no answer.
+    fn request(&mut self, command: &str, arguments: Value) -> Result<Value, String> {
+        let seq = self.send_request(command, arguments)?;
+        let response = self.wait_for(command, |message| {
+            message.get("type").and_then(Value::as_str) == Some("response")
+                && message.get("request_seq").and_then(Value::as_u64) == Some(seq)
+        })?;
+        if !succeeded(&response) {
+            return Err(failure(&response));
+        }
+        Ok(response.get("body").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Waits for the message `wanted` picks, answering the adapter's requests and
+    /// keeping its other messages and the session's commands for later meanwhile.
+    /// Gives up when the session stops or the adapter exits.
+    fn wait_for(&mut self, what: &str, wanted: impl Fn(&Value) -> bool) -> Result<Value, String> {
+        let start = Instant::now();
+        loop {
+            let message = match self
+                .incoming
+                .recv_timeout(TIMEOUT.saturating_sub(start.elapsed()))
+            {
+                Ok(Incoming::Message(message)) => message,
+                Ok(Incoming::Command(Command::Stop)) => {
+                    self.stopping = true;
+                    return Err("the debug session was stopped".to_string());
+                }
+                Ok(Incoming::Command(command)) => {
+                    self.queued.push(command);
+                    continue;
+                }
+                Ok(Incoming::Exited) | Err(RecvTimeoutError::Disconnected) => {
+                    self.exited = true;
+                    return Err("the debug adapter exited".to_string());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("the debug adapter didn't answer {what} in time"))
+                }
+            };
+
+            if wanted(&message) {
+                return Ok(message);
+            }
+            match message.get("type").and_then(Value::as_str) {
+                Some("request") => self.refuse(&message),
+                _ => self.pending.push(message),
+            }
+        }
+    }
+
+    /// Answers a request of the adapter, such as to run the program in a terminal,
+    /// with a refusal, as the editor has nowhere to do it.
+    fn refuse(&mut self, request: &Value) {
+        let answer = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request.get("seq").cloned().unwrap_or(Value::Null),
+            "command": request.get("command").cloned().unwrap_or(Value::Null),
+            "success": false,
+            "message": "not supported",
+        });
+        self.send(&answer).ok();
+    }
+
+    fn send_request(&mut self, command: &str, arguments: Value) -> Result<u64, String> {
+        let seq = self.next_seq();
+        let request = json!({ "seq": seq, "type": "request", "command": command });
+        self.send(&lsp::with_field(request, "arguments", arguments))?;
+        Ok(seq)
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq = self.seq.saturating_add(1);
+        self.seq
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())
+            .and_then(|()| self.stdin.flush())
+            .map_err(|err| format!("could not write to the debug adapter: {err}"))
+    }
+}
+
+fn succeeded(response: &Value) -> bool {
+    response
+        .get("success")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// The message of a failed response, or its command when it has none.
+fn failure(response: &Value) -> String {
+    let command = response
+        .get("command")
+        .and_then(Value::as_str)
+        .unwrap_or("the request");
+    match response.get("message").and_then(Value::as_str) {
+        Some(message) => format!("{command} failed: {message}"),
+        None => format!("{command} failed"),
+    }
+}
@@ -0,0 +1,311 @@
+//! Line diffs, for comparing a buffer with another version of its file.
+use std::ops::Range;
+
+/// Beyond this many inserted and removed lines the differing middle of two texts is
+/// reported as one hunk, since finding the exact edits gets slow.
+const MAX_EDITS: usize = 1000;
+
+/// A run of lines that differ between an old and a new text.
+///
+/// # Fields
+///
+/// - `old`: The lines of the old text it replaces, empty when lines were only added.
+/// - `new`: The lines of the new text, empty when lines were only removed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Finds the hunks that turn `old` into `new`, in order, using the fewest changed
+/// lines.
+pub fn hunks<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let old_rest = old.get(prefix..).unwrap_or_default();
+    let new_rest = new.get(prefix..).unwrap_or_default();
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = old_rest
+        .get(..old_rest.len().saturating_sub(suffix))
+        .unwrap_or_default();
+    let b = new_rest
+        .get(..new_rest.len().saturating_sub(suffix))
+        .unwrap_or_default();
+
+    let common = common_lines(a, b).unwrap_or_default();
+    let mut hunks = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    for (next_x, next_y) in common.into_iter().chain([(a.len(), b.len())]) {
+        if next_x > x || next_y > y {
+            hunks.push(Hunk {
+                old: prefix.saturating_add(x)..prefix.saturating_add(next_x),
+                new: prefix.saturating_add(y)..prefix.saturating_add(next_y),
+            });
+        }
+        x = next_x.saturating_add(1);
+        y = next_y.saturating_add(1);
+    }
+
+    hunks
+}
+
+/// The pairs of equal lines, as indices into `a` and `b`, that a shortest edit script
+/// keeps, found with Myers' algorithm. Returns `None` when the texts need more than
+/// `MAX_EDITS` edits.
+#[allow(clippy::many_single_char_names)]
+fn common_lines<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (a.len(), b.len());
+    // `trace[d]` holds the furthest `x` reached on each diagonal `k` with `d` edits,
+    // at index `k + d`
+    let mut trace: Vec<Vec<usize>> = Vec::new();
+    let furthest = |row: &[usize], d: usize, k: isize| -> usize {
+        k.checked_add_unsigned(d)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| row.get(index).copied())
+            .unwrap_or(0)
+    };
+
+    'search: for d in 0..=n.saturating_add(m).min(MAX_EDITS) {
+        let mut row = vec![0; d.saturating_mul(2).saturating_add(1)];
+        let d_signed = isize::try_from(d).ok()?;
+        let lowest = d_signed.checked_neg()?;
+        for k in (lowest..=d_signed).step_by(2) {
+            let mut x = match trace.last() {
+                None => 0,
+                Some(previous) => {
+                    let before = d.saturating_sub(1);
+                    if k == lowest
+                        || (k != d_signed
+                            && furthest(previous, before, k.saturating_sub(1))
+                                < furthest(previous, before, k.saturating_add(1)))
+                    {
+                        furthest(previous, before, k.saturating_add(1))
+                    } else {
+                        furthest(previous, before, k.saturating_sub(1)).saturating_add(1)
+                    }
+                }
+            };
+            let mut y = x.checked_add_signed(k.checked_neg()?)?;
+            while x < n && y < m && a.get(x) == b.get(y) {
+                x = x.saturating_add(1);
+                y = y.saturating_add(1);
+            }
+            if let Some(slot) = k
+                .checked_add_unsigned(d)
+                .and_then(|index| usize::try_from(index).ok())
+                .and_then(|index| row.get_mut(index))
+            {
+                *slot = x;
+            }
+
+            if x >= n && y >= m {
+                trace.push(row);
+                break 'search;
+            }
+        }
+        trace.push(row);
+    }
+
+    let (mut x, mut y) = (n, m);
+    let mut common = Vec::new();
+    let last = trace.len().checked_sub(1)?;
+    if furthest(
+        trace.get(last)?,
+        last,
+        isize::try_from(n)
+            .ok()?
+            .checked_sub(isize::try_from(m).ok()?)?,
+    ) < n
+    {
+        return None;
+    }
+
+    for d in (1..=last).rev() {
+        let previous = trace.get(d.saturating_sub(1))?;
+        let before = d.saturating_sub(1);
+        let d_signed = isize::try_from(d).ok()?;
+        let k = isize::try_from(x)
+            .ok()?
+            .checked_sub(isize::try_from(y).ok()?)?;
+        let previous_k = if Some(k) == d_signed.checked_neg()
+            || (k != d_signed
+                && furthest(previous, before, k.saturating_sub(1))
+                    < furthest(previous, before, k.saturating_add(1)))
+        {
+            k.saturating_add(1)
+        } else {
+            k.saturating_sub(1)
+        };
+        let previous_x = furthest(previous, before, previous_k);
+        let previous_y = previous_x.checked_add_signed(previous_k.checked_neg()?)?;
+
+        while x > previous_x && y > previous_y {
+            x = x.saturating_sub(1);
+            y = y.saturating_sub(1);
+            common.push((x, y));
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    while x > 0 && y > 0 {
+        x = x.saturating_sub(1);
+        y = y.saturating_sub(1);
+        common.push((x, y));
+    }
+
+    common.reverse();
+    Some(common)
+}
+
+/// Writes the changes from `old` to `new` as the hunks of a unified diff, each with
+/// up to `context` unchanged lines around it. Hunks whose context would overlap are
+/// joined.
+pub fn unified(old: &[&str], new: &[&str], context: usize) -> Vec<String> {
+    let hunks = hunks(old, new);
+    let mut lines = Vec::new();
+    let mut first = 0;
+
+    while let Some(start) = hunks.get(first) {
+        let mut last = first;
+        while let (Some(current), Some(next)) = (hunks.get(last), hunks.get(last.saturating_add(1)))
+        {
+            if next.old.start.saturating_sub(current.old.end) > context.saturating_mul(2) {
+                break;
+            }
+            last = last.saturating_add(1);
+        }
+        let Some(end) = hunks.get(last) else {
+            break;
+        };
+
+        let old_start = start.old.start.saturating_sub(context);
+        let new_start = start.new.start.saturating_sub(context);
+        let old_end = end.old.end.saturating_add(context).min(old.len());
+        let new_end = end.new.end.saturating_add(context).min(new.len());
+        lines.push(format!(
+            "@@ -{} +{} @@",
+            range(old_start, old_end.saturating_sub(old_start)),
+            range(new_start, new_end.saturating_sub(new_start))
+        ));
+
+        let unchanged = |range: Range<usize>, lines: &mut Vec<String>| {
+            lines.extend(
+                old.get(range)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|line| format!(" {line}")),
+            );
+        };
+        let mut position = old_start;
+        for hunk in hunks.get(first..=last).unwrap_or_default() {
+            unchanged(position..hunk.old.start, &mut lines);
+            lines.extend(
+                old.get(hunk.old.clone())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|line| format!("-{line}")),
+            );
+            lines.extend(
+                new.get(hunk.new.clone())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|line| format!("+{line}")),
+            );
+            position = hunk.old.end;
+        }
+        unchanged(position..old_end, &mut lines);
+
+        first = last.saturating_add(1);
+    }
+
+    lines
+}
+
+/// A line range in a hunk header, which names the line before an empty range.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => start.saturating_add(1).to_string(),
+        _ => format!("{},{len}", start.saturating_add(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old: Range<usize>, new: Range<usize>) -> Hunk {
+        Hunk { old, new }
+    }
+
+    #[test]
+    fn identical_and_empty_texts_have_no_hunks() {
+        assert!(hunks(&["a", "b"], &["a", "b"]).is_empty());
+        assert!(hunks::<&str>(&[], &[]).is_empty());
+        assert_eq!(hunks(&[], &["a", "b"]), [hunk(0..0, 0..2)]);
+        assert_eq!(hunks(&["a", "b"], &[]), [hunk(0..2, 0..0)]);
+    }
+
+    #[test]
+    fn finds_inserted_deleted_and_replaced_lines() {
+        assert_eq!(hunks(&["a", "c"], &["a", "b", "c"]), [hunk(1..1, 1..2)]);
+        assert_eq!(hunks(&["a", "b", "c"], &["a", "c"]), [hunk(1..2, 1..1)]);
+        assert_eq!(
+            hunks(&["a", "b", "c"], &["a", "x", "c"]),
+            [hunk(1..2, 1..2)]
+        );
+        assert_eq!(
+            hunks(&["a", "b", "c", "d", "e"], &["x", "b", "c", "e", "y"]),
+            [hunk(0..1, 0..1), hunk(3..4, 3..3), hunk(5..5, 4..5)]
+        );
+    }
+
+    #[test]
+    fn common_lines_are_a_longest_common_subsequence() {
+        assert_eq!(
+            common_lines(
+                &["a", "b", "c", "a", "b", "b", "a"],
+                &["c", "b", "a", "b", "a", "c"]
+            )
+            .map(|common| common.len()),
+            Some(4)
+        );
+        assert_eq!(common_lines(&["a"], &["b"]), Some(Vec::new()));
+        assert_eq!(
+            common_lines(&["x", "a", "y"], &["a", "z"]),
+            Some(vec![(1, 0)])
+        );
+    }
+
+    #[test]
+    fn writes_unified_hunks_with_context() {
+        let old = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+        let new = ["1", "two", "3", "4", "5", "6", "7", "8", "10"];
+
+        assert_eq!(
+            unified(&old, &new, 1),
+            [
+                "@@ -1,3 +1,3 @@",
+                " 1",
+                "-2",
+                "+two",
+                " 3",
+                "@@ -8,3 +8,2 @@",
+                " 8",
+                "-9",
+                " 10",
+            ]
+        );
+        // With more context the two hunks share lines and are joined
+        assert_eq!(
+            unified(&old, &new, 3).first().map(String::as_str),
+            Some("@@ -1,10 +1,9 @@")
+        );
+        assert!(unified(&old, &old, 3).is_empty());
+        assert_eq!(unified(&[], &["a"], 3), ["@@ -0,0 +1 @@", "+a"]);
+    }
+}
@@ -1,615 +1,5991 @@
-use crossterm::{event::KeyCode, style::Color};
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    style::{style, Stylize},
+};
 use std::{
+    cmp::Ordering,
     env,
+    fmt::Write as _,
+    fs,
     io::{self},
+    ops::Range,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
-use crate::{terminal::Terminal, Document, Row};
-
-// Definition of two constants named STATUS_BG_COLOR and STATUS_FG_COLOR,
-// representing background and foreground colors.
-const STATUS_BG_COLOR: Color = Color::Rgb {
-    r: 63,
-    g: 63,
-    b: 63,
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use voider_core::{
+    command::{EditorCommand, Job, Motion, Movement, Resolution, Step},
+    Appended, Document, LineRange, Position, Row, SearchDirection, TextObject,
 };
-const STATUS_FG_COLOR: Color = Color::Rgb {
-    r: 239,
-    g: 239,
-    b: 239,
+
+use crate::{
+    backend::{Backend, Input},
+    cli::Args,
+    config::{self, Config, CursorShape},
+    conflict::{Conflicts, Region},
+    dap::{self, Session},
+    diff, fuzzy,
+    git::{Gutter, Marker, Repository},
+    hooks, icons,
+    keymap::Keymap,
+    lsp::{self, Asked, Client, Servers, TextEdit},
+    markdown,
+    notifications::{Notifications, Severity},
+    overlay::{Anchor, Overlay},
+    path_completion::PathCompleter,
+    project,
+    quickfix::Quickfix,
+    render,
+    repl::Repl,
+    signals,
+    state::State,
+    status::{Provider, Segment, Side, StatusLine},
+    symbols::{self, Outline, Syntax},
+    tags,
+    tasks::{self, Event, RunningJob, Tasks},
+    terminal::Terminal,
+    theme::{self, Theme},
+    tutor,
+    watcher::{self, Change, Watcher},
+    workspace::{self, Workspace},
 };
 
+#[cfg(feature = "headless")]
+use crate::headless;
+#[cfg(feature = "wasm")]
+use crate::plugins::Plugins;
+#[cfg(feature = "lua")]
+use crate::scripting::{Host, Scripts};
+
 // VERSION constant stores a reference to a string that holds the version information
 // obtained from the environment variable CARGO_PKG_VERSION. This likely represents the
 // version number of a Rust package or application.
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-// QUIT_TIMES constant is assigned the value 3 and represents a limit on the number
-// of allowed quit times. This value is an unsigned 8-bit integer (u8).
-const QUIT_TIMES: u8 = 3;
+// How often the editor wakes up to run its timers when no key is pressed.
+const TICK: Duration = Duration::from_millis(250);
 
-/// An enum representing the search direction.
-///
-/// This enum is used to indicate the direction of a search operation,
-/// and it can have two possible values: `Forward` and `Backward`.
-///
-/// - `Forward`: Represents a forward search direction.
-/// - `Backward`: Represents a backward search direction.
-///
-/// # Examples
-///
-/// ```
-/// use my_module::SearchDirection;
-///
-/// let direction = SearchDirection::Forward;
-/// assert_eq!(direction, SearchDirection::Forward);
-///
-/// let opposite_direction = SearchDirection::Backward;
-/// assert_eq!(opposite_direction, SearchDirection::Backward);
-/// ```
-#[derive(PartialEq, Clone, Copy)]
-pub enum SearchDirection {
-    Forward,
-    Backward,
+// How often the editor checks on background tasks while any are running.
+const TASK_POLL: Duration = Duration::from_millis(20);
+
+// A pause in typing longer than this starts a new undo step.
+const TYPING_PAUSE: Duration = Duration::from_secs(1);
+
+// The longest symbol shown in the outline panel, in characters.
+const OUTLINE_WIDTH: usize = 40;
+
+// The widest the documentation shown by the hover popup is wrapped to.
+const HOVER_WIDTH: usize = 72;
+
+// The unchanged lines shown around each change when comparing with the saved file.
+const DIFF_CONTEXT: usize = 3;
+
+// The most matches a picker lists, best first.
+const PICK_LIMIT: usize = 100;
+
+// The extensions of the files whose `#rrggbb` colors get a swatch.
+const SWATCH_EXTENSIONS: [&str; 12] = [
+    "css", "scss", "sass", "less", "toml", "json", "yaml", "yml", "ini", "conf", "cfg", "theme",
+];
+
+// Clicks closer together than this in the same place count as a double or triple click.
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+
+// The fewest columns a pane can be resized to.
+const MIN_PANE_WIDTH: usize = 10;
+
+// The pane showing the code sent to the REPL and what it printed.
+const REPL_PANE: &str = "[repl]";
+
+/// The answer to a yes/no question asked with `Editor::confirm`.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Choice {
+    Yes,
+    No,
+    Cancel,
 }
 
-/// A struct representing a 2D position.
-///
-/// This struct holds the X and Y coordinates of a point in a 2D space.
+/// The state of an in-progress word completion, kept while the complete key is pressed repeatedly.
 ///
 /// # Fields
 ///
-/// - `x`: The X coordinate, represented as a `usize`.
-/// - `y`: The Y coordinate, represented as a `usize`.
-///
-/// # Default
-///
-/// This struct implements the `Default` trait, allowing you to create instances
-/// with default values using `Position::default()`, which sets both `x` and `y` to 0.
-///
-/// # Clone
-///
-/// This struct implements the `Clone` trait, allowing you to create cloned copies
-/// of `Position` instances.
-///
-/// # Examples
-///
-/// ```
-/// use my_module::Position;
-///
-/// let position = Position { x: 10, y: 20 };
-/// assert_eq!(position.x, 10);
-/// assert_eq!(position.y, 20);
-///
-/// let default_position = Position::default();
-/// assert_eq!(default_position.x, 0);
-/// assert_eq!(default_position.y, 0);
-///
-/// let cloned_position = position.clone();
-/// assert_eq!(cloned_position, position);
-/// ```
-#[derive(Default, Clone)]
-pub struct Position {
-    pub x: usize,
-    pub y: usize,
+/// - `start`: Where the completed word starts in the document.
+/// - `prefix`: The text that was typed before asking for completions.
+/// - `candidates`: The matching words, nearest first.
+/// - `index`: The candidate currently inserted, or `candidates.len()` for the bare prefix.
+struct Completion {
+    start: Position,
+    prefix: String,
+    candidates: Vec<String>,
+    index: usize,
 }
 
-/// A struct representing a status message with text and a timestamp.
-///
-/// This struct holds a text message and the time it was created, represented
-/// as an `Instant` instance.
+/// A run of typing or deleting that is undone as a single step.
 ///
 /// # Fields
 ///
-/// - `text`: The text of the status message, represented as a `String`.
-/// - `time`: The timestamp when the status message was created, represented as an `Instant`.
-///
-/// # Examples
-///
-/// ```
-/// use my_module::StatusMessage;
-/// use std::time::Instant;
-///
-/// let timestamp = Instant::now();
-/// let message = StatusMessage {
-///     text: "Hello, World!".to_string(),
-///     time: timestamp,
-/// };
-///
-/// assert_eq!(message.text, "Hello, World!");
-/// assert_eq!(message.time, timestamp);
-/// ```
-///
-/// # Creation
+/// - `command`: What is being repeated, an insertion or one kind of deletion.
+/// - `before`: Where the cursor was when the run started.
+/// - `last`: When the run last grew, to end it after a pause.
+struct Typing {
+    command: std::mem::Discriminant<EditorCommand>,
+    before: Position,
+    last: Instant,
+}
+
+/// How long the parts of the last frame took, shown by the debug overlay.
 ///
-/// You can create a `StatusMessage` instance from a `String` message using the `from` method.
+/// # Fields
 ///
-/// ```
-/// use my_module::StatusMessage;
+/// - `highlight`: Highlighting the rows up to the bottom of the screen.
+/// - `render`: Drawing the rows.
+/// - `input`: Carrying out the key that led to the frame.
+/// - `rows`: How many document rows were drawn.
+#[derive(Default)]
+struct FrameStats {
+    highlight: Duration,
+    render: Duration,
+    input: Duration,
+    rows: usize,
+}
+
+/// An open file that isn't the one being edited right now.
 ///
-/// let message = StatusMessage::from("An important message".to_string());
-/// ```
-struct StatusMessage {
-    text: String,
-    time: Instant,
+/// The active buffer lives directly in the `Editor` fields; switching buffers swaps
+/// them with one of these.
+#[derive(Default)]
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+    selection_anchor: Option<Position>,
 }
 
-impl StatusMessage {
-    /// Creates a new `StatusMessage` instance from a given text message.
-    ///
-    /// This method takes a `String` message and generates a `StatusMessage` instance
-    /// with the provided message text and the current timestamp.
-    ///
-    /// # Arguments
-    ///
-    /// - `message`: A `String` containing the text message.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use my_module::StatusMessage;
-    ///
-    /// let message = StatusMessage::from("An important message".to_string());
-    /// ```
-    fn from(message: String) -> Self {
-        Self {
-            text: message,
-            time: Instant::now(),
-        }
-    }
+/// A second buffer shown beside the active one, the text area being split into a
+/// left and a right pane with a divider between them.
+///
+/// # Fields
+///
+/// - `buffer`: The index of the buffer shown in the pane that isn't active.
+/// - `width`: The columns of the left pane, the divider being the one after them.
+/// - `left`: Whether the active buffer is the one in the left pane.
+struct Split {
+    buffer: usize,
+    width: usize,
+    left: bool,
 }
 
 /// A struct representing a text editor.
 ///
 /// This struct encapsulates the state and functionality of a simple text editor.
 /// It contains various fields to store information such as the editor's terminal,
-/// cursor position, document, and status message, among others.
+/// cursor position, document, and notifications, among others.
 ///
 /// # Fields
 ///
 /// - `should_quit`: A boolean flag indicating whether the editor should quit.
-/// - `terminal`: An instance of the `Terminal` struct for interacting with the terminal.
+/// - `terminal`: The `Backend` drawn to and read from, the crossterm `Terminal` unless
+///   the editor was created with `with_backend`.
 /// - `cursor_position`: The current position of the cursor, represented as a `Position`.
-/// - `offset`: The offset position, often used for scrolling, represented as a `Position`.
+/// - `offset`: The first row and screen column shown, used for scrolling, represented as a `Position`.
 /// - `document`: An instance of the `Document` struct representing the text document.
-/// - `status_message`: An instance of the `StatusMessage` struct for displaying status messages.
-/// - `quit_times`: An unsigned 8-bit integer (`u8`) representing the number of allowed quit times.
+/// - `notifications`: The messages shown in the message bar.
 /// - `highlighted_word`: An optional `String` representing a currently highlighted word.
+/// - `selection_anchor`: The position where the current selection started, if any.
+/// - `clipboard`: The text last copied or cut from the document.
+/// - `keymap`: Translates key presses into the commands they run.
+/// - `completion`: The word completion being cycled through, if the last key requested one.
+/// - `overwrite`: Whether typed characters replace the one under the cursor instead of being inserted.
+/// - `config`: The user settings loaded at startup.
+/// - `buffers`: Every open buffer, where the slot of the active one is left empty.
+/// - `current_buffer`: The index of the active buffer in `buffers`.
+/// - `overlay`: The popup drawn above the text area, if one is open.
+/// - `prompt_line`: The text of the open prompt and the column of its cursor, shown instead of the notifications.
+/// - `theme`: The colors used to draw everything.
+/// - `title`: The terminal title last set, to avoid setting it again on every refresh.
+/// - `state`: What is remembered between sessions, such as the prompt histories.
+/// - `message_log`: Every message worth keeping around, such as the output of on-save hooks.
+/// - `typing`: The run of typed or deleted characters not yet committed as an undo step.
+/// - `args`: The command line options, which also apply when the config is reloaded.
+/// - `frame_stats`: The timings shown by the debug overlay, while it's open.
+/// - `tasks`: The work running off the UI thread, such as on-save hooks.
+/// - `jobs`: The project commands running, so each only runs once at a time and can be stopped.
+/// - `scripts`: The commands and key bindings registered by the user's Lua scripts.
+/// - `plugins`: The loaded WebAssembly plugins, with their commands and status segments.
+/// - `workspace`: The files of the project in the working directory, for project-wide commands.
+/// - `quickfix`: The locations found in the output of the last project command that finished.
+/// - `commit`: The repository and message file of the commit being written, committed on save.
+/// - `git_gutter`: How the active buffer differs from the staged version of its file.
+/// - `conflicts`: The merge conflicts in the active buffer.
+/// - `outline`: The symbols of the active buffer, while the outline panel is open.
+/// - `language_servers`: The language servers started so far, by file type.
+/// - `debugger`: The debug session, while a program is being debugged.
+/// - `repl`: The interpreter code is sent to, once it was started.
+/// - `last_click`: When and where the mouse was last clicked, and how many clicks in a
+///   row that made.
+/// - `watcher`: Tells about changes other programs make to the open files.
+/// - `split`: The other pane, while the text area is split in two.
+/// - `resizing`: Whether the divider between the panes is being dragged.
+/// - `status_line`: The segments the status bar is made of.
+/// - `last_edit`: The commands `RepeatEdit` does again, a run of typing or one edit.
+/// - `extending_edit`: Whether the last command typed or deleted, so typing more
+///   adds to `last_edit`.
+/// - `cursor_style`: The cursor shape and blinking last set, so they're only set
+///   again when they change.
+/// - `repeating`: Whether `last_edit` or a counted command is being carried out,
+///   which becomes a single undo step once it's done.
+/// - `recording`: The commands of the macro being recorded, while one is.
+/// - `last_macro`: The commands of the macro recorded last, which `PlayMacro` does.
 ///
 /// # Examples
 ///
-/// ```
-/// use my_module::Editor;
-///
-/// // Create a new text editor instance.
-/// let mut editor = Editor::new();
+/// ```no_run
+/// use text_editor::{cli::Args, Editor};
 ///
-/// // Initialize the editor's state and interact with it.
-/// editor.open_document("example.txt");
-/// editor.insert_text("Hello, World!");
-/// editor.move_cursor(8, 0);
-/// editor.delete_word();
-/// editor.save_document();
+/// // Open a file and edit it until the user quits.
+/// let args = Args {
+///     files: vec!["example.txt".to_string()],
+///     ..Args::default()
+/// };
+/// Editor::new(args).run();
 /// ```
+#[allow(clippy::struct_excessive_bools)]
 pub struct Editor {
     should_quit: bool,
-    terminal: Terminal,
+    terminal: Box<dyn Backend>,
     cursor_position: Position,
     offset: Position,
     document: Document,
-    status_message: StatusMessage,
-    quit_times: u8,
+    notifications: Notifications,
     highlighted_word: Option<String>,
+    selection_anchor: Option<Position>,
+    clipboard: String,
+    keymap: Keymap,
+    completion: Option<Completion>,
+    overwrite: bool,
+    config: Config,
+    buffers: Vec<Buffer>,
+    current_buffer: usize,
+    overlay: Option<Overlay>,
+    prompt_line: Option<(String, usize)>,
+    theme: Theme,
+    title: String,
+    state: State,
+    message_log: Vec<String>,
+    typing: Option<Typing>,
+    args: Args,
+    frame_stats: Option<FrameStats>,
+    tasks: Tasks,
+    jobs: Vec<RunningJob>,
+    #[cfg(feature = "lua")]
+    scripts: Scripts,
+    #[cfg(feature = "wasm")]
+    plugins: Plugins,
+    workspace: Workspace,
+    quickfix: Quickfix,
+    commit: Option<(Repository, String)>,
+    git_gutter: Gutter,
+    conflicts: Conflicts,
+    outline: Option<Outline>,
+    language_servers: Servers,
+    debugger: Option<Session>,
+    repl: Option<Repl>,
+    last_click: Option<(Instant, Position, usize)>,
+    watcher: Watcher,
+    split: Option<Split>,
+    resizing: bool,
+    status_line: StatusLine,
+    last_edit: Vec<EditorCommand>,
+    extending_edit: bool,
+    repeating: bool,
+    recording: Option<Vec<EditorCommand>>,
+    last_macro: Vec<EditorCommand>,
+    cursor_style: (CursorShape, bool),
 }
 
 impl Editor {
     //Constructor
-    pub fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut initial_status = String::from("HELP: F3 = find | F5 = save | F8 = quit");
-
-        //Opening a file, otherwise, main application
-        let document = if let Some(file_name) = args.get(1) {
-            let doc = Document::open(file_name);
-            if let Ok(doc) = doc {
-                doc
+    ///
+    /// # Panics
+    ///
+    /// Panics when the terminal can't be set up, such as when there is none.
+    #[must_use]
+    pub fn new(args: Args) -> Self {
+        #[allow(clippy::expect_used)]
+        let terminal = Terminal::new().expect("Jesus Christ, what have you done?");
+        Self::with_backend(args, Box::new(terminal))
+    }
+
+    /// Creates an editor that draws to and takes its keys from `terminal`, for front
+    /// ends other than the crossterm one.
+    #[allow(clippy::too_many_lines)]
+    pub fn with_backend(args: Args, terminal: Box<dyn Backend>) -> Self {
+        let mut notifications = Notifications::default();
+        notifications.info(String::from(
+            "HELP: F1 = help | F3 = find | F5 = save | F8 = quit",
+        ));
+
+        let state = State::load();
+        let config = load_config(&args, &state).unwrap_or_else(|err| {
+            notifications.error(format!("Could not load config: {err}"));
+            Config::default()
+        });
+
+        //Opening the files, otherwise, main application
+        let mut buffers = Vec::new();
+        if args.tutor {
+            buffers.push(Buffer {
+                document: Document::from_text(&tutor::text(&config.keys)),
+                ..Buffer::default()
+            });
+        }
+        for file_name in &args.files {
+            if let Ok(mut document) = Document::open(file_name) {
+                if document.is_binary() {
+                    notifications.warning(format!(
+                        "{file_name} looks like a binary file, opened read-only."
+                    ));
+                } else {
+                    document.set_read_only(args.read_only);
+                }
+                document.set_ansi(config.editor.ansi_colors && document.has_ansi_escapes());
+                buffers.push(Buffer {
+                    document,
+                    ..Buffer::default()
+                });
             } else {
-                initial_status = format!("ERR: Could not open file: {file_name}");
-                Document::default()
+                notifications.error(format!("Could not open file: {file_name}"));
             }
-        } else {
-            Document::default()
+        }
+
+        if buffers.is_empty() {
+            buffers.push(Buffer::default());
+        }
+
+        let document = buffers
+            .first_mut()
+            .map(|buffer| std::mem::take(&mut buffer.document))
+            .unwrap_or_default();
+        let cursor_position = Position {
+            x: 0,
+            y: args
+                .line
+                .unwrap_or(1)
+                .saturating_sub(1)
+                .min(document.len().saturating_sub(1)),
         };
 
-        Self {
+        let tasks = Tasks::default();
+        let mut watcher = Watcher::new(tasks.sender());
+        for file_name in &args.files {
+            watcher.watch(file_name);
+        }
+
+        let theme = Theme::from_config(&config).unwrap_or_else(|err| {
+            notifications.error(format!("Invalid theme: {err}"));
+            Theme::default()
+        });
+
+        let workspace = Workspace::new(
+            workspace::project_root(args.files.first().map(String::as_str))
+                .unwrap_or_else(|| PathBuf::from(".")),
+        );
+
+        let mut editor = Self {
             should_quit: false,
-            terminal: Terminal::default().expect("Jesus Christ, what have you done?"),
+            terminal,
             document,
-            cursor_position: Position::default(),
+            cursor_position,
             offset: Position::default(),
-            status_message: StatusMessage::from(initial_status),
-            quit_times: QUIT_TIMES,
+            notifications,
             highlighted_word: None,
-        }
+            selection_anchor: None,
+            clipboard: String::new(),
+            keymap: Keymap::new(config.keys.bindings()),
+            completion: None,
+            overwrite: false,
+            config,
+            buffers,
+            current_buffer: 0,
+            overlay: None,
+            prompt_line: None,
+            theme,
+            title: String::new(),
+            state,
+            message_log: Vec::new(),
+            typing: None,
+            args,
+            frame_stats: None,
+            tasks,
+            jobs: Vec::new(),
+            #[cfg(feature = "lua")]
+            scripts: Scripts::default(),
+            #[cfg(feature = "wasm")]
+            plugins: Plugins::default(),
+            workspace,
+            quickfix: Quickfix::default(),
+            commit: None,
+            git_gutter: Gutter::default(),
+            conflicts: Conflicts::default(),
+            outline: None,
+            language_servers: Servers::default(),
+            debugger: None,
+            repl: None,
+            last_click: None,
+            watcher,
+            split: None,
+            resizing: false,
+            status_line: StatusLine::default(),
+            last_edit: Vec::new(),
+            extending_edit: false,
+            repeating: false,
+            recording: None,
+            last_macro: Vec::new(),
+            cursor_style: (CursorShape::Default, true),
+        };
+        editor.register_segments();
+        editor.load_scripts();
+        editor.load_plugins();
+        editor
     }
 
     //Callable implementation
     pub fn run(&mut self) {
+        if let Err(error) = self.offer_recovery().and_then(|()| self.offer_trust()) {
+            die(self.terminal.as_mut(), &error);
+        }
+        // The cursor may start further down, such as on a line given with `+N`
+        self.scroll();
+        let mut redraw = true;
         loop {
-            if let Err(error) = self.refresh_screen() {
-                die(&error);
+            // Checked before drawing, as drawing fails once the terminal has hung up
+            if signals::terminating() {
+                self.emergency_save();
+                self.terminal.restore();
+                break;
             }
 
-            if self.should_quit {
-                (crossterm::terminal::disable_raw_mode()).unwrap();
-                break;
+            if redraw {
+                if let Err(error) = self.refresh_screen() {
+                    die(self.terminal.as_mut(), &error);
+                }
             }
 
-            if let Err(error) = self.process_keypress() {
-                die(&error);
+            if self.should_quit {
+                self.terminal.restore();
+                break;
             }
-        }
-    }
 
-    //Private keyboard processor
-    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let actual_key: KeyCode = Terminal::read_key();
-
-        match actual_key {
-            KeyCode::F(8) => {
-                if self.quit_times > 0 && self.document.is_dirty() {
-                    self.status_message = StatusMessage::from(format!(
-                        "WARNING! File has unsaved changes. Press F8 {} more times to quit.",
-                        self.quit_times
-                    ));
-                    self.quit_times -= 1;
-                    return Ok(());
+            // Wait for a key for at most one tick, so timers run even when idle, and
+            // check back sooner while background tasks or the debugger may deliver results
+            let timeout = if self.tasks.is_busy() || self.debugger.is_some() {
+                TASK_POLL
+            } else {
+                TICK
+            };
+            redraw = match self.terminal.poll_input(timeout) {
+                Some(Input::Key(event)) => {
+                    let start = Instant::now();
+                    if let Err(error) = self.process_keypress(&event) {
+                        die(self.terminal.as_mut(), &error);
+                    }
+                    if let Some(stats) = self.frame_stats.as_mut() {
+                        stats.input = start.elapsed();
+                    }
+                    true
                 }
-                self.should_quit = true;
-            }
-            KeyCode::F(3) => self.search(),
-            KeyCode::F(5) => self.save(),
-            KeyCode::Enter => {
-                self.document.insert(&self.cursor_position, '\n');
-                self.move_cursor(KeyCode::Right);
-            }
-            KeyCode::Char(c) => {
-                self.document.insert(&self.cursor_position, c);
-                self.move_cursor(KeyCode::Right);
-            }
-            KeyCode::Delete => self.document.delete(&self.cursor_position),
-            KeyCode::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(KeyCode::Left);
-                    self.document.delete(&self.cursor_position);
+                Some(Input::Mouse(event)) => {
+                    let changed = self.handle_mouse(event);
+                    if let Err(error) = &changed {
+                        die(self.terminal.as_mut(), error);
+                    }
+                    changed.unwrap_or(false)
                 }
-            }
-            KeyCode::Up
-            | KeyCode::Down
-            | KeyCode::Left
-            | KeyCode::Right
-            | KeyCode::PageUp
-            | KeyCode::PageDown
-            | KeyCode::End
-            | KeyCode::Home => self.move_cursor(actual_key),
-            _ => (),
+                Some(Input::Resize(width, height)) => {
+                    self.terminal.resize(width, height);
+                    self.scroll();
+                    true
+                }
+                None => self.tick(),
+            };
         }
+    }
 
+    /// Presses the keys of the headless script one by one, drawing a frame after
+    /// each like `run` does, until the script runs out or the editor quits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a command fails to write to the screen or to a file.
+    #[cfg(feature = "headless")]
+    pub fn run_script(&mut self) -> Result<(), std::io::Error> {
         self.scroll();
-
-        if self.quit_times < QUIT_TIMES {
-            self.quit_times = QUIT_TIMES;
-            self.status_message = StatusMessage::from(String::new());
+        self.refresh_screen()?;
+        while let Some(Input::Key(event)) = self.terminal.poll_input(TICK) {
+            self.process_keypress(&event)?;
+            self.refresh_screen()?;
+            if self.should_quit {
+                break;
+            }
         }
-        //This is used to propagate the error along the system
         Ok(())
     }
 
-    fn search(&mut self) {
-        let old_position = self.cursor_position.clone();
-        let mut direction = SearchDirection::Forward;
-        let query = self
-            .prompt("Search: ", |editor, key_code, query| {
-                let mut moved = false;
-                match key_code {
-                    KeyCode::Right | KeyCode::Down => {
-                        direction = SearchDirection::Forward;
-                        editor.move_cursor(KeyCode::Right);
-                        moved = true;
-                    }
-                    KeyCode::Left | KeyCode::Up => direction = SearchDirection::Backward,
-                    _ => direction = SearchDirection::Forward,
-                }
-
-                if let Some(position) =
-                    editor
-                        .document
-                        .find(&query, &editor.cursor_position, direction)
-                {
-                    editor.cursor_position = position;
-                    editor.scroll();
-                } else if moved {
-                    editor.move_cursor(KeyCode::Left);
-                }
-
-                editor.highlighted_word = Some(query.to_string());
-            })
-            .unwrap_or(None);
+    /// Runs the work that depends on time rather than on keys. Returns whether the
+    /// screen needs to be redrawn.
+    fn tick(&mut self) -> bool {
+        let mut changed = false;
 
-        if query.is_none() {
-            self.cursor_position = old_position;
-            self.scroll();
+        if self.notifications.expire() {
+            changed = true;
         }
 
-        self.highlighted_word = None;
-    }
+        if self.follow_file() {
+            changed = true;
+        }
 
-    fn prompt<C>(&mut self, prompt: &str, mut callback: C) -> Result<Option<String>, std::io::Error>
-    where
-        C: FnMut(&mut Self, KeyCode, &String),
-    {
-        let mut result = String::new();
-        loop {
-            self.status_message = StatusMessage::from(format!("{prompt}{result}"));
-            self.refresh_screen()?;
-            let key = Terminal::read_key();
-            match key {
-                KeyCode::Backspace => result.truncate(result.len().saturating_sub(1)),
-                KeyCode::Enter => break,
-                KeyCode::Char(c) => {
-                    if !c.is_control() {
-                        result.push(c);
-                    }
-                }
-                KeyCode::Esc => {
-                    result.truncate(0);
-                    break;
-                }
-                _ => (),
-            }
-            callback(self, key, &result)
+        for event in self.tasks.poll() {
+            self.handle_event(event);
+            changed = true;
         }
-        self.status_message = StatusMessage::from(String::new());
-        if result.is_empty() {
-            return Ok(None);
+
+        for problem in self.language_servers.catch_up() {
+            self.message_log.push(problem);
         }
-        Ok(Some(result))
-    }
 
-    fn scroll(&mut self) {
-        let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
-        let offset = &mut self.offset;
+        if self.update_semantic_tokens() {
+            changed = true;
+        }
 
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
+        if self.update_debugger() {
+            changed = true;
         }
 
-        if x < offset.x {
-            offset.x = x;
-        } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_sub(width).saturating_add(1);
+        changed
+    }
+
+    /// Refines the highlighting of the active buffer with what its language server
+    /// says the names are, if it's running. Returns whether new tokens came in.
+    fn update_semantic_tokens(&mut self) -> bool {
+        let Some(client) = self.language_servers.running(&self.document.file_type()) else {
+            return false;
+        };
+        match client.semantic_tokens(&self.document) {
+            Ok(Some(tokens)) => {
+                self.document.set_semantic_tokens(tokens);
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                self.message_log
+                    .push(format!("Could not get the semantic tokens: {err}"));
+                false
+            }
         }
     }
 
-    fn move_cursor(&mut self, key_selection: KeyCode) {
-        let terminal_height = self.terminal.size().height as usize;
-        let Position { mut y, mut x } = self.cursor_position;
-        let height = self.document.len();
-        let mut width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
+    /// Handles what happened to the debugged program, showing where it paused.
+    /// Returns whether anything did.
+    fn update_debugger(&mut self) -> bool {
+        let Some(session) = self.debugger.as_mut() else {
+            return false;
         };
+        let events = session.poll();
+        let changed = !events.is_empty();
 
-        match key_selection {
-            KeyCode::Up => y = y.saturating_sub(1),
-            KeyCode::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
+        for event in events {
+            match event {
+                dap::Event::Started => {
+                    let keys = &self.config.keys;
+                    self.notifications.info(format!(
+                        "Debugging, {} steps over, {} into, {} out and {} stops.",
+                        keys.step_over, keys.step_into, keys.step_out, keys.debug_stop
+                    ));
                 }
-            }
-            KeyCode::Left => {
-                if x > 0 {
-                    x -= 1;
-                } else if y > 0 {
-                    y -= 1;
-                    if let Some(row) = self.document.row(y) {
-                        x = row.len();
-                    } else {
-                        x = 0;
+                dap::Event::Stopped(reason) => self.show_pause(&reason),
+                dap::Event::Continued => {}
+                dap::Event::Output(output) => {
+                    self.message_log.extend(output.lines().map(String::from));
+                }
+                dap::Event::Failed(err) => {
+                    self.notifications.error(format!("Debugger: {err}"));
+                }
+                // A session that failed to start already told why
+                dap::Event::Ended => {
+                    if self
+                        .debugger
+                        .take()
+                        .is_some_and(|session| session.is_started())
+                    {
+                        self.notifications
+                            .info("The debugged program ended.".to_string());
                     }
                 }
             }
-            KeyCode::Right => {
-                if x < width {
-                    x += 1;
-                } else if y < height {
-                    y += 1;
-                    x = 0;
-                }
+        }
+        changed
+    }
+
+    /// Turns a mouse event into commands: the left button puts the cursor on the
+    /// text clicked, and with Ctrl held also opens the URL there, dragging selects
+    /// and the wheel scrolls. Returns whether the event did anything.
+    fn handle_mouse(&mut self, event: MouseEvent) -> Result<bool, std::io::Error> {
+        // The view doesn't scroll away from what a prompt asks about, and the prompt
+        // ends any drag
+        if self.prompt_line.is_some() {
+            self.resizing = false;
+            return Ok(false);
+        }
+
+        let dragged = match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => false,
+            MouseEventKind::Drag(MouseButton::Left) => true,
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                self.scroll_view(event.kind == MouseEventKind::ScrollDown);
+                return Ok(true);
             }
-            KeyCode::PageUp => {
-                y = if y > terminal_height {
-                    y.saturating_sub(terminal_height)
-                } else {
-                    0
-                }
+            _ => {
+                self.resizing = false;
+                return Ok(false);
             }
-            KeyCode::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
-                    y.saturating_add(terminal_height)
-                } else {
-                    height
+        };
+
+        // The divider is grabbed to resize the panes, and clicking the pane that
+        // isn't active moves there before the click is handled like any other
+        let column = usize::from(event.column);
+        let on_text = event.row < self.terminal.size().height;
+        if let Some(split) = self.split.as_mut() {
+            if dragged && self.resizing {
+                let total = usize::from(self.terminal.size().width);
+                let width = column.clamp(
+                    MIN_PANE_WIDTH,
+                    total
+                        .saturating_sub(MIN_PANE_WIDTH)
+                        .saturating_sub(1)
+                        .max(MIN_PANE_WIDTH),
+                );
+                if width == split.width {
+                    return Ok(false);
                 }
+                split.width = width;
+                self.scroll();
+                return Ok(true);
+            }
+            if !dragged && on_text && column == split.width {
+                self.resizing = true;
+                return Ok(false);
+            }
+            if !dragged && on_text && (column < split.width) != split.left {
+                self.focus_pane();
             }
-            KeyCode::Home => x = 0,
-            KeyCode::End => x = width,
-            _ => (),
         }
 
-        width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
+        let Some(position) = self.document_position(event.column, event.row) else {
+            return Ok(false);
         };
 
-        if x > width {
-            x = width;
+        // Dragging moves the cursor from where the button went down, which stays
+        // the anchor, so the selection grows and shrinks as the mouse moves
+        if dragged {
+            if position == self.cursor_position {
+                return Ok(false);
+            }
+            self.execute(EditorCommand::MoveTo {
+                position,
+                select: true,
+            })?;
+            return Ok(true);
         }
 
-        self.cursor_position = Position { x, y }
-    }
-
-    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
-        Terminal::cursor_hide();
-
-        Terminal::cursor_position(&Position::default());
-
-        if self.should_quit {
-            Terminal::clear_screen();
-            println!("Goodbye.\r");
-        } else {
-            self.document.highlight(
-                &self.highlighted_word,
-                Some(
-                    self.offset
-                        .y
-                        .saturating_add(self.terminal.size().height as usize),
-                ),
-            );
+        let clicks = match &self.last_click {
+            Some((time, at, clicks)) if *at == position && time.elapsed() < MULTI_CLICK => {
+                clicks.rem_euclid(3).saturating_add(1)
+            }
+            _ => 1,
+        };
+        self.last_click = Some((Instant::now(), position.clone(), clicks));
 
-            self.draw_rows();
-            self.draw_status_bar();
-            self.draw_message_bar();
-            Terminal::cursor_position(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
-                y: self.cursor_position.y.saturating_sub(self.offset.y),
-            });
+        self.execute(EditorCommand::MoveTo {
+            position,
+            select: event.modifiers.contains(KeyModifiers::SHIFT),
+        })?;
+        match clicks {
+            2 => self.execute(EditorCommand::SelectWord)?,
+            3 => self.execute(EditorCommand::SelectLine)?,
+            _ if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.execute(EditorCommand::OpenUrl)?;
+            }
+            _ => {}
         }
+        Ok(true)
+    }
 
-        Terminal::cursor_show();
-        Terminal::flush()
+    //Private keyboard processor
+    fn process_keypress(&mut self, event: &KeyEvent) -> Result<(), std::io::Error> {
+        let Some(command) = self.keymap.command(event) else {
+            self.completion = None;
+            self.overlay = None;
+            return Ok(());
+        };
+        if let Some(recording) = self.recording.as_mut() {
+            if !matches!(
+                command,
+                EditorCommand::RecordMacro | EditorCommand::PlayMacro
+            ) {
+                recording.push(command.clone());
+            }
+        }
+        self.execute(command)
+    }
+
+    /// Carries out a single command, whether it came from a key press or elsewhere.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the terminal when a command that asks for something
+    /// can't read or draw its prompt.
+    #[allow(clippy::too_many_lines)]
+    pub fn execute(&mut self, command: EditorCommand) -> Result<(), std::io::Error> {
+        if command.edits() && self.document.is_read_only() {
+            self.notifications
+                .warning("This buffer is read-only.".to_string());
+            return Ok(());
+        }
+
+        // What is typed or deleted without moving in between is repeated as a whole
+        let typed = matches!(
+            command,
+            EditorCommand::InsertChar(_)
+                | EditorCommand::InsertNewline
+                | EditorCommand::DeleteBackward
+                | EditorCommand::DeleteForward
+        );
+        if self.repeating {
+            // Part of an edit that was recorded as a whole
+        } else if typed && self.extending_edit {
+            self.last_edit.push(command.clone());
+        } else if command.is_repeatable() {
+            self.last_edit = vec![command.clone()];
+        }
+        if !self.repeating {
+            self.extending_edit = typed;
+        }
+
+        let completion = self.completion.take();
+        self.overlay = None;
+
+        // Typing and deleting characters one after another only becomes an undo step
+        // once something else happens or the keys pause
+        let repeatable = match command {
+            EditorCommand::InsertChar(_)
+            | EditorCommand::DeleteBackward
+            | EditorCommand::DeleteForward => Some(std::mem::discriminant(&command)),
+            _ => None,
+        };
+        let before = match self.typing.take() {
+            Some(typing)
+                if Some(typing.command) == repeatable && typing.last.elapsed() < TYPING_PAUSE =>
+            {
+                typing.before
+            }
+            Some(typing) => {
+                self.document
+                    .commit_changes(&typing.before, &self.cursor_position);
+                self.cursor_position.clone()
+            }
+            None => self.cursor_position.clone(),
+        };
+
+        match command {
+            EditorCommand::Complete => self.complete(completion),
+            EditorCommand::Undo => {
+                let cursor = self.document.undo();
+                self.restore_cursor(cursor, "Nothing to undo.");
+            }
+            EditorCommand::Redo => {
+                let cursor = self.document.redo();
+                self.restore_cursor(cursor, "Nothing to redo.");
+            }
+            EditorCommand::UndoTree => self.show_undo_tree()?,
+            EditorCommand::RepeatEdit => self.repeat_edit()?,
+            EditorCommand::RecordMacro => self.record_macro(),
+            EditorCommand::PlayMacro => self.play_macro()?,
+            EditorCommand::Repeat { command, count } => {
+                self.repeat(&vec![*command; count])?;
+            }
+            EditorCommand::SelectAll => self.select_all(),
+            EditorCommand::SelectInside => {
+                if let Some(object) = self.read_text_object("Select inside")? {
+                    self.select_object(object, false);
+                }
+            }
+            EditorCommand::SelectAround => {
+                if let Some(object) = self.read_text_object("Select around")? {
+                    self.select_object(object, true);
+                }
+            }
+            EditorCommand::Copy => self.copy(),
+            EditorCommand::Cut => self.cut(),
+            EditorCommand::Paste => self.paste(self.config.editor.reindent_paste),
+            EditorCommand::PasteReindented => self.paste(true),
+            EditorCommand::Replace => self.replace(),
+            EditorCommand::ProjectSearch => self.project_search()?,
+            EditorCommand::ProjectReplace => self.project_replace()?,
+            EditorCommand::RunJob(job) => self.run_job(job),
+            EditorCommand::StopJobs => self.stop_jobs(),
+            EditorCommand::GitStatus => self.git_status()?,
+            EditorCommand::GitCommit => self.git_commit(),
+            EditorCommand::ResolveConflict(resolution) => self.resolve_conflict(resolution),
+            EditorCommand::CompareSaved => self.compare_saved(),
+            EditorCommand::Outline => self.outline()?,
+            EditorCommand::StageHunk => self.stage_hunk(),
+            EditorCommand::RevertHunk => self.revert_hunk(),
+            EditorCommand::NextLocation => self.go_to_location(true),
+            EditorCommand::PreviousLocation => self.go_to_location(false),
+            EditorCommand::Surround => self.surround(),
+            EditorCommand::ChangeSurround => self.change_surround()?,
+            EditorCommand::SortLines => self.sort_lines(),
+            EditorCommand::Uppercase => self.transform_case(&str::to_uppercase),
+            EditorCommand::Lowercase => self.transform_case(&str::to_lowercase),
+            EditorCommand::TitleCase => self.transform_case(&title_case),
+            EditorCommand::ReflowParagraph => self.reflow_paragraph(),
+            EditorCommand::ShowStatistics => self.show_statistics(),
+            EditorCommand::InspectCharacter => self.inspect_character(),
+            EditorCommand::NextBuffer => {
+                let next = self.current_buffer.saturating_add(1);
+                self.switch_buffer(next.checked_rem(self.buffers.len()).unwrap_or_default());
+            }
+            EditorCommand::PreviousBuffer => {
+                let count = self.buffers.len();
+                let previous = self.current_buffer.saturating_add(count).saturating_sub(1);
+                self.switch_buffer(previous.checked_rem(count).unwrap_or_default());
+            }
+            EditorCommand::SaveAll => {
+                self.save_all();
+            }
+            EditorCommand::CloseAll => self.close_all(),
+            EditorCommand::SaveAllAndQuit => {
+                if self.save_all() {
+                    self.should_quit = true;
+                }
+            }
+            EditorCommand::ShowMessageLog => self.show_message_log()?,
+            EditorCommand::InsertCodepoint => self.insert_codepoint(),
+            EditorCommand::ReloadConfig => self.reload_config(),
+            EditorCommand::RunScript(name) => self.run_script_command(&name),
+            EditorCommand::RunPlugin(name) => self.run_plugin_command(&name),
+            EditorCommand::Quit => {
+                let dirty = self.dirty_buffers();
+                if dirty > 0 {
+                    let question = if dirty == 1 {
+                        "1 buffer has unsaved changes. Save before quitting?".to_string()
+                    } else {
+                        format!("{dirty} buffers have unsaved changes. Save before quitting?")
+                    };
+                    match self.confirm(&question).unwrap_or(Choice::Cancel) {
+                        Choice::Yes => {
+                            if !self.save_all() {
+                                return Ok(());
+                            }
+                        }
+                        Choice::No => (),
+                        Choice::Cancel => return Ok(()),
+                    }
+                }
+                self.should_quit = true;
+            }
+            EditorCommand::ShowHelp => self.show_help()?,
+            EditorCommand::Search => self.search(),
+            EditorCommand::Save => self.save(),
+            EditorCommand::Revert => self.revert()?,
+            EditorCommand::ToggleFollow => self.toggle_follow(),
+            EditorCommand::ToggleAnsiColors => {
+                let ansi = !self.document.shows_ansi();
+                self.document.set_ansi(ansi);
+                self.move_cursor(KeyCode::Null);
+            }
+            EditorCommand::ToggleDebugOverlay => {
+                self.frame_stats = match self.frame_stats {
+                    Some(_) => None,
+                    None => Some(FrameStats::default()),
+                };
+            }
+            EditorCommand::Open => self.open()?,
+            EditorCommand::FindFile => self.find_file()?,
+            EditorCommand::Rename => self.rename()?,
+            EditorCommand::RenameSymbol => self.rename_symbol()?,
+            EditorCommand::Hover => self.hover(),
+            EditorCommand::CodeActions => self.code_actions(),
+            EditorCommand::FormatSelection => self.format_selection(),
+            EditorCommand::WorkspaceSymbols => self.workspace_symbols()?,
+            EditorCommand::ToggleBreakpoint => self.toggle_breakpoint(),
+            EditorCommand::DebugContinue => self.debug_continue(),
+            EditorCommand::DebugStep(step) => self.debug_step(step),
+            EditorCommand::SendToRepl => self.send_to_repl(),
+            EditorCommand::DebugStop => {
+                if self.debugger.take().is_some() {
+                    self.notifications.info("Stopped debugging.".to_string());
+                } else {
+                    self.notifications
+                        .info("No program is being debugged.".to_string());
+                }
+            }
+            EditorCommand::InsertNewline => {
+                self.delete_selection();
+                self.insert_newline();
+            }
+            EditorCommand::ToggleCheckbox => self.toggle_checkbox(),
+            EditorCommand::FormatTable => self.format_table(),
+            EditorCommand::OpenUrl => self.open_url(),
+            EditorCommand::MoveTo { position, select } => {
+                self.extend_selection(select);
+                self.cursor_position = position;
+                self.move_cursor(KeyCode::Null);
+            }
+            EditorCommand::SelectWord => {
+                if let Some((start, end)) = self.document.word_at(&self.cursor_position) {
+                    self.selection_anchor = Some(start);
+                    self.cursor_position = end;
+                }
+            }
+            EditorCommand::SelectLine => self.select_line(),
+            EditorCommand::SplitPane => self.split_pane(),
+            EditorCommand::FocusPane => self.focus_pane(),
+            EditorCommand::InsertChar(c) => {
+                let replaced_selection = self.delete_selection();
+                let row_len = self
+                    .document
+                    .row(self.cursor_position.y)
+                    .map_or(0, Row::len);
+
+                if self.overwrite && !replaced_selection && self.cursor_position.x < row_len {
+                    self.document.delete(&self.cursor_position);
+                }
+
+                self.document.insert(&self.cursor_position, c);
+                self.move_cursor(KeyCode::Right);
+                if matches!(c, '(' | ',') {
+                    self.signature_help();
+                }
+            }
+            EditorCommand::ToggleOverwrite => self.overwrite = !self.overwrite,
+            EditorCommand::DeleteForward => {
+                if !self.delete_selection() {
+                    self.document.delete(&self.cursor_position);
+                }
+            }
+            EditorCommand::DeleteBackward => {
+                if !self.delete_selection()
+                    && (self.cursor_position.x > 0 || self.cursor_position.y > 0)
+                {
+                    self.move_cursor(KeyCode::Left);
+                    self.document.delete(&self.cursor_position);
+                }
+            }
+            EditorCommand::Move { movement, select } => {
+                self.extend_selection(select);
+                match movement {
+                    Movement::HalfPageUp => self.scroll_half_page(false),
+                    Movement::HalfPageDown => self.scroll_half_page(true),
+                    _ => self.move_cursor(movement_key(movement)),
+                }
+            }
+            EditorCommand::Jump { motion, select } => {
+                self.extend_selection(select);
+                self.jump(motion);
+            }
+        }
+
+        if self.repeating {
+            // Committed by the `RepeatEdit` the command is part of
+        } else if let Some(command) = repeatable {
+            self.typing = Some(Typing {
+                command,
+                before,
+                last: Instant::now(),
+            });
+        } else {
+            self.document.commit_changes(&before, &self.cursor_position);
+        }
+        self.scroll();
+        //This is used to propagate the error along the system
+        Ok(())
+    }
+
+    /// Does the commands of the last edit again, as a single undo step.
+    fn repeat_edit(&mut self) -> Result<(), std::io::Error> {
+        if self.last_edit.is_empty() {
+            self.notifications
+                .info("There is no edit to repeat yet.".to_string());
+            return Ok(());
+        }
+        self.repeat(&self.last_edit.clone())
+    }
+
+    /// Starts recording a macro, or keeps the one being recorded for `play_macro`.
+    fn record_macro(&mut self) {
+        match self.recording.take() {
+            Some(commands) => {
+                self.notifications
+                    .info(format!("Recorded a macro of {} commands.", commands.len()));
+                self.last_macro = commands;
+            }
+            None => self.recording = Some(Vec::new()),
+        }
+    }
+
+    /// Does the commands of the last recorded macro again, as a single undo step.
+    fn play_macro(&mut self) -> Result<(), std::io::Error> {
+        if self.last_macro.is_empty() {
+            self.notifications
+                .info("There is no macro to play yet.".to_string());
+            return Ok(());
+        }
+        self.repeat(&self.last_macro.clone())
+    }
+
+    /// Carries out commands one after another, committed as one undo step by the
+    /// `execute` this is called from.
+    fn repeat(&mut self, commands: &[EditorCommand]) -> Result<(), std::io::Error> {
+        let repeating = std::mem::replace(&mut self.repeating, true);
+        let result = commands
+            .iter()
+            .try_for_each(|command| self.execute(command.clone()));
+        self.repeating = repeating;
+        result
+    }
+
+    /// Puts the cursor where an undo or redo left off, or says there was nothing to do.
+    fn restore_cursor(&mut self, cursor: Option<Position>, nothing: &str) {
+        if let Some(cursor) = cursor {
+            self.cursor_position = cursor;
+            self.selection_anchor = None;
+            self.move_cursor(KeyCode::Null);
+        } else {
+            self.notifications.info(nothing.to_string());
+        }
+    }
+
+    fn show_undo_tree(&mut self) -> Result<(), std::io::Error> {
+        let history = self.document.history();
+        let (lines, states) = history.tree();
+        let current = states
+            .iter()
+            .position(|state| *state == history.current())
+            .unwrap_or(0);
+
+        let overlay = Overlay::new("Undo tree (Enter: go to state)", lines, Anchor::Center)
+            .with_selection(current);
+        if let Some(state) = self
+            .show_overlay(overlay)?
+            .and_then(|index| states.get(index))
+        {
+            let cursor = self.document.go_to_state(*state);
+            self.restore_cursor(cursor, "Already in that state.");
+        }
+        Ok(())
+    }
+
+    /// Starts or keeps the selection before a movement when `select` is set, and
+    /// drops it otherwise.
+    fn extend_selection(&mut self, select: bool) {
+        if select {
+            if self.selection_anchor.is_none() {
+                self.selection_anchor = Some(self.cursor_position.clone());
+            }
+        } else {
+            self.selection_anchor = None;
+        }
+    }
+
+    /// Returns the selected range ordered from start to end, if anything is selected.
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor.as_ref()?;
+        let cursor = &self.cursor_position;
+
+        if (anchor.y, anchor.x) == (cursor.y, cursor.x) {
+            return None;
+        }
+
+        if (anchor.y, anchor.x) < (cursor.y, cursor.x) {
+            Some((anchor.clone(), cursor.clone()))
+        } else {
+            Some((cursor.clone(), anchor.clone()))
+        }
+    }
+
+    fn select_all(&mut self) {
+        let last_row = self.document.len().saturating_sub(1);
+        let x = self.document.row(last_row).map_or(0, Row::len);
+
+        self.selection_anchor = Some(Position::default());
+        self.cursor_position = Position { x, y: last_row };
+    }
+
+    /// Selects the cursor's row up to the start of the next one, or to its end on the
+    /// last row.
+    fn select_line(&mut self) {
+        let y = self.cursor_position.y;
+        self.selection_anchor = Some(Position { x: 0, y });
+        self.cursor_position = if y.saturating_add(1) < self.document.len() {
+            Position {
+                x: 0,
+                y: y.saturating_add(1),
+            }
+        } else {
+            Position {
+                x: self.document.row(y).map_or(0, Row::len),
+                y,
+            }
+        };
+    }
+
+    fn select_object(&mut self, object: TextObject, around: bool) {
+        if let Some((start, end)) = self
+            .document
+            .text_object(&self.cursor_position, object, around)
+        {
+            self.selection_anchor = Some(start);
+            self.cursor_position = end;
+        } else {
+            self.notifications
+                .warning("Nothing to select around the cursor.".to_string());
+        }
+    }
+
+    /// Asks for the key naming a text object, returning `None` when cancelled with Esc.
+    fn read_text_object(&mut self, action: &str) -> Result<Option<TextObject>, std::io::Error> {
+        let text = format!("{action}: w, ( [ {{ <, \" ' ` (Esc to cancel) ");
+        self.prompt_line = Some((text.clone(), text.chars().count()));
+        self.refresh_screen()?;
+
+        let object = loop {
+            match self.terminal.read_key().code {
+                KeyCode::Char(c) => {
+                    if let Some(object) = TextObject::from_key(c) {
+                        break Some(object);
+                    }
+                }
+                KeyCode::Esc => break None,
+                _ => (),
+            }
+        };
+
+        self.prompt_line = None;
+        Ok(object)
+    }
+
+    /// Removes the selected text, returning whether there was anything to remove.
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.selection_anchor = None;
+            return false;
+        };
+
+        self.document.delete_range(&start, &end);
+        self.cursor_position = start;
+        self.selection_anchor = None;
+        true
+    }
+
+    fn copy(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            self.clipboard = self.document.text(&start, &end);
+            self.terminal.set_clipboard(&self.clipboard);
+            self.notifications.info("Copied selection.".to_string());
+        }
+    }
+
+    fn cut(&mut self) {
+        if let Some((start, end)) = self.selection() {
+            self.clipboard = self.document.text(&start, &end);
+            self.terminal.set_clipboard(&self.clipboard);
+            self.delete_selection();
+        }
+    }
+
+    fn paste(&mut self, reindent: bool) {
+        // Text copied in other programs takes the place of what was copied here
+        if let Some(text) = self.terminal.clipboard().filter(|text| !text.is_empty()) {
+            self.clipboard = text;
+        }
+        if self.clipboard.is_empty() {
+            return;
+        }
+
+        self.delete_selection();
+
+        let text = if reindent {
+            let indentation = self
+                .document
+                .row(self.cursor_position.y)
+                .map_or("", Row::indentation);
+            reindent_text(&self.clipboard, indentation)
+        } else {
+            self.clipboard.clone()
+        };
+
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+    }
+
+    fn complete(&mut self, previous: Option<Completion>) {
+        let cycling = previous.is_some();
+        let mut completion = if let Some(completion) = previous {
+            completion
+        } else {
+            self.selection_anchor = None;
+            let prefix = self.document.word_prefix(&self.cursor_position);
+            if prefix.is_empty() {
+                return;
+            }
+
+            let mut candidates = self.document.completions(&prefix, &self.cursor_position);
+            for buffer in &self.buffers {
+                for word in buffer
+                    .document
+                    .completions(&prefix, &buffer.cursor_position)
+                {
+                    if !candidates.contains(&word) {
+                        candidates.push(word);
+                    }
+                }
+            }
+            if candidates.is_empty() {
+                self.notifications
+                    .warning(format!("No completions for {prefix}"));
+                return;
+            }
+
+            Completion {
+                start: Position {
+                    x: self
+                        .cursor_position
+                        .x
+                        .saturating_sub(prefix.graphemes(true).count()),
+                    y: self.cursor_position.y,
+                },
+                index: candidates.len(),
+                prefix,
+                candidates,
+            }
+        };
+
+        // Cycle through every candidate and then back to what was originally typed
+        completion.index = completion
+            .index
+            .saturating_add(1)
+            .checked_rem(completion.candidates.len().saturating_add(1))
+            .unwrap_or(0);
+
+        let word = completion
+            .candidates
+            .get(completion.index)
+            .unwrap_or(&completion.prefix);
+
+        self.document
+            .delete_range(&completion.start, &self.cursor_position);
+        self.cursor_position = self.document.insert_str(&completion.start, word);
+        self.overlay = (completion.index < completion.candidates.len()).then(|| {
+            Overlay::new(
+                "",
+                completion.candidates.clone(),
+                Anchor::Below(self.screen_position(&completion.start)),
+            )
+            .with_selection(completion.index)
+        });
+        let message = if completion.index < completion.candidates.len() {
+            format!(
+                "Completion {} of {}",
+                completion.index.saturating_add(1),
+                completion.candidates.len()
+            )
+        } else {
+            format!("Back to original: {}", completion.prefix)
+        };
+        if cycling {
+            self.notifications.update(Severity::Info, message);
+        } else {
+            self.notifications.info(message);
+        }
+        self.completion = Some(completion);
+    }
+
+    /// Returns the rows covered by the selection, or the whole document when nothing is selected.
+    fn selected_rows(&self) -> Range<usize> {
+        match self.selection() {
+            Some((start, end)) => LineRange::covering(&start, &end).rows(),
+            None => 0..self.document.len(),
+        }
+    }
+
+    /// Wraps the selection, or the word under the cursor, in a pair of delimiters.
+    fn surround(&mut self) {
+        let selected = self.selection();
+        let Some((start, end)) = selected
+            .clone()
+            .or_else(|| self.document.word_at(&self.cursor_position))
+        else {
+            self.notifications
+                .warning("Nothing to surround.".to_string());
+            return;
+        };
+
+        let Some((open, close)) = self
+            .prompt(
+                "Surround with (a character, text or <tag>): ",
+                Some("surround"),
+                |_, _, _| None,
+            )
+            .unwrap_or(None)
+            .as_deref()
+            .and_then(delimiters)
+        else {
+            return;
+        };
+
+        let close_end = self.document.insert_str(&end, &close);
+        self.document.insert_str(&start, &open);
+
+        let shift = if start.y == end.y {
+            open.graphemes(true).count()
+        } else {
+            0
+        };
+        // A selection grows to cover the delimiters, otherwise the cursor stays on its word
+        if selected.is_some() {
+            self.selection_anchor = Some(start);
+            self.cursor_position = Position {
+                x: close_end.x.saturating_add(shift),
+                y: close_end.y,
+            };
+        } else {
+            self.cursor_position.x = self.cursor_position.x.saturating_add(shift);
+        }
+    }
+
+    /// Replaces the brackets or quotes around the cursor with another pair, or
+    /// removes them when no new pair is given.
+    fn change_surround(&mut self) -> Result<(), std::io::Error> {
+        let Some(object) = self.read_text_object("Change surrounding")? else {
+            return Ok(());
+        };
+        let Some((start, end)) = (object != TextObject::Word)
+            .then(|| {
+                self.document
+                    .text_object(&self.cursor_position, object, true)
+            })
+            .flatten()
+        else {
+            self.notifications
+                .warning("No surrounding pair found.".to_string());
+            return Ok(());
+        };
+
+        let Some(replacement) = self
+            .prompt(
+                "Change to (empty to remove): ",
+                Some("surround"),
+                |_, _, _| None,
+            )
+            .unwrap_or(None)
+        else {
+            return Ok(());
+        };
+        let (open, close) = delimiters(&replacement).unwrap_or_default();
+
+        let close_start = Position {
+            x: end.x.saturating_sub(1),
+            y: end.y,
+        };
+        self.document.delete(&close_start);
+        self.document.insert_str(&close_start, &close);
+        self.document.delete(&start);
+        self.document.insert_str(&start, &open);
+
+        self.selection_anchor = None;
+        self.cursor_position = start;
+        Ok(())
+    }
+
+    fn sort_lines(&mut self) {
+        let Some(options) = self
+            .prompt(
+                "Sort lines ([l]exical or [n]umeric, plus [r]everse, [u]nique): ",
+                Some("sort_lines"),
+                |_, _, _| None,
+            )
+            .unwrap_or(None)
+        else {
+            return;
+        };
+
+        let numeric = options.contains('n');
+        let reverse = options.contains('r');
+        let unique = options.contains('u');
+        let range = self.selected_rows();
+        let count = range.len();
+        let mut lines: Vec<String> = range
+            .clone()
+            .filter_map(|index| self.document.row(index).map(|row| row.as_str().to_string()))
+            .collect();
+
+        if numeric {
+            lines.sort_by(|a, b| {
+                leading_number(a)
+                    .partial_cmp(&leading_number(b))
+                    .unwrap_or(Ordering::Equal)
+            });
+        } else {
+            lines.sort();
+        }
+
+        if reverse {
+            lines.reverse();
+        }
+
+        if unique {
+            lines.dedup();
+        }
+
+        // Only the rows that moved are recorded, unless duplicates were dropped
+        if lines.len() == count {
+            let mut sorted = lines.into_iter();
+            self.document.edit_lines(LineRange::from(range), |row| {
+                if let Some(line) = sorted.next() {
+                    row.set_text(&line);
+                }
+            });
+        } else {
+            self.document.transform_rows(range, |_| lines);
+        }
+
+        // Keeps the cursor inside its row in case the row got shorter
+        self.move_cursor(KeyCode::Null);
+        self.notifications.info(format!("Sorted {count} lines."));
+    }
+
+    /// Breaks the line at the cursor. In markdown files the list item the cursor is
+    /// in is continued on the new line, unless it's empty, which ends the list instead.
+    fn insert_newline(&mut self) {
+        let y = self.cursor_position.y;
+        let item = if self.document.file_type() == "Markdown" {
+            self.document
+                .row(y)
+                .and_then(|row| Some((markdown::list_item(row.as_str())?, row.len())))
+                .filter(|(item, _)| self.cursor_position.x >= item.prefix)
+        } else {
+            None
+        };
+
+        match item {
+            Some((item, len)) if len == item.prefix => {
+                self.document
+                    .edit_lines(LineRange::row(y), |row| row.set_text(""));
+                self.cursor_position.x = 0;
+            }
+            Some((item, _)) => {
+                self.document.insert(&self.cursor_position, '\n');
+                self.move_cursor(KeyCode::Right);
+                self.cursor_position = self.document.insert_str(&self.cursor_position, &item.next);
+            }
+            None => {
+                self.document.insert(&self.cursor_position, '\n');
+                self.move_cursor(KeyCode::Right);
+            }
+        }
+    }
+
+    /// Checks or unchecks the markdown checkboxes of the selected lines, or of the
+    /// cursor's line, turning lines without one into unchecked list items.
+    fn toggle_checkbox(&mut self) {
+        let y = self.cursor_position.y;
+        let lines = match self.selection() {
+            Some((start, end)) => LineRange::covering(&start, &end),
+            None => LineRange::row(y),
+        };
+        let before = self.document.row(y).map_or(0, Row::len);
+
+        self.document.edit_lines(lines, |row| {
+            let toggled = markdown::toggle_checkbox(row.as_str());
+            row.set_text(&toggled);
+        });
+
+        // The cursor keeps its place in the text after the checkbox
+        let after = self.document.row(y).map_or(0, Row::len);
+        if self.cursor_position.x > 0 {
+            self.cursor_position.x = self
+                .cursor_position
+                .x
+                .saturating_add(after)
+                .saturating_sub(before);
+        }
+        self.move_cursor(KeyCode::Null);
+    }
+
+    /// Lines up the markdown table the cursor is in, the run of rows around it that
+    /// start with a pipe. The cursor moves to the start of the cell it was in.
+    fn format_table(&mut self) {
+        let y = self.cursor_position.y;
+        let is_row = |index: usize| {
+            self.document
+                .row(index)
+                .is_some_and(|row| markdown::is_table_row(row.as_str()))
+        };
+        if !is_row(y) {
+            self.notifications
+                .warning("The cursor isn't in a markdown table.".to_string());
+            return;
+        }
+        let start = (0..y)
+            .rev()
+            .take_while(|index| is_row(*index))
+            .last()
+            .unwrap_or(y);
+        let end = (y..self.document.len())
+            .take_while(|index| is_row(*index))
+            .last()
+            .unwrap_or(y)
+            .saturating_add(1);
+        let cell = self.document.row(y).map_or(0, |row| {
+            row.substring(0, self.cursor_position.x)
+                .matches('|')
+                .count()
+        });
+
+        let lines: Vec<String> = (start..end)
+            .filter_map(|index| self.document.row(index).map(|row| row.as_str().to_string()))
+            .collect();
+        let mut formatted = markdown::format_table(&lines).into_iter();
+        self.document
+            .edit_lines(LineRange::from(start..end), |row| {
+                if let Some(line) = formatted.next() {
+                    row.set_text(&line);
+                }
+            });
+
+        if let Some(row) = self.document.row(y) {
+            let pipe = row
+                .as_str()
+                .graphemes(true)
+                .enumerate()
+                .filter(|(_, grapheme)| *grapheme == "|")
+                .nth(cell.saturating_sub(1))
+                .map_or(0, |(x, _)| x.saturating_add(2));
+            self.cursor_position.x = pipe;
+        }
+        self.move_cursor(KeyCode::Null);
+        self.notifications.info(format!(
+            "Aligned a table of {} rows.",
+            end.saturating_sub(start)
+        ));
+    }
+
+    /// Opens the URL under the cursor in the browser, or whatever else the system
+    /// opens URLs with.
+    fn open_url(&mut self) {
+        let Some(row) = self.document.row(self.cursor_position.y) else {
+            return;
+        };
+        // URLs are found by character, the cursor counts graphemes
+        let at = row.substring(0, self.cursor_position.x).chars().count();
+        let url = row
+            .urls()
+            .into_iter()
+            .find(|range| range.start <= at && at <= range.end)
+            .map(|range| {
+                row.as_str()
+                    .chars()
+                    .skip(range.start)
+                    .take(range.len())
+                    .collect::<String>()
+            });
+
+        let Some(url) = url else {
+            self.notifications
+                .warning("No URL under the cursor.".to_string());
+            return;
+        };
+        match hooks::open(&url) {
+            Ok(()) => self.notifications.info(format!("Opening {url}")),
+            Err(err) => self
+                .notifications
+                .error(format!("Could not open {url}: {err}")),
+        }
+    }
+
+    /// Changes the case of the selection, or of the word under the cursor when nothing is selected.
+    fn transform_case(&mut self, transform: &dyn Fn(&str) -> String) {
+        let selected = self.selection();
+        let Some((start, end)) = selected
+            .clone()
+            .or_else(|| self.document.word_at(&self.cursor_position))
+        else {
+            return;
+        };
+
+        let text = transform(&self.document.text(&start, &end));
+        self.document.delete_range(&start, &end);
+        let new_end = self.document.insert_str(&start, &text);
+
+        if selected.is_some() {
+            self.selection_anchor = Some(start);
+            self.cursor_position = new_end;
+        } else {
+            self.move_cursor(KeyCode::Null);
+        }
+    }
+
+    /// Returns the rows of the paragraph around the cursor, delimited by blank lines.
+    fn paragraph_rows(&self, comment: Option<&str>) -> Range<usize> {
+        let is_text = |y: usize| {
+            self.document.row(y).is_some_and(|row| {
+                let line = row.as_str();
+                !line
+                    .get(line_prefix(line, comment).len()..)
+                    .unwrap_or_default()
+                    .trim()
+                    .is_empty()
+            })
+        };
+
+        let y = self.cursor_position.y;
+        if !is_text(y) {
+            return y..y;
+        }
+
+        let mut start = y;
+        while start > 0 && is_text(start.saturating_sub(1)) {
+            start = start.saturating_sub(1);
+        }
+
+        let mut end = y;
+        while is_text(end) {
+            end = end.saturating_add(1);
+        }
+
+        start..end
+    }
+
+    fn reflow_paragraph(&mut self) {
+        let comment = self.document.line_comment();
+        let range = if self.selection().is_some() {
+            self.selected_rows()
+        } else {
+            self.paragraph_rows(comment)
+        };
+
+        if range.is_empty() {
+            return;
+        }
+
+        let width = self.config.editor.text_width;
+        let start = range.start;
+        let count = self
+            .document
+            .transform_rows(range, |lines| reflow(&lines, width, comment));
+
+        self.selection_anchor = None;
+        self.cursor_position = Position {
+            x: 0,
+            y: start.saturating_add(count).saturating_sub(1),
+        };
+        self.move_cursor(KeyCode::End);
+    }
+
+    /// Reports the size of the document, and of the selection when there is one.
+    fn show_statistics(&mut self) {
+        let mut text = String::new();
+        for index in 0..self.document.len() {
+            if let Some(row) = self.document.row(index) {
+                text.push_str(row.as_str());
+                text.push('\n');
+            }
+        }
+
+        let mut message = statistics(&text);
+        if let Some((start, end)) = self.selection() {
+            let selected = self.document.text(&start, &end);
+            message = format!("{message} (selection: {})", statistics(&selected));
+        }
+        self.notifications.info(message);
+    }
+
+    /// Describes every codepoint of the character under the cursor, to tell apart
+    /// lookalike or invisible characters.
+    fn inspect_character(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let grapheme = self
+            .document
+            .row(y)
+            .map(|row| row.substring(x, x.saturating_add(1)))
+            .unwrap_or_default();
+
+        if grapheme.is_empty() {
+            self.notifications
+                .info("No character under the cursor.".to_string());
+            return;
+        }
+
+        let codepoints: Vec<String> = grapheme.chars().map(describe_char).collect();
+        self.notifications.info(codepoints.join(" + "));
+    }
+
+    fn insert_codepoint(&mut self) {
+        let Some(input) = self
+            .prompt("Insert codepoint (hex): ", Some("codepoint"), |_, _, _| {
+                None
+            })
+            .unwrap_or(None)
+            .filter(|input| !input.is_empty())
+        else {
+            return;
+        };
+
+        let digits = input.trim();
+        let digits = digits
+            .strip_prefix("U+")
+            .or_else(|| digits.strip_prefix("u+"))
+            .or_else(|| digits.strip_prefix("0x"))
+            .unwrap_or(digits);
+
+        if let Some(c) = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            self.delete_selection();
+            self.document.insert(&self.cursor_position, c);
+            self.move_cursor(KeyCode::Right);
+        } else {
+            self.notifications
+                .error(format!("Invalid codepoint: {input}"));
+        }
+    }
+
+    fn search(&mut self) {
+        self.selection_anchor = None;
+        let old_position = self.cursor_position.clone();
+        let mut direction = SearchDirection::Forward;
+        let query = self
+            .prompt_with_input(
+                "Search (Up/Down: previous/next, Ctrl+P/Ctrl+N: history): ",
+                "",
+                Some("search"),
+                false,
+                false,
+                |editor, key_code, query| {
+                    let mut moved = false;
+                    match key_code {
+                        KeyCode::Down => {
+                            direction = SearchDirection::Forward;
+                            editor.move_cursor(KeyCode::Right);
+                            moved = true;
+                        }
+                        KeyCode::Up => direction = SearchDirection::Backward,
+                        _ => direction = SearchDirection::Forward,
+                    }
+
+                    let mut hint = None;
+                    if let Some((position, wrapped)) =
+                        editor
+                            .document
+                            .find_wrapping(query, &editor.cursor_position, direction)
+                    {
+                        editor.cursor_position = position;
+                        editor.scroll();
+                        if wrapped {
+                            hint = Some("  [search wrapped]".to_string());
+                        }
+                    } else {
+                        if moved {
+                            editor.move_cursor(KeyCode::Left);
+                        }
+                        if !query.is_empty() {
+                            hint = Some("  [no more matches]".to_string());
+                        }
+                    }
+
+                    editor.highlighted_word = Some(query.clone());
+                    hint
+                },
+            )
+            .unwrap_or(None);
+
+        if query.is_none() {
+            self.cursor_position = old_position;
+            self.scroll();
+        }
+
+        self.highlighted_word = None;
+    }
+
+    /// Replaces every match of a regular expression in the selected rows, or in the
+    /// whole document. The replacement can refer to capture groups as `$1` or `${name}`.
+    fn replace(&mut self) {
+        let Some(pattern) = self
+            .prompt("Replace (regex): ", Some("replace"), |_, _, _| None)
+            .unwrap_or(None)
+            .filter(|pattern| !pattern.is_empty())
+        else {
+            return;
+        };
+
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(err) => {
+                // Syntax errors span several lines, ending with the actual problem
+                let err = err.to_string();
+                self.notifications.error(format!(
+                    "Invalid regex: {}",
+                    err.lines().last().unwrap_or_default()
+                ));
+                return;
+            }
+        };
+
+        let Some(replacement) = self
+            .prompt("Replace with: ", Some("replace_with"), |_, _, _| None)
+            .unwrap_or(None)
+        else {
+            return;
+        };
+
+        let range = self.selected_rows();
+        let text = range
+            .clone()
+            .filter_map(|y| self.document.row(y))
+            .map(Row::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let count = regex.find_iter(&text).count();
+        if count == 0 {
+            self.notifications
+                .warning(format!("No matches for {pattern}"));
+            return;
+        }
+
+        if self
+            .confirm(&format!("Replace {count} matches?"))
+            .unwrap_or(Choice::Cancel)
+            != Choice::Yes
+        {
+            self.notifications.info("Replace aborted.".to_string());
+            return;
+        }
+
+        let replaced = regex.replace_all(&text, replacement.as_str());
+        self.document.transform_rows(range, |_| {
+            replaced.split('\n').map(str::to_string).collect()
+        });
+        self.selection_anchor = None;
+        self.move_cursor(KeyCode::Null);
+        self.notifications.info(format!("Replaced {count} matches"));
+    }
+
+    /// Asks for a regex, reporting one that doesn't compile. Returns it with the
+    /// pattern it was typed as, or `None` when the prompt was cancelled or empty.
+    fn prompt_regex(
+        &mut self,
+        prompt: &str,
+        history: &str,
+    ) -> Result<Option<(String, Regex)>, std::io::Error> {
+        let Some(pattern) = self
+            .prompt(prompt, Some(history), |_, _, _| None)?
+            .filter(|pattern| !pattern.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        match Regex::new(&pattern) {
+            Ok(regex) => Ok(Some((pattern, regex))),
+            Err(err) => {
+                let err = err.to_string();
+                self.notifications.error(format!(
+                    "Invalid regex: {}",
+                    err.lines().last().unwrap_or_default()
+                ));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Searches a regex in every project file and makes the matching lines the
+    /// quickfix list, going to the first of them.
+    fn project_search(&mut self) -> Result<(), std::io::Error> {
+        let Some((pattern, regex)) = self.prompt_regex("Search project (regex): ", "search")?
+        else {
+            return Ok(());
+        };
+
+        self.notifications.update(
+            Severity::Info,
+            format!("Searching the project for {pattern}..."),
+        );
+        self.refresh_screen()?;
+        let matches = project::search(&mut self.workspace, &regex);
+        if matches.is_empty() {
+            self.notifications
+                .warning(format!("No matches for {pattern} in the project."));
+            return Ok(());
+        }
+        self.quickfix = Quickfix::from_matches(&matches);
+        self.go_to_location(true);
+        Ok(())
+    }
+
+    /// Replaces a regex in every file under the working directory. The matching
+    /// lines are listed first, where Enter leaves a line out or puts it back, until
+    /// Enter on the last line applies the replacement to the files.
+    #[allow(clippy::too_many_lines)]
+    fn project_replace(&mut self) -> Result<(), std::io::Error> {
+        let Some((pattern, regex)) =
+            self.prompt_regex("Replace in project (regex): ", "replace")?
+        else {
+            return Ok(());
+        };
+
+        let Some(replacement) =
+            self.prompt("Replace with: ", Some("replace_with"), |_, _, _| None)?
+        else {
+            return Ok(());
+        };
+
+        self.notifications.update(
+            Severity::Info,
+            format!("Searching the project for {pattern}..."),
+        );
+        self.refresh_screen()?;
+        let matches = project::search(&mut self.workspace, &regex);
+        if matches.is_empty() {
+            self.notifications
+                .warning(format!("No matches for {pattern} in the project."));
+            return Ok(());
+        }
+
+        let mut selected = vec![true; matches.len()];
+        let mut index = 0;
+        loop {
+            let count = selected.iter().filter(|selected| **selected).count();
+            let mut lines: Vec<String> = matches
+                .iter()
+                .zip(&selected)
+                .map(|(found, selected)| {
+                    format!(
+                        "[{}] {}:{}: {}",
+                        if *selected { 'x' } else { ' ' },
+                        found.file,
+                        found.line.saturating_add(1),
+                        found.text.trim()
+                    )
+                })
+                .collect();
+            lines.push(format!("Replace on the {count} selected lines"));
+
+            let overlay = Overlay::new(
+                "Replace in project (Enter toggles a line)",
+                lines,
+                Anchor::Center,
+            )
+            .with_selection(index);
+            match self.show_overlay(overlay)? {
+                Some(choice) if choice < matches.len() => {
+                    if let Some(selected) = selected.get_mut(choice) {
+                        *selected = !*selected;
+                    }
+                    index = choice;
+                }
+                Some(_) if count > 0 => break,
+                _ => {
+                    self.notifications.info("Replace aborted.".to_string());
+                    return Ok(());
+                }
+            }
+        }
+
+        let mut files: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (found, _) in matches
+            .iter()
+            .zip(&selected)
+            .filter(|(_, selected)| **selected)
+        {
+            match files.last_mut() {
+                Some((file, lines)) if *file == found.file => lines.push(found.line),
+                _ => files.push((&found.file, vec![found.line])),
+            }
+        }
+
+        let mut replaced = 0_usize;
+        let mut changed = 0_usize;
+        for (file, lines) in files {
+            if self
+                .open_document(file)
+                .is_some_and(|document| document.is_dirty())
+            {
+                self.message_log
+                    .push(format!("{file}: skipped, it has unsaved changes"));
+                continue;
+            }
+
+            match project::replace_in_file(file, &regex, &replacement, &lines) {
+                Ok(count) => {
+                    self.message_log
+                        .push(format!("{file}: replaced {count} matches"));
+                    replaced = replaced.saturating_add(count);
+                    changed = changed.saturating_add(1);
+
+                    if let (Ok(updated), Some(document)) =
+                        (Document::open(file), self.open_document(file))
+                    {
+                        document.replace_contents(&updated);
+                    }
+                }
+                Err(err) => self
+                    .message_log
+                    .push(format!("{file}: could not replace: {err}")),
+            }
+        }
+
+        self.move_cursor(KeyCode::Null);
+        self.notifications.info(format!(
+            "Replaced {replaced} matches in {changed} files (see message log)."
+        ));
+        Ok(())
+    }
+
+    /// Lists the changed files of the repository, staging or unstaging the chosen one.
+    /// The last line starts writing the commit.
+    fn git_status(&mut self) -> Result<(), std::io::Error> {
+        let repository = match Repository::open(self.workspace.root()) {
+            Ok(repository) => repository,
+            Err(err) => {
+                self.notifications.error(format!("Git: {err}"));
+                return Ok(());
+            }
+        };
+
+        let mut index = 0;
+        loop {
+            let entries = match repository.status() {
+                Ok(entries) => entries,
+                Err(err) => {
+                    self.notifications.error(format!("Git: {err}"));
+                    return Ok(());
+                }
+            };
+            if entries.is_empty() {
+                self.notifications
+                    .info("Nothing to commit, the working tree is clean.".to_string());
+                return Ok(());
+            }
+
+            let staged = entries
+                .iter()
+                .filter(|entry| entry.has_staged_changes())
+                .count();
+            let mut lines: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let icon = if self.config.editor.icons {
+                        format!("{} ", icons::file(&entry.path))
+                    } else {
+                        String::new()
+                    };
+                    format!(
+                        "[{}] {}{} {icon}{}",
+                        if entry.is_staged() { 'x' } else { ' ' },
+                        entry.index,
+                        entry.worktree,
+                        entry.path
+                    )
+                })
+                .collect();
+            lines.push(format!("Commit the {staged} staged files"));
+
+            let overlay = Overlay::new(
+                "Git status (Enter stages or unstages a file)",
+                lines,
+                Anchor::Center,
+            )
+            .with_selection(index);
+            match self.show_overlay(overlay)? {
+                Some(choice) => match entries.get(choice) {
+                    Some(entry) => {
+                        let result = if entry.is_staged() {
+                            repository.unstage(&entry.path)
+                        } else {
+                            repository.stage(&entry.path)
+                        };
+                        self.git_gutter.invalidate();
+                        if let Err(err) = result {
+                            self.notifications.error(format!("Git: {err}"));
+                            return Ok(());
+                        }
+                        index = choice;
+                    }
+                    None if staged > 0 => {
+                        self.git_commit();
+                        return Ok(());
+                    }
+                    None => {
+                        self.notifications
+                            .warning("Nothing is staged yet.".to_string());
+                        index = choice;
+                    }
+                },
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Opens the commit message in a buffer, listing the staged files in comments.
+    /// Saving the buffer commits, and a message that's still being written is kept.
+    fn git_commit(&mut self) {
+        let repository = match Repository::open(self.workspace.root()) {
+            Ok(repository) => repository,
+            Err(err) => {
+                self.notifications.error(format!("Git: {err}"));
+                return;
+            }
+        };
+        let (entries, path) = match repository.status().and_then(|entries| {
+            let path = repository.message_file()?;
+            Ok((entries, path))
+        }) {
+            Ok(found) => found,
+            Err(err) => {
+                self.notifications.error(format!("Git: {err}"));
+                return;
+            }
+        };
+
+        let staged: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.has_staged_changes())
+            .collect();
+        if staged.is_empty() {
+            self.notifications.warning(format!(
+                "Nothing is staged, stage files with {} first.",
+                self.config.keys.git_status
+            ));
+            return;
+        }
+
+        let file_name = project::file_name(repository.root(), &path);
+        if !self
+            .open_document(&file_name)
+            .is_some_and(|document| document.is_dirty())
+        {
+            let mut message = String::from(
+                "\n# Write the commit message above and save to commit. Lines starting\n\
+                 # with '#' are left out, and an empty message aborts the commit.\n#\n\
+                 # Changes to be committed:\n",
+            );
+            for entry in staged {
+                writeln!(message, "#   {} {}", entry.index, entry.path).ok();
+            }
+            if let Err(err) = fs::write(&path, message) {
+                self.notifications
+                    .error(format!("Could not write {file_name}: {err}"));
+                return;
+            }
+            if let (Ok(updated), Some(document)) =
+                (Document::open(&file_name), self.open_document(&file_name))
+            {
+                document.replace_contents(&updated);
+            }
+        }
+
+        if self.open_file(&file_name) {
+            self.cursor_position = Position::default();
+            self.selection_anchor = None;
+            self.scroll();
+            self.commit = Some((repository, file_name));
+            self.notifications
+                .info("Write the commit message, saving commits.".to_string());
+        }
+    }
+
+    /// Replaces the merge conflict under the cursor with one of its sides, or both.
+    fn resolve_conflict(&mut self, resolution: Resolution) {
+        self.conflicts.update(&self.document);
+        let Some(conflict) = self.conflicts.at(self.cursor_position.y) else {
+            self.notifications
+                .warning("The cursor isn't in a merge conflict.".to_string());
+            return;
+        };
+
+        let rows = conflict.rows();
+        let offset = |range: Range<usize>| {
+            range.start.saturating_sub(rows.start)..range.end.saturating_sub(rows.start)
+        };
+        let ours = offset(conflict.ours());
+        let theirs = offset(conflict.theirs());
+        self.selection_anchor = None;
+        self.document.transform_rows(rows.clone(), |lines| {
+            let side = |range: Range<usize>| lines.get(range).unwrap_or_default().to_vec();
+            match resolution {
+                Resolution::Ours => side(ours),
+                Resolution::Theirs => side(theirs),
+                Resolution::Both => [side(ours), side(theirs)].concat(),
+            }
+        });
+        self.cursor_position = Position {
+            x: 0,
+            y: rows.start,
+        };
+        self.move_cursor(KeyCode::Null);
+
+        self.conflicts.update(&self.document);
+        let kept = match resolution {
+            Resolution::Ours => "our side",
+            Resolution::Theirs => "their side",
+            Resolution::Both => "both sides",
+        };
+        self.notifications.info(format!(
+            "Kept {kept}, {} merge conflicts left.",
+            self.conflicts.len()
+        ));
+    }
+
+    /// Stages the change under the cursor as it is in the buffer, leaving the file's
+    /// other changes unstaged.
+    fn stage_hunk(&mut self) {
+        self.git_gutter.update_now(&self.document);
+        let Some((repository, staged)) = self.git_gutter.staged() else {
+            self.notifications.warning(format!(
+                "Git doesn't track this file, stage it with {} first.",
+                self.config.keys.git_status
+            ));
+            return;
+        };
+        let Some(hunk) = self.git_gutter.hunk_at(self.cursor_position.y) else {
+            self.notifications
+                .warning("There is no change under the cursor.".to_string());
+            return;
+        };
+
+        let line = hunk.new.start.saturating_add(1);
+        let text = staged.with_hunk(hunk, &self.document);
+        match repository.stage_text(staged, &text) {
+            Ok(()) => {
+                self.git_gutter.invalidate();
+                self.notifications
+                    .info(format!("Staged the change at line {line}."));
+            }
+            Err(err) => self.notifications.error(format!("Git: {err}")),
+        }
+    }
+
+    /// Puts the staged version of the change under the cursor back into the buffer,
+    /// as an edit that can be undone.
+    fn revert_hunk(&mut self) {
+        self.git_gutter.update_now(&self.document);
+        let Some((_, staged)) = self.git_gutter.staged() else {
+            self.notifications
+                .warning("Git doesn't track this file.".to_string());
+            return;
+        };
+        let Some(hunk) = self.git_gutter.hunk_at(self.cursor_position.y) else {
+            self.notifications
+                .warning("There is no change under the cursor.".to_string());
+            return;
+        };
+
+        let lines = staged
+            .lines
+            .get(hunk.old.clone())
+            .unwrap_or_default()
+            .to_vec();
+        let range = hunk.new.clone();
+        let line = range.start.saturating_add(1);
+        self.selection_anchor = None;
+        self.document.transform_rows(range.clone(), |_| lines);
+        self.cursor_position = Position {
+            x: 0,
+            y: range.start,
+        };
+        self.move_cursor(KeyCode::Null);
+        self.notifications
+            .info(format!("Reverted the change at line {line}."));
+    }
+
+    /// Starts the commit when the saved document is the commit message. Returns
+    /// whether it was.
+    fn commit_on_save(&mut self) -> bool {
+        let Some((repository, message_file)) = self.commit.clone() else {
+            return false;
+        };
+        if !self
+            .document
+            .file_name
+            .as_deref()
+            .is_some_and(|name| same_file(name, &message_file))
+        {
+            return false;
+        }
+
+        self.notifications.info("Committing...".to_string());
+        self.tasks.spawn(move || Event::Committed {
+            result: repository.commit(Path::new(&message_file)),
+        });
+        true
+    }
+
+    /// The open document of a file, whether it's the active buffer or another one.
+    fn open_document(&mut self, file: &str) -> Option<&mut Document> {
+        let same = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| same_file(name, file))
+        };
+
+        if same(&self.document) {
+            return Some(&mut self.document);
+        }
+        self.buffers
+            .iter_mut()
+            .map(|buffer| &mut buffer.document)
+            .find(|document| same(document))
+    }
+
+    /// Asks a yes/no question in the message bar and waits for a single key: `y`,
+    /// `n`, or Esc to cancel.
+    fn confirm(&mut self, question: &str) -> Result<Choice, std::io::Error> {
+        let text = format!("{question} (y/n, Esc to cancel) ");
+        self.prompt_line = Some((text.clone(), text.chars().count()));
+        self.refresh_screen()?;
+
+        let choice = loop {
+            match self.terminal.read_key().code {
+                KeyCode::Char('y' | 'Y') => break Choice::Yes,
+                KeyCode::Char('n' | 'N') => break Choice::No,
+                KeyCode::Esc => break Choice::Cancel,
+                _ => (),
+            }
+        };
+
+        self.prompt_line = None;
+        Ok(choice)
+    }
+
+    /// Reads a line of input in the message bar, with the usual line editing keys:
+    /// Left/Right/Home/End move, Backspace/Delete remove and Ctrl+U clears the input.
+    /// Prompts with a `history` name recall earlier input with Up/Down or
+    /// Ctrl+P/Ctrl+N and remember what is submitted. After every key the callback
+    /// can inspect or change the input, and return a hint shown after it. Returns
+    /// `None` when cancelled with Esc.
+    fn prompt<C>(
+        &mut self,
+        prompt: &str,
+        history: Option<&str>,
+        callback: C,
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, KeyCode, &mut String) -> Option<String>,
+    {
+        self.prompt_with_input(prompt, "", history, true, false, callback)
+    }
+
+    /// Like `prompt`, but starts with `input` already typed. Without `arrows`, Up and
+    /// Down are left to the callback and only Ctrl+P/Ctrl+N recall earlier input.
+    /// With `ticks`, the callback is also called with `KeyCode::Null` every `TICK`
+    /// no key is pressed, for input whose answers come in later.
+    #[allow(clippy::too_many_lines)]
+    fn prompt_with_input<C>(
+        &mut self,
+        prompt: &str,
+        input: &str,
+        history: Option<&str>,
+        arrows: bool,
+        ticks: bool,
+        mut callback: C,
+    ) -> Result<Option<String>, std::io::Error>
+    where
+        C: FnMut(&mut Self, KeyCode, &mut String) -> Option<String>,
+    {
+        let mut result = input.to_string();
+        let mut cursor = result.chars().count();
+        let mut hint: Option<String> = None;
+        let entries = history
+            .map(|name| self.state.history(name).to_vec())
+            .unwrap_or_default();
+        // The history entry being shown, and the input typed before browsing started
+        let mut recalled: Option<usize> = None;
+        let mut draft = String::new();
+        let mut cancelled = false;
+        loop {
+            self.prompt_line = Some((
+                format!("{prompt}{result}{}", hint.as_deref().unwrap_or_default()),
+                prompt.chars().count().saturating_add(cursor),
+            ));
+            self.refresh_screen()?;
+
+            let event = if ticks {
+                self.terminal
+                    .read_key_within(TICK)
+                    .unwrap_or_else(|| KeyEvent::from(KeyCode::Null))
+            } else {
+                self.terminal.read_key()
+            };
+            let key = event.code;
+            let len = result.chars().count();
+            let byte_index = |at: usize| {
+                result
+                    .char_indices()
+                    .nth(at)
+                    .map_or(result.len(), |(index, _)| index)
+            };
+
+            let control = event.modifiers.contains(KeyModifiers::CONTROL);
+            let back = (arrows && key == KeyCode::Up) || (control && key == KeyCode::Char('p'));
+            let forward =
+                (arrows && key == KeyCode::Down) || (control && key == KeyCode::Char('n'));
+
+            match key {
+                KeyCode::Char('u') if control => {
+                    result.clear();
+                    cursor = 0;
+                }
+                KeyCode::Left => cursor = cursor.saturating_sub(1),
+                KeyCode::Right => cursor = cursor.saturating_add(1).min(len),
+                KeyCode::Home => cursor = 0,
+                KeyCode::End => cursor = len,
+                _ if back && !entries.is_empty() => {
+                    let index = if let Some(index) = recalled {
+                        index.saturating_sub(1)
+                    } else {
+                        draft.clone_from(&result);
+                        entries.len().saturating_sub(1)
+                    };
+                    recalled = Some(index);
+                    result = entries.get(index).cloned().unwrap_or_default();
+                    cursor = result.chars().count();
+                }
+                _ if forward => {
+                    if let Some(index) = recalled {
+                        if let Some(entry) = entries.get(index.saturating_add(1)) {
+                            recalled = Some(index.saturating_add(1));
+                            result.clone_from(entry);
+                        } else {
+                            recalled = None;
+                            result = std::mem::take(&mut draft);
+                        }
+                        cursor = result.chars().count();
+                    }
+                }
+                KeyCode::Backspace if cursor > 0 => {
+                    cursor = cursor.saturating_sub(1);
+                    result.remove(byte_index(cursor));
+                }
+                KeyCode::Delete if cursor < len => {
+                    result.remove(byte_index(cursor));
+                }
+                KeyCode::Enter => break,
+                KeyCode::Char(c)
+                    if !c.is_control()
+                        && !event
+                            .modifiers
+                            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    result.insert(byte_index(cursor), c);
+                    cursor = cursor.saturating_add(1);
+                }
+                KeyCode::Esc => {
+                    cancelled = true;
+                    break;
+                }
+                _ => (),
+            }
+
+            let before = result.clone();
+            hint = callback(self, key, &mut result);
+            if result != before {
+                cursor = result.chars().count();
+            }
+        }
+        self.prompt_line = None;
+        if cancelled {
+            return Ok(None);
+        }
+
+        if let (Some(name), false) = (history, result.is_empty()) {
+            self.state.push_history(name, &result);
+            if let Err(err) = self.state.save() {
+                self.message_log
+                    .push(format!("Could not save the prompt history: {err}"));
+            }
+        }
+        Ok(Some(result))
+    }
+
+    /// The width of the line number gutter, including its padding, or 0 when it's hidden.
+    fn gutter_width(&self) -> usize {
+        self.number_width()
+            .saturating_add(usize::from(self.shows_markers()))
+    }
+
+    /// Whether the gutter has a column of markers, for changes, for the sides of merge
+    /// conflicts or for breakpoints and where the debugger paused.
+    fn shows_markers(&self) -> bool {
+        self.config.editor.change_markers
+            || self.conflicts.len() > 0
+            || self.debugger.is_some()
+            || self.document.has_breakpoints()
+    }
+
+    /// The columns taken by line numbers, including the space after them.
+    fn number_width(&self) -> usize {
+        if !self.config.editor.line_numbers {
+            return 0;
+        }
+
+        let digits = usize::try_from(self.document.len().max(1).ilog10().saturating_add(1))
+            .unwrap_or_default();
+        digits.max(3).saturating_add(1)
+    }
+
+    /// The number of columns left for text next to the gutter.
+    fn text_width(&self) -> usize {
+        self.pane().1.saturating_sub(self.gutter_width())
+    }
+
+    /// The first screen column of the active pane and how many columns it has, the
+    /// whole width unless the text area is split.
+    fn pane(&self) -> (usize, usize) {
+        let total = usize::from(self.terminal.size().width);
+        match &self.split {
+            None => (0, total),
+            Some(split) => {
+                let width = split.width.min(total.saturating_sub(2));
+                if split.left {
+                    (0, width)
+                } else {
+                    let start = width.saturating_add(1);
+                    (start, total.saturating_sub(start))
+                }
+            }
+        }
+    }
+
+    /// The screen column of a document position, which differs from its `x` when
+    /// the row has characters drawn wider than one column.
+    fn column(&self, at: &Position) -> usize {
+        let ansi = self.document.shows_ansi();
+        self.document
+            .row(at.y)
+            .map_or(at.x, |row| row.column(at.x, ansi))
+    }
+
+    /// The place in the document drawn at a screen cell of the active pane, or `None`
+    /// for cells outside it. Cells past the end of a row or of the document give
+    /// the end of the last one.
+    fn document_position(&self, column: u16, row: u16) -> Option<Position> {
+        let (start, width) = self.pane();
+        if row >= self.terminal.size().height || usize::from(column) >= start.saturating_add(width)
+        {
+            return None;
+        }
+        let y = self
+            .offset
+            .y
+            .saturating_add(usize::from(row))
+            .min(self.document.len().saturating_sub(1));
+        let column = usize::from(column)
+            .checked_sub(start.saturating_add(self.gutter_width()))?
+            .saturating_add(self.offset.x);
+        let x = self
+            .document
+            .row(y)
+            .map_or(0, |row| row.x_at_column(column, self.document.shows_ansi()));
+        Some(Position { x, y })
+    }
+
+    /// Where a document position is drawn on the screen.
+    fn screen_position(&self, at: &Position) -> Position {
+        Position {
+            x: self
+                .column(at)
+                .saturating_sub(self.offset.x)
+                .saturating_add(self.pane().0)
+                .saturating_add(self.gutter_width()),
+            y: at.y.saturating_sub(self.offset.y),
+        }
+    }
+
+    /// The rows kept between the cursor and the top or bottom edge, at most half of
+    /// the text area.
+    fn scroll_margin(&self) -> usize {
+        let height = usize::from(self.terminal.size().height);
+        self.config
+            .editor
+            .scroll_off
+            .min(height.saturating_sub(1).checked_div(2).unwrap_or_default())
+    }
+
+    fn scroll(&mut self) {
+        let x = self.column(&self.cursor_position);
+        let y = self.cursor_position.y;
+        let width = self.text_width();
+        let height = usize::from(self.terminal.size().height);
+        let margin = self.scroll_margin();
+        // The rows that must be visible, with the margin cut short at the document's end
+        let top = y.saturating_sub(margin);
+        let bottom = y.saturating_add(margin).min(self.document.len().max(y));
+        // A clipped row gives its edge columns to the `<` and `>` markers, so the
+        // cursor is kept off them, one column in from an edge the row goes past
+        let continues = self
+            .document
+            .row(y)
+            .is_some_and(|row| row.is_wider_than(x.saturating_add(1), self.document.shows_ansi()));
+        let right = x.saturating_add(usize::from(continues));
+        let offset = &mut self.offset;
+
+        if top < offset.y {
+            offset.y = top;
+        } else if bottom >= offset.y.saturating_add(height) {
+            offset.y = bottom.saturating_sub(height).saturating_add(1);
+        }
+
+        if x < offset.x || (x == offset.x && x > 0) {
+            offset.x = x.saturating_sub(1);
+        } else if right >= offset.x.saturating_add(width) {
+            offset.x = right.saturating_sub(width).saturating_add(1);
+        }
+    }
+
+    /// Scrolls the view by `wheel_lines` rows, taking the cursor along when it
+    /// would be left outside the scroll margin.
+    fn scroll_view(&mut self, down: bool) {
+        let lines = self.config.editor.wheel_lines;
+        let height = usize::from(self.terminal.size().height);
+        let margin = self.scroll_margin();
+        self.offset.y = if down {
+            self.offset
+                .y
+                .saturating_add(lines)
+                .min(self.document.len().saturating_sub(1))
+        } else {
+            self.offset.y.saturating_sub(lines)
+        };
+
+        let top = if self.offset.y == 0 {
+            0
+        } else {
+            self.offset.y.saturating_add(margin)
+        };
+        let bottom = self
+            .offset
+            .y
+            .saturating_add(height.saturating_sub(1))
+            .saturating_sub(margin)
+            .max(top);
+        self.cursor_position.y = self
+            .cursor_position
+            .y
+            .clamp(top, bottom)
+            .min(self.document.len());
+        self.move_cursor(KeyCode::Null);
+    }
+
+    /// Scrolls the view and the cursor by half the text area, so the cursor stays on
+    /// the same row of the screen until the view reaches an end of the document.
+    fn scroll_half_page(&mut self, down: bool) {
+        let height = usize::from(self.terminal.size().height);
+        let half = height.saturating_div(2).max(1);
+        let len = self.document.len();
+        if down {
+            self.offset.y = self
+                .offset
+                .y
+                .saturating_add(half)
+                .min(len.saturating_sub(height))
+                .max(self.offset.y);
+            self.cursor_position.y = self.cursor_position.y.saturating_add(half).min(len);
+        } else {
+            self.offset.y = self.offset.y.saturating_sub(half);
+            self.cursor_position.y = self.cursor_position.y.saturating_sub(half);
+        }
+        self.move_cursor(KeyCode::Null);
+    }
+
+    fn move_cursor(&mut self, key_selection: KeyCode) {
+        // A page keeps `page_overlap` rows of the one before it
+        let terminal_height = usize::from(self.terminal.size().height)
+            .saturating_sub(self.config.editor.page_overlap)
+            .max(1);
+        let Position { mut y, mut x } = self.cursor_position;
+        let height = self.document.len();
+        let mut width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+
+        match key_selection {
+            KeyCode::Up => y = y.saturating_sub(1),
+            KeyCode::Down if y < height => y = y.saturating_add(1),
+            KeyCode::Left => {
+                if x > 0 {
+                    x = x.saturating_sub(1);
+                } else if y > 0 {
+                    y = y.saturating_sub(1);
+                    if let Some(row) = self.document.row(y) {
+                        x = row.len();
+                    } else {
+                        x = 0;
+                    }
+                }
+            }
+            KeyCode::Right => {
+                if x < width {
+                    x = x.saturating_add(1);
+                } else if y < height {
+                    y = y.saturating_add(1);
+                    x = 0;
+                }
+            }
+            KeyCode::PageUp => {
+                y = if y > terminal_height {
+                    y.saturating_sub(terminal_height)
+                } else {
+                    0
+                }
+            }
+            KeyCode::PageDown => {
+                y = if y.saturating_add(terminal_height) < height {
+                    y.saturating_add(terminal_height)
+                } else {
+                    height
+                }
+            }
+            KeyCode::Home => x = 0,
+            KeyCode::End => x = width,
+            _ => (),
+        }
+
+        width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+
+        if x > width {
+            x = width;
+        }
+
+        self.cursor_position = Position { x, y }
+    }
+
+    fn jump(&mut self, motion: Motion) {
+        let y = self.cursor_position.y;
+        let last = self.document.len().saturating_sub(1);
+        let is_blank = |y: usize| {
+            self.document
+                .row(y)
+                .is_none_or(|row| row.as_str().trim().is_empty())
+        };
+        let indentation = |y: usize| {
+            self.document
+                .row(y)
+                .map_or(0, |row| row.indentation().chars().count())
+        };
+
+        let (y, x) = match motion {
+            Motion::PreviousParagraph => {
+                let mut target = y.saturating_sub(1);
+                while target > 0 && is_blank(target) {
+                    target = target.saturating_sub(1);
+                }
+                while target > 0 && !is_blank(target) {
+                    target = target.saturating_sub(1);
+                }
+                (target, 0)
+            }
+            Motion::NextParagraph => {
+                let mut target = y.saturating_add(1).min(last);
+                while target < last && is_blank(target) {
+                    target = target.saturating_add(1);
+                }
+                while target < last && !is_blank(target) {
+                    target = target.saturating_add(1);
+                }
+                (target, 0)
+            }
+            Motion::BlockStart | Motion::BlockEnd => {
+                let level = indentation(y);
+                let in_block = |y: usize| is_blank(y) || indentation(y) >= level;
+                let mut target = y;
+
+                if motion == Motion::BlockStart {
+                    while target > 0 && in_block(target.saturating_sub(1)) {
+                        target = target.saturating_sub(1);
+                    }
+                    while target < y && is_blank(target) {
+                        target = target.saturating_add(1);
+                    }
+                    if target == y {
+                        target = y.saturating_sub(1);
+                    }
+                } else {
+                    while target < last && in_block(target.saturating_add(1)) {
+                        target = target.saturating_add(1);
+                    }
+                    while target > y && is_blank(target) {
+                        target = target.saturating_sub(1);
+                    }
+                    if target == y {
+                        target = y.saturating_add(1).min(last);
+                    }
+                }
+                (target, indentation(target))
+            }
+        };
+
+        self.cursor_position = Position { x, y };
+        self.move_cursor(KeyCode::Null);
+    }
+
+    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
+        self.terminal.begin_frame();
+        self.terminal.hide_cursor();
+
+        self.terminal.move_cursor(&Position::default());
+
+        if self.should_quit {
+            self.terminal.clear_screen();
+            self.terminal.print("Goodbye.\r\n");
+        } else {
+            let start = Instant::now();
+            self.document.highlight(
+                &self.highlighted_word,
+                Some(
+                    self.offset
+                        .y
+                        .saturating_add(usize::from(self.terminal.size().height)),
+                ),
+            );
+            self.git_gutter.update(&self.document, &mut self.tasks);
+            if self.conflicts.update(&self.document) {
+                let keys = &self.config.keys;
+                self.notifications.warning(format!(
+                    "{} merge conflicts, resolve them with {} (ours), {} (theirs) or {} (both).",
+                    self.conflicts.len(),
+                    keys.keep_ours,
+                    keys.keep_theirs,
+                    keys.keep_both
+                ));
+            }
+            let highlighted = Instant::now();
+
+            self.update_title();
+            let rows = self.draw_rows();
+            if let Some(stats) = self.frame_stats.as_mut() {
+                stats.highlight = highlighted.duration_since(start);
+                stats.render = highlighted.elapsed();
+                stats.rows = rows;
+            }
+            self.draw_debug_overlay();
+            if !self.draw_variables() {
+                self.draw_outline();
+            }
+            if let Some(overlay) = self.overlay.as_mut() {
+                overlay.draw(self.terminal.as_mut(), &self.theme);
+            }
+
+            // Every line is drawn at an explicit position rather than relying on how
+            // the console handles newlines, which differs between Unix and Windows
+            let height = usize::from(self.terminal.size().height);
+            self.terminal.move_cursor(&Position { x: 0, y: height });
+            self.draw_status_bar();
+            self.terminal.move_cursor(&Position {
+                x: 0,
+                y: height.saturating_add(1),
+            });
+            self.draw_message_bar();
+            if let Some((_, x)) = self.prompt_line {
+                self.terminal.move_cursor(&Position {
+                    x,
+                    y: usize::from(self.terminal.size().height).saturating_add(1),
+                });
+            } else {
+                let position = self.screen_position(&self.cursor_position);
+                self.terminal.move_cursor(&position);
+            }
+        }
+
+        // The prompt line always inserts, whatever mode the text is typed in
+        let shape = if self.overwrite && self.prompt_line.is_none() {
+            self.config.editor.overwrite_cursor_shape
+        } else {
+            self.config.editor.cursor_shape
+        };
+        let style = (shape, self.config.editor.cursor_blink);
+        if style != self.cursor_style {
+            self.terminal.set_cursor_style(shape.style(style.1));
+            self.cursor_style = style;
+        }
+        self.terminal.show_cursor();
+        self.terminal.end_frame()
+    }
+
+    /// Reads the config and theme files again and applies them. When either can't
+    /// be parsed the current settings are kept and the error is shown instead.
+    fn reload_config(&mut self) {
+        let loaded = load_config(&self.args, &self.state)
+            .map_err(|err| format!("Could not load config: {err}"))
+            .and_then(|config| {
+                let theme =
+                    Theme::from_config(&config).map_err(|err| format!("Invalid theme: {err}"))?;
+                Ok((config, theme))
+            });
+
+        match loaded {
+            Ok((config, theme)) => {
+                self.keymap = Keymap::new(config.keys.bindings());
+                self.config = config;
+                self.load_scripts();
+                self.load_plugins();
+                self.theme = theme;
+                self.document.invalidate_rendering();
+                for buffer in &mut self.buffers {
+                    buffer.document.invalidate_rendering();
+                }
+                self.move_cursor(KeyCode::Null);
+                self.notifications.info("Config reloaded.".to_string());
+                if let Err(err) = self.offer_trust() {
+                    self.message_log
+                        .push(format!("Could not ask about the project config: {err}"));
+                }
+            }
+            Err(err) => self.notifications.error(err),
+        }
+    }
+
+    /// Asks whether to trust the project config with the settings that run
+    /// commands, when it has some the user didn't trust yet, and loads the config
+    /// again with them when they're trusted. They're asked about in every session
+    /// until then.
+    fn offer_trust(&mut self) -> Result<(), std::io::Error> {
+        let Some(untrusted) = self.config.untrusted.take() else {
+            return Ok(());
+        };
+
+        let question = format!(
+            "{} sets {}, which run commands. Trust this project?",
+            untrusted.root.join(config::PROJECT_CONFIG).display(),
+            untrusted.settings.join(", ")
+        );
+        if self.confirm(&question)? != Choice::Yes {
+            self.notifications.warning(format!(
+                "Ignoring {} of the project config.",
+                untrusted.settings.join(", ")
+            ));
+            return Ok(());
+        }
+
+        self.state.trust(&untrusted.root, &untrusted.commands);
+        if let Err(err) = self.state.save() {
+            self.message_log
+                .push(format!("Could not remember the trusted project: {err}"));
+        }
+        self.reload_config();
+        Ok(())
+    }
+
+    /// Runs the user's scripts again and binds the keys they ask for, reporting the
+    /// scripts that failed.
+    #[cfg(feature = "lua")]
+    fn load_scripts(&mut self) {
+        let (scripts, errors) = Scripts::load(Config::dir().as_deref());
+        self.scripts = scripts;
+        self.keymap.bind(
+            self.scripts
+                .bindings()
+                .into_iter()
+                .map(|(_, binding, name)| (binding, EditorCommand::RunScript(name))),
+        );
+
+        if let Some(first) = errors.first() {
+            self.notifications
+                .error(format!("Script failed: {first} (see message log)"));
+        }
+        self.message_log.extend(errors);
+    }
+
+    #[cfg(not(feature = "lua"))]
+    #[allow(clippy::unused_self)]
+    fn load_scripts(&mut self) {}
+
+    #[cfg(feature = "lua")]
+    fn run_script_command(&mut self, name: &str) {
+        // The scripts are taken out while they run so they can borrow the editor
+        let scripts = std::mem::take(&mut self.scripts);
+        let result = scripts.run(name, self);
+        self.scripts = scripts;
+
+        if let Err(err) = result {
+            self.message_log.push(format!("{name}: {err}"));
+            self.notifications
+                .error(format!("Script command {name} failed (see message log)"));
+        }
+        self.move_cursor(KeyCode::Null);
+    }
+
+    #[cfg(not(feature = "lua"))]
+    fn run_script_command(&mut self, name: &str) {
+        self.notifications.error(format!(
+            "Cannot run {name}: this build has no scripting support."
+        ));
+    }
+
+    /// Loads the plugins again and binds the keys they ask for, reporting the plugins
+    /// that failed.
+    #[cfg(feature = "wasm")]
+    fn load_plugins(&mut self) {
+        let (plugins, errors) = Plugins::load(Config::dir().as_deref());
+        self.plugins = plugins;
+        self.keymap.bind(
+            self.plugins
+                .bindings()
+                .into_iter()
+                .map(|(_, binding, name)| (binding, EditorCommand::RunPlugin(name))),
+        );
+
+        if let Some(first) = errors.first() {
+            self.notifications
+                .error(format!("Plugin failed: {first} (see message log)"));
+        }
+        self.message_log.extend(errors);
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    #[allow(clippy::unused_self)]
+    fn load_plugins(&mut self) {}
+
+    #[cfg(feature = "wasm")]
+    fn run_plugin_command(&mut self, name: &str) {
+        let result = self
+            .plugins
+            .run(name, &mut self.document, &mut self.cursor_position);
+
+        match result {
+            Ok(messages) => {
+                for message in messages {
+                    self.notifications.info(message);
+                }
+            }
+            Err(err) => {
+                self.message_log.push(format!("{name}: {err}"));
+                self.notifications
+                    .error(format!("Plugin command {name} failed (see message log)"));
+            }
+        }
+        self.move_cursor(KeyCode::Null);
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn run_plugin_command(&mut self, name: &str) {
+        self.notifications.error(format!(
+            "Cannot run {name}: this build has no plugin support."
+        ));
+    }
+
+    /// Shows the active file and whether it's modified in the terminal's title,
+    /// only writing it when it changed.
+    fn update_title(&mut self) {
+        let title = format!(
+            "{}{} - Voider",
+            self.document.file_name.as_deref().unwrap_or("[No Name]"),
+            if self.document.is_dirty() { " [+]" } else { "" }
+        );
+
+        if title != self.title {
+            self.terminal.set_title(&title);
+            self.title = title;
+        }
     }
 
     fn save(&mut self) {
         if self.document.file_name.is_none() {
-            let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or(None);
+            let mut completer = PathCompleter::default();
+            let new_name = self
+                .prompt("Save as: ", Some("save_as"), |_, key, input| {
+                    if key == KeyCode::Tab {
+                        path_hint(&completer.complete(input))
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(None)
+                .filter(|name| !name.is_empty());
+
+            let Some(new_name) = new_name else {
+                self.notifications.info("Save aborted: ".to_string());
+                return;
+            };
+
+            if Path::new(&new_name).exists()
+                && self
+                    .confirm(&format!("{new_name} already exists. Overwrite it?"))
+                    .unwrap_or(Choice::Cancel)
+                    != Choice::Yes
+            {
+                self.notifications.info("Save aborted: ".to_string());
+                return;
+            }
+
+            if !self.create_parent_directories(&new_name).unwrap_or(false) {
+                self.notifications.info("Save aborted: ".to_string());
+                return;
+            }
+
+            self.watcher.watch(&new_name);
+            self.document.set_file_name(new_name);
+            // Some file systems keep directory times too coarse to notice the new file
+            self.workspace.invalidate();
+        }
+
+        let trim = self
+            .config
+            .trim_trailing_whitespace(&self.document.file_type());
+
+        match self.document.save(trim, self.config.editor.follow_symlinks) {
+            Ok(()) => {
+                self.move_cursor(KeyCode::Null);
+                self.notifications
+                    .info("File saved successfully.".to_string());
+                if !self.commit_on_save() {
+                    self.run_save_hooks();
+                }
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Error writing file: {err}")),
+        }
+    }
+
+    /// Throws away the unsaved changes by reading the file again. The revert itself
+    /// can be undone.
+    fn revert(&mut self) -> Result<(), std::io::Error> {
+        let Some(file_name) = self.document.file_name.clone() else {
+            self.notifications
+                .warning("There is no file to revert to.".to_string());
+            return Ok(());
+        };
+
+        if self.document.is_dirty()
+            && self.confirm(&format!("Discard unsaved changes to {file_name}?"))? != Choice::Yes
+        {
+            return Ok(());
+        }
+
+        match Document::open(&file_name) {
+            Ok(document) => {
+                self.document.replace_contents(&document);
+                self.selection_anchor = None;
+                self.move_cursor(KeyCode::Null);
+                self.notifications.info(format!("Reverted {file_name}."));
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not read {file_name}: {err}")),
+        }
+        Ok(())
+    }
+
+    /// Asks for a file and opens it in a buffer of its own, with Tab completing the
+    /// path.
+    fn open(&mut self) -> Result<(), std::io::Error> {
+        let mut completer = PathCompleter::default();
+        let Some(file_name) = self
+            .prompt("Open: ", Some("open"), |_, key, input| {
+                if key == KeyCode::Tab {
+                    path_hint(&completer.complete(input))
+                } else {
+                    None
+                }
+            })?
+            .filter(|name| !name.is_empty())
+        else {
+            return Ok(());
+        };
+
+        self.open_file(&file_name);
+        Ok(())
+    }
+
+    /// Makes a file the active buffer. A file that's already open is switched to, and
+    /// a name that doesn't exist yet starts a new file. Returns whether the file is
+    /// active now.
+    fn open_file(&mut self, file_name: &str) -> bool {
+        let is_file = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| same_file(name, file_name))
+        };
+        if is_file(&self.document) {
+            return true;
+        }
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| is_file(&buffer.document))
+        {
+            self.switch_buffer(index);
+            return true;
+        }
+
+        let document = if Path::new(file_name).exists() {
+            match Document::open(file_name) {
+                Ok(mut document) => {
+                    if document.is_binary() {
+                        self.notifications.warning(format!(
+                            "{file_name} looks like a binary file, opened read-only."
+                        ));
+                    }
+                    document
+                        .set_ansi(self.config.editor.ansi_colors && document.has_ansi_escapes());
+                    document
+                }
+                Err(err) => {
+                    self.notifications
+                        .error(format!("Could not open {file_name}: {err}"));
+                    return false;
+                }
+            }
+        } else {
+            let mut document = Document::default();
+            document.set_file_name(file_name.to_string());
+            self.notifications.info(format!("New file: {file_name}"));
+            document
+        };
+
+        // An empty buffer that was never named or typed into is replaced rather than
+        // left behind
+        if self.document.file_name.is_none()
+            && self.document.is_empty()
+            && !self.document.is_dirty()
+        {
+            self.document = document;
+            self.cursor_position = Position::default();
+            self.offset = Position::default();
+            self.selection_anchor = None;
+        } else {
+            self.buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            });
+            self.switch_buffer(self.buffers.len().saturating_sub(1));
+        }
+        self.watcher.watch(file_name);
+        true
+    }
+
+    /// Renames or moves the file on disk, keeping the buffer open on its new path.
+    fn rename(&mut self) -> Result<(), std::io::Error> {
+        let Some(old_name) = self.document.file_name.clone() else {
+            self.notifications
+                .warning("Save the file before renaming it.".to_string());
+            return Ok(());
+        };
+
+        let mut completer = PathCompleter::default();
+        let Some(new_name) = self
+            .prompt_with_input(
+                "Rename to: ",
+                &old_name,
+                Some("save_as"),
+                true,
+                false,
+                |_, key, input| {
+                    if key == KeyCode::Tab {
+                        path_hint(&completer.complete(input))
+                    } else {
+                        None
+                    }
+                },
+            )?
+            .filter(|name| !name.is_empty() && *name != old_name)
+        else {
+            return Ok(());
+        };
+
+        if Path::new(&new_name).exists()
+            && self.confirm(&format!("{new_name} already exists. Overwrite it?"))? != Choice::Yes
+        {
+            return Ok(());
+        }
+
+        if !self.create_parent_directories(&new_name)? {
+            return Ok(());
+        }
+
+        match fs::rename(&old_name, &new_name) {
+            Ok(()) => {
+                self.document.set_file_name(new_name.clone());
+                self.watcher.watch(&new_name);
+                self.workspace.invalidate();
+                self.notifications
+                    .info(format!("Renamed {old_name} to {new_name}."));
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not rename {old_name}: {err}")),
+        }
+        Ok(())
+    }
+
+    /// Renames the symbol under the cursor with the help of the language server,
+    /// making its edits in the open buffers and writing them to the other files.
+    fn rename_symbol(&mut self) -> Result<(), std::io::Error> {
+        let Some((start, end)) = self.document.word_at(&self.cursor_position) else {
+            self.notifications
+                .warning("There's no symbol under the cursor to rename.".to_string());
+            return Ok(());
+        };
+        if !self.has_server_file() {
+            return Ok(());
+        }
+
+        let at = lsp::position_params(&self.document, &start);
+        let old_name = self.document.text(&start, &end);
+        let Some(new_name) = self
+            .prompt_with_input(
+                &format!("Rename {old_name} to: "),
+                &old_name,
+                None,
+                true,
+                false,
+                |_, _, _| None,
+            )?
+            .filter(|name| !name.is_empty() && *name != old_name)
+        else {
+            return Ok(());
+        };
+
+        self.notifications
+            .update(Severity::Info, format!("Renaming {old_name}..."));
+        let asked = Asked::Rename {
+            old_name: old_name.clone(),
+            new_name: new_name.clone(),
+        };
+        if let Err(err) = self.ask(asked, |client| client.rename(&at, &new_name)) {
+            self.notifications
+                .error(format!("Could not rename {old_name}: {err}"));
+        }
+        Ok(())
+    }
+
+    /// Makes the edits of a rename the language server answered with.
+    fn renamed(&mut self, old_name: &str, new_name: &str, files: &[(PathBuf, Vec<TextEdit>)]) {
+        if files.is_empty() {
+            self.notifications.warning(format!(
+                "The language server found nothing to rename at {old_name}."
+            ));
+            return;
+        }
+
+        let renamed: usize = files.iter().map(|(_, edits)| edits.len()).sum();
+        let changed = self.apply_workspace_edit(files);
+        let failed = files.len().saturating_sub(changed);
+        if failed > 0 {
+            self.notifications.error(format!(
+                "Renamed {old_name} to {new_name} in {changed} files, {failed} could not be \
+                 changed (see message log)."
+            ));
+        } else {
+            self.notifications.info(format!(
+                "Renamed {renamed} occurrences of {old_name} to {new_name} in {changed} files."
+            ));
+        }
+    }
+
+    /// Asks the language server for the documentation of the symbol under the
+    /// cursor, which `show_hover` shows once it answers.
+    fn hover(&mut self) {
+        if !self.has_server_file() {
+            return;
+        }
+
+        let at = lsp::position_params(&self.document, &self.cursor_position);
+        let title = self
+            .document
+            .word_at(&self.cursor_position)
+            .map(|(start, end)| self.document.text(&start, &end))
+            .unwrap_or_default();
+        if let Err(err) = self.ask(Asked::Hover { title }, |client| client.hover(&at)) {
+            self.notifications
+                .error(format!("Could not get the documentation: {err}"));
+        }
+    }
+
+    /// Shows the documentation of `title` in a popup below the cursor, which the
+    /// arrow keys scroll and any other key closes.
+    fn show_hover(&mut self, title: &str, text: &str) -> Result<(), std::io::Error> {
+        let width = usize::from(self.terminal.size().width)
+            .saturating_sub(6)
+            .min(HOVER_WIDTH);
+        let lines = markdown::plain_lines(text, width);
+        if lines.is_empty() {
+            self.notifications
+                .info("No documentation for the symbol under the cursor.".to_string());
+            return Ok(());
+        }
+
+        let anchor = Anchor::Below(self.screen_position(&self.cursor_position));
+        self.show_overlay(Overlay::new(title, lines, anchor))?;
+        Ok(())
+    }
+
+    /// Asks the language server for the code actions it offers for the selection or
+    /// the cursor, such as quick fixes and added imports, which `pick_action` lists
+    /// once it answers.
+    fn code_actions(&mut self) {
+        if !self.has_server_file() {
+            return;
+        }
+
+        let (start, end) = self
+            .selection()
+            .unwrap_or_else(|| (self.cursor_position.clone(), self.cursor_position.clone()));
+        let at = lsp::range_params(&self.document, &start, &end);
+        if let Err(err) = self.ask(Asked::CodeActions, |client| client.code_actions(&at)) {
+            self.notifications
+                .error(format!("Could not get the code actions: {err}"));
+        }
+    }
+
+    /// Lists the code actions the language server offered and runs the chosen one.
+    fn pick_action(&mut self, actions: &[lsp::Action]) -> Result<(), std::io::Error> {
+        if actions.is_empty() {
+            self.notifications.info("No code actions here.".to_string());
+            return Ok(());
+        }
+
+        let titles = actions
+            .iter()
+            .map(|action| action.title().to_string())
+            .collect();
+        let anchor = Anchor::Below(self.screen_position(&self.cursor_position));
+        let overlay = Overlay::new("Code actions", titles, anchor).with_selection(0);
+        if let Some(action) = self
+            .show_overlay(overlay)?
+            .and_then(|choice| actions.get(choice))
+        {
+            self.run_action(action);
+        }
+        Ok(())
+    }
+
+    /// Runs a code action: fetches its edits when the server left them out, and has
+    /// the server run its command, making the edits once it's done.
+    fn run_action(&mut self, action: &lsp::Action) {
+        let title = action.title().to_string();
+        let asked = if action.is_unresolved() {
+            let asked = Asked::ResolveAction {
+                title: title.clone(),
+            };
+            self.ask(asked, |client| client.resolve_action(action))
+        } else {
+            match action.edits() {
+                (files, Some(command)) => {
+                    let asked = Asked::ExecuteCommand {
+                        title: title.clone(),
+                        files,
+                    };
+                    self.ask(asked, |client| client.execute_command(command))
+                }
+                (files, None) => {
+                    self.ran_action(&title, &files);
+                    Ok(())
+                }
+            }
+        };
+        if let Err(err) = asked {
+            self.notifications
+                .error(format!("Could not run {title}: {err}"));
+        }
+    }
+
+    /// Makes the edits of the code action `title`.
+    fn ran_action(&mut self, title: &str, files: &[(PathBuf, Vec<TextEdit>)]) {
+        let changed = self.apply_workspace_edit(files);
+        let failed = files.len().saturating_sub(changed);
+        if failed > 0 {
+            self.notifications.error(format!(
+                "{title}: {failed} files could not be changed (see message log)."
+            ));
+        } else {
+            self.notifications
+                .info(format!("{title}: changed {changed} files."));
+        }
+    }
+
+    /// Opens a file of the project picked by typing part of its path, from the files
+    /// the workspace scan found, so ignored and binary files aren't offered.
+    fn find_file(&mut self) -> Result<(), std::io::Error> {
+        let root = self.workspace.root().to_path_buf();
+        let files: Vec<String> = self
+            .workspace
+            .files()
+            .iter()
+            .map(|path| project::file_name(&root, path))
+            .collect();
+        if files.is_empty() {
+            self.notifications
+                .warning("There are no files in the project.".to_string());
+            return Ok(());
+        }
+
+        if let Some(file_name) = self
+            .pick("Find file", |_, _| Some(files.clone()))?
+            .and_then(|choice| files.get(choice))
+        {
+            self.open_file(file_name);
+        }
+        Ok(())
+    }
+
+    /// Finds a definition anywhere in the project by part of its name and opens its
+    /// file there. The language server is asked when the file type has one, and
+    /// otherwise the `tags` file that ctags wrote in the project root is read.
+    fn workspace_symbols(&mut self) -> Result<(), std::io::Error> {
+        let root = self.workspace.root().to_path_buf();
+        if self
+            .config
+            .language_server(&self.document.file_type())
+            .is_some()
+        {
+            if !self.has_server_file() {
+                return Ok(());
+            }
+
+            let mut symbols: Vec<lsp::WorkspaceSymbol> = Vec::new();
+            let choice = self.pick("Symbols", |editor, query| {
+                match editor
+                    .language_server()
+                    .and_then(|client| client.workspace_symbols(query))
+                {
+                    Ok(Some(found)) => symbols = found,
+                    // The symbols found so far stay listed until the answer comes
+                    Ok(None) => return None,
+                    Err(err) => editor
+                        .message_log
+                        .push(format!("Could not get the symbols: {err}")),
+                }
+                Some(
+                    symbols
+                        .iter()
+                        .map(|symbol| {
+                            format!(
+                                "{}  {} {}:{}",
+                                symbol.name,
+                                symbol.kind,
+                                project::file_name(&root, &symbol.path),
+                                symbol.start.0.saturating_add(1)
+                            )
+                        })
+                        .collect(),
+                )
+            })?;
+
+            if let Some(symbol) = choice.and_then(|choice| symbols.get(choice)) {
+                if self.open_file(&project::file_name(&root, &symbol.path)) {
+                    self.cursor_position = lsp::position(&self.document, symbol.start);
+                }
+            }
+        } else {
+            if !root.join(tags::FILE_NAME).is_file() {
+                self.notifications.warning(format!(
+                    "No language server for {} files and no {} file in the project.",
+                    self.document.file_type(),
+                    tags::FILE_NAME
+                ));
+                return Ok(());
+            }
+            let tags = match tags::read(&root) {
+                Ok(tags) => tags,
+                Err(err) => {
+                    self.notifications
+                        .error(format!("Could not read the tags: {err}"));
+                    return Ok(());
+                }
+            };
+
+            let lines: Vec<String> = tags
+                .iter()
+                .map(|tag| {
+                    format!(
+                        "{}  {} {}",
+                        tag.name,
+                        tag.kind.as_deref().unwrap_or("tag"),
+                        project::file_name(&root, &tag.file)
+                    )
+                })
+                .collect();
+            let choice = self.pick("Symbols", |_, _| Some(lines.clone()))?;
+
+            if let Some(tag) = choice.and_then(|choice| tags.get(choice)) {
+                if self.open_file(&project::file_name(&root, &tag.file)) {
+                    self.cursor_position = Position {
+                        x: 0,
+                        y: tag.row(&self.document),
+                    };
+                }
+            }
+        }
+
+        self.selection_anchor = None;
+        self.move_cursor(KeyCode::Null);
+        self.scroll();
+        Ok(())
+    }
+
+    /// Sets or clears the breakpoint on the cursor's line, passing the file's
+    /// breakpoints on to the debugger when a program is being debugged.
+    fn toggle_breakpoint(&mut self) {
+        let line = self.cursor_position.y.saturating_add(1);
+        let set = self.document.toggle_breakpoint(self.cursor_position.y);
+
+        if let (Some(session), Some(file_name)) =
+            (self.debugger.as_mut(), self.document.file_name.as_deref())
+        {
+            if let Err(err) =
+                session.set_breakpoints(Path::new(file_name), &self.document.breakpoints())
+            {
+                self.notifications
+                    .error(format!("Could not set the breakpoint: {err}"));
+                return;
+            }
+        }
+
+        self.notifications.info(if set {
+            format!("Breakpoint on line {line}.")
+        } else {
+            format!("Removed the breakpoint on line {line}.")
+        });
+    }
+
+    /// Lets the paused program run on, or starts debugging it with the adapter of
+    /// the config, stopping at the breakpoints of every open file.
+    fn debug_continue(&mut self) {
+        if let Some(session) = self.debugger.as_mut() {
+            if !session.is_started() {
+                self.notifications
+                    .info("The debugger is still starting.".to_string());
+            } else if session.pause().is_none() {
+                self.notifications
+                    .info("The program is running, set a breakpoint to pause it.".to_string());
+            } else if let Err(err) = session.resume() {
+                self.notifications
+                    .error(format!("Could not continue: {err}"));
+            }
+            return;
+        }
+
+        let Some(adapter) = self.config.debug.adapter.clone() else {
+            self.notifications
+                .warning("Set adapter and launch in the [debug] table of the config.".to_string());
+            return;
+        };
+        let breakpoints: Vec<(PathBuf, Vec<usize>)> = self
+            .buffers
+            .iter()
+            .map(|buffer| &buffer.document)
+            .chain([&self.document])
+            .filter_map(|document| {
+                let rows = document.breakpoints();
+                let file_name = document.file_name.as_deref()?;
+                (!rows.is_empty()).then(|| (PathBuf::from(file_name), rows))
+            })
+            .collect();
+
+        match Session::start(
+            &adapter,
+            self.workspace.root(),
+            &self.config.debug.launch,
+            breakpoints,
+        ) {
+            Ok(session) => {
+                self.debugger = Some(session);
+                self.notifications
+                    .update(Severity::Info, format!("Starting {adapter}..."));
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not start debugging: {err}")),
+        }
+    }
+
+    fn debug_step(&mut self, step: Step) {
+        let Some(session) = self.debugger.as_mut() else {
+            self.notifications
+                .info("No program is being debugged.".to_string());
+            return;
+        };
+        if let Err(err) = session.step(step) {
+            self.notifications.error(format!("Could not step: {err}"));
+        }
+    }
+
+    /// Opens the file the debugged program paused in at the line it paused at.
+    fn show_pause(&mut self, reason: &str) {
+        let Some(pause) = self.debugger.as_ref().and_then(Session::pause) else {
+            return;
+        };
+        let function = pause.function.clone();
+        let row = pause.row;
+        let file_name = pause
+            .path
+            .as_deref()
+            .map(|path| project::file_name(self.workspace.root(), path));
+
+        if let Some(file_name) = file_name {
+            if self.open_file(&file_name) {
+                self.cursor_position = Position { x: 0, y: row };
+                self.selection_anchor = None;
+                self.move_cursor(KeyCode::Null);
+                self.scroll();
+            }
+        }
+        self.notifications
+            .info(format!("Paused in {function} ({reason})."));
+    }
+
+    /// The row of the active buffer the debugged program is paused at, when it's
+    /// paused in its file.
+    fn paused_row(&self) -> Option<usize> {
+        let pause = self.debugger.as_ref()?.pause()?;
+        let file_name = project::file_name(self.workspace.root(), pause.path.as_deref()?);
+        (self.document.file_name.as_deref() == Some(file_name.as_str())).then_some(pause.row)
+    }
+
+    /// Lets the user pick a line by typing part of it, with the lines it matches
+    /// fuzzily listed best first in an overlay, where Up/Down move the selection and
+    /// Enter takes it. `source` gives the lines for what's typed, and is only asked
+    /// again when that changes, or every `TICK` while it gives `None` as they're still
+    /// coming. Returns the index of the chosen line among the lines last given.
+    fn pick<S>(&mut self, title: &str, mut source: S) -> Result<Option<usize>, std::io::Error>
+    where
+        S: FnMut(&mut Self, &str) -> Option<Vec<String>>,
+    {
+        let mut query = String::new();
+        let mut shown: Vec<usize> = Vec::new();
+        // Returns whether the lines are still coming
+        let mut list = |editor: &mut Self, query: &str, shown: &mut Vec<usize>| {
+            let Some(lines) = source(editor, query) else {
+                return true;
+            };
+            *shown = fuzzy::filter(query, &lines);
+            shown.truncate(PICK_LIMIT);
+            let titles = shown
+                .iter()
+                .filter_map(|index| lines.get(*index).cloned())
+                .collect();
+            editor.overlay = Some(
+                Overlay::new(
+                    &format!("{title} ({})", shown.len()),
+                    titles,
+                    Anchor::Center,
+                )
+                .with_selection(0),
+            );
+            false
+        };
+
+        let mut waiting = list(self, &query, &mut shown);
+        let prompt = format!("{title}: ");
+        let input =
+            self.prompt_with_input(&prompt, "", None, true, true, |editor, key, input| {
+                match key {
+                    KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {
+                        let delta = match key {
+                            KeyCode::Up => -1,
+                            KeyCode::Down => 1,
+                            KeyCode::PageUp => -10,
+                            _ => 10,
+                        };
+                        if let Some(overlay) = editor.overlay.as_mut() {
+                            overlay.move_by(delta);
+                        }
+                    }
+                    _ if *input != query => {
+                        query.clone_from(input);
+                        waiting = list(editor, &query, &mut shown);
+                    }
+                    KeyCode::Null if waiting => waiting = list(editor, &query, &mut shown),
+                    _ => {}
+                }
+                None
+            })?;
+
+        let selected = self.overlay.take().and_then(|overlay| overlay.selected());
+        Ok(input
+            .and(selected)
+            .and_then(|selected| shown.get(selected).copied()))
+    }
+
+    /// Formats only the selected rows, with the file type's formatter when one is set
+    /// and otherwise with the language server, so a block can be tidied up without
+    /// reformatting the whole file.
+    fn format_selection(&mut self) {
+        if self.selection().is_none() {
+            self.notifications
+                .warning("Select the lines to format first.".to_string());
+            return;
+        }
+
+        let range = self.selected_rows();
+        let file_type = self.document.file_type();
+        if let Some(command) = self.config.formatter(&file_type).map(str::to_string) {
+            let lines: Vec<String> = range
+                .clone()
+                .filter_map(|index| self.document.row(index).map(|row| row.as_str().to_string()))
+                .collect();
+            let timeout = Duration::from_secs(self.config.editor.hook_timeout);
+            match format_lines(&command, &lines, timeout) {
+                Ok(formatted) => {
+                    self.document.transform_rows(range.clone(), |_| formatted);
+                }
+                Err(err) => {
+                    self.message_log.push(format!("{command}: {err}"));
+                    self.notifications.error(format!("Could not format: {err}"));
+                    return;
+                }
+            }
+        } else if self.config.language_server(&file_type).is_some() {
+            if !self.has_server_file() {
+                return;
+            }
+
+            let start = Position {
+                x: 0,
+                y: range.start,
+            };
+            let end = if self.document.row(range.end).is_some() {
+                Position { x: 0, y: range.end }
+            } else {
+                let y = range.end.saturating_sub(1);
+                Position {
+                    x: self.document.row(y).map_or(0, Row::len),
+                    y,
+                }
+            };
+            let at = lsp::range_params(&self.document, &start, &end);
+            // Indent the way the file already does, with tabs if its first indented row does
+            let tabs = (0..self.document.len())
+                .filter_map(|index| self.document.row(index))
+                .map(Row::indentation)
+                .find(|indentation| !indentation.is_empty())
+                .is_some_and(|indentation| indentation.starts_with('\t'));
+
+            self.notifications
+                .update(Severity::Info, "Formatting...".to_string());
+            let asked = Asked::Format { rows: range };
+            if let Err(err) = self.ask(asked, |client| client.range_formatting(&at, 4, !tabs)) {
+                self.notifications.error(format!("Could not format: {err}"));
+            }
+            return;
+        } else {
+            self.notifications.warning(format!(
+                "No formatter for {file_type} files, set formatter in [filetype.{}].",
+                file_type.to_lowercase()
+            ));
+            return;
+        }
+
+        self.formatted(&range);
+    }
+
+    /// Leaves the cursor at the start of the rows that were just formatted.
+    fn formatted(&mut self, rows: &Range<usize>) {
+        self.selection_anchor = None;
+        self.cursor_position = Position {
+            x: 0,
+            y: rows.start,
+        };
+        self.move_cursor(KeyCode::Null);
+        self.notifications
+            .info(format!("Formatted {} lines.", rows.len()));
+    }
+
+    /// Makes the edits a language server asked for, in the open buffers or else in
+    /// the files, and logs each file. Returns how many files were changed.
+    fn apply_workspace_edit(&mut self, files: &[(PathBuf, Vec<TextEdit>)]) -> usize {
+        let mut changed = 0_usize;
+        for (path, edits) in files {
+            let file = project::file_name(self.workspace.root(), path);
+            let result = if self
+                .document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| same_file(name, &file))
+            {
+                // The active buffer's undo step is committed once the command is done
+                lsp::apply(&mut self.document, edits);
+                Ok(())
+            } else if let Some(document) = self.open_document(&file) {
+                let first = lsp::apply(document, edits);
+                document.commit_changes(&first, &first);
+                Ok(())
+            } else {
+                fs::read_to_string(path)
+                    .and_then(|text| fs::write(path, lsp::apply_to_text(&text, edits)))
+            };
+
+            match result {
+                Ok(()) => {
+                    self.message_log
+                        .push(format!("{file}: made {} edits", edits.len()));
+                    changed = changed.saturating_add(1);
+                }
+                Err(err) => self
+                    .message_log
+                    .push(format!("{file}: could not edit: {err}")),
+            }
+        }
+
+        self.workspace.invalidate();
+        self.move_cursor(KeyCode::Null);
+        changed
+    }
+
+    /// Whether the active buffer has a file a language server can know, saying why
+    /// not when it doesn't.
+    fn has_server_file(&mut self) -> bool {
+        if self.document.file_name.is_none() {
+            self.notifications.warning(
+                "Save the buffer first, the language server only knows files.".to_string(),
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Sends a request about the active buffer to its language server, with a task
+    /// waiting for the answer, which comes back as an event for `answered`.
+    fn ask(
+        &mut self,
+        asked: Asked,
+        send: impl FnOnce(&mut Client) -> Result<lsp::Pending, String>,
+    ) -> Result<(), String> {
+        let pending = send(self.language_server()?)?;
+        self.wait_for(pending, asked);
+        Ok(())
+    }
+
+    /// Has a task wait for the answer to a request about the active buffer.
+    fn wait_for(&mut self, pending: lsp::Pending, asked: Asked) {
+        let request = lsp::Request {
+            file_type: self.document.file_type(),
+            file_name: self.document.file_name.clone(),
+            revision: self.document.revision(),
+            cursor: self.cursor_position.clone(),
+            asked,
+        };
+        self.tasks.spawn(move || Event::Answered {
+            request,
+            result: pending.wait(),
+        });
+    }
+
+    /// Puts the answer of a language server to use. Answers about the active buffer
+    /// are dropped when it changed since it was asked about.
+    fn answered(
+        &mut self,
+        request: lsp::Request,
+        result: Result<serde_json::Value, String>,
+    ) -> Result<(), std::io::Error> {
+        let unchanged = self.document.file_name == request.file_name
+            && self.document.revision() == request.revision;
+        let answer = match result {
+            Ok(answer) => answer,
+            Err(err) => {
+                let problem = match &request.asked {
+                    Asked::Rename { old_name, .. } => format!("Could not rename {old_name}"),
+                    Asked::Hover { .. } => "Could not get the documentation".to_string(),
+                    Asked::Format { .. } => "Could not format".to_string(),
+                    Asked::CodeActions => "Could not get the code actions".to_string(),
+                    Asked::SignatureHelp => {
+                        self.message_log.push(format!("Signature help: {err}"));
+                        return Ok(());
+                    }
+                    Asked::ResolveAction { title } | Asked::ExecuteCommand { title, .. } => {
+                        format!("Could not run {title}")
+                    }
+                };
+                self.notifications.error(format!("{problem}: {err}"));
+                return Ok(());
+            }
+        };
+
+        match request.asked {
+            Asked::Rename { old_name, new_name } if unchanged => {
+                self.renamed(&old_name, &new_name, &lsp::workspace_edit(&answer));
+            }
+            Asked::Rename { old_name, .. } => self.notifications.warning(format!(
+                "The buffer changed before {old_name} was renamed, rename it again."
+            )),
+            Asked::Hover { title } => {
+                if unchanged && self.cursor_position == request.cursor {
+                    self.show_hover(&title, &lsp::hover_text(&answer).unwrap_or_default())?;
+                }
+            }
+            Asked::Format { rows } if unchanged => {
+                lsp::apply(&mut self.document, &lsp::text_edits(&answer));
+                self.formatted(&rows);
+            }
+            Asked::Format { .. } => self.notifications.warning(
+                "The buffer changed before it was formatted, format it again.".to_string(),
+            ),
+            Asked::CodeActions => {
+                if unchanged {
+                    self.pick_action(&lsp::actions(&answer))?;
+                }
+            }
+            // Typing goes on meanwhile, so the popup is shown while it's still on the line
+            Asked::SignatureHelp => {
+                if self.document.file_name == request.file_name
+                    && self.cursor_position.y == request.cursor.y
+                {
+                    if let Some(signature) = lsp::signature(&answer) {
+                        self.show_signature(signature);
+                    }
+                }
+            }
+            Asked::ResolveAction { title } => match lsp::Action::new(answer) {
+                Some(action) => self.run_action(&action),
+                None => self.notifications.error(format!(
+                    "Could not run {title}: the language server lost it"
+                )),
+            },
+            Asked::ExecuteCommand { title, mut files } => {
+                match self
+                    .language_servers
+                    .running(&request.file_type)
+                    .map(Client::take_applied)
+                {
+                    Some(Ok(applied)) => files.extend(applied),
+                    Some(Err(err)) => self.message_log.push(format!("{title}: {err}")),
+                    None => {}
+                }
+                self.ran_action(&title, &files);
+            }
+        }
+        Ok(())
+    }
+
+    /// The language server for the active buffer's file type, started if it isn't
+    /// running yet, and told about every open buffer of that type.
+    fn language_server(&mut self) -> Result<&mut Client, String> {
+        let file_type = self.document.file_type();
+        let command = self
+            .config
+            .language_server(&file_type)
+            .ok_or_else(|| format!("no language server is set up for {file_type} files"))?;
+        let client = self
+            .language_servers
+            .client(&file_type, command, self.workspace.root())?;
+        sync_documents(client, &self.document, &self.buffers)?;
+        Ok(client)
+    }
+
+    /// Asks for the signature of the function being called, which `show_signature`
+    /// shows once the server answers. Only a language server that is already running
+    /// for the file is asked, and only about this buffer, so typing never waits.
+    fn signature_help(&mut self) {
+        if self.document.file_name.is_none() {
+            return;
+        }
+        let at = lsp::position_params(&self.document, &self.cursor_position);
+        let Some(client) = self.language_servers.running(&self.document.file_type()) else {
+            return;
+        };
+
+        match client
+            .sync(&self.document)
+            .and_then(|()| client.signature_help(&at))
+        {
+            Ok(pending) => self.wait_for(pending, Asked::SignatureHelp),
+            // Typing goes on, so the problem is only logged
+            Err(err) => self.message_log.push(format!("Signature help: {err}")),
+        }
+    }
+
+    /// Shows the signature of the function being called in a popup, with the
+    /// parameter the cursor is at highlighted.
+    fn show_signature(&mut self, signature: lsp::Signature) {
+        let anchor = Anchor::Below(self.screen_position(&self.cursor_position));
+        let overlay = Overlay::new("", vec![signature.label], anchor);
+        self.overlay = Some(match signature.parameter {
+            Some(parameter) => overlay.with_highlight(0, parameter),
+            None => overlay,
+        });
+    }
+
+    /// Offers to create the directories missing on the way to `path`, returning
+    /// whether they all exist afterwards.
+    fn create_parent_directories(&mut self, path: &str) -> Result<bool, std::io::Error> {
+        let mut missing: Vec<&Path> = Path::new(path)
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .collect();
+        let Some(parent) = missing.first().copied() else {
+            return Ok(true);
+        };
+
+        if self.confirm(&format!("{} doesn't exist. Create it?", parent.display()))? != Choice::Yes
+        {
+            return Ok(false);
+        }
+
+        if let Err(err) = fs::create_dir_all(parent) {
+            self.notifications
+                .error(format!("Could not create {}: {err}", parent.display()));
+            return Ok(false);
+        }
+
+        missing.reverse();
+        let created: Vec<String> = missing
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        self.notifications
+            .info(format!("Created directories: {}", created.join(", ")));
+        Ok(true)
+    }
+
+    fn toggle_follow(&mut self) {
+        if self.document.is_following() {
+            self.document.stop_following();
+            self.notifications.info("Stopped following.".to_string());
+            return;
+        }
+
+        match self.document.start_following() {
+            Ok(()) => {
+                self.selection_anchor = None;
+                self.cursor_position = Position {
+                    x: 0,
+                    y: self.document.len().saturating_sub(1),
+                };
+                self.notifications
+                    .info("Following appended lines.".to_string());
+            }
+            Err(err) => self
+                .notifications
+                .error(format!("Could not follow the file: {err}")),
+        }
+    }
+
+    /// Loads the lines appended to a followed file, keeping the cursor on the last
+    /// row when it was there already, or when the file was loaded again. Returns
+    /// whether anything was loaded.
+    fn follow_file(&mut self) -> bool {
+        let at_bottom = self.cursor_position.y.saturating_add(1) >= self.document.len();
+        match self.document.load_appended() {
+            Ok(Appended::Rows(0)) => false,
+            Ok(Appended::Reloaded) => {
+                self.selection_anchor = None;
+                self.cursor_position = Position {
+                    x: 0,
+                    y: self.document.len().saturating_sub(1),
+                };
+                self.move_cursor(KeyCode::Null);
+                self.scroll();
+                true
+            }
+            Ok(Appended::Rows(_)) => {
+                let last = self.document.len().saturating_sub(1);
+                if at_bottom || self.cursor_position.y > last {
+                    self.cursor_position = Position { x: 0, y: last };
+                }
+                self.move_cursor(KeyCode::Null);
+                self.scroll();
+                true
+            }
+            Err(err) => {
+                self.document.stop_following();
+                self.notifications
+                    .error(format!("Stopped following: {err}"));
+                true
+            }
+        }
+    }
+
+    /// Brings the buffers up to date with a change another program made to their
+    /// files, and has the project's files scanned again when files come or go.
+    fn file_changed(&mut self, change: Change) {
+        match change {
+            Change::Modified(path) => self.reload_changed(&path),
+            Change::Created(path) => {
+                self.workspace.invalidate();
+                self.reload_changed(&path);
+            }
+            Change::Removed(path) => {
+                self.workspace.invalidate();
+                if path.exists() {
+                    return;
+                }
+                if let Some(document) = self.watched_document(&path) {
+                    let name = document.file_name.clone().unwrap_or_default();
+                    document.acknowledge_disk_changes();
+                    self.notifications.warning(format!(
+                        "{name} was deleted, saving the buffer writes it again."
+                    ));
+                }
+            }
+            Change::Renamed { from, to } => {
+                self.workspace.invalidate();
+                // The buffer moves along with its file
+                let new_name = project::file_name(Path::new(""), &to);
+                if let Some(document) = self.watched_document(&from).filter(|_| !from.exists()) {
+                    let old_name = document.file_name.clone().unwrap_or_default();
+                    document.set_file_name(new_name.clone());
+                    self.notifications
+                        .info(format!("{old_name} was renamed to {new_name}."));
+                }
+                self.reload_changed(&to);
+            }
+        }
+    }
+
+    /// Reloads the buffer of a file another program wrote, unless it has unsaved
+    /// changes, which are kept with a warning instead.
+    fn reload_changed(&mut self, path: &Path) {
+        let revert = self.config.keys.revert.clone();
+        let Some(document) = self
+            .watched_document(path)
+            .filter(|document| document.changed_on_disk() && !document.is_following())
+        else {
+            return;
+        };
+        let name = document.file_name.clone().unwrap_or_default();
+
+        if document.is_dirty() {
+            document.acknowledge_disk_changes();
+            self.notifications.warning(format!(
+                "{name} changed on disk, {revert} loads it in place of your changes."
+            ));
+            return;
+        }
+        match Document::open(&name) {
+            Ok(updated) => {
+                document.replace_contents(&updated);
+                self.move_cursor(KeyCode::Null);
+                self.notifications
+                    .info(format!("Reloaded {name}, it changed on disk."));
+            }
+            Err(err) => {
+                document.acknowledge_disk_changes();
+                self.notifications
+                    .error(format!("Could not reload {name}: {err}"));
+            }
+        }
+    }
+
+    /// The open document of the file the watcher calls `path`.
+    fn watched_document(&mut self, path: &Path) -> Option<&mut Document> {
+        let is_file = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .and_then(|name| watcher::resolve(Path::new(name)))
+                .is_some_and(|resolved| resolved == path)
+        };
+        if is_file(&self.document) {
+            return Some(&mut self.document);
+        }
+        self.buffers
+            .iter_mut()
+            .map(|buffer| &mut buffer.document)
+            .find(|document| is_file(document))
+    }
+
+    /// Makes another buffer the active one, keeping each buffer's cursor and scroll position.
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.buffers.len() {
+            return;
+        }
+
+        self.swap_active(index);
+        self.buffers.swap(index, self.current_buffer);
+        // The other pane takes the buffer that was active when it was the one shown
+        if let Some(split) = self.split.as_mut().filter(|split| split.buffer == index) {
+            split.buffer = self.current_buffer;
+        }
+        self.current_buffer = index;
+    }
+
+    /// Exchanges the active buffer with the one at `index`, leaving which buffer
+    /// counts as the current one to the caller.
+    fn swap_active(&mut self, index: usize) {
+        let Some(buffer) = self.buffers.get_mut(index) else {
+            return;
+        };
+        std::mem::swap(&mut self.document, &mut buffer.document);
+        std::mem::swap(&mut self.cursor_position, &mut buffer.cursor_position);
+        std::mem::swap(&mut self.offset, &mut buffer.offset);
+        std::mem::swap(&mut self.selection_anchor, &mut buffer.selection_anchor);
+    }
+
+    /// Splits the text area to show the next buffer in a pane right of the active
+    /// one, or closes the other pane when it's split already.
+    fn split_pane(&mut self) {
+        if self.split.take().is_some() {
+            self.resizing = false;
+            self.scroll();
+            return;
+        }
+        if self.buffers.len() < 2 {
+            self.notifications
+                .info("Open another file to show it beside this one.".to_string());
+            return;
+        }
+
+        self.split = Some(Split {
+            buffer: self
+                .current_buffer
+                .saturating_add(1)
+                .checked_rem(self.buffers.len())
+                .unwrap_or_default(),
+            width: usize::from(self.terminal.size().width)
+                .checked_div(2)
+                .unwrap_or_default(),
+            left: true,
+        });
+        self.scroll();
+    }
+
+    /// Moves to the pane that isn't active, making its buffer the active one.
+    fn focus_pane(&mut self) {
+        let Some(buffer) = self.split.as_ref().map(|split| split.buffer) else {
+            self.notifications
+                .info("There is only one pane.".to_string());
+            return;
+        };
+
+        self.switch_buffer(buffer);
+        self.flip_pane();
+        self.scroll();
+    }
+
+    /// Writes every modified buffer to the recovery directory, named after its file
+    /// with `/` replaced by `%`, when the editor is killed or its terminal goes away.
+    fn emergency_save(&mut self) {
+        let Some(dir) = recovery_dir() else {
+            return;
+        };
+        if fs::create_dir_all(&dir).is_err() {
+            return;
+        }
+
+        let current = self.current_buffer;
+        let documents = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != current)
+            .map(|(index, buffer)| (index, &buffer.document))
+            .chain([(current, &self.document)]);
+
+        for (index, document) in documents.filter(|(_, document)| document.is_dirty()) {
+            let name = document.file_name.as_ref().map_or_else(
+                || format!("unnamed-{}-{index}", std::process::id()),
+                |file_name| {
+                    fs::canonicalize(file_name)
+                        .map_or_else(|_| file_name.clone(), |path| path.display().to_string())
+                        .replace('/', "%")
+                },
+            );
+            document.write_to(&dir.join(name)).ok();
+        }
+    }
+
+    /// Offers the buffers `emergency_save` wrote when an earlier session was killed,
+    /// before the files asked for are shown. Enter restores the selected one as
+    /// unsaved changes to its file, and Esc keeps the rest for the next start.
+    fn offer_recovery(&mut self) -> Result<(), std::io::Error> {
+        let Some(Ok(entries)) = recovery_dir().map(fs::read_dir) else {
+            return Ok(());
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+
+        let active = self.current_buffer;
+        let mut restored = 0_usize;
+        while !files.is_empty() {
+            let mut lines: Vec<String> = files
+                .iter()
+                .map(|file| recovered_file(file).unwrap_or_else(|| "[No Name]".to_string()))
+                .collect();
+            lines.push("Discard the rest".to_string());
+            let overlay = Overlay::new(
+                "Unsaved changes of a killed session (Esc keeps them for later)",
+                lines,
+                Anchor::Center,
+            )
+            .with_selection(0);
+
+            let Some(choice) = self.show_overlay(overlay)? else {
+                break;
+            };
+            if choice >= files.len() {
+                for file in files.drain(..) {
+                    fs::remove_file(file).ok();
+                }
+                break;
+            }
+
+            // A file that couldn't be opened keeps its changes for the next start
+            let file = files.remove(choice);
+            match fs::read_to_string(&file) {
+                Ok(text) => {
+                    if self.restore(recovered_file(&file), &text) {
+                        fs::remove_file(&file).ok();
+                        restored = restored.saturating_add(1);
+                    }
+                }
+                Err(err) => self
+                    .notifications
+                    .error(format!("Could not read {}: {err}", file.display())),
+            }
+        }
+
+        self.switch_buffer(active);
+        if restored > 0 {
+            self.notifications.info(format!(
+                "Restored {restored} buffers, save them to keep the changes."
+            ));
+        }
+        Ok(())
+    }
+
+    /// Puts recovered text in the buffer of its file, as an unsaved change that can
+    /// be undone, or in a new buffer when it had no file. Returns whether it did,
+    /// which it doesn't when the file can't be opened.
+    fn restore(&mut self, file_name: Option<String>, text: &str) -> bool {
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if let Some(file_name) = file_name {
+            if !self.open_file(&file_name) {
+                return false;
+            }
+        } else {
+            self.buffers.push(Buffer::default());
+            self.switch_buffer(self.buffers.len().saturating_sub(1));
+        }
+
+        self.document.replace_lines(lines);
+        self.document
+            .commit_changes(&Position::default(), &Position::default());
+        self.cursor_position = Position::default();
+        self.selection_anchor = None;
+        true
+    }
+
+    fn dirty_buffers(&self) -> usize {
+        let others = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter(|(index, buffer)| *index != self.current_buffer && buffer.document.is_dirty())
+            .count();
+
+        if self.document.is_dirty() {
+            others.saturating_add(1)
+        } else {
+            others
+        }
+    }
+
+    /// Saves every modified buffer, returning whether all of them were saved.
+    fn save_all(&mut self) -> bool {
+        let original = self.current_buffer;
+        let mut saved = 0_usize;
+
+        for index in 0..self.buffers.len() {
+            self.switch_buffer(index);
+            if self.document.is_dirty() {
+                self.save();
+                if !self.document.is_dirty() {
+                    saved = saved.saturating_add(1);
+                }
+            }
+        }
+
+        self.switch_buffer(original);
+
+        let remaining = self.dirty_buffers();
+        if remaining == 0 {
+            self.notifications.info(format!("Saved {saved} buffers."));
+        } else {
+            self.notifications.error(format!(
+                "Saved {saved} buffers, {remaining} could not be saved."
+            ));
+        }
+
+        remaining == 0
+    }
+
+    fn close_all(&mut self) {
+        let dirty = self.dirty_buffers();
+
+        if dirty > 0 {
+            let answer = self
+                .confirm(&format!(
+                    "{dirty} buffers modified. Close all without saving?"
+                ))
+                .unwrap_or(Choice::Cancel);
+
+            if answer != Choice::Yes {
+                self.notifications.info("Close aborted.".to_string());
+                return;
+            }
+        }
+
+        self.should_quit = true;
+    }
+
+    /// Starts the hooks matching the saved file in the background. Their output goes
+    /// to the message log once they all finished.
+    fn run_save_hooks(&mut self) {
+        let Some(file_name) = self.document.file_name.clone() else {
+            return;
+        };
+
+        let timeout = Duration::from_secs(self.config.editor.hook_timeout);
+        let commands: Vec<String> = self
+            .config
+            .hooks
+            .iter()
+            .filter(|hook| hooks::matches(&hook.pattern, &file_name))
+            .map(|hook| hook.command.clone())
+            .collect();
+
+        if commands.is_empty() {
+            return;
+        }
+
+        self.notifications.update(
+            Severity::Info,
+            format!("File saved, running {} hooks...", commands.len()),
+        );
+
+        // The hooks of one save run in order, as a formatter may need to go before a linter
+        self.tasks.spawn(move || {
+            let runs = commands
+                .into_iter()
+                .map(|command| {
+                    let result = hooks::run(&command, &file_name, timeout);
+                    (command, result)
+                })
+                .collect();
+            Event::Hooks {
+                file_name,
+                timeout,
+                runs,
+            }
+        });
+    }
+
+    /// Carries out the result of a background task.
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Hooks {
+                file_name,
+                timeout,
+                runs,
+            } => self.finish_save_hooks(&file_name, timeout, runs),
+            Event::Output { job, line } => self.append_output(&output_pane(job), &line),
+            Event::ReplOutput { line } => {
+                // Results show in the message bar too, so the code that was sent
+                // stays in view
+                if self.document.file_name.as_deref() != Some(REPL_PANE) {
+                    self.notifications.info(line.clone());
+                }
+                self.append_output(REPL_PANE, &line);
+            }
+            Event::ReplClosed => {
+                self.append_output(REPL_PANE, "[exited]");
+                if !self.repl.as_mut().is_some_and(Repl::is_running) {
+                    self.repl = None;
+                }
+            }
+            Event::Watched(change) => self.file_changed(change),
+            Event::Staged { file_name, staged } => self.git_gutter.set_staged(&file_name, staged),
+            Event::Answered { request, result } => {
+                if let Err(error) = self.answered(request, result) {
+                    die(self.terminal.as_mut(), &error);
+                }
+            }
+            Event::Diffed {
+                file_name,
+                revision,
+                hunks,
+            } => self.git_gutter.set_hunks(&file_name, revision, hunks),
+            Event::Committed {
+                result: Ok(summary),
+            } => {
+                self.commit = None;
+                self.git_gutter.invalidate();
+                self.message_log.push(format!("git: {summary}"));
+                self.notifications.info(format!("Committed {summary}"));
+            }
+            Event::Committed { result: Err(err) } => {
+                self.message_log.push(format!("git: {err}"));
+                self.notifications.error(format!("Could not commit: {err}"));
+            }
+            Event::Finished { job, status } => {
+                self.jobs.retain(|running| running.job != job);
+                let name = job.name();
+                let count = self.collect_locations(job);
+                let next = &self.config.keys.next_location;
+                let locations = match count {
+                    0 => String::new(),
+                    1 => format!(" 1 location, go to it with {next}."),
+                    count => format!(" {count} locations, go to the first with {next}."),
+                };
+                match status {
+                    Ok(status) if status.success() => {
+                        self.append_output(&output_pane(job), "[finished]");
+                        self.notifications
+                            .info(format!("The {name} command finished.{locations}"));
+                    }
+                    Ok(status) => {
+                        self.append_output(&output_pane(job), &format!("[{status}]"));
+                        self.notifications.error(format!(
+                            "The {name} command failed with {status}.{locations}"
+                        ));
+                    }
+                    Err(err) => {
+                        self.append_output(&output_pane(job), &format!("[could not start: {err}]"));
+                        self.notifications
+                            .error(format!("Could not start the {name} command: {err}"));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts a project command in the background and switches to a read-only pane
+    /// that shows its output as it's printed.
+    fn run_job(&mut self, job: Job) {
+        let name = job.name();
+        let commands = &self.config.commands;
+        let command = match job {
+            Job::Build => &commands.build,
+            Job::Test => &commands.test,
+            Job::Run => &commands.run,
+        };
+        let Some(command) = command.clone() else {
+            self.notifications.warning(format!(
+                "No {name} command, set commands.{name} in the project's .voider.toml."
+            ));
+            return;
+        };
+        if self.jobs.iter().any(|running| running.job == job) {
+            self.notifications
+                .warning(format!("The {name} command is still running."));
+            return;
+        }
+
+        let mut document = Document::from_text(&format!("$ {command}"));
+        document.set_ansi(self.config.editor.ansi_colors);
+        self.show_pane(&output_pane(job), document);
+
+        match tasks::run_job(job, &command, self.workspace.root(), &mut self.tasks) {
+            Ok(running) => {
+                self.notifications.info(format!("Running {command}"));
+                self.jobs.push(running);
+            }
+            Err(err) => {
+                self.append_output(&output_pane(job), &format!("[could not start: {err}]"));
+                self.notifications
+                    .error(format!("Could not start the {name} command: {err}"));
+            }
+        }
+    }
+
+    /// Kills the project commands that are running. Each still reports how it ended
+    /// in its output pane.
+    fn stop_jobs(&mut self) {
+        if self.jobs.is_empty() {
+            self.notifications
+                .info("No project command is running.".to_string());
+            return;
+        }
+        let names: Vec<&str> = self.jobs.iter().map(|running| running.job.name()).collect();
+        self.notifications
+            .info(format!("Stopping the {} command.", names.join(" and ")));
+        for running in &self.jobs {
+            running.stop();
+        }
+    }
+
+    /// Sends the selection, or the cursor's line, to the project's REPL, starting it
+    /// the first time. The code and what the REPL prints go to its pane.
+    fn send_to_repl(&mut self) {
+        let Some(command) = self.config.commands.repl.clone() else {
+            self.notifications.warning(
+                "No repl command, set commands.repl in the project's .voider.toml.".to_string(),
+            );
+            return;
+        };
+        let code = match self.selection() {
+            Some((start, end)) => self.document.text(&start, &end),
+            None => self
+                .document
+                .row(self.cursor_position.y)
+                .map_or_else(String::new, |row| row.as_str().to_string()),
+        };
+
+        if !self.repl.as_mut().is_some_and(Repl::is_running) {
+            match Repl::start(&command, self.workspace.root(), &mut self.tasks) {
+                Ok(repl) => self.repl = Some(repl),
+                Err(err) => {
+                    self.notifications
+                        .error(format!("Could not start {command}: {err}"));
+                    return;
+                }
+            }
+            if self.open_document(REPL_PANE).is_none() {
+                let mut document = Document::default();
+                document.set_file_name(REPL_PANE.to_string());
+                document.set_read_only(true);
+                self.buffers.push(Buffer {
+                    document,
+                    ..Buffer::default()
+                });
+            }
+            self.append_output(REPL_PANE, &format!("$ {command}"));
+            self.notifications.info(format!(
+                "Started {command}, its output is in the {REPL_PANE} buffer."
+            ));
+        }
+
+        for line in code.lines().filter(|line| !line.trim().is_empty()) {
+            self.append_output(REPL_PANE, &format!("» {line}"));
+        }
+        if let Some(Err(err)) = self.repl.as_mut().map(|repl| repl.send(&code)) {
+            self.notifications
+                .error(format!("Could not send to the REPL: {err}"));
+        }
+    }
+
+    /// Fills the quickfix list from the output pane of a project command. Returns how
+    /// many locations it found.
+    fn collect_locations(&mut self, job: Job) -> usize {
+        let root = self.workspace.root().to_path_buf();
+        let Some(document) = self.open_document(&output_pane(job)) else {
+            return 0;
+        };
+        let lines = (0..document.len()).filter_map(|index| document.row(index).map(Row::as_str));
+        self.quickfix = Quickfix::parse(lines, &root);
+        self.quickfix.len()
+    }
+
+    /// Jumps to the next or previous location of the quickfix list, opening its file.
+    fn go_to_location(&mut self, forward: bool) {
+        let count = self.quickfix.len();
+        let location = if forward {
+            self.quickfix.next()
+        } else {
+            self.quickfix.previous()
+        };
+        let Some((index, location)) = location else {
+            self.notifications.warning(String::from(
+                "No locations, run a project command or search the project first.",
+            ));
+            return;
+        };
+        let file = location.file.clone();
+        let position = Position {
+            x: location.column,
+            y: location.line,
+        };
+        let message = format!(
+            "[{}/{}] {}",
+            index.saturating_add(1),
+            count,
+            location.message
+        );
+
+        if !self.open_file(&file) {
+            return;
+        }
+        self.selection_anchor = None;
+        self.cursor_position = position;
+        self.move_cursor(KeyCode::Null);
+        self.scroll();
+        self.notifications.info(message);
+    }
+
+    /// Shows a document in a read-only pane, taking the place of an earlier pane with
+    /// the same name.
+    fn show_pane(&mut self, name: &str, mut document: Document) {
+        document.set_file_name(name.to_string());
+        document.set_read_only(true);
+        match self.open_document(name) {
+            Some(existing) => *existing = document,
+            None => self.buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            }),
+        }
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.document.file_name.as_deref() == Some(name))
+        {
+            self.switch_buffer(index);
+        }
+        self.cursor_position = Position::default();
+        self.offset = Position::default();
+        self.selection_anchor = None;
+    }
 
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from("Save aborted: ".to_string());
+    /// Shows what saving would change, as a unified diff of the saved file and the
+    /// buffer in a read-only pane.
+    fn compare_saved(&mut self) {
+        let Some(file_name) = self.document.file_name.clone() else {
+            self.notifications
+                .warning("The buffer has no file to compare with.".to_string());
+            return;
+        };
+        let saved = match fs::read_to_string(&file_name) {
+            Ok(saved) => saved,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                self.notifications
+                    .error(format!("Could not read {file_name}: {err}"));
                 return;
             }
+        };
+
+        // Saving may trim trailing whitespace, which the diff should show too
+        let trim = self
+            .config
+            .trim_trailing_whitespace(&self.document.file_type());
+        let old: Vec<&str> = saved.lines().collect();
+        let new: Vec<&str> = (0..self.document.len())
+            .filter_map(|index| self.document.row(index).map(Row::as_str))
+            .map(|line| if trim { line.trim_end() } else { line })
+            .collect();
+        let hunks = diff::unified(&old, &new, DIFF_CONTEXT);
+        if hunks.is_empty() {
+            self.notifications
+                .info(format!("The buffer matches {file_name} on disk."));
+            return;
+        }
+
+        let text = [
+            format!("--- {file_name}\t(saved)"),
+            format!("+++ {file_name}\t(buffer)"),
+        ]
+        .into_iter()
+        .chain(hunks)
+        .collect::<Vec<_>>()
+        .join("\n");
+        self.show_pane(&format!("[diff] {file_name}"), Document::from_text(&text));
+    }
+
+    /// Adds a line to an output pane, such as that of a project command, keeping the
+    /// cursor on the last line when it was there.
+    fn append_output(&mut self, pane: &str, line: &str) {
+        let active = self.document.file_name.as_deref() == Some(pane);
+        let at_bottom = active && self.cursor_position.y.saturating_add(1) >= self.document.len();
+
+        let Some(document) = self.open_document(pane) else {
+            return;
+        };
+        document.push_line(line);
+
+        if at_bottom {
+            self.cursor_position = Position {
+                x: 0,
+                y: self.document.len().saturating_sub(1),
+            };
+            self.scroll();
+        }
+    }
+
+    fn finish_save_hooks(
+        &mut self,
+        file_name: &str,
+        timeout: Duration,
+        runs: Vec<(String, Result<hooks::HookOutput, io::Error>)>,
+    ) {
+        let count = runs.len();
+        let mut failures = Vec::new();
+        for (command, result) in runs {
+            match result {
+                Ok(result) => {
+                    self.message_log.push(format!("$ {command}"));
+                    self.message_log
+                        .extend(result.output.lines().map(ToString::to_string));
+
+                    if result.timed_out {
+                        self.message_log
+                            .push(format!("hook killed after {} seconds", timeout.as_secs()));
+                        failures.push(command);
+                    } else if !result.success {
+                        failures.push(command);
+                    }
+                }
+                Err(err) => {
+                    self.message_log
+                        .push(format!("could not run hook `{command}`: {err}"));
+                    failures.push(command);
+                }
+            }
+        }
 
-            self.document.file_name = new_name;
+        // Hooks such as formatters may have rewritten the file. Edits made while they
+        // ran are kept rather than thrown away.
+        if self.document.file_name.as_deref() == Some(file_name) {
+            if let Ok(document) = Document::open(file_name) {
+                if !self.document.same_contents(&document) {
+                    if self.document.is_dirty() {
+                        self.message_log.push(format!(
+                            "{file_name} was changed by a hook but has unsaved edits, not reloaded"
+                        ));
+                    } else {
+                        self.document.replace_contents(&document);
+                        self.move_cursor(KeyCode::Null);
+                    }
+                }
+            }
         }
 
-        if self.document.save().is_ok() {
-            self.status_message = StatusMessage::from("File saved successfully.".to_string());
+        if failures.is_empty() {
+            self.notifications.update(
+                Severity::Info,
+                format!("File saved successfully, {count} hooks ran."),
+            );
         } else {
-            self.status_message = StatusMessage::from("Error writing file!".to_string());
+            self.notifications.update(
+                Severity::Error,
+                format!("Hook failed: {} (see message log)", failures.join(", ")),
+            );
         }
     }
 
-    fn draw_welcome_message(&self) {
+    fn show_message_log(&mut self) -> Result<(), std::io::Error> {
+        let overlay = if self.message_log.is_empty() {
+            Overlay::new(
+                "Message log",
+                vec!["No messages yet.".to_string()],
+                Anchor::Center,
+            )
+        } else {
+            Overlay::new("Message log", self.message_log.clone(), Anchor::Center)
+                .scrolled_to_bottom()
+        };
+
+        self.show_overlay(overlay)?;
+        Ok(())
+    }
+
+    fn show_help(&mut self) -> Result<(), std::io::Error> {
+        let mut bindings = vec![
+            ("F1".to_string(), "show this help"),
+            ("F3".to_string(), "find"),
+            ("F5".to_string(), "save"),
+            ("F8".to_string(), "quit"),
+            ("Insert".to_string(), "toggle overwrite mode"),
+            ("shift+arrows".to_string(), "select text"),
+            ("ctrl+up/down".to_string(), "previous/next paragraph"),
+            ("alt+up/down".to_string(), "start/end of indentation block"),
+            (
+                "alt+digits".to_string(),
+                "repeat the next command that many times",
+            ),
+        ];
+        bindings.extend(self.config.keys.help());
+        #[cfg(feature = "lua")]
+        let scripted = self.scripts.bindings();
+        #[cfg(feature = "lua")]
+        bindings.extend(
+            scripted
+                .iter()
+                .map(|(key, _, name)| (key.clone(), name.as_str())),
+        );
+        #[cfg(feature = "wasm")]
+        let plugged = self.plugins.bindings();
+        #[cfg(feature = "wasm")]
+        bindings.extend(
+            plugged
+                .iter()
+                .map(|(key, _, name)| (key.clone(), name.as_str())),
+        );
+
+        let width = bindings.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        let lines = bindings
+            .iter()
+            .map(|(key, action)| format!("{key:<width$}  {action}"))
+            .collect();
+
+        self.show_overlay(Overlay::new("Help", lines, Anchor::Center))?;
+        Ok(())
+    }
+
+    /// Shows an overlay until a key closes it. Up/Down scroll or move the selection and
+    /// Enter returns the selected line, any other key closes it without a choice.
+    fn show_overlay(&mut self, overlay: Overlay) -> Result<Option<usize>, std::io::Error> {
+        let page = isize::try_from(self.terminal.size().height)
+            .unwrap_or(isize::MAX)
+            .checked_div(2)
+            .unwrap_or_default();
+        self.overlay = Some(overlay);
+
+        let choice = loop {
+            self.refresh_screen()?;
+
+            let Some(overlay) = self.overlay.as_mut() else {
+                break None;
+            };
+
+            match self.terminal.read_key().code {
+                KeyCode::Up => overlay.move_by(-1),
+                KeyCode::Down => overlay.move_by(1),
+                KeyCode::PageUp => overlay.move_by(page.saturating_neg()),
+                KeyCode::PageDown => overlay.move_by(page),
+                KeyCode::Enter => break overlay.selected(),
+                _ => break None,
+            }
+        };
+
+        self.overlay = None;
+        Ok(choice)
+    }
+
+    fn welcome_message(&self) -> String {
         let mut welcome_message = format!("Voider -- version {VERSION}");
-        let width = self.terminal.size().width as usize;
+        let width = self.pane().1;
         let len = welcome_message.len();
-        let padding = width.saturating_sub(len) / 2;
+        let padding = width.saturating_sub(len).checked_div(2).unwrap_or_default();
         let spaces = " ".repeat(padding.saturating_sub(1));
 
-        welcome_message = format!("~{spaces}{welcome_message}");
-        welcome_message.truncate(width);
+        welcome_message = format!("{spaces}{welcome_message}");
+        welcome_message.truncate(width.saturating_sub(1));
+
+        format!(
+            "{}{}",
+            style('~').with(Terminal::color(self.theme.tilde)),
+            style(welcome_message).with(Terminal::color(self.theme.welcome))
+        )
+    }
+
+    /// Styles a row for its screen line, with the gutter and clipping markers.
+    /// `paused` marks the row the debugged program is paused at.
+    fn draw_row(&self, row: &Row, index: usize, paused: bool) -> String {
+        let width = self.text_width();
+        let mut start = self.offset.x;
+        let mut end = self.offset.x.saturating_add(width);
+
+        // Rows cut off by the viewport give up their edge columns to `<` and `>`
+        // markers, so it's clear there is more text to either side. Rows too long
+        // to highlight in full get `»` instead, as only their start is highlighted
+        let ansi = self.document.shows_ansi();
+        let clipped_left = start > 0 && row.is_wider_than(start, ansi);
+        let clipped_right = row.is_wider_than(end, ansi);
+        if clipped_left {
+            start = start.saturating_add(1);
+        }
+        if clipped_right {
+            end = end.saturating_sub(1);
+        }
+
+        let selection = match self.selection() {
+            Some((selection_start, selection_end))
+                if (selection_start.y..=selection_end.y).contains(&index) =>
+            {
+                let from = if index == selection_start.y {
+                    selection_start.x
+                } else {
+                    0
+                };
+                let to = if index == selection_end.y {
+                    selection_end.x
+                } else {
+                    row.len()
+                };
+                from..to
+            }
+            _ => 0..0,
+        };
+        let region = self.conflicts.region(index);
+        let text = if region == Some(Region::Marker) {
+            style(row.substring(start, end))
+                .with(Terminal::color(self.theme.conflict))
+                .reverse()
+                .to_string()
+        } else {
+            render::row(row, start, end, &selection, &self.theme, ansi)
+        };
+
+        let mut line = String::new();
+        if self.shows_markers() {
+            // Files tracked by git are compared with their staged version instead of
+            // the last save. Where the debugger stops matters more than changes
+            let (marker, color) = match (region, self.git_gutter.marker(index)) {
+                _ if paused => ('▶', self.theme.warning),
+                _ if row.has_breakpoint => ('●', self.theme.error),
+                (Some(Region::Ours), _) => ('▌', self.theme.added),
+                (Some(Region::Base), _) => ('▌', self.theme.tilde),
+                (Some(Region::Theirs), _) => ('▌', self.theme.modified),
+                _ if !self.config.editor.change_markers => (' ', self.theme.modified),
+                (_, Some(Marker::Added)) => ('▎', self.theme.added),
+                (_, Some(Marker::Changed)) => ('▎', self.theme.modified),
+                (_, Some(Marker::Removed)) => ('▁', self.theme.removed),
+                (_, None) if self.git_gutter.staged().is_none() && row.is_modified => {
+                    ('▎', self.theme.modified)
+                }
+                (_, None) => (' ', self.theme.modified),
+            };
+            line.push_str(&style(marker).with(Terminal::color(color)).to_string());
+        }
+        let number_width = self.number_width();
+        if number_width > 0 {
+            let number = format!(
+                "{:>width$} ",
+                index.saturating_add(1),
+                width = number_width.saturating_sub(1)
+            );
+            line.push_str(
+                &style(number)
+                    .with(Terminal::color(self.theme.line_number))
+                    .to_string(),
+            );
+        }
+        let marker = |text: char| {
+            style(text)
+                .with(Terminal::color(self.theme.tilde))
+                .to_string()
+        };
+        if clipped_left {
+            line.push_str(&marker('<'));
+        }
+        line.push_str(&text);
+        if clipped_right {
+            line.push_str(&marker(if row.is_long() { '»' } else { '>' }));
+        } else if self.shows_swatches() {
+            // The swatches go after the text rather than next to each color, so they
+            // don't shift the columns the cursor is placed by
+            let colors = theme::hex_colors(row.as_str());
+            let needed = colors.len().saturating_mul(3);
+            if !colors.is_empty() && !row.is_wider_than(end.saturating_sub(needed), ansi) {
+                for color in colors {
+                    line.push(' ');
+                    line.push_str(&style("██").with(Terminal::color(color)).to_string());
+                }
+            }
+        }
+        line
+    }
 
-        println!("{welcome_message}\r");
+    /// Whether `#rrggbb` colors get a swatch in the current file, a CSS or config one.
+    fn shows_swatches(&self) -> bool {
+        self.config.editor.color_swatches
+            && self
+                .document
+                .file_name
+                .as_deref()
+                .and_then(|name| Path::new(name).extension())
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    SWATCH_EXTENSIONS
+                        .iter()
+                        .any(|known| known.eq_ignore_ascii_case(extension))
+                })
     }
 
-    fn draw_row(&self, row: &Row) {
-        let width = self.terminal.size().width as usize;
-        let start = self.offset.x;
-        let end = self.offset.x.saturating_add(width);
-        let row = row.render(start, end);
+    /// Draws the text area, returning how many document rows were drawn. A split
+    /// one also gets the buffer of the other pane and the divider between them.
+    fn draw_rows(&mut self) -> usize {
+        let height = self.terminal.size().height;
+        for terminal_row in 0..height {
+            self.terminal.move_cursor(&Position {
+                x: 0,
+                y: usize::from(terminal_row),
+            });
+            self.terminal.clear_line();
+        }
+
+        let rows = self.draw_pane();
+        let Some((buffer, divider)) = self.split.as_ref().map(|split| (split.buffer, split.width))
+        else {
+            return rows;
+        };
+
+        let divider = divider.min(usize::from(self.terminal.size().width).saturating_sub(2));
+        let line = style('│')
+            .with(Terminal::color(self.theme.tilde))
+            .to_string();
+        for terminal_row in 0..height {
+            self.terminal.move_cursor(&Position {
+                x: divider,
+                y: usize::from(terminal_row),
+            });
+            self.terminal.print(&line);
+        }
+
+        // The other buffer is drawn as if it were active, without the git and
+        // conflict markers that only the active one keeps up to date
+        self.swap_active(buffer);
+        let git_gutter = std::mem::take(&mut self.git_gutter);
+        let conflicts = std::mem::take(&mut self.conflicts);
+        self.flip_pane();
+        self.document.highlight(
+            &self.highlighted_word,
+            Some(self.offset.y.saturating_add(usize::from(height))),
+        );
+        self.draw_pane();
+        self.flip_pane();
+        self.git_gutter = git_gutter;
+        self.conflicts = conflicts;
+        self.swap_active(buffer);
+        rows
+    }
 
-        println!("{row}\r");
+    /// Makes the other pane count as the active one.
+    fn flip_pane(&mut self) {
+        if let Some(split) = self.split.as_mut() {
+            split.left = !split.left;
+        }
     }
 
-    fn draw_rows(&mut self) {
+    /// Draws the rows of the active buffer in its pane, returning how many document
+    /// rows were drawn.
+    fn draw_pane(&mut self) -> usize {
         let height = self.terminal.size().height;
+        let (start, width) = self.pane();
+        let mut rows: usize = 0;
+        let mut header = self.sticky_header();
+        let paused_row = self.paused_row();
 
         for terminal_row in 0..height {
-            Terminal::clear_current_line();
-            if let Some(row) = self
-                .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
-            {
-                self.draw_row(row);
-            } else if self.document.is_empty() && terminal_row == height / 3 {
-                self.draw_welcome_message();
+            self.terminal.move_cursor(&Position {
+                x: start,
+                y: usize::from(terminal_row),
+            });
+            let index = self.offset.y.saturating_add(usize::from(terminal_row));
+            let line = if let Some(header) = header.take() {
+                let text: String = format!(" {header}").chars().take(width).collect();
+                style(format!("{text:width$}"))
+                    .with(Terminal::color(self.theme.status_bar_fg))
+                    .on(Terminal::color(self.theme.status_bar_bg))
+                    .to_string()
+            } else if let Some(row) = self.document.row(index) {
+                rows = rows.saturating_add(1);
+                self.draw_row(row, index, paused_row == Some(index))
+            } else if self.document.is_empty() && Some(terminal_row) == height.checked_div(3) {
+                self.welcome_message()
             } else {
-                println!("~\r");
-            }
+                style('~')
+                    .with(Terminal::color(self.theme.tilde))
+                    .to_string()
+            };
+            self.terminal.print(&line);
+        }
+
+        rows
+    }
+
+    /// The line pinned over the first row naming the symbols the cursor is inside,
+    /// when they're scrolled out of view.
+    fn sticky_header(&self) -> Option<String> {
+        // The row under the header is hidden, so the cursor must not be on it
+        if !self.config.editor.sticky_header || self.cursor_position.y <= self.offset.y {
+            return None;
+        }
+
+        let syntax = Syntax::of(&self.document);
+        let header = symbols::enclosing(&self.document, &syntax, self.cursor_position.y)
+            .into_iter()
+            .filter(|row| *row < self.offset.y)
+            .filter_map(|row| self.document.row(row))
+            .map(|row| symbols::trim(row.as_str()))
+            .collect::<Vec<_>>()
+            .join(" › ");
+        (!header.is_empty()).then_some(header)
+    }
+
+    /// Opens the outline panel and lets the user pick a symbol to jump to, or closes
+    /// the panel when it's open. The panel stays open after a jump, following the
+    /// cursor.
+    fn outline(&mut self) -> Result<(), std::io::Error> {
+        if self.outline.take().is_some() {
+            return Ok(());
+        }
+
+        let mut outline = Outline::default();
+        outline.update(&self.document);
+        if outline.symbols().is_empty() {
+            self.notifications
+                .info("There are no functions, types or headings here.".to_string());
+            return Ok(());
         }
+
+        let overlay = outline_overlay(&outline, self.cursor_position.y);
+        let rows: Vec<usize> = outline.symbols().iter().map(|symbol| symbol.row).collect();
+        self.outline = Some(outline);
+        if let Some(row) = self
+            .show_overlay(overlay)?
+            .and_then(|choice| rows.get(choice))
+        {
+            self.selection_anchor = None;
+            self.cursor_position = Position { x: 0, y: *row };
+            self.move_cursor(KeyCode::Null);
+        }
+        Ok(())
+    }
+
+    /// Draws the outline panel, when it's open and no other overlay is.
+    fn draw_outline(&mut self) {
+        if self.overlay.is_some() {
+            return;
+        }
+        let Some(outline) = self.outline.as_mut() else {
+            return;
+        };
+
+        outline.update(&self.document);
+        outline_overlay(outline, self.cursor_position.y).draw(self.terminal.as_mut(), &self.theme);
     }
 
-    fn draw_status_bar(&self) {
-        let mut status;
-        let width = self.terminal.size().width as usize;
+    /// Draws the values in scope where the debugged program is paused, in place of
+    /// the outline panel and unless another overlay is open. Returns whether it did.
+    fn draw_variables(&mut self) -> bool {
+        if self.overlay.is_some() {
+            return false;
+        }
+        let Some(pause) = self.debugger.as_ref().and_then(Session::pause) else {
+            return false;
+        };
 
-        let modifier_indicator = if self.document.is_dirty() {
-            " (modified)"
+        let lines = if pause.variables.is_empty() {
+            vec!["No variables in scope".to_string()]
         } else {
-            ""
+            pause.variables.clone()
+        };
+        Overlay::new("Variables", lines, Anchor::TopRight)
+            .draw(self.terminal.as_mut(), &self.theme);
+        true
+    }
+
+    /// Draws the timings of the last frame and the size of the document in the top
+    /// right corner, when the debug overlay is open.
+    fn draw_debug_overlay(&mut self) {
+        let Some(stats) = &self.frame_stats else {
+            return;
+        };
+
+        let milliseconds =
+            |duration: Duration| format!("{:.2} ms", duration.as_secs_f64() * 1000.0);
+        let lines = vec![
+            format!("highlight  {}", milliseconds(stats.highlight)),
+            format!("render     {}", milliseconds(stats.render)),
+            format!("input      {}", milliseconds(stats.input)),
+            format!("rows drawn {}", stats.rows),
+            format!("lines      {}", self.document.len()),
+            format!("bytes      {}", self.document.byte_len()),
+        ];
+        Overlay::new("Debug", lines, Anchor::TopRight).draw(self.terminal.as_mut(), &self.theme);
+    }
+
+    /// Registers the segments the status bar shows by default, the file on the left
+    /// and where the cursor is on the right.
+    fn register_segments(&mut self) {
+        let segments: [(&'static str, Side, Provider); 9] = [
+            ("file", Side::Left, |editor| Some(editor.file_segment())),
+            ("lines", Side::Left, |editor| {
+                Some(format!("{} lines", editor.document.len()))
+            }),
+            ("git", Side::Left, Self::git_segment),
+            ("diagnostics", Side::Right, Self::diagnostics_segment),
+            ("selection", Side::Right, Self::selection_segment),
+            ("macro", Side::Right, |editor| {
+                editor.recording.is_some().then(|| "REC".to_string())
+            }),
+            ("count", Side::Right, |editor| {
+                editor.keymap.count().map(|count| count.to_string())
+            }),
+            ("follow", Side::Right, |editor| {
+                editor.document.is_following().then(|| "FOLLOW".to_string())
+            }),
+            ("overwrite", Side::Right, |editor| {
+                editor.overwrite.then(|| "OVR".to_string())
+            }),
+        ];
+        for (name, side, text) in segments {
+            self.status_line.register(Segment { name, side, text });
+        }
+        #[cfg(feature = "wasm")]
+        self.status_line.register(Segment {
+            name: "plugins",
+            side: Side::Right,
+            text: |editor| {
+                let status = editor
+                    .plugins
+                    .status(&mut editor.document, &editor.cursor_position);
+                (!status.is_empty()).then(|| status.join(" | "))
+            },
+        });
+        self.status_line.register(Segment {
+            name: "file_type",
+            side: Side::Right,
+            text: |editor| Some(editor.document.file_type()),
+        });
+        self.status_line.register(Segment {
+            name: "position",
+            side: Side::Right,
+            text: |editor| {
+                Some(format!(
+                    "{} / {}",
+                    editor.cursor_position.y.saturating_add(1),
+                    editor.document.len()
+                ))
+            },
+        });
+    }
+
+    /// The name of the file, with its place among the buffers and whether it has
+    /// unsaved changes.
+    fn file_segment(&self) -> String {
+        let icons = self.config.editor.icons;
+        let modifier_indicator = match (self.document.is_dirty(), self.document.is_read_only()) {
+            (true, _) if icons => format!(" {}", icons::MODIFIED),
+            (true, _) => " (modified)".to_string(),
+            (false, true) if icons => format!(" {}", icons::READ_ONLY),
+            (false, true) => " (read-only)".to_string(),
+            (false, false) => String::new(),
         };
 
         let mut file_name = "[No Name]".to_string();
 
         if let Some(name) = &self.document.file_name {
-            file_name = name.clone();
+            file_name.clone_from(name);
             file_name.truncate(20);
         }
 
-        status = format!(
-            "{} - {} lines {}",
-            file_name,
-            self.document.len(),
-            modifier_indicator
-        );
+        if icons {
+            let name = self.document.file_name.as_deref().unwrap_or_default();
+            file_name = format!("{} {file_name}", icons::file(name));
+        }
 
-        let line_indicator = format!(
-            "{} | {} / {}",
-            self.document.file_type(),
-            self.cursor_position.y.saturating_add(1),
-            self.document.len()
-        );
+        if self.buffers.len() > 1 {
+            file_name = format!(
+                "[{}/{}] {}",
+                self.current_buffer.saturating_add(1),
+                self.buffers.len(),
+                file_name
+            );
+        }
+
+        format!("{file_name}{modifier_indicator}")
+    }
+
+    /// How much is selected, in characters within a row and in rows otherwise.
+    fn selection_segment(&mut self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        if start.y == end.y {
+            return Some(format!("SEL {}", end.x.saturating_sub(start.x)));
+        }
+        let rows = LineRange::covering(&start, &end).len();
+        Some(format!("SEL {rows} lines"))
+    }
+
+    /// How many lines differ from the staged version of the file, when any do.
+    fn git_segment(&mut self) -> Option<String> {
+        let (added, changed, removed) = self.git_gutter.summary();
+        let counts: Vec<String> = [('+', added), ('~', changed), ('-', removed)]
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(sign, count)| format!("{sign}{count}"))
+            .collect();
+        (!counts.is_empty()).then(|| counts.join(" "))
+    }
+
+    /// How many errors and warnings the language server reported in the buffer.
+    fn diagnostics_segment(&mut self) -> Option<String> {
+        let client = self.language_servers.running(&self.document.file_type())?;
+        let (errors, warnings) = client.diagnostic_counts(&self.document);
+        let counts: Vec<String> = [('E', errors), ('W', warnings)]
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(letter, count)| format!("{letter}{count}"))
+            .collect();
+        (!counts.is_empty()).then(|| counts.join(" "))
+    }
+
+    fn draw_status_bar(&mut self) {
+        let width = usize::from(self.terminal.size().width);
+
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for segment in self.status_line.segments() {
+            if let Some(text) = (segment.text)(self) {
+                match segment.side {
+                    Side::Left => left.push(text),
+                    Side::Right => right.push(text),
+                }
+            }
+        }
+
+        // Each piece is a text and whether it's drawn in the inverted colors that
+        // set off the first and last powerline segments
+        let (left, right) = if self.config.editor.powerline {
+            let mut pieces = Vec::new();
+            for (index, text) in left.into_iter().enumerate() {
+                match index {
+                    0 => {}
+                    1 => pieces.push((icons::SEPARATOR_LEFT.to_string(), false)),
+                    _ => pieces.push((icons::THIN_SEPARATOR_LEFT.to_string(), false)),
+                }
+                pieces.push((format!(" {text} "), index == 0));
+            }
+            let last = right.len().saturating_sub(1);
+            let mut right_pieces = Vec::new();
+            for (index, text) in right.into_iter().enumerate() {
+                if index == last {
+                    right_pieces.push((icons::SEPARATOR_RIGHT.to_string(), false));
+                } else if index > 0 {
+                    right_pieces.push((icons::THIN_SEPARATOR_RIGHT.to_string(), false));
+                }
+                right_pieces.push((format!(" {text} "), index == last));
+            }
+            (pieces, right_pieces)
+        } else {
+            (
+                vec![(left.join(" - "), false)],
+                vec![(right.join(" | "), false)],
+            )
+        };
+
+        let used: usize = left
+            .iter()
+            .chain(&right)
+            .map(|(text, _)| text.chars().count())
+            .sum();
+        let padding = (" ".repeat(width.saturating_sub(used)), false);
+        let mut remaining = width;
+        for (text, inverted) in left.into_iter().chain([padding]).chain(right) {
+            let text: String = text.chars().take(remaining).collect();
+            remaining = remaining.saturating_sub(text.chars().count());
+            let (fg, bg) = if inverted {
+                (self.theme.status_bar_bg, self.theme.status_bar_fg)
+            } else {
+                (self.theme.status_bar_fg, self.theme.status_bar_bg)
+            };
+            self.terminal.set_bg_color(bg);
+            self.terminal.set_fg_color(fg);
+            self.terminal.print(&text);
+        }
+        self.terminal.reset_colors();
+    }
+
+    fn draw_message_bar(&mut self) {
+        self.terminal.clear_line();
+        let width = usize::from(self.terminal.size().width);
+        if let Some((text, _)) = &self.prompt_line {
+            let text: String = text.chars().take(width).collect();
+            self.terminal.set_fg_color(self.theme.message_bar_fg);
+            self.terminal.print(&text);
+            self.terminal.reset_colors();
+        } else {
+            self.notifications
+                .draw(self.terminal.as_mut(), width, &self.theme);
+        }
+    }
+}
+
+/// Where `emergency_save` writes the modified buffers.
+fn recovery_dir() -> Option<PathBuf> {
+    State::dir().map(|dir| dir.join("recovery"))
+}
+
+/// The file a buffer written by `emergency_save` belongs to, or `None` for one that
+/// had no file.
+fn recovered_file(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    (!name.starts_with("unnamed-")).then(|| name.replace('%', "/"))
+}
+
+/// Whether two paths name the same file, such as `./src/main.rs` and `src/main.rs`.
+fn same_file(a: &str, b: &str) -> bool {
+    Path::new(a) == Path::new(b)
+        || matches!(
+            (fs::canonicalize(a), fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// Tells a language server about every open buffer of the active one's file type.
+fn sync_documents(
+    client: &mut Client,
+    active: &Document,
+    buffers: &[Buffer],
+) -> Result<(), String> {
+    let file_type = active.file_type();
+    for document in std::iter::once(active).chain(buffers.iter().map(|buffer| &buffer.document)) {
+        if document.file_name.is_some() && document.file_type() == file_type {
+            client.sync(document)?;
+        }
+    }
+    Ok(())
+}
+
+/// The outline panel, with the symbol the cursor is in selected.
+fn outline_overlay(outline: &Outline, row: usize) -> Overlay {
+    let lines = outline
+        .symbols()
+        .iter()
+        .map(|symbol| {
+            let text: String = symbol.text.chars().take(OUTLINE_WIDTH).collect();
+            format!("{}{text}", "  ".repeat(symbol.level))
+        })
+        .collect();
+    let overlay = Overlay::new("Outline", lines, Anchor::TopRight);
+    match outline.current(row) {
+        Some(current) => overlay.with_selection(current),
+        None => overlay,
+    }
+}
+
+/// The name of the pane showing the output of a project command.
+fn output_pane(job: Job) -> String {
+    format!("[{}]", job.name())
+}
+
+/// Formats path completion candidates to be shown after the prompt input.
+fn path_hint(candidates: &[String]) -> Option<String> {
+    match candidates {
+        [] => Some("  [no matches]".to_string()),
+        [_] => None,
+        candidates => Some(format!("  [{}]", candidates.join("  "))),
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut word_start = true;
+
+    for c in text.chars() {
+        if word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        word_start = !c.is_alphanumeric() && c != '\'';
+    }
+
+    result
+}
+
+/// Returns the indentation and comment marker (such as `    /// `) that starts a line.
+fn line_prefix<'a>(line: &'a str, comment: Option<&str>) -> &'a str {
+    let mut rest = line.trim_start();
+
+    if let Some(comment) = comment {
+        if let Some(after) = rest.strip_prefix(comment) {
+            // Doc comment markers like `///` and `//!` or `##` belong to the prefix too
+            let markers: Vec<char> = comment.chars().chain(['!']).collect();
+            rest = after.trim_start_matches(markers.as_slice());
+            rest = rest.strip_prefix(' ').unwrap_or(rest);
+        }
+    }
+
+    line.get(..line.len().saturating_sub(rest.len()))
+        .unwrap_or_default()
+}
+
+/// Rewraps the words of `lines` to `width` columns, repeating the first line's prefix.
+fn reflow(lines: &[String], width: usize, comment: Option<&str>) -> Vec<String> {
+    let Some(first) = lines.first() else {
+        return Vec::new();
+    };
+
+    let prefix = line_prefix(first, comment);
+    let mut result = Vec::new();
+    let mut current = prefix.to_string();
+    let mut current_width = prefix.chars().count();
 
-        #[allow(clippy::arithmetic_side_effects)]
-        let len = status.len() + line_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{status}{line_indicator}");
-        status.truncate(width);
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{}\r", status);
-        Terminal::reset_fg_color();
-        Terminal::reset_bg_color();
+    for line in lines {
+        let content = line
+            .get(line_prefix(line, comment).len()..)
+            .unwrap_or_default();
+
+        for word in content.split_whitespace() {
+            let word_width = word.chars().count();
+
+            if current.len() > prefix.len() {
+                if current_width.saturating_add(word_width) >= width {
+                    result.push(current);
+                    current = prefix.to_string();
+                    current_width = prefix.chars().count();
+                } else {
+                    current.push(' ');
+                    current_width = current_width.saturating_add(1);
+                }
+            }
+
+            current.push_str(word);
+            current_width = current_width.saturating_add(word_width);
+        }
+    }
+
+    result.push(current);
+    result
+}
+
+/// Returns the opening and closing delimiters for what was typed at a surround
+/// prompt: a bracket gets its counterpart, `<tag attributes>` its closing tag and
+/// anything else is used on both sides.
+fn delimiters(input: &str) -> Option<(String, String)> {
+    if input.is_empty() {
+        return None;
+    }
+
+    let close = match input {
+        "(" | ")" => return Some(("(".to_string(), ")".to_string())),
+        "[" | "]" => return Some(("[".to_string(), "]".to_string())),
+        "{" | "}" => return Some(("{".to_string(), "}".to_string())),
+        "<" | ">" => return Some(("<".to_string(), ">".to_string())),
+        _ => match input.strip_prefix('<') {
+            Some(tag) => {
+                let name = tag
+                    .trim_end_matches('>')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default();
+                format!("</{name}>")
+            }
+            None => input.to_string(),
+        },
+    };
+
+    let open = if input.starts_with('<') && !input.ends_with('>') {
+        format!("{input}>")
+    } else {
+        input.to_string()
+    };
+    Some((open, close))
+}
+
+/// Describes the lines, words, characters and bytes of `text`, like `wc` does.
+fn statistics(text: &str) -> String {
+    format!(
+        "{} lines, {} words, {} characters, {} bytes",
+        text.lines().count(),
+        text.split_whitespace().count(),
+        text.chars().count(),
+        text.len()
+    )
+}
+
+/// Describes a character as its codepoint, name and UTF-8 bytes, such as
+/// `U+00E9 LATIN SMALL LETTER E WITH ACUTE (C3 A9)`.
+fn describe_char(c: char) -> String {
+    let name = unicode_names2::name(c).map_or_else(
+        || {
+            if c.is_control() {
+                "<control>".to_string()
+            } else {
+                "<unnamed>".to_string()
+            }
+        },
+        |name| name.to_string(),
+    );
+    let mut buffer = [0; 4];
+    let bytes: Vec<String> = c
+        .encode_utf8(&mut buffer)
+        .bytes()
+        .map(|byte| format!("{byte:02X}"))
+        .collect();
+
+    format!("U+{:04X} {name} ({})", u32::from(c), bytes.join(" "))
+}
+
+#[cfg(feature = "lua")]
+impl Host for Editor {
+    fn line_count(&self) -> usize {
+        self.document.len()
+    }
+
+    fn line(&self, index: usize) -> Option<String> {
+        self.document.row(index).map(|row| row.as_str().to_string())
+    }
+
+    fn set_line(&mut self, index: usize, text: &str) -> Result<(), String> {
+        if self.document.is_read_only() {
+            return Err("this buffer is read-only".to_string());
+        }
+        let len = self
+            .document
+            .row(index)
+            .map(Row::len)
+            .ok_or_else(|| format!("there is no line {}", index.saturating_add(1)))?;
+
+        let start = Position { x: 0, y: index };
+        self.document
+            .delete_range(&start, &Position { x: len, y: index });
+        self.document.insert_str(&start, text);
+        Ok(())
+    }
+
+    fn insert(&mut self, text: &str) -> Result<(), String> {
+        if self.document.is_read_only() {
+            return Err("this buffer is read-only".to_string());
+        }
+        self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+        Ok(())
+    }
+
+    fn cursor(&self) -> Position {
+        self.cursor_position.clone()
+    }
+
+    fn set_cursor(&mut self, position: Position) {
+        self.cursor_position = position;
+        self.move_cursor(KeyCode::Null);
+    }
+
+    fn file_name(&self) -> Option<String> {
+        self.document.file_name.clone()
+    }
+
+    fn message(&mut self, text: String) {
+        self.notifications.info(text);
+    }
+}
+
+/// Loads the config the command line points to, with its theme option on top and
+/// the project settings that run commands only when `state` trusts them.
+fn load_config(args: &Args, state: &State) -> Result<Config, String> {
+    let root = workspace::project_root(args.files.first().map(String::as_str));
+    // Scripted runs only get the config they are pointed to, not the project's
+    #[cfg(feature = "headless")]
+    let root = root.filter(|_| !headless::is_active());
+    let mut config = Config::load(args.config.as_deref(), root.as_deref(), |root, commands| {
+        state.trusts(root, commands)
+    })?;
+    if args.theme.is_some() {
+        config.editor.theme.clone_from(&args.theme);
     }
+    Ok(config)
+}
+
+/// Parses the first number in a line for numeric sorting, lines without one sort first.
+fn leading_number(line: &str) -> Option<f64> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let start = if line
+        .get(..start)
+        .is_some_and(|before| before.ends_with('-'))
+    {
+        start.saturating_sub(1)
+    } else {
+        start
+    };
+
+    let number: String = line
+        .get(start..)
+        .unwrap_or_default()
+        .char_indices()
+        .take_while(|(index, c)| c.is_ascii_digit() || *c == '.' || (*index == 0 && *c == '-'))
+        .map(|(_, c)| c)
+        .collect();
+
+    number.parse().ok()
+}
+
+/// Runs lines through a formatter without the indentation they share, which a
+/// formatter given part of a file would take away, and puts it back on the result.
+fn format_lines(command: &str, lines: &[String], timeout: Duration) -> Result<Vec<String>, String> {
+    let indent_len = |line: &str| line.len().saturating_sub(line.trim_start().len());
+    let code = || lines.iter().filter(|line| !line.trim().is_empty());
+    let common = code().map(|line| indent_len(line)).min().unwrap_or(0);
+    let indentation = code()
+        .next()
+        .and_then(|line| line.get(..common))
+        .unwrap_or_default();
+
+    let input: String = lines
+        .iter()
+        .flat_map(|line| [line.get(common..).unwrap_or_default(), "\n"])
+        .collect();
+    let output = hooks::filter(command, &input, timeout)?;
+    Ok(output
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indentation}{line}")
+            }
+        })
+        .collect())
+}
+
+/// Shifts every line after the first so the block keeps its relative indentation
+/// but starts at `indentation` instead of wherever it was copied from.
+fn reindent_text(text: &str, indentation: &str) -> String {
+    let indent_len = |line: &str| line.len().saturating_sub(line.trim_start().len());
+    let mut lines = text.split('\n');
+    let first = lines.next().unwrap_or_default();
+
+    // A first line copied from the middle of a row carries no indentation of its own
+    let common = text
+        .split('\n')
+        .enumerate()
+        .filter(|(index, line)| !line.trim().is_empty() && (*index > 0 || indent_len(line) > 0))
+        .map(|(_, line)| indent_len(line))
+        .min()
+        .unwrap_or(0);
+
+    let mut result = first
+        .get(indent_len(first).min(common)..)
+        .unwrap_or(first)
+        .to_string();
 
-    fn draw_message_bar(&self) {
-        Terminal::clear_current_line();
-        let message = &self.status_message;
-        if message.time.elapsed() < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{text}");
+    for line in lines {
+        result.push('\n');
+        if !line.trim().is_empty() {
+            result.push_str(indentation);
+            result.push_str(line.get(common..).unwrap_or(line));
         }
     }
+
+    result
+}
+
+/// The key `move_cursor` handles for a movement.
+fn movement_key(movement: Movement) -> KeyCode {
+    match movement {
+        Movement::Up => KeyCode::Up,
+        Movement::Down => KeyCode::Down,
+        Movement::Left => KeyCode::Left,
+        Movement::Right => KeyCode::Right,
+        Movement::PageUp => KeyCode::PageUp,
+        Movement::PageDown => KeyCode::PageDown,
+        Movement::Home => KeyCode::Home,
+        Movement::End => KeyCode::End,
+        // Not a key of its own, `scroll_half_page` moves the cursor instead
+        Movement::HalfPageUp | Movement::HalfPageDown => KeyCode::Null,
+    }
 }
 
 //Error catcher
-fn die(e: &io::Error) {
+#[allow(clippy::panic)]
+fn die(terminal: &mut dyn Backend, e: &io::Error) {
+    terminal.restore();
     panic!("{e:?}");
 }
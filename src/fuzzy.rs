@@ -0,0 +1,50 @@
+//! Fuzzy matching, where the characters of a query have to appear in a text in
+//! order but not next to each other, such as `wsym` in `workspace_symbols`.
+use std::cmp::Reverse;
+
+/// How well `query` matches `text`, higher being better, or `None` when it doesn't
+/// match at all. Case is ignored. Characters that follow the one matched before or
+/// start a word count more, and shorter texts win ties.
+pub fn score(query: &str, text: &str) -> Option<usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    let mut score = 0_usize;
+    let mut next = 0_usize;
+    let mut previous: Option<usize> = None;
+
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..chars.len())
+            .find(|index| chars.get(*index).is_some_and(|c| same(*c, wanted)))?;
+        let before = found.checked_sub(1).and_then(|index| chars.get(index));
+        let starts_word = match (before, chars.get(found)) {
+            (None, _) => true,
+            (Some(before), Some(c)) => {
+                !before.is_alphanumeric() || (before.is_lowercase() && c.is_uppercase())
+            }
+            (Some(_), None) => false,
+        };
+
+        score = score.saturating_add(1);
+        if previous.is_some() && previous == found.checked_sub(1) {
+            score = score.saturating_add(4);
+        }
+        if starts_word {
+            score = score.saturating_add(3);
+        }
+        previous = Some(found);
+        next = found.saturating_add(1);
+    }
+
+    Some(score.saturating_mul(64).saturating_sub(chars.len().min(63)))
+}
+
+/// The indices of the lines `query` matches, best first and otherwise in order.
+pub fn filter(query: &str, lines: &[String]) -> Vec<usize> {
+    let mut matches: Vec<(usize, usize)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| Some((index, score(query, line)?)))
+        .collect();
+    matches.sort_by_key(|(_, score)| Reverse(*score));
+    matches.into_iter().map(|(index, _)| index).collect()
+}
@@ -0,0 +1,472 @@
+//! The git round-trip of the editor: reading the status, staging files and hunks,
+//! and committing. Everything runs the `git` executable of the system.
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::Arc,
+};
+
+use voider_core::{Document, Row};
+
+use crate::{
+    diff::{self, Hunk},
+    tasks::{Event, Tasks},
+};
+
+/// The name git gives the file it edits commit messages in.
+const COMMIT_MESSAGE: &str = "COMMIT_EDITMSG";
+
+/// A changed file, as listed by `git status`.
+///
+/// # Fields
+///
+/// - `path`: The path of the file, relative to the root of the repository.
+/// - `index`: The status letter of the staged change, `' '` when nothing is staged.
+/// - `worktree`: The status letter of the unstaged change, `' '` when there's none.
+pub struct Entry {
+    pub path: String,
+    pub index: char,
+    pub worktree: char,
+}
+
+impl Entry {
+    /// Whether the whole change is staged, so committing now would include all of it.
+    pub fn is_staged(&self) -> bool {
+        !matches!(self.index, ' ' | '?') && self.worktree == ' '
+    }
+
+    pub fn has_staged_changes(&self) -> bool {
+        !matches!(self.index, ' ' | '?')
+    }
+}
+
+/// The version of a file in the index, which the next commit would record.
+///
+/// # Fields
+///
+/// - `path`: The path of the file, relative to the root of the repository.
+/// - `mode`: The file mode git recorded, such as `100644`.
+/// - `lines`: The lines of the file, without their line endings.
+/// - `line_ending`: The line ending the file uses.
+/// - `final_newline`: Whether the last line ends with a line ending.
+pub struct Staged {
+    pub path: String,
+    pub mode: String,
+    pub lines: Vec<String>,
+    pub line_ending: &'static str,
+    pub final_newline: bool,
+}
+
+impl Staged {
+    /// The text of the file with the lines of `hunk` taken from `document` instead.
+    pub fn with_hunk(&self, hunk: &Hunk, document: &Document) -> String {
+        let before = self.lines.get(..hunk.old.start).unwrap_or_default();
+        let after = self.lines.get(hunk.old.end..).unwrap_or_default();
+        let lines: Vec<&str> = before
+            .iter()
+            .map(String::as_str)
+            .chain(
+                hunk.new
+                    .clone()
+                    .filter_map(|index| document.row(index).map(Row::as_str)),
+            )
+            .chain(after.iter().map(String::as_str))
+            .collect();
+
+        let mut text = lines.join(self.line_ending);
+        if self.final_newline || (self.lines.is_empty() && !lines.is_empty()) {
+            text.push_str(self.line_ending);
+        }
+        text
+    }
+}
+
+/// The hunks between a buffer and the staged version of its file, worked out again
+/// only when the buffer changes. The index is read when another file is shown or
+/// after `invalidate`. While the screen is drawn both happen in tasks, whose events
+/// go to `set_staged` and `set_hunks`, so neither git nor the diff hold up typing.
+///
+/// # Fields
+///
+/// - `file_name`: The file the hunks are for.
+/// - `staged`: Its repository and staged version, once read, when git tracks it.
+/// - `busy`: Whether a task is reading the index or diffing, so one runs at a time.
+/// - `revision`: The revision of the buffer the hunks were worked out for.
+/// - `hunks`: How the buffer differs from the staged version.
+#[derive(Default)]
+pub struct Gutter {
+    file_name: Option<String>,
+    staged: Option<Arc<(Repository, Staged)>>,
+    busy: bool,
+    revision: Option<usize>,
+    hunks: Vec<Hunk>,
+}
+
+/// How a row differs from the staged version of its file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Added,
+    Changed,
+    /// Lines were removed right below the row.
+    Removed,
+}
+
+impl Gutter {
+    /// Starts bringing the hunks up to date with `document` in a task of `tasks`,
+    /// unless one is running already. The markers stay as they were until it's done.
+    pub fn update(&mut self, document: &Document, tasks: &mut Tasks) {
+        if document.file_name != self.file_name {
+            self.switch_to(document);
+            if let Some(file_name) = self.file_name.clone() {
+                self.busy = true;
+                tasks.spawn(move || {
+                    let staged = read_staged(&file_name);
+                    Event::Staged { file_name, staged }
+                });
+            }
+            return;
+        }
+        if self.busy || self.revision == Some(document.revision()) {
+            return;
+        }
+        let (Some(file_name), Some(staged)) = (self.file_name.clone(), self.staged.clone()) else {
+            return;
+        };
+
+        let revision = document.revision();
+        let rows = rows(document);
+        self.busy = true;
+        tasks.spawn(move || Event::Diffed {
+            file_name,
+            revision,
+            hunks: diff_rows(&staged, &rows),
+        });
+    }
+
+    /// Brings the hunks up to date with `document` right away, for commands that act
+    /// on them.
+    pub fn update_now(&mut self, document: &Document) {
+        if document.file_name != self.file_name || (self.busy && self.staged.is_none()) {
+            self.switch_to(document);
+            self.staged = self
+                .file_name
+                .as_deref()
+                .and_then(read_staged)
+                .map(Arc::new);
+        }
+        if self.revision == Some(document.revision()) {
+            return;
+        }
+        self.revision = Some(document.revision());
+        self.hunks = self
+            .staged
+            .as_ref()
+            .map(|staged| diff_rows(staged, &rows(document)))
+            .unwrap_or_default();
+    }
+
+    /// Takes the staged version a task read for `file_name`, if it's still the file
+    /// shown.
+    pub fn set_staged(&mut self, file_name: &str, staged: Option<(Repository, Staged)>) {
+        if self.file_name.as_deref() != Some(file_name) {
+            return;
+        }
+        self.busy = false;
+        self.staged = staged.map(Arc::new);
+        self.revision = None;
+        if self.staged.is_none() {
+            self.hunks.clear();
+        }
+    }
+
+    /// Takes the hunks a task worked out for `revision` of `file_name`, if it's still
+    /// the file shown. A later revision gets its own task on the next update.
+    pub fn set_hunks(&mut self, file_name: &str, revision: usize, hunks: Vec<Hunk>) {
+        if self.file_name.as_deref() != Some(file_name) {
+            return;
+        }
+        self.busy = false;
+        self.revision = Some(revision);
+        self.hunks = hunks;
+    }
+
+    /// Forgets the staged version, so the next update reads the index again.
+    pub fn invalidate(&mut self) {
+        self.file_name = None;
+        self.staged = None;
+    }
+
+    /// The repository and staged version of the file, when git tracks it.
+    pub fn staged(&self) -> Option<&(Repository, Staged)> {
+        self.staged.as_deref()
+    }
+
+    fn switch_to(&mut self, document: &Document) {
+        self.file_name.clone_from(&document.file_name);
+        self.staged = None;
+        self.busy = false;
+        self.revision = None;
+        self.hunks.clear();
+    }
+
+    /// The hunk that covers a row, or that removed lines right next to it.
+    pub fn hunk_at(&self, row: usize) -> Option<&Hunk> {
+        self.hunks.iter().find(|hunk| {
+            hunk.new.contains(&row)
+                || (hunk.new.is_empty()
+                    && (hunk.new.start == row || hunk.new.start == row.saturating_add(1)))
+        })
+    }
+
+    /// How many lines were added, changed and removed since the file was staged.
+    pub fn summary(&self) -> (usize, usize, usize) {
+        self.hunks
+            .iter()
+            .fold((0, 0, 0), |(added, changed, removed), hunk| {
+                match (hunk.old.is_empty(), hunk.new.is_empty()) {
+                    (true, _) => (added.saturating_add(hunk.new.len()), changed, removed),
+                    (_, true) => (added, changed, removed.saturating_add(hunk.old.len())),
+                    _ => (added, changed.saturating_add(hunk.new.len()), removed),
+                }
+            })
+    }
+
+    /// The marker of a row, when the file is tracked and the row differs.
+    pub fn marker(&self, row: usize) -> Option<Marker> {
+        self.hunks.iter().find_map(|hunk| {
+            if hunk.new.contains(&row) {
+                Some(if hunk.old.is_empty() {
+                    Marker::Added
+                } else {
+                    Marker::Changed
+                })
+            } else {
+                (hunk.new.is_empty() && hunk.new.start.max(1) == row.saturating_add(1))
+                    .then_some(Marker::Removed)
+            }
+        })
+    }
+}
+
+/// The repository and staged version of a file, when git tracks it.
+fn read_staged(file_name: &str) -> Option<(Repository, Staged)> {
+    let path = Path::new(file_name);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let repository = Repository::open(dir.unwrap_or(Path::new("."))).ok()?;
+    let staged = repository.staged(path).ok()??;
+    Some((repository, staged))
+}
+
+/// The rows of a document, copied so they can be diffed on another thread.
+fn rows(document: &Document) -> Vec<String> {
+    (0..document.len())
+        .filter_map(|index| document.row(index).map(|row| row.as_str().to_string()))
+        .collect()
+}
+
+fn diff_rows((_, staged): &(Repository, Staged), rows: &[String]) -> Vec<Hunk> {
+    diff::hunks(&staged.lines, rows)
+}
+
+/// A git repository, found from any directory inside it.
+#[derive(Clone)]
+pub struct Repository {
+    root: PathBuf,
+}
+
+impl Repository {
+    /// Finds the repository `dir` belongs to.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when `dir` isn't in a repository or git can't be run.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+        Ok(Self {
+            root: PathBuf::from(root.trim_end()),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The changed and untracked files, in the order git lists them.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the status can't be read.
+    pub fn status(&self) -> Result<Vec<Entry>, String> {
+        let output = git(&self.root, &["status", "--porcelain", "-z"])?;
+        let mut fields = output.split('\0').filter(|field| !field.is_empty());
+        let mut entries = Vec::new();
+
+        while let Some(field) = fields.next() {
+            let mut letters = field.chars();
+            let (Some(index), Some(worktree)) = (letters.next(), letters.next()) else {
+                continue;
+            };
+            // A rename or copy is followed by the path it came from
+            if matches!(index, 'R' | 'C') {
+                fields.next();
+            }
+            entries.push(Entry {
+                path: field.get(3..).unwrap_or_default().to_string(),
+                index,
+                worktree,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Stages the whole change of a file, including its removal.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the file can't be staged.
+    pub fn stage(&self, path: &str) -> Result<(), String> {
+        git(&self.root, &["add", "--all", "--", path]).map(drop)
+    }
+
+    /// Takes the change of a file out of the next commit, leaving the file alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the file can't be unstaged.
+    pub fn unstage(&self, path: &str) -> Result<(), String> {
+        // Before the first commit there's no HEAD to reset to
+        if git(&self.root, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+            git(&self.root, &["reset", "--quiet", "--", path]).map(drop)
+        } else {
+            git(&self.root, &["rm", "--cached", "--quiet", "--", path]).map(drop)
+        }
+    }
+
+    /// The version of `file` in the index, or `None` when git doesn't track it.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the index can't be read.
+    pub fn staged(&self, file: &Path) -> Result<Option<Staged>, String> {
+        let path = self.relative_path(file)?;
+        let entry = git(&self.root, &["ls-files", "--stage", "--", &path])?;
+        let mut fields = entry.split_whitespace();
+        let (Some(mode), Some(object)) = (fields.next(), fields.next()) else {
+            return Ok(None);
+        };
+
+        let text = git(&self.root, &["cat-file", "blob", object])?;
+        let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        Ok(Some(Staged {
+            mode: mode.to_string(),
+            lines: text.lines().map(str::to_string).collect(),
+            line_ending,
+            final_newline: text.ends_with('\n'),
+            path,
+        }))
+    }
+
+    /// Replaces the staged version of a file with `text`, leaving the file itself
+    /// alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the text can't be stored or staged.
+    pub fn stage_text(&self, staged: &Staged, text: &str) -> Result<(), String> {
+        let object = git_with_input(&self.root, &["hash-object", "-w", "--stdin"], text)?;
+        let entry = format!("{},{},{}", staged.mode, object.trim_end(), staged.path);
+        git(&self.root, &["update-index", "--cacheinfo", &entry]).map(drop)
+    }
+
+    /// The path of a file relative to the root of the repository, the way git names
+    /// it.
+    fn relative_path(&self, file: &Path) -> Result<String, String> {
+        let absolute = |path: &Path| {
+            path.canonicalize()
+                .map_err(|err| format!("{}: {err}", path.display()))
+        };
+        let root = absolute(&self.root)?;
+        let file = absolute(file)?;
+        let Ok(relative) = file.strip_prefix(&root) else {
+            return Err(format!("{} is outside the repository", file.display()));
+        };
+        Ok(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// The file git keeps the message of the commit being written in.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the git directory can't be found.
+    pub fn message_file(&self) -> Result<PathBuf, String> {
+        let path = git(&self.root, &["rev-parse", "--git-path", COMMIT_MESSAGE])?;
+        Ok(self.root.join(path.trim_end()))
+    }
+
+    /// Commits the staged changes with the message in `message_file`, leaving out
+    /// its `#` comment lines. Returns the summary git prints.
+    ///
+    /// # Errors
+    ///
+    /// Returns git's message when the commit fails, for example because the message
+    /// is empty or a hook rejected it.
+    pub fn commit(&self, message_file: &Path) -> Result<String, String> {
+        let message_file = message_file.to_string_lossy();
+        let output = git(
+            &self.root,
+            &["commit", "--cleanup=strip", "--file", &message_file],
+        )?;
+        Ok(output.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+/// Runs git in `dir` and returns what it printed, or what it complained about when it
+/// failed.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("could not run git: {err}"))?;
+    finish(&output)
+}
+
+/// Runs git in `dir` like `git`, writing `input` to its stdin.
+fn git_with_input(dir: &Path, args: &[&str], input: &str) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("could not run git: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|err| format!("could not write to git: {err}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("could not run git: {err}"))?;
+    finish(&output)
+}
+
+fn finish(output: &Output) -> Result<String, String> {
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if output.status.success() {
+        return Ok(stdout);
+    }
+
+    // Some complaints, like having nothing to commit, go to stdout
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = if stderr.trim().is_empty() {
+        stdout.trim()
+    } else {
+        stderr.trim()
+    };
+    Err(message.lines().last().unwrap_or("git failed").to_string())
+}
@@ -0,0 +1,232 @@
+//! Running the editor without a terminal, for integration tests.
+//!
+//! With the `headless` feature, `Terminal` takes its keys from a script and draws
+//! to an in-memory screen while one is started on the current thread. The editor
+//! then starts from the default config, ignoring the user's and the project's, and
+//! neither reads nor writes the state directory unless given one with
+//! `use_state_dir`:
+//!
+//! ```ignore
+//! headless::start(80, 24, headless::keys("hello\n"));
+//! let mut editor = Editor::new(Args::default());
+//! editor.run_script()?;
+//! assert!(headless::screen().line(0).contains("hello"));
+//! ```
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{cell::RefCell, collections::VecDeque, path::PathBuf};
+
+/// The characters drawn on the in-memory screen, and where the cursor is.
+#[derive(Clone)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<char>>,
+    cursor: (usize, usize),
+}
+
+impl Screen {
+    fn new(width: u16, height: u16) -> Self {
+        Self {
+            width: usize::from(width),
+            height: usize::from(height),
+            cells: vec![vec![' '; usize::from(width)]; usize::from(height)],
+            cursor: (0, 0),
+        }
+    }
+
+    /// The text of a screen line, without trailing spaces.
+    #[must_use]
+    pub fn line(&self, y: usize) -> String {
+        self.cells
+            .get(y)
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Every line of the screen, one per line.
+    #[must_use]
+    pub fn text(&self) -> String {
+        (0..self.height)
+            .map(|y| self.line(y))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The cursor position as a column and a row.
+    #[must_use]
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    fn put(&mut self, c: char) {
+        let (x, y) = self.cursor;
+        if let Some(cell) = self.cells.get_mut(y).and_then(|line| line.get_mut(x)) {
+            *cell = c;
+        }
+        self.cursor.0 = x.saturating_add(1);
+    }
+
+    fn clear_line(&mut self) {
+        if let Some(line) = self.cells.get_mut(self.cursor.1) {
+            line.fill(' ');
+        }
+    }
+
+    /// Carries out the escape sequences the editor uses: moving the cursor and
+    /// clearing. Colors, titles and modes don't change the text, so they're skipped.
+    fn apply(&mut self, output: &str) {
+        let mut chars = output.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let mut parameters = String::new();
+                        let mut end = None;
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                end = Some(c);
+                                break;
+                            }
+                            parameters.push(c);
+                        }
+                        self.apply_csi(&parameters, end);
+                    }
+                    // Operating system commands, such as setting the title, run up to BEL
+                    Some(']') => for _ in chars.by_ref().take_while(|c| *c != '\x07') {},
+                    _ => (),
+                },
+                '\r' => self.cursor.0 = 0,
+                '\n' => self.cursor.1 = self.cursor.1.saturating_add(1),
+                _ => self.put(c),
+            }
+        }
+    }
+
+    fn apply_csi(&mut self, parameters: &str, end: Option<char>) {
+        let mut numbers = parameters
+            .split(';')
+            .map(|number| number.parse::<usize>().unwrap_or(1));
+
+        match end {
+            Some('H') => {
+                let y = numbers.next().unwrap_or(1).saturating_sub(1);
+                let x = numbers.next().unwrap_or(1).saturating_sub(1);
+                self.cursor = (x.min(self.width), y.min(self.height));
+            }
+            Some('K') if parameters == "2" => self.clear_line(),
+            Some('J') if parameters == "2" => {
+                for line in &mut self.cells {
+                    line.fill(' ');
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct Backend {
+    keys: VecDeque<KeyEvent>,
+    screen: Screen,
+    frames: usize,
+    state_dir: Option<PathBuf>,
+}
+
+thread_local! {
+    static BACKEND: RefCell<Option<Backend>> = const { RefCell::new(None) };
+}
+
+/// Makes the `Terminal` of this thread headless, with a screen of the given size
+/// and the keys to press in order.
+pub fn start(width: u16, height: u16, keys: impl IntoIterator<Item = KeyEvent>) {
+    BACKEND.with_borrow_mut(|backend| {
+        *backend = Some(Backend {
+            keys: keys.into_iter().collect(),
+            screen: Screen::new(width, height),
+            frames: 0,
+            state_dir: None,
+        });
+    });
+}
+
+/// The key presses that type `text`, with newlines as Enter.
+#[must_use]
+pub fn keys(text: &str) -> Vec<KeyEvent> {
+    text.chars()
+        .map(|c| match c {
+            '\n' => KeyEvent::from(KeyCode::Enter),
+            '\t' => KeyEvent::from(KeyCode::Tab),
+            c if c.is_uppercase() => KeyEvent::new(KeyCode::Char(c), KeyModifiers::SHIFT),
+            c => KeyEvent::from(KeyCode::Char(c)),
+        })
+        .collect()
+}
+
+/// Adds key presses to the end of the script.
+pub fn press(keys: impl IntoIterator<Item = KeyEvent>) {
+    BACKEND.with_borrow_mut(|backend| {
+        if let Some(backend) = backend {
+            backend.keys.extend(keys);
+        }
+    });
+}
+
+/// Has the editor keep its state, such as recovered buffers, in `dir` rather than
+/// nowhere.
+pub fn use_state_dir(dir: impl Into<PathBuf>) {
+    BACKEND.with_borrow_mut(|backend| {
+        if let Some(backend) = backend {
+            backend.state_dir = Some(dir.into());
+        }
+    });
+}
+
+/// What the screen shows after the last frame.
+#[must_use]
+pub fn screen() -> Screen {
+    BACKEND.with_borrow(|backend| {
+        backend
+            .as_ref()
+            .map_or_else(|| Screen::new(0, 0), |backend| backend.screen.clone())
+    })
+}
+
+/// How many frames were drawn.
+#[must_use]
+pub fn frames() -> usize {
+    BACKEND.with_borrow(|backend| backend.as_ref().map_or(0, |backend| backend.frames))
+}
+
+pub(crate) fn is_active() -> bool {
+    BACKEND.with_borrow(Option::is_some)
+}
+
+/// The state directory given with `use_state_dir`.
+pub(crate) fn state_dir() -> Option<PathBuf> {
+    BACKEND.with_borrow(|backend| backend.as_ref()?.state_dir.clone())
+}
+
+/// The size of the screen, when headless.
+pub(crate) fn size() -> Option<(u16, u16)> {
+    BACKEND.with_borrow(|backend| {
+        backend.as_ref().map(|backend| {
+            let size = |length: usize| u16::try_from(length).unwrap_or(u16::MAX);
+            (size(backend.screen.width), size(backend.screen.height))
+        })
+    })
+}
+
+/// Takes the next key of the script, or `None` once it ran out.
+pub(crate) fn next_key() -> Option<KeyEvent> {
+    BACKEND.with_borrow_mut(|backend| backend.as_mut()?.keys.pop_front())
+}
+
+/// Draws a frame of terminal output on the screen.
+pub(crate) fn draw(output: &[u8]) {
+    BACKEND.with_borrow_mut(|backend| {
+        if let Some(backend) = backend {
+            backend.screen.apply(&String::from_utf8_lossy(output));
+            backend.frames = backend.frames.saturating_add(1);
+        }
+    });
+}
@@ -0,0 +1,212 @@
+use std::{
+    io::{self, Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The result of running an on-save hook command.
+pub struct HookOutput {
+    pub success: bool,
+    pub timed_out: bool,
+    pub output: String,
+}
+
+/// Checks a file name against a glob pattern where `*` matches anything but `/`,
+/// `**` matches anything and `?` matches a single character. Patterns without a
+/// `/` are matched against the base name only.
+pub fn matches(pattern: &str, file_name: &str) -> bool {
+    let file_name = if pattern.contains('/') {
+        file_name
+    } else {
+        file_name.rsplit('/').next().unwrap_or(file_name)
+    };
+
+    matches_path(pattern, file_name)
+}
+
+/// Like `matches`, but always against the whole path.
+pub fn matches_path(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob(&pattern, &path)
+}
+
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = pattern.get(2..).unwrap_or_default();
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|skip| glob(rest, text.get(skip..).unwrap_or_default()))
+        }
+        Some('*') => {
+            let rest = pattern.get(1..).unwrap_or_default();
+            (0..=text.len())
+                .take_while(|skip| *skip == 0 || text.get(skip.saturating_sub(1)) != Some(&'/'))
+                .any(|skip| glob(rest, text.get(skip..).unwrap_or_default()))
+        }
+        Some(c) => match text.first() {
+            Some(t) if *c == '?' || c == t => glob(
+                pattern.get(1..).unwrap_or_default(),
+                text.get(1..).unwrap_or_default(),
+            ),
+            _ => false,
+        },
+    }
+}
+
+/// Runs a hook through the shell, replacing `{file}` with the saved file's name.
+/// The command is killed if it doesn't finish within `timeout`.
+///
+/// # Errors
+///
+/// Returns an error when the command can't be started.
+pub fn run(command: &str, file_name: &str, timeout: Duration) -> Result<HookOutput, io::Error> {
+    let command = command.replace("{file}", file_name);
+
+    let mut child = shell(&command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Read both pipes on their own threads so a chatty command can't fill a pipe and stall
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let status = wait(&mut child, timeout)?;
+    let timed_out = status.is_none();
+
+    // Background processes started by a killed command may keep the pipes open,
+    // so the readers are only waited for when the command finished on its own
+    let mut output = String::new();
+    if !timed_out {
+        for reader in [stdout, stderr].into_iter().flatten() {
+            if let Ok(text) = reader.join() {
+                output.push_str(&text);
+            }
+        }
+    }
+
+    Ok(HookOutput {
+        success: status.is_some_and(|status| status.success()),
+        timed_out,
+        output,
+    })
+}
+
+/// Runs a command through the shell with `input` on its stdin, the way a formatter
+/// is run, and returns what it wrote to stdout. The command is killed if it doesn't
+/// finish within `timeout`.
+///
+/// # Errors
+///
+/// Returns a description of the problem when the command can't be started, times
+/// out or fails, which is the first line it wrote to stderr in the last case.
+pub fn filter(command: &str, input: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("could not start {command}: {err}"))?;
+
+    // A command may start writing before it read everything, so the input is fed
+    // from a thread of its own
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()).ok());
+    }
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let status = wait(&mut child, timeout)
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("{command} took longer than {} seconds", timeout.as_secs()))?;
+    let read = |reader: Option<thread::JoinHandle<String>>| {
+        reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default()
+    };
+
+    if status.success() {
+        Ok(read(stdout))
+    } else {
+        let errors = read(stderr);
+        Err(errors
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map_or_else(|| format!("{command} failed"), str::to_string))
+    }
+}
+
+/// Waits for a child to exit, killing it once `timeout` passed. Returns `None` when
+/// it had to be killed.
+pub fn wait(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>, io::Error> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        if started.elapsed() >= timeout {
+            child.kill().ok();
+            child.wait().ok();
+            return Ok(None);
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Opens `target`, such as a URL, with the program the system picks for it, without
+/// waiting for that program to exit.
+///
+/// # Errors
+///
+/// Returns the error of starting the system's opener.
+pub fn open(target: &str) -> Result<(), io::Error> {
+    let mut opener = if cfg!(windows) {
+        let mut opener = Command::new("cmd");
+        opener.args(["/C", "start", ""]);
+        opener
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = opener
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Waited for in the background so it doesn't linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// A command that runs `command` through the shell of the platform.
+pub fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut text = String::new();
+        pipe.read_to_string(&mut text).ok();
+        text
+    })
+}
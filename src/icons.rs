@@ -0,0 +1,62 @@
+//! The Nerd Font glyphs and powerline separators the status bar and file lists use
+//! when `[editor] icons` or `powerline` is on. They need a patched font, so without
+//! them everything is plain ASCII.
+use std::path::Path;
+
+/// Ends a powerline segment on the left, pointing right.
+pub const SEPARATOR_LEFT: char = '\u{e0b0}';
+/// Divides two segments with the same colors on the left.
+pub const THIN_SEPARATOR_LEFT: char = '\u{e0b1}';
+/// Starts a powerline segment on the right, pointing left.
+pub const SEPARATOR_RIGHT: char = '\u{e0b2}';
+/// Divides two segments with the same colors on the right.
+pub const THIN_SEPARATOR_RIGHT: char = '\u{e0b3}';
+
+/// Shown after the name of a file with unsaved changes.
+pub const MODIFIED: char = '\u{f111}';
+/// Shown after the name of a file that can't be saved.
+pub const READ_ONLY: char = '\u{f023}';
+
+/// The icon of a file, by its extension or, for files like `Makefile`, its name.
+pub fn file(name: &str) -> char {
+    let path = Path::new(name);
+    let base = path
+        .file_name()
+        .and_then(|base| base.to_str())
+        .unwrap_or_default();
+    match base {
+        "Makefile" | "makefile" | "GNUmakefile" => return '\u{e779}',
+        "Dockerfile" => return '\u{f308}',
+        "Cargo.lock" => return '\u{e7a8}',
+        ".gitignore" | ".gitattributes" | ".gitmodules" => return '\u{f1d3}',
+        _ => {}
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "rs" => '\u{e7a8}',
+        "sh" | "bash" | "zsh" | "fish" => '\u{f489}',
+        "md" | "markdown" => '\u{f48a}',
+        "toml" | "ini" | "conf" | "cfg" | "yaml" | "yml" => '\u{e615}',
+        "json" => '\u{e60b}',
+        "lua" => '\u{e620}',
+        "py" => '\u{e606}',
+        "js" | "mjs" | "cjs" => '\u{e74e}',
+        "ts" => '\u{e628}',
+        "html" | "htm" => '\u{e736}',
+        "css" | "scss" | "sass" | "less" => '\u{e749}',
+        "c" | "h" => '\u{e61e}',
+        "cpp" | "cc" | "hpp" => '\u{e61d}',
+        "go" => '\u{e626}',
+        "java" => '\u{e738}',
+        "wasm" => '\u{e6a1}',
+        "txt" | "log" => '\u{f15c}',
+        "lock" => '\u{f023}',
+        "png" | "jpg" | "jpeg" | "gif" | "svg" => '\u{f1c5}',
+        _ => '\u{f15b}',
+    }
+}
@@ -0,0 +1,309 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use voider_core::command::{EditorCommand, Job, Motion, Movement, Resolution, Step};
+
+use crate::config::{KeyBinding, KeyBindings};
+
+/// The largest count that can be typed, so a slip of the finger can't keep the
+/// editor busy for long.
+const MAX_COUNT: usize = 9999;
+
+/// Translates key presses into editor commands, using the keys bound by scripts
+/// and plugins first, then the configurable bindings and the fixed keys after them.
+///
+/// Digits typed with alt make up a count, which repeats the command of the next key
+/// that many times.
+pub struct Keymap {
+    bindings: KeyBindings,
+    extensions: Vec<(KeyBinding, EditorCommand)>,
+    count: Option<usize>,
+}
+
+impl Keymap {
+    pub fn new(bindings: KeyBindings) -> Self {
+        Self {
+            bindings,
+            extensions: Vec::new(),
+            count: None,
+        }
+    }
+
+    /// Binds keys to the commands of scripts and plugins, ahead of the bindings
+    /// bound earlier.
+    #[cfg(any(feature = "lua", feature = "wasm"))]
+    pub fn bind(&mut self, bindings: impl IntoIterator<Item = (KeyBinding, EditorCommand)>) {
+        self.extensions.extend(bindings);
+    }
+
+    /// Returns the command bound to a key press, if there is one, repeated by the
+    /// count typed before it when it takes one; other commands drop the count. A
+    /// digit typed with alt adds to the count instead.
+    pub fn command(&mut self, event: &KeyEvent) -> Option<EditorCommand> {
+        if let Some(command) = self.extension_command(event) {
+            self.count = None;
+            return Some(command);
+        }
+        if let (KeyCode::Char(c), KeyModifiers::ALT) = (event.code, event.modifiers) {
+            if let Some(digit) = c.to_digit(10) {
+                let count = self
+                    .count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(usize::try_from(digit).unwrap_or_default());
+                self.count = Some(count.min(MAX_COUNT));
+                return None;
+            }
+        }
+
+        let command = self
+            .bound_command(event)
+            .or_else(|| Self::fixed_command(event));
+        match (self.count.take(), command) {
+            (Some(count), Some(command)) if count > 1 && command.takes_count() => {
+                Some(EditorCommand::Repeat {
+                    command: Box::new(command),
+                    count,
+                })
+            }
+            (_, command) => command,
+        }
+    }
+
+    /// The count typed so far for the next command.
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    fn extension_command(&self, event: &KeyEvent) -> Option<EditorCommand> {
+        self.extensions
+            .iter()
+            .find(|(binding, _)| binding.matches(event))
+            .map(|(_, command)| command.clone())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn bound_command(&self, event: &KeyEvent) -> Option<EditorCommand> {
+        let keys = &self.bindings;
+        let commands = [
+            (&keys.complete, EditorCommand::Complete),
+            (&keys.undo, EditorCommand::Undo),
+            (&keys.redo, EditorCommand::Redo),
+            (&keys.undo_tree, EditorCommand::UndoTree),
+            (&keys.select_all, EditorCommand::SelectAll),
+            (&keys.select_inside, EditorCommand::SelectInside),
+            (&keys.select_around, EditorCommand::SelectAround),
+            (&keys.copy, EditorCommand::Copy),
+            (&keys.cut, EditorCommand::Cut),
+            (&keys.paste, EditorCommand::Paste),
+            (&keys.paste_reindented, EditorCommand::PasteReindented),
+            (&keys.replace, EditorCommand::Replace),
+            (&keys.project_search, EditorCommand::ProjectSearch),
+            (&keys.project_replace, EditorCommand::ProjectReplace),
+            (&keys.build, EditorCommand::RunJob(Job::Build)),
+            (&keys.test, EditorCommand::RunJob(Job::Test)),
+            (&keys.run, EditorCommand::RunJob(Job::Run)),
+            (&keys.stop_jobs, EditorCommand::StopJobs),
+            (&keys.git_status, EditorCommand::GitStatus),
+            (&keys.git_commit, EditorCommand::GitCommit),
+            (
+                &keys.keep_ours,
+                EditorCommand::ResolveConflict(Resolution::Ours),
+            ),
+            (
+                &keys.keep_theirs,
+                EditorCommand::ResolveConflict(Resolution::Theirs),
+            ),
+            (
+                &keys.keep_both,
+                EditorCommand::ResolveConflict(Resolution::Both),
+            ),
+            (&keys.compare_saved, EditorCommand::CompareSaved),
+            (&keys.outline, EditorCommand::Outline),
+            (&keys.stage_hunk, EditorCommand::StageHunk),
+            (&keys.revert_hunk, EditorCommand::RevertHunk),
+            (&keys.next_location, EditorCommand::NextLocation),
+            (&keys.previous_location, EditorCommand::PreviousLocation),
+            (&keys.surround, EditorCommand::Surround),
+            (&keys.change_surround, EditorCommand::ChangeSurround),
+            (&keys.sort_lines, EditorCommand::SortLines),
+            (&keys.uppercase, EditorCommand::Uppercase),
+            (&keys.lowercase, EditorCommand::Lowercase),
+            (&keys.title_case, EditorCommand::TitleCase),
+            (&keys.reflow_paragraph, EditorCommand::ReflowParagraph),
+            (&keys.next_buffer, EditorCommand::NextBuffer),
+            (&keys.previous_buffer, EditorCommand::PreviousBuffer),
+            (&keys.revert, EditorCommand::Revert),
+            (&keys.follow, EditorCommand::ToggleFollow),
+            (&keys.ansi_colors, EditorCommand::ToggleAnsiColors),
+            (&keys.debug_overlay, EditorCommand::ToggleDebugOverlay),
+            (&keys.open, EditorCommand::Open),
+            (&keys.find_file, EditorCommand::FindFile),
+            (&keys.rename, EditorCommand::Rename),
+            (&keys.rename_symbol, EditorCommand::RenameSymbol),
+            (&keys.hover, EditorCommand::Hover),
+            (&keys.code_actions, EditorCommand::CodeActions),
+            (&keys.format_selection, EditorCommand::FormatSelection),
+            (&keys.workspace_symbols, EditorCommand::WorkspaceSymbols),
+            (&keys.toggle_breakpoint, EditorCommand::ToggleBreakpoint),
+            (&keys.debug_continue, EditorCommand::DebugContinue),
+            (&keys.step_over, EditorCommand::DebugStep(Step::Over)),
+            (&keys.step_into, EditorCommand::DebugStep(Step::Into)),
+            (&keys.step_out, EditorCommand::DebugStep(Step::Out)),
+            (&keys.debug_stop, EditorCommand::DebugStop),
+            (&keys.send_to_repl, EditorCommand::SendToRepl),
+            (&keys.toggle_checkbox, EditorCommand::ToggleCheckbox),
+            (&keys.format_table, EditorCommand::FormatTable),
+            (&keys.open_url, EditorCommand::OpenUrl),
+            (
+                &keys.half_page_down,
+                EditorCommand::Move {
+                    movement: Movement::HalfPageDown,
+                    select: false,
+                },
+            ),
+            (
+                &keys.half_page_up,
+                EditorCommand::Move {
+                    movement: Movement::HalfPageUp,
+                    select: false,
+                },
+            ),
+            (&keys.repeat_edit, EditorCommand::RepeatEdit),
+            (&keys.record_macro, EditorCommand::RecordMacro),
+            (&keys.play_macro, EditorCommand::PlayMacro),
+            (&keys.split_pane, EditorCommand::SplitPane),
+            (&keys.focus_pane, EditorCommand::FocusPane),
+            (&keys.save_all, EditorCommand::SaveAll),
+            (&keys.close_all, EditorCommand::CloseAll),
+            (&keys.save_all_and_quit, EditorCommand::SaveAllAndQuit),
+            (&keys.message_log, EditorCommand::ShowMessageLog),
+            (&keys.insert_codepoint, EditorCommand::InsertCodepoint),
+            (&keys.reload_config, EditorCommand::ReloadConfig),
+            (&keys.statistics, EditorCommand::ShowStatistics),
+            (&keys.inspect_character, EditorCommand::InspectCharacter),
+        ];
+
+        commands
+            .into_iter()
+            .find(|(binding, _)| binding.matches(event))
+            .map(|(_, command)| command)
+    }
+
+    fn fixed_command(event: &KeyEvent) -> Option<EditorCommand> {
+        let command = match event.code {
+            KeyCode::F(1) => EditorCommand::ShowHelp,
+            KeyCode::F(3) => EditorCommand::Search,
+            KeyCode::F(5) => EditorCommand::Save,
+            KeyCode::F(8) => EditorCommand::Quit,
+            KeyCode::Enter => EditorCommand::InsertNewline,
+            KeyCode::Char(c) => {
+                if event
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                {
+                    return None;
+                }
+                EditorCommand::InsertChar(c)
+            }
+            KeyCode::Insert => EditorCommand::ToggleOverwrite,
+            KeyCode::Delete => EditorCommand::DeleteForward,
+            KeyCode::Backspace => EditorCommand::DeleteBackward,
+            KeyCode::Up | KeyCode::Down
+                if event
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                let motion = match (event.code, event.modifiers.contains(KeyModifiers::ALT)) {
+                    (KeyCode::Up, false) => Motion::PreviousParagraph,
+                    (KeyCode::Down, false) => Motion::NextParagraph,
+                    (KeyCode::Up, true) => Motion::BlockStart,
+                    _ => Motion::BlockEnd,
+                };
+                EditorCommand::Jump {
+                    motion,
+                    select: event.modifiers.contains(KeyModifiers::SHIFT),
+                }
+            }
+            KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::PageUp
+            | KeyCode::PageDown
+            | KeyCode::End
+            | KeyCode::Home => {
+                let movement = match event.code {
+                    KeyCode::Up => Movement::Up,
+                    KeyCode::Down => Movement::Down,
+                    KeyCode::Left => Movement::Left,
+                    KeyCode::Right => Movement::Right,
+                    KeyCode::PageUp => Movement::PageUp,
+                    KeyCode::PageDown => Movement::PageDown,
+                    KeyCode::End => Movement::End,
+                    _ => Movement::Home,
+                };
+                EditorCommand::Move {
+                    movement,
+                    select: event.modifiers.contains(KeyModifiers::SHIFT),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeysConfig;
+
+    fn alt(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT)
+    }
+
+    /// Types a count with alt and the digits of `count`, then presses `key`.
+    fn counted(count: &str, key: KeyCode) -> Option<EditorCommand> {
+        let mut keymap = Keymap::new(KeysConfig::default().bindings());
+        for digit in count.chars() {
+            assert_eq!(keymap.command(&alt(digit)), None);
+        }
+        keymap.command(&KeyEvent::from(key))
+    }
+
+    #[test]
+    fn repeats_a_movement_by_the_count() {
+        let down = EditorCommand::Move {
+            movement: Movement::Down,
+            select: false,
+        };
+        assert_eq!(
+            counted("12", KeyCode::Down),
+            Some(EditorCommand::Repeat {
+                command: Box::new(down.clone()),
+                count: 12,
+            })
+        );
+        // A count of one is just the command
+        assert_eq!(counted("1", KeyCode::Down), Some(down));
+    }
+
+    #[test]
+    fn drops_the_count_of_commands_that_take_none() {
+        assert_eq!(counted("3", KeyCode::F(5)), Some(EditorCommand::Save));
+
+        // Nor is it kept for the next key
+        let mut keymap = Keymap::new(KeysConfig::default().bindings());
+        keymap.command(&alt('3'));
+        keymap.command(&KeyEvent::from(KeyCode::F(5)));
+        assert_eq!(keymap.count(), None);
+    }
+
+    #[test]
+    fn clamps_the_count() {
+        let Some(EditorCommand::Repeat { count, .. }) = counted("123456", KeyCode::Down) else {
+            panic!("the movement isn't repeated");
+        };
+        assert_eq!(count, MAX_COUNT);
+    }
+}
@@ -0,0 +1,56 @@
+//! The terminal front end of voider: drawing, key bindings, prompts and the config,
+//! wired to the editing engine in `voider-core`. Also built as a library so the
+//! benchmarks and the headless mode can drive it directly.
+#![warn(clippy::all, clippy::pedantic, clippy::restriction)]
+#![allow(
+    clippy::missing_docs_in_private_items,
+    clippy::implicit_return,
+    clippy::shadow_reuse,
+    clippy::print_stdout,
+    clippy::wildcard_enum_match_arm,
+    clippy::else_if_without_else
+)]
+pub mod backend;
+pub mod cli;
+mod clipboard;
+mod config;
+mod conflict;
+mod dap;
+mod diff;
+mod editor;
+mod fuzzy;
+mod git;
+#[cfg(feature = "headless")]
+pub mod headless;
+mod hooks;
+mod icons;
+mod keymap;
+mod lsp;
+mod markdown;
+mod notifications;
+mod overlay;
+mod path_completion;
+#[cfg(feature = "wasm")]
+mod plugins;
+mod project;
+mod quickfix;
+pub mod render;
+mod repl;
+#[cfg(feature = "lua")]
+mod scripting;
+pub mod signals;
+mod state;
+mod status;
+mod symbols;
+mod tags;
+mod tasks;
+mod terminal;
+mod theme;
+mod tutor;
+mod watcher;
+mod workspace;
+pub use backend::Backend;
+pub use editor::Editor;
+pub use terminal::Terminal;
+pub use theme::Theme;
+pub use voider_core::{Document, FileType, HighlightingOptions, Position, Row, SearchDirection};
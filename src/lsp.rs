@@ -0,0 +1,1480 @@
+//! A client for language servers, which know the code of a project the way its
+//! compiler does. A server runs as a child process speaking JSON-RPC over its stdin
+//! and stdout, and is started the first time a file of its type needs it.
+use std::{
+    collections::{hash_map, HashMap},
+    env,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write as _},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{self, Child, ChildStdin, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+use unicode_segmentation::UnicodeSegmentation as _;
+use voider_core::{highlighting, Document, Position, Row};
+
+use crate::hooks;
+
+/// How long a server may take to answer a request before the editor gives up on it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A change a server asks for, replacing the text between two places with `text`.
+///
+/// # Fields
+///
+/// - `start`: The line and column the replaced text starts at, with the column
+///   counted in UTF-16 code units the way servers count them.
+/// - `end`: The line and column right after the replaced text.
+/// - `text`: The text put in its place.
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+/// The signature of the function being called.
+///
+/// # Fields
+///
+/// - `label`: The signature, as the server writes it.
+/// - `parameter`: The graphemes of `label` that make up the parameter the cursor is at.
+pub struct Signature {
+    pub label: String,
+    pub parameter: Option<Range<usize>>,
+}
+
+/// A fix or refactoring a server offers, such as adding a missing import.
+///
+/// # Fields
+///
+/// - `title`: What it does, as the server describes it.
+/// - `action`: The `CodeAction` or `Command` the server sent, run as it is.
+pub struct Action {
+    title: String,
+    action: Value,
+}
+
+impl Action {
+    /// The action a server sent, as in the answers to `code_actions` and
+    /// `resolve_action`.
+    pub fn new(action: Value) -> Option<Self> {
+        Some(Self {
+            title: action.get("title")?.as_str()?.to_string(),
+            action,
+        })
+    }
+
+    /// What it does, as the server describes it.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Whether the server left out the edits, for `Client::resolve_action` to fetch.
+    pub fn is_unresolved(&self) -> bool {
+        !self.is_command() && self.action.get("edit").is_none() && self.action.get("data").is_some()
+    }
+
+    /// The edits to make in each file, and the command to run after them.
+    pub fn edits(&self) -> (Vec<(PathBuf, Vec<TextEdit>)>, Option<&Value>) {
+        if self.is_command() {
+            return (Vec::new(), Some(&self.action));
+        }
+        (
+            self.action
+                .get("edit")
+                .map(workspace_edit)
+                .unwrap_or_default(),
+            self.action.get("command"),
+        )
+    }
+
+    /// Whether it's a bare command, which comes with its name as a string where an
+    /// action has a command object.
+    fn is_command(&self) -> bool {
+        self.action.get("command").is_some_and(Value::is_string)
+    }
+}
+
+/// A definition a server found somewhere in the project.
+///
+/// # Fields
+///
+/// - `name`: The name it defines.
+/// - `kind`: What it defines, such as `function`.
+/// - `path`: The file it's in.
+/// - `start`: The line and UTF-16 column it starts at.
+pub struct WorkspaceSymbol {
+    pub name: String,
+    pub kind: &'static str,
+    pub path: PathBuf,
+    pub start: (usize, usize),
+}
+
+/// Where a language server says a name is and what it is, as the row, its
+/// graphemes and how to highlight it.
+pub type SemanticToken = (usize, Range<usize>, highlighting::Type);
+
+/// A request sent to a server, whose answer a task waits for so the editor doesn't.
+///
+/// # Fields
+///
+/// - `method`: What was asked, for the message when there's no answer.
+/// - `answer`: Where the reader of the server's output sends the answer.
+pub struct Pending {
+    method: String,
+    answer: Receiver<Value>,
+}
+
+impl Pending {
+    /// Waits for the answer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the server's message when the request failed, or a description of the
+    /// problem when there's no answer.
+    pub fn wait(self) -> Result<Value, String> {
+        match self.answer.recv_timeout(TIMEOUT) {
+            Ok(message) => result(&message),
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "the language server didn't answer {} in time",
+                self.method
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err("the language server exited".to_string()),
+        }
+    }
+}
+
+/// What the editor asked a server about the active buffer, so the answer can be put
+/// to use once a task delivers it.
+pub enum Asked {
+    /// To rename the symbol at the cursor.
+    Rename { old_name: String, new_name: String },
+    /// For the documentation of `title`, the symbol at the cursor.
+    Hover { title: String },
+    /// For edits that format `rows`.
+    Format { rows: Range<usize> },
+    /// For the code actions at the cursor or selection.
+    CodeActions,
+    /// For the signature of the function called at the cursor, asked while typing.
+    SignatureHelp,
+    /// For the edits of the code action `title`, which it left out.
+    ResolveAction { title: String },
+    /// To run the command of the code action `title`, which also makes `files`.
+    ExecuteCommand {
+        title: String,
+        files: Vec<(PathBuf, Vec<TextEdit>)>,
+    },
+}
+
+/// A request about the active buffer, with the state of the buffer when it was made,
+/// since an answer about text that changed meanwhile would land in the wrong places.
+///
+/// # Fields
+///
+/// - `file_type`: The file type whose server was asked.
+/// - `file_name`: The file of the buffer.
+/// - `revision`: The revision of the buffer.
+/// - `cursor`: Where the cursor was.
+/// - `asked`: What was asked.
+pub struct Request {
+    pub file_type: String,
+    pub file_name: Option<String>,
+    pub revision: usize,
+    pub cursor: Position,
+    pub asked: Asked,
+}
+
+/// The language servers started so far, one per file type.
+#[derive(Default)]
+pub struct Servers {
+    clients: HashMap<String, Client>,
+}
+
+impl Servers {
+    /// The client of the server for `file_type`, which is started by running
+    /// `command` in `root` unless it's already running.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be started.
+    pub fn client(
+        &mut self,
+        file_type: &str,
+        command: &str,
+        root: &Path,
+    ) -> Result<&mut Client, String> {
+        if self
+            .clients
+            .get_mut(file_type)
+            .is_some_and(|client| !client.is_running())
+        {
+            self.clients.remove(file_type);
+        }
+
+        match self.clients.entry(file_type.to_string()) {
+            hash_map::Entry::Occupied(entry) => Ok(entry.into_mut()),
+            hash_map::Entry::Vacant(entry) => Ok(entry.insert(Client::start(command, root)?)),
+        }
+    }
+
+    /// The client of the server for `file_type` if one was started and still runs.
+    pub fn running(&mut self, file_type: &str) -> Option<&mut Client> {
+        let client = self.clients.get_mut(file_type)?;
+        client.is_running().then_some(client)
+    }
+
+    /// Handles what the running servers wrote since they were last listened to, such
+    /// as the answer to `initialize` and the requests they make while a task waits
+    /// for an answer. Returns the problems, by file type.
+    pub fn catch_up(&mut self) -> Vec<String> {
+        self.clients
+            .iter_mut()
+            .filter_map(|(file_type, client)| {
+                if !client.is_running() {
+                    return None;
+                }
+                client
+                    .catch_up()
+                    .err()
+                    .map(|err| format!("{file_type} language server: {err}"))
+            })
+            .collect()
+    }
+}
+
+/// A running language server.
+///
+/// # Fields
+///
+/// - `child`: The server process, killed when the client is dropped.
+/// - `stdin`: Where requests are written to.
+/// - `messages`: What the server wrote back, read on a thread of its own, other than
+///   the answers in `waiting`.
+/// - `waiting`: Where to send the answer to each request made with `request`, by id.
+/// - `initializing`: The id of the `initialize` request until the server answered it.
+/// - `queued`: The messages held back until the server is initialized.
+/// - `next_id`: The id of the last request, so each gets a new one.
+/// - `documents`: The version last sent of each open document by URI, with the
+///   revision the document was at.
+/// - `diagnostics`: The problems the server last reported in each document by URI.
+/// - `applied`: The edits the server asked for while running a command.
+/// - `legend`: How to highlight each kind of semantic token the server sends, by
+///   its index, `None` for kinds left to the highlighter.
+/// - `later`: The requests whose answers are picked up later, with the answer once
+///   it came.
+/// - `tokens`: The semantic tokens request being waited for, with the URI and
+///   revision of the document it's about and when it was sent.
+/// - `tokens_seen`: The revision of each document by URI whose semantic tokens
+///   were last handed out.
+/// - `symbols`: The workspace symbols request being waited for, with its query and
+///   when it was sent.
+pub struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    waiting: Arc<Mutex<HashMap<u64, Sender<Value>>>>,
+    initializing: Option<u64>,
+    queued: Vec<Value>,
+    next_id: u64,
+    documents: HashMap<String, (u64, usize)>,
+    diagnostics: HashMap<String, Vec<Value>>,
+    applied: Vec<(PathBuf, Vec<TextEdit>)>,
+    legend: Vec<Option<highlighting::Type>>,
+    later: HashMap<u64, Option<Value>>,
+    tokens: Option<(u64, String, usize, Instant)>,
+    tokens_seen: HashMap<String, usize>,
+    symbols: Option<(u64, String, Instant)>,
+}
+
+impl Client {
+    /// Starts the server and asks it to initialize, without waiting for it to be
+    /// done. What the editor sends meanwhile is held back until it is.
+    fn start(command: &str, root: &Path) -> Result<Self, String> {
+        let mut child = hooks::shell(command)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("could not start {command}: {err}"))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(format!("could not talk to {command}"));
+        };
+
+        let (sender, messages) = mpsc::channel();
+        let waiting: Arc<Mutex<HashMap<u64, Sender<Value>>>> = Arc::default();
+        let answers = Arc::clone(&waiting);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(message) = read_message(&mut reader) {
+                // An answer someone waits for goes straight to them
+                let waiter = message
+                    .get("id")
+                    .and_then(Value::as_u64)
+                    .filter(|_| message.get("method").is_none())
+                    .and_then(|id| answers.lock().ok()?.remove(&id));
+                match waiter {
+                    Some(waiter) => {
+                        waiter.send(message).ok();
+                    }
+                    None => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut client = Self {
+            child,
+            stdin,
+            messages,
+            waiting,
+            initializing: None,
+            queued: Vec::new(),
+            next_id: 0,
+            documents: HashMap::new(),
+            diagnostics: HashMap::new(),
+            applied: Vec::new(),
+            legend: Vec::new(),
+            later: HashMap::new(),
+            tokens: None,
+            tokens_seen: HashMap::new(),
+            symbols: None,
+        };
+        let root = absolute(root);
+        let name = root
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let initialize = client.send_request(
+            "initialize",
+            json!({
+                "processId": process::id(),
+                "rootUri": uri(&root),
+                "workspaceFolders": [{ "uri": uri(&root), "name": name }],
+                "capabilities": capabilities(),
+            }),
+        )?;
+        client.initializing = Some(initialize);
+        Ok(client)
+    }
+
+    /// Takes the server's answer to `initialize` and sends what was held back.
+    fn initialized(&mut self, message: &Value) -> Result<(), String> {
+        let initialized = match result(message) {
+            Ok(initialized) => initialized,
+            Err(err) => {
+                self.child.kill().ok();
+                return Err(format!("could not initialize: {err}"));
+            }
+        };
+        self.legend = initialized
+            .pointer("/capabilities/semanticTokensProvider/legend/tokenTypes")
+            .and_then(Value::as_array)
+            .map(|kinds| {
+                kinds
+                    .iter()
+                    .map(|kind| kind.as_str().and_then(token_type))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.initializing = None;
+        self.notify("initialized", Some(json!({})))?;
+        for message in std::mem::take(&mut self.queued) {
+            self.write(&message)?;
+        }
+        Ok(())
+    }
+
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// How many errors and warnings the server last reported in `document`.
+    pub fn diagnostic_counts(&self, document: &Document) -> (usize, usize) {
+        let Some(diagnostics) = document
+            .file_name
+            .as_deref()
+            .and_then(|file_name| self.diagnostics.get(&uri(&absolute(Path::new(file_name)))))
+        else {
+            return (0, 0);
+        };
+        diagnostics
+            .iter()
+            .fold((0, 0), |(errors, warnings), diagnostic| {
+                match diagnostic.get("severity").and_then(Value::as_u64) {
+                    Some(1) => (errors.saturating_add(1), warnings),
+                    Some(2) => (errors, warnings.saturating_add(1)),
+                    _ => (errors, warnings),
+                }
+            })
+    }
+
+    /// Tells the server about the text of `document`, opening it the first time and
+    /// sending the whole text again whenever it changed since.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn sync(&mut self, document: &Document) -> Result<(), String> {
+        let Some(file_name) = document.file_name.as_deref() else {
+            return Ok(());
+        };
+        let uri = uri(&absolute(Path::new(file_name)));
+        let revision = document.revision();
+
+        match self.documents.get(&uri).copied() {
+            None => {
+                self.notify(
+                    "textDocument/didOpen",
+                    Some(json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(&document.file_type()),
+                            "version": 1,
+                            "text": text(document),
+                        },
+                    })),
+                )?;
+                self.documents.insert(uri, (1, revision));
+            }
+            Some((_, synced)) if synced == revision => {}
+            Some((version, _)) => {
+                let version = version.saturating_add(1);
+                self.notify(
+                    "textDocument/didChange",
+                    Some(json!({
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": [{ "text": text(document) }],
+                    })),
+                )?;
+                self.documents.insert(uri, (version, revision));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Asks the server to rename the symbol at `at`, as made by `position_params`,
+    /// to `new_name`. Its answer is a `WorkspaceEdit`, see `workspace_edit`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn rename(&mut self, at: &Value, new_name: &str) -> Result<Pending, String> {
+        let params = with_field(at.clone(), "newName", json!(new_name));
+        self.request("textDocument/rename", params)
+    }
+
+    /// Asks the server for the documentation of the symbol at `at`, as made by
+    /// `position_params`. Its answer is read with `hover_text`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn hover(&mut self, at: &Value) -> Result<Pending, String> {
+        self.request("textDocument/hover", at.clone())
+    }
+
+    /// Asks the server for the signature of the function called at `at`, as made by
+    /// `position_params`. Its answer is read with `signature`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn signature_help(&mut self, at: &Value) -> Result<Pending, String> {
+        self.request("textDocument/signatureHelp", at.clone())
+    }
+
+    /// The definitions in the project whose names match `query`, which servers match
+    /// loosely, once the server answered. Asks for them when the query changed since,
+    /// without waiting, as it's typed into a picker, so this is meant to be called
+    /// again until they come. Returns `None` in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be talked to or
+    /// doesn't answer in time.
+    pub fn workspace_symbols(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<WorkspaceSymbol>>, String> {
+        self.catch_up()?;
+
+        if let Some((id, asked_query, asked_at)) = self.symbols.take() {
+            if asked_query == query {
+                match self.later.get(&id) {
+                    Some(Some(_)) => {
+                        let answer = self.later.remove(&id).flatten().unwrap_or_default();
+                        return Ok(Some(workspace_symbols(&answer)));
+                    }
+                    Some(None) if asked_at.elapsed() < TIMEOUT => {
+                        self.symbols = Some((id, asked_query, asked_at));
+                        return Ok(None);
+                    }
+                    _ => {
+                        self.later.remove(&id);
+                        return Err("the language server didn't answer workspace/symbol in time"
+                            .to_string());
+                    }
+                }
+            }
+            // The answer for an earlier query isn't wanted anymore
+            self.later.remove(&id);
+        }
+
+        let id = self.request_later("workspace/symbol", json!({ "query": query }))?;
+        self.symbols = Some((id, query.to_string(), Instant::now()));
+        Ok(None)
+    }
+
+    /// Asks the server how to format the range at `at`, as made by `range_params`,
+    /// indenting by `tab_size` spaces or with tabs. Its answer is read with
+    /// `text_edits`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn range_formatting(
+        &mut self,
+        at: &Value,
+        tab_size: usize,
+        insert_spaces: bool,
+    ) -> Result<Pending, String> {
+        let options = json!({ "tabSize": tab_size, "insertSpaces": insert_spaces });
+        let params = with_field(at.clone(), "options", options);
+        self.request("textDocument/rangeFormatting", params)
+    }
+
+    /// Asks the server for the code actions for the range at `at`, as made by
+    /// `range_params`, telling it about the problems it reported there. Its answer is
+    /// read with `actions`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be talked to.
+    pub fn code_actions(&mut self, at: &Value) -> Result<Pending, String> {
+        self.catch_up()?;
+        let line = |value: &Value, pointer: &str| value.pointer(pointer).and_then(Value::as_u64);
+        let (first, last) = (
+            line(at, "/range/start/line").unwrap_or(0),
+            line(at, "/range/end/line").unwrap_or(0),
+        );
+        let diagnostics: Vec<Value> = at
+            .pointer("/textDocument/uri")
+            .and_then(Value::as_str)
+            .and_then(|uri| self.diagnostics.get(uri))
+            .map(|diagnostics| {
+                diagnostics
+                    .iter()
+                    .filter(|diagnostic| {
+                        line(diagnostic, "/range/start/line").is_some_and(|start| start <= last)
+                            && line(diagnostic, "/range/end/line").is_some_and(|end| end >= first)
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let context = json!({ "diagnostics": diagnostics, "triggerKind": 1 });
+        let params = with_field(at.clone(), "context", context);
+        self.request("textDocument/codeAction", params)
+    }
+
+    /// Asks the server for the edits of a code action it left them out of, see
+    /// `Action::is_unresolved`. Its answer is the action with its edits.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn resolve_action(&mut self, action: &Action) -> Result<Pending, String> {
+        self.request("codeAction/resolve", action.action.clone())
+    }
+
+    /// Has the server run a command, such as that of a code action. The edits it asks
+    /// for meanwhile are collected for `take_applied`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn execute_command(&mut self, command: &Value) -> Result<Pending, String> {
+        let mut params = json!({ "command": command.get("command").cloned().unwrap_or_default() });
+        if let Some(arguments) = command.get("arguments") {
+            params = with_field(params, "arguments", arguments.clone());
+        }
+        self.applied.clear();
+        self.request("workspace/executeCommand", params)
+    }
+
+    /// The edits the server asked for while running the last command.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be talked to.
+    pub fn take_applied(&mut self) -> Result<Vec<(PathBuf, Vec<TextEdit>)>, String> {
+        self.catch_up()?;
+        Ok(std::mem::take(&mut self.applied))
+    }
+
+    /// The semantic tokens of `document`, once the server answered for its current
+    /// text. Asks for them when the text changed since, without waiting, so this is
+    /// meant to be called again until they come. Returns `None` in the meantime and
+    /// when the server doesn't classify names.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be talked to.
+    pub fn semantic_tokens(
+        &mut self,
+        document: &Document,
+    ) -> Result<Option<Vec<SemanticToken>>, String> {
+        let Some(file_name) = document.file_name.as_deref() else {
+            return Ok(None);
+        };
+        if self.legend.is_empty() {
+            return Ok(None);
+        }
+        let uri = uri(&absolute(Path::new(file_name)));
+        let revision = document.revision();
+        self.catch_up()?;
+
+        if let Some((id, asked_uri, asked_revision, asked_at)) = self.tokens.clone() {
+            let answer = self.later.get(&id).cloned();
+            if matches!(answer, Some(None)) && asked_at.elapsed() < TIMEOUT {
+                return Ok(None);
+            }
+            self.later.remove(&id);
+            self.tokens = None;
+            // Tokens for text that changed meanwhile would land in the wrong places
+            if let Some(Some(answer)) =
+                answer.filter(|_| asked_uri == uri && asked_revision == revision)
+            {
+                self.tokens_seen.insert(uri, revision);
+                return Ok(self.decode_tokens(document, &answer));
+            }
+        }
+
+        if self.tokens_seen.get(&uri) != Some(&revision) {
+            self.sync(document)?;
+            let id = self.request_later(
+                "textDocument/semanticTokens/full",
+                json!({ "textDocument": { "uri": uri } }),
+            )?;
+            self.tokens = Some((id, uri, revision, Instant::now()));
+        }
+        Ok(None)
+    }
+
+    /// Turns the numbers servers encode semantic tokens as into the places of the
+    /// names in `document`, skipping the kinds the highlighter handles.
+    fn decode_tokens(&self, document: &Document, tokens: &Value) -> Option<Vec<SemanticToken>> {
+        let data: Vec<usize> = tokens
+            .get("data")?
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_u64)
+            .filter_map(|number| usize::try_from(number).ok())
+            .collect();
+
+        // Each token is five numbers, its place relative to the token before
+        let (mut line, mut character) = (0_usize, 0_usize);
+        let mut decoded = Vec::new();
+        for token in data.chunks_exact(5) {
+            let [delta_line, delta_character, length, kind, _] = token else {
+                continue;
+            };
+            if *delta_line > 0 {
+                line = line.saturating_add(*delta_line);
+                character = *delta_character;
+            } else {
+                character = character.saturating_add(*delta_character);
+            }
+
+            let (Some(Some(highlighting_type)), Some(row)) =
+                (self.legend.get(*kind), document.row(line))
+            else {
+                continue;
+            };
+            let start = grapheme_index(row.as_str(), character);
+            let end = grapheme_index(row.as_str(), character.saturating_add(*length));
+            decoded.push((line, start..end, *highlighting_type));
+        }
+        Some(decoded)
+    }
+
+    /// Handles what the server wrote since it was last listened to.
+    fn catch_up(&mut self) -> Result<(), String> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(message) if message.get("method").is_some() => self.handle(&message)?,
+                Ok(message) => self.keep_answer(&message)?,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    return Err("the language server exited".to_string())
+                }
+            }
+        }
+    }
+
+    /// Sends a request whose answer is waited for with `Pending::wait`, usually by a
+    /// task.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem when the server can't be written to.
+    pub fn request(&mut self, method: &str, params: Value) -> Result<Pending, String> {
+        self.next_id = self.next_id.saturating_add(1);
+        let id = self.next_id;
+        let (sender, answer) = mpsc::channel();
+        self.waiting
+            .lock()
+            .map_err(|err| format!("lost the answers of the language server: {err}"))?
+            .insert(id, sender);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        self.send(&with_field(message, "params", params))?;
+        Ok(Pending {
+            method: method.to_string(),
+            answer,
+        })
+    }
+
+    /// Sends a request without waiting for its result, which is kept in `later`
+    /// under the returned id once it comes.
+    fn request_later(&mut self, method: &str, params: Value) -> Result<u64, String> {
+        let id = self.send_request(method, params)?;
+        self.later.insert(id, None);
+        Ok(id)
+    }
+
+    fn send_request(&mut self, method: &str, params: Value) -> Result<u64, String> {
+        self.next_id = self.next_id.saturating_add(1);
+        let id = self.next_id;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        self.send(&with_field(message, "params", params))?;
+        Ok(id)
+    }
+
+    /// Keeps the answer to a request made with `request_later`, or takes that to
+    /// `initialize`. A failed request is answered with null, answers to other
+    /// requests are dropped.
+    fn keep_answer(&mut self, message: &Value) -> Result<(), String> {
+        let id = message.get("id").and_then(Value::as_u64);
+        if id.is_some() && id == self.initializing {
+            return self.initialized(message);
+        }
+        if let Some(slot) = id.and_then(|id| self.later.get_mut(&id)) {
+            *slot = Some(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+        Ok(())
+    }
+
+    /// Handles a notification or request of the server. Reported problems are kept,
+    /// edits are collected for the command being run, settings are empty and the
+    /// server's other requests are answered with nothing.
+    fn handle(&mut self, message: &Value) -> Result<(), String> {
+        let params = message.get("params");
+        let result = match message.get("method").and_then(Value::as_str) {
+            Some("textDocument/publishDiagnostics") => {
+                if let Some(uri) = params
+                    .and_then(|params| params.get("uri"))
+                    .and_then(Value::as_str)
+                {
+                    let diagnostics = params
+                        .and_then(|params| params.get("diagnostics"))
+                        .and_then(Value::as_array)
+                        .cloned()
+                        .unwrap_or_default();
+                    self.diagnostics.insert(uri.to_string(), diagnostics);
+                }
+                Value::Null
+            }
+            Some("workspace/configuration") => {
+                let items = params
+                    .and_then(|params| params.get("items"))
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+                Value::Array(vec![Value::Null; items])
+            }
+            Some("workspace/applyEdit") => {
+                if let Some(edit) = params.and_then(|params| params.get("edit")) {
+                    self.applied.extend(workspace_edit(edit));
+                }
+                json!({ "applied": true })
+            }
+            _ => Value::Null,
+        };
+
+        // Notifications need no answer, but the server waits on its requests
+        match message.get("id") {
+            Some(id) => self.write(&json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+            None => Ok(()),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Option<Value>) -> Result<(), String> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        match params {
+            Some(params) => self.send(&with_field(message, "params", params)),
+            None => self.send(&message),
+        }
+    }
+
+    /// Writes a message to the server, or holds it back while it's initializing.
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        if self.initializing.is_some() {
+            self.queued.push(message.clone());
+            return Ok(());
+        }
+        self.write(message)
+    }
+
+    fn write(&mut self, message: &Value) -> Result<(), String> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())
+            .and_then(|()| self.stdin.flush())
+            .map_err(|err| format!("could not write to the language server: {err}"))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.write(&json!({ "jsonrpc": "2.0", "method": "exit" }))
+            .ok();
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// The document and position parameters of a request about the text at `at`.
+pub fn position_params(document: &Document, at: &Position) -> Value {
+    json!({
+        "textDocument": document_identifier(document),
+        "position": point(document, at),
+    })
+}
+
+/// The document and range parameters of a request about the text from `start` to
+/// `end`.
+pub fn range_params(document: &Document, start: &Position, end: &Position) -> Value {
+    json!({
+        "textDocument": document_identifier(document),
+        "range": { "start": point(document, start), "end": point(document, end) },
+    })
+}
+
+fn document_identifier(document: &Document) -> Value {
+    let file_name = document.file_name.as_deref().unwrap_or_default();
+    json!({ "uri": uri(&absolute(Path::new(file_name))) })
+}
+
+/// A position the way servers count it, in UTF-16 columns.
+fn point(document: &Document, at: &Position) -> Value {
+    let character = document
+        .row(at.y)
+        .map_or(0, |row| utf16_column(row.as_str(), at.x));
+    json!({ "line": at.y, "character": character })
+}
+
+/// The position in `document` of a line and UTF-16 column from a server.
+pub fn position(document: &Document, (line, character): (usize, usize)) -> Position {
+    Position {
+        x: document
+            .row(line)
+            .map_or(0, |row| grapheme_index(row.as_str(), character)),
+        y: line,
+    }
+}
+
+/// The result of an answer, or the server's message when the request failed.
+fn result(message: &Value) -> Result<Value, String> {
+    if let Some(error) = message.get("error") {
+        return Err(error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("the request failed")
+            .to_string());
+    }
+    Ok(message.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// The documentation in the answer to `hover`, as markdown, or `None` when there is
+/// none.
+pub fn hover_text(hover: &Value) -> Option<String> {
+    let text = hover.get("contents").map(markdown).unwrap_or_default();
+    Some(text).filter(|text| !text.trim().is_empty())
+}
+
+/// The signature in the answer to `signature_help`, or `None` when there is none.
+pub fn signature(help: &Value) -> Option<Signature> {
+    let index = |value: Option<&Value>| usize::try_from(value?.as_u64()?).ok();
+    let signatures = help
+        .get("signatures")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    let signature = signatures
+        .get(index(help.get("activeSignature")).unwrap_or(0))
+        .or_else(|| signatures.first())?;
+
+    let label = signature
+        .get("label")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    // The signature's own active parameter takes precedence over the overall one
+    let parameter = index(signature.get("activeParameter"))
+        .or_else(|| index(help.get("activeParameter")))
+        .and_then(|active| signature.get("parameters")?.as_array()?.get(active))
+        .and_then(|parameter| match parameter.get("label")? {
+            Value::String(name) => {
+                let start = label.find(name.as_str())?;
+                let before = label.get(..start)?.graphemes(true).count();
+                Some(before..before.saturating_add(name.graphemes(true).count()))
+            }
+            Value::Array(offsets) => {
+                let offset = |position: usize| {
+                    index(offsets.get(position)).map(|character| grapheme_index(&label, character))
+                };
+                Some(offset(0)?..offset(1)?)
+            }
+            _ => None,
+        });
+
+    Some(Signature { label, parameter })
+}
+
+/// The edits in the answer to `range_formatting`.
+pub fn text_edits(edits: &Value) -> Vec<TextEdit> {
+    edits
+        .as_array()
+        .map(|edits| edits.iter().filter_map(text_edit).collect())
+        .unwrap_or_default()
+}
+
+/// The code actions in the answer to `code_actions`, leaving out disabled ones.
+pub fn actions(actions: &Value) -> Vec<Action> {
+    actions
+        .as_array()
+        .map(|actions| {
+            actions
+                .iter()
+                .filter(|action| action.get("disabled").is_none())
+                .filter_map(|action| Action::new(action.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Makes the edits in `document`, which must not overlap. Returns where the first
+/// one starts.
+pub fn apply(document: &mut Document, edits: &[TextEdit]) -> Position {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| edit.start);
+    // Going from the end keeps the positions of the edits before valid
+    for edit in sorted.iter().rev() {
+        let start = position(document, edit.start);
+        let end = position(document, edit.end);
+        if edit.start != edit.end {
+            document.delete_range(&start, &end);
+        }
+        document.insert_str(&start, &edit.text.replace("\r\n", "\n"));
+    }
+
+    sorted
+        .first()
+        .map_or_else(Position::default, |edit| position(document, edit.start))
+}
+
+/// Makes the edits in the text of a file, keeping its line endings.
+pub fn apply_to_text(text: &str, edits: &[TextEdit]) -> String {
+    let mut line_starts = vec![0];
+    line_starts.extend(
+        text.match_indices('\n')
+            .map(|(index, _)| index.saturating_add(1)),
+    );
+    let offset = |(line, character): (usize, usize)| {
+        let Some(start) = line_starts.get(line).copied() else {
+            return text.len();
+        };
+        let end = line_starts
+            .get(line.saturating_add(1))
+            .map_or(text.len(), |next| next.saturating_sub(1));
+        let line = text.get(start..end).unwrap_or_default();
+        start.saturating_add(byte_offset(line.trim_end_matches('\r'), character))
+    };
+    let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| edit.start);
+    let mut result = text.to_string();
+    for edit in sorted.iter().rev() {
+        let start = offset(edit.start);
+        let end = offset(edit.end).max(start);
+        let replacement = edit.text.replace("\r\n", "\n").replace('\n', line_ending);
+        if result.is_char_boundary(start) && result.is_char_boundary(end) {
+            result.replace_range(start..end, &replacement);
+        }
+    }
+    result
+}
+
+/// The edits of a `WorkspaceEdit` by file, from either of the two forms servers
+/// send them in. Creating, renaming and deleting files isn't supported.
+pub fn workspace_edit(edit: &Value) -> Vec<(PathBuf, Vec<TextEdit>)> {
+    let edits = |edits: Option<&Value>| -> Vec<TextEdit> {
+        edits
+            .and_then(Value::as_array)
+            .map(|edits| edits.iter().filter_map(text_edit).collect())
+            .unwrap_or_default()
+    };
+
+    let mut files = Vec::new();
+    if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        files.extend(
+            changes
+                .iter()
+                .filter_map(|(uri, file_edits)| Some((path(uri)?, edits(Some(file_edits))))),
+        );
+    }
+    if let Some(changes) = edit.get("documentChanges").and_then(Value::as_array) {
+        files.extend(changes.iter().filter_map(|change| {
+            let uri = change.pointer("/textDocument/uri")?.as_str()?;
+            Some((path(uri)?, edits(change.get("edits"))))
+        }));
+    }
+
+    files.retain(|(_, edits)| !edits.is_empty());
+    files
+}
+
+fn text_edit(edit: &Value) -> Option<TextEdit> {
+    let place = |pointer: &str| -> Option<(usize, usize)> {
+        let place = edit.pointer(pointer)?;
+        let number = |name: &str| usize::try_from(place.get(name)?.as_u64()?).ok();
+        Some((number("line")?, number("character")?))
+    };
+
+    Some(TextEdit {
+        start: place("/range/start")?,
+        end: place("/range/end")?,
+        text: edit.get("newText")?.as_str()?.to_string(),
+    })
+}
+
+/// The markdown of hover contents, which servers send as markup, as a string, as a
+/// piece of code in a language or as a list of those.
+fn markdown(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(markdown).collect::<Vec<_>>().join("\n\n"),
+        Value::Object(part) => {
+            let value = part
+                .get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match part.get("language").and_then(Value::as_str) {
+                Some(language) => format!("```{language}\n{value}\n```"),
+                None => value.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// The symbols in the answer to `workspace/symbol`.
+fn workspace_symbols(symbols: &Value) -> Vec<WorkspaceSymbol> {
+    symbols
+        .as_array()
+        .map(|symbols| symbols.iter().filter_map(workspace_symbol).collect())
+        .unwrap_or_default()
+}
+
+fn workspace_symbol(symbol: &Value) -> Option<WorkspaceSymbol> {
+    let location = symbol.get("location")?;
+    let start = location
+        .pointer("/range/start")
+        .and_then(|start| {
+            let number = |name: &str| usize::try_from(start.get(name)?.as_u64()?).ok();
+            Some((number("line")?, number("character")?))
+        })
+        .unwrap_or_default();
+    Some(WorkspaceSymbol {
+        name: symbol.get("name")?.as_str()?.to_string(),
+        kind: symbol_kind(symbol.get("kind").and_then(Value::as_u64)),
+        path: path(location.get("uri")?.as_str()?)?,
+        start,
+    })
+}
+
+/// The name of a kind of symbol, as servers number them.
+fn symbol_kind(kind: Option<u64>) -> &'static str {
+    match kind {
+        Some(1) => "file",
+        Some(2) => "module",
+        Some(3) => "namespace",
+        Some(4) => "package",
+        Some(5) => "class",
+        Some(6) => "method",
+        Some(7) => "property",
+        Some(8) => "field",
+        Some(9) => "constructor",
+        Some(10) => "enum",
+        Some(11) => "interface",
+        Some(12) => "function",
+        Some(13) => "variable",
+        Some(14) => "constant",
+        Some(15) => "string",
+        Some(16) => "number",
+        Some(17) => "boolean",
+        Some(18) => "array",
+        Some(19) => "object",
+        Some(20) => "key",
+        Some(21) => "null",
+        Some(22) => "enum member",
+        Some(23) => "struct",
+        Some(24) => "event",
+        Some(25) => "operator",
+        Some(26) => "type parameter",
+        _ => "symbol",
+    }
+}
+
+/// How to highlight a kind of semantic token, for the kinds that tell names apart
+/// in ways the highlighter can't.
+fn token_type(kind: &str) -> Option<highlighting::Type> {
+    match kind {
+        "type" | "class" | "struct" | "enum" | "interface" | "typeParameter" | "typeAlias"
+        | "builtinType" => Some(highlighting::Type::TypeName),
+        "function" | "method" => Some(highlighting::Type::Function),
+        "macro" => Some(highlighting::Type::Macro),
+        "parameter" => Some(highlighting::Type::Parameter),
+        "variable" => Some(highlighting::Type::Local),
+        _ => None,
+    }
+}
+
+/// Reads one message, or `None` once the server closed its stdout or wrote
+/// something that isn't one. Debug adapters frame their messages the same way.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+/// The whole text of a document, the way the server is sent it.
+fn text(document: &Document) -> String {
+    (0..document.len())
+        .filter_map(|index| document.row(index).map(Row::as_str))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// What the editor can do, as it tells servers when initializing them.
+fn capabilities() -> Value {
+    json!({
+        "general": { "positionEncodings": ["utf-16"] },
+        "textDocument": {
+            "synchronization": { "didSave": false },
+            "rename": { "prepareSupport": false },
+            "hover": { "contentFormat": ["markdown", "plaintext"] },
+            "rangeFormatting": {},
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": [
+                            "quickfix",
+                            "refactor",
+                            "refactor.extract",
+                            "refactor.inline",
+                            "refactor.rewrite",
+                            "source",
+                            "source.organizeImports",
+                        ],
+                    },
+                },
+                "resolveSupport": { "properties": ["edit"] },
+                "dataSupport": true,
+            },
+            "publishDiagnostics": {},
+            "semanticTokens": {
+                "requests": { "full": true },
+                "tokenTypes": [
+                    "type",
+                    "class",
+                    "struct",
+                    "enum",
+                    "interface",
+                    "typeParameter",
+                    "function",
+                    "method",
+                    "macro",
+                    "parameter",
+                    "variable",
+                ],
+                "tokenModifiers": [],
+                "formats": ["relative"],
+            },
+            "signatureHelp": {
+                "signatureInformation": {
+                    "parameterInformation": { "labelOffsetSupport": true },
+                    "activeParameterSupport": true,
+                },
+            },
+        },
+        "workspace": {
+            "applyEdit": true,
+            "symbol": {},
+            "executeCommand": {},
+            "workspaceEdit": { "documentChanges": true },
+            "workspaceFolders": true,
+        },
+    })
+}
+
+/// The language identifier servers expect for a file type.
+fn language_id(file_type: &str) -> String {
+    match file_type {
+        "Shell" => "shellscript".to_string(),
+        file_type => file_type.to_lowercase(),
+    }
+}
+
+pub fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        env::current_dir().map_or_else(|_| path.to_path_buf(), |dir| dir.join(path))
+    })
+}
+
+/// `object` with `key` set to `value`, such as the parameters of a request with one
+/// more of them. Anything but an object is left as it is.
+pub fn with_field(mut object: Value, key: &str, value: Value) -> Value {
+    if let Some(fields) = object.as_object_mut() {
+        fields.insert(key.to_string(), value);
+    }
+    object
+}
+
+/// The `file://` URI of an absolute path.
+fn uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    // Windows paths start with their drive letter rather than a slash
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            write!(uri, "%{byte:02X}").ok();
+        }
+    }
+    uri
+}
+
+/// The path a `file://` URI names, or `None` for other kinds of URI.
+fn path(uri: &str) -> Option<PathBuf> {
+    let mut rest = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(rest.len());
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(escaped) = escaped {
+            bytes.push(escaped);
+            rest = tail.get(2..).unwrap_or_default();
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    let path = String::from_utf8(bytes).ok()?;
+    // A Windows path comes after the slash that precedes its drive letter
+    let path = if let Some(rest) = path
+        .strip_prefix('/')
+        .filter(|rest| rest.get(1..2) == Some(":"))
+    {
+        rest.to_string()
+    } else {
+        path
+    };
+    Some(PathBuf::from(path))
+}
+
+/// The UTF-16 column of the grapheme at index `x` of `line`.
+fn utf16_column(line: &str, x: usize) -> usize {
+    line.graphemes(true)
+        .take(x)
+        .map(|grapheme| grapheme.encode_utf16().count())
+        .sum()
+}
+
+/// The index of the grapheme at UTF-16 column `character` of `line`.
+fn grapheme_index(line: &str, character: usize) -> usize {
+    let mut column = 0_usize;
+    let mut index = 0_usize;
+    for grapheme in line.graphemes(true) {
+        if column >= character {
+            break;
+        }
+        column = column.saturating_add(grapheme.encode_utf16().count());
+        index = index.saturating_add(1);
+    }
+    index
+}
+
+/// The byte offset of UTF-16 column `character` of `line`.
+fn byte_offset(line: &str, character: usize) -> usize {
+    let mut column = 0_usize;
+    for (offset, c) in line.char_indices() {
+        if column >= character {
+            return offset;
+        }
+        column = column.saturating_add(c.len_utf16());
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The files of a workspace edit with the columns and text of their edits.
+    type Files = Vec<(PathBuf, Vec<(usize, usize, String)>)>;
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> TextEdit {
+        TextEdit {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn uris_escape_and_unescape_paths() {
+        let path = Path::new("/home/me/my file%20é.rs");
+        let uri = uri(path);
+        assert_eq!(uri, "file:///home/me/my%20file%2520%C3%A9.rs");
+        assert_eq!(super::path(&uri).as_deref(), Some(path));
+
+        assert_eq!(super::path("https://example.com/a"), None);
+        assert_eq!(
+            super::path("file:///tmp/100%zz").as_deref(),
+            Some(Path::new("/tmp/100%zz"))
+        );
+    }
+
+    #[test]
+    fn uris_of_windows_paths_start_with_the_drive() {
+        assert_eq!(
+            uri(Path::new(r"C:\Users\me\a b.rs")),
+            "file:///C:/Users/me/a%20b.rs"
+        );
+        assert_eq!(
+            path("file:///C:/Users/me/a%20b.rs").as_deref(),
+            Some(Path::new("C:/Users/me/a b.rs"))
+        );
+        // Some clients escape the colon after the drive letter too
+        assert_eq!(
+            path("file:///c%3A/src/main.rs").as_deref(),
+            Some(Path::new("c:/src/main.rs"))
+        );
+    }
+
+    #[test]
+    fn columns_count_utf16_code_units() {
+        assert_eq!(utf16_column("a😀b", 2), 3);
+        assert_eq!(utf16_column("a😀b", 10), 4);
+        assert_eq!(grapheme_index("a😀b", 3), 2);
+        // A column inside a grapheme goes to the end of it
+        assert_eq!(grapheme_index("a😀b", 2), 2);
+        assert_eq!(grapheme_index("e\u{301}x", 2), 1);
+        assert_eq!(byte_offset("😀x", 2), 4);
+    }
+
+    #[test]
+    fn edits_text_keeping_crlf_line_endings() {
+        let text = "fn a() {\r\n    x\r\n}\r\n";
+        let edits = [
+            edit((1, 4), (1, 5), "y\nz"),
+            edit((0, 0), (0, 0), "// top\n"),
+        ];
+        assert_eq!(
+            apply_to_text(text, &edits),
+            "// top\r\nfn a() {\r\n    y\r\nz\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn edits_text_after_characters_outside_the_bmp() {
+        let text = "let 😀x = 1;\nnext\n";
+        let edits = [edit((0, 6), (0, 7), "y"), edit((1, 0), (2, 0), "")];
+        assert_eq!(apply_to_text(text, &edits), "let 😀y = 1;\n");
+    }
+
+    #[test]
+    fn reads_both_forms_of_workspace_edits() {
+        let range =
+            json!({ "start": { "line": 0, "character": 1 }, "end": { "line": 0, "character": 2 } });
+        let changes = json!({
+            "changes": { "file:///a.rs": [{ "range": range, "newText": "x" }] },
+        });
+        let document_changes = json!({
+            "documentChanges": [
+                { "textDocument": { "uri": "file:///b.rs", "version": 3 },
+                  "edits": [{ "range": range, "newText": "y" }] },
+                { "kind": "create", "uri": "file:///c.rs" },
+                { "textDocument": { "uri": "file:///d.rs", "version": 1 }, "edits": [] },
+            ],
+        });
+
+        let files = |edit: &Value| -> Files {
+            workspace_edit(edit)
+                .into_iter()
+                .map(|(path, edits)| {
+                    let edits = edits
+                        .into_iter()
+                        .map(|edit| (edit.start.1, edit.end.1, edit.text))
+                        .collect();
+                    (path, edits)
+                })
+                .collect()
+        };
+        assert_eq!(
+            files(&changes),
+            [(PathBuf::from("/a.rs"), vec![(1, 2, "x".to_string())])]
+        );
+        assert_eq!(
+            files(&document_changes),
+            [(PathBuf::from("/b.rs"), vec![(1, 2, "y".to_string())])]
+        );
+    }
+}
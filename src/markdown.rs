@@ -0,0 +1,437 @@
+//! Markdown as language servers write documentation in, turned into plain lines for
+//! showing in a popup, and the list and table editing helpers of markdown files.
+use std::sync::OnceLock;
+
+use regex::Regex;
+use unicode_width::UnicodeWidthStr as _;
+
+static LINK: OnceLock<Option<Regex>> = OnceLock::new();
+
+/// Turns markdown into lines at most `width` characters wide. Code blocks keep their
+/// lines, indented, while paragraphs and list items are wrapped and lose their
+/// emphasis markers and link targets.
+pub fn plain_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    // The paragraph or list item being collected, with the prefixes of its first
+    // and following lines
+    let mut block: Option<(String, String, String)> = None;
+    let mut in_code = false;
+
+    let flush = |block: &mut Option<(String, String, String)>, lines: &mut Vec<String>| {
+        if let Some((first, rest, text)) = block.take() {
+            lines.extend(wrap(&inline(&text), width, &first, &rest));
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            flush(&mut block, &mut lines);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(format!("  {}", line.trim_end()));
+            continue;
+        }
+
+        let rule = trimmed.len() >= 3
+            && ['-', '*', '_']
+                .iter()
+                .any(|marker| trimmed.chars().all(|c| c == *marker));
+        if trimmed.is_empty() || rule {
+            flush(&mut block, &mut lines);
+            if rule {
+                lines.push("─".repeat(width.min(40)));
+            } else if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+
+        let heading = trimmed.trim_start_matches('#');
+        if heading.len() < trimmed.len() && (heading.is_empty() || heading.starts_with(' ')) {
+            flush(&mut block, &mut lines);
+            lines.push(inline(heading.trim()).to_uppercase());
+            continue;
+        }
+
+        let item = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker));
+        let indent = " ".repeat(line.len().saturating_sub(line.trim_start().len()));
+        match (item, &mut block) {
+            (Some(item), _) => {
+                flush(&mut block, &mut lines);
+                block = Some((
+                    format!("{indent}• "),
+                    format!("{indent}  "),
+                    item.to_string(),
+                ));
+            }
+            (None, Some((_, _, text))) => {
+                text.push(' ');
+                text.push_str(trimmed);
+            }
+            (None, None) => block = Some((String::new(), String::new(), trimmed.to_string())),
+        }
+    }
+    flush(&mut block, &mut lines);
+
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// A line of markdown that is a list item, such as `  - [ ] milk` or `3. eggs`.
+///
+/// # Fields
+///
+/// - `prefix`: The length in bytes of what comes before the item's text: the
+///   indentation, the marker and the checkbox.
+/// - `next`: What the item after it starts with, the number counted up and with an
+///   unchecked box when this one has a box.
+/// - `checked`: Whether its checkbox is checked, when it has one.
+pub struct ListItem {
+    pub prefix: usize,
+    pub next: String,
+    pub checked: Option<bool>,
+}
+
+/// The list item `line` is, if it's one.
+pub fn list_item(line: &str) -> Option<ListItem> {
+    let text = line.trim_start();
+    let indent = line.get(..line.len().saturating_sub(text.len()))?;
+
+    let (marker, next_marker) = if let Some(bullet) = ["- ", "* ", "+ "]
+        .into_iter()
+        .find(|bullet| text.starts_with(bullet))
+    {
+        (bullet.to_string(), bullet.to_string())
+    } else {
+        // Numbered items are up to nine digits followed by `.` or `)`
+        let digits = text.chars().take_while(char::is_ascii_digit).count();
+        let number: usize = text.get(..digits)?.parse().ok()?;
+        let after = text.get(digits..)?;
+        let delimiter = after.chars().next().filter(|c| matches!(c, '.' | ')'))?;
+        if digits > 9 || !after.get(1..)?.starts_with(' ') {
+            return None;
+        }
+        (
+            format!("{number}{delimiter} "),
+            format!("{}{delimiter} ", number.saturating_add(1)),
+        )
+    };
+
+    let rest = text.get(marker.len()..)?;
+    let checked = match rest.get(..4) {
+        Some("[ ] ") => Some(false),
+        Some("[x] " | "[X] ") => Some(true),
+        _ => None,
+    };
+    let checkbox = if checked.is_some() { "[ ] " } else { "" };
+
+    Some(ListItem {
+        prefix: indent
+            .len()
+            .saturating_add(marker.len())
+            .saturating_add(checkbox.len()),
+        next: format!("{indent}{next_marker}{checkbox}"),
+        checked,
+    })
+}
+
+/// Checks or unchecks the checkbox of a list item. A list item without one gets an
+/// unchecked one, and any other line becomes an unchecked item.
+pub fn toggle_checkbox(line: &str) -> String {
+    let Some(item) = list_item(line) else {
+        let text = line.trim_start();
+        let indent = line
+            .get(..line.len().saturating_sub(text.len()))
+            .unwrap_or("");
+        return format!("{indent}- [ ] {text}");
+    };
+    let before = line.get(..item.prefix).unwrap_or("");
+    let text = line.get(item.prefix..).unwrap_or("");
+    if let Some(checked) = item.checked {
+        let marker = before.get(..before.len().saturating_sub(4)).unwrap_or("");
+        let checkbox = if checked { "[ ] " } else { "[x] " };
+        format!("{marker}{checkbox}{text}")
+    } else {
+        format!("{before}[ ] {text}")
+    }
+}
+
+/// How the cells of a table column are aligned, as its separator cell says.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    /// `---`, drawn like `Left` but kept without colons.
+    None,
+    /// `:--`, with the text at the left.
+    Left,
+    /// `--:`, with the text at the right.
+    Right,
+    /// `:-:`, with the text in the middle.
+    Center,
+}
+
+/// Whether a line of markdown is a table row, which starts with a pipe.
+pub fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// Lines up the pipes of a markdown table, padding each cell to the widest one of
+/// its column and aligning it the way the separator row says. Wide characters such
+/// as CJK and emoji count as two columns, as terminals draw them. Rows with fewer
+/// cells than others get empty ones, and the table keeps the indentation of its
+/// first row.
+pub fn format_table(lines: &[String]) -> Vec<String> {
+    let indent = lines.first().map_or("", |line| {
+        line.get(..line.len().saturating_sub(line.trim_start().len()))
+            .unwrap_or("")
+    });
+    let rows: Vec<Vec<String>> = lines.iter().map(|line| table_cells(line)).collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+    let separator = rows
+        .iter()
+        .position(|cells| !cells.is_empty() && cells.iter().all(|cell| alignment(cell).is_some()));
+    let alignments: Vec<Alignment> = (0..columns)
+        .map(|column| {
+            separator
+                .and_then(|row| rows.get(row)?.get(column))
+                .and_then(|cell| alignment(cell))
+                .unwrap_or(Alignment::None)
+        })
+        .collect();
+    // Separator cells take at least three columns, as `:-:` does
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != separator)
+                .filter_map(|(_, cells)| cells.get(column))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        })
+        .collect();
+
+    rows.iter()
+        .enumerate()
+        .map(|(index, cells)| {
+            let cells: Vec<String> = widths
+                .iter()
+                .zip(&alignments)
+                .enumerate()
+                .map(|(column, (width, alignment))| {
+                    if Some(index) == separator {
+                        return separator_cell(*alignment, *width);
+                    }
+                    let cell = cells.get(column).map_or("", String::as_str);
+                    let padding = width.saturating_sub(cell.width());
+                    let half = padding.checked_div(2).unwrap_or_default();
+                    let (before, after) = match alignment {
+                        Alignment::None | Alignment::Left => (0, padding),
+                        Alignment::Right => (padding, 0),
+                        Alignment::Center => (half, padding.saturating_sub(half)),
+                    };
+                    format!("{}{cell}{}", " ".repeat(before), " ".repeat(after))
+                })
+                .collect();
+            format!("{indent}| {} |", cells.join(" | "))
+        })
+        .collect()
+}
+
+/// The trimmed cells of a table row, split at pipes that aren't escaped.
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                cell.push(c);
+                if let Some(next) = chars.next() {
+                    cell.push(next);
+                }
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// The alignment a separator cell such as `:--` sets, or `None` when the cell
+/// isn't one.
+fn alignment(cell: &str) -> Option<Alignment> {
+    let left = cell.starts_with(':');
+    let right = cell.len() > 1 && cell.ends_with(':');
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+        return None;
+    }
+    Some(match (left, right) {
+        (false, false) => Alignment::None,
+        (true, false) => Alignment::Left,
+        (false, true) => Alignment::Right,
+        (true, true) => Alignment::Center,
+    })
+}
+
+fn separator_cell(alignment: Alignment, width: usize) -> String {
+    let (left, right) = match alignment {
+        Alignment::None => ("", ""),
+        Alignment::Left => (":", ""),
+        Alignment::Right => ("", ":"),
+        Alignment::Center => (":", ":"),
+    };
+    let dashes = width.saturating_sub(left.len()).saturating_sub(right.len());
+    format!("{left}{}{right}", "-".repeat(dashes))
+}
+
+/// Drops the markers of inline markdown: bold, code spans, link targets and
+/// backslash escapes. Single `*` and `_` are kept, as they're as often part of a
+/// name as emphasis.
+fn inline(text: &str) -> String {
+    let text = text.replace("**", "").replace("__", "").replace('`', "");
+    let text = match LINK
+        .get_or_init(|| Regex::new(r"\[([^\]]*)\]\([^)]*\)").ok())
+        .as_ref()
+    {
+        Some(link) => link.replace_all(&text, "$1").into_owned(),
+        None => text,
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next)) if next.is_ascii_punctuation() => {
+                result.push(next);
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Wraps text into lines of at most `width` characters, starting the first with
+/// `first` and the others with `rest`. Words longer than a line get one of their own.
+fn wrap(text: &str, width: usize, first: &str, rest: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = first.to_string();
+    let mut current_width = first.chars().count();
+    let mut empty = true;
+
+    for word in text.split_whitespace() {
+        let word_width = word.chars().count();
+        if !empty {
+            if current_width.saturating_add(word_width).saturating_add(1) > width {
+                lines.push(std::mem::replace(&mut current, rest.to_string()));
+                current_width = rest.chars().count();
+            } else {
+                current.push(' ');
+                current_width = current_width.saturating_add(1);
+            }
+        }
+        current.push_str(word);
+        current_width = current_width.saturating_add(word_width);
+        empty = false;
+    }
+
+    if !empty {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continues_list_items() {
+        let item = list_item("- milk").unwrap();
+        assert_eq!(
+            (item.prefix, item.next.as_str(), item.checked),
+            (2, "- ", None)
+        );
+
+        // Numbers count up, also past a digit more, and keep their delimiter
+        assert_eq!(list_item("9. nine").unwrap().next, "10. ");
+        assert_eq!(list_item("3) three").unwrap().next, "4) ");
+        assert!(list_item("1234567890. too long").is_none());
+        assert!(list_item("1.5 percent").is_none());
+        assert!(list_item("-not a bullet").is_none());
+
+        // Nested items keep their indentation, and a checked box continues unchecked
+        let item = list_item("    * [x] done").unwrap();
+        assert_eq!(
+            (item.prefix, item.next.as_str(), item.checked),
+            (10, "    * [ ] ", Some(true))
+        );
+    }
+
+    #[test]
+    fn finds_empty_list_items() {
+        // An item that is only its prefix is empty, which ends the list on Enter
+        for line in ["- ", "  - [ ] ", "12. "] {
+            assert_eq!(list_item(line).map(|item| item.prefix), Some(line.len()));
+        }
+        let line = "- [ ] text";
+        assert!(list_item(line).is_some_and(|item| item.prefix < line.len()));
+    }
+
+    #[test]
+    fn toggles_checkboxes() {
+        assert_eq!(toggle_checkbox("- [ ] milk"), "- [x] milk");
+        assert_eq!(toggle_checkbox("  * [X] eggs"), "  * [ ] eggs");
+        assert_eq!(toggle_checkbox("1. bread"), "1. [ ] bread");
+        assert_eq!(toggle_checkbox("  butter"), "  - [ ] butter");
+    }
+
+    #[test]
+    fn lines_up_tables() {
+        let lines = ["  | name | n |", "|:-|-:|", "| a | 10 | extra |", "| b |"];
+        let lines: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            format_table(&lines),
+            [
+                "  | name |   n |       |",
+                "  | :--- | --: | ----- |",
+                "  | a    |  10 | extra |",
+                "  | b    |     |       |",
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_up_wide_characters_and_escaped_pipes() {
+        // CJK characters and emoji take two columns, and an escaped pipe stays in
+        // its cell
+        let lines = ["| 名前 | note |", "|:-:|---|", "| 👍 | a \\| b |"];
+        let lines: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            format_table(&lines),
+            [
+                "| 名前 | note   |",
+                "| :--: | ------ |",
+                "|  👍  | a \\| b |",
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crossterm::style::{style, Stylize};
+use std::time::{Duration, Instant};
+
+use crate::{backend::Backend, theme::Theme, Terminal};
+
+/// How much attention a notification needs, which decides its color and how long it stays.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn timeout(self) -> Duration {
+        match self {
+            Self::Info => Duration::from_secs(5),
+            Self::Warning => Duration::from_secs(8),
+            Self::Error => Duration::from_secs(12),
+        }
+    }
+}
+
+struct Notification {
+    text: String,
+    severity: Severity,
+    time: Instant,
+}
+
+/// The messages shown in the message bar. Each one expires on its own, and when
+/// several are showing at once they share the bar, newest last.
+#[derive(Default)]
+pub struct Notifications {
+    queue: Vec<Notification>,
+}
+
+impl Notifications {
+    /// More notifications than this at once only push the oldest out.
+    const LIMIT: usize = 5;
+
+    pub fn push(&mut self, severity: Severity, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.queue.push(Notification {
+            text,
+            severity,
+            time: Instant::now(),
+        });
+        if self.queue.len() > Self::LIMIT {
+            self.queue.remove(0);
+        }
+    }
+
+    /// Replaces the newest notification instead of adding one, for messages that
+    /// follow up on each other such as progress or a count being cycled through.
+    pub fn update(&mut self, severity: Severity, text: String) {
+        self.queue.pop();
+        self.push(severity, text);
+    }
+
+    pub fn info(&mut self, text: String) {
+        self.push(Severity::Info, text);
+    }
+
+    pub fn warning(&mut self, text: String) {
+        self.push(Severity::Warning, text);
+    }
+
+    pub fn error(&mut self, text: String) {
+        self.push(Severity::Error, text);
+    }
+
+    /// Drops the notifications that timed out, returning whether any did.
+    pub fn expire(&mut self) -> bool {
+        let count = self.queue.len();
+        self.queue
+            .retain(|notification| notification.time.elapsed() < notification.severity.timeout());
+        self.queue.len() != count
+    }
+
+    /// Draws the notifications that fit in `width` columns, preferring the newest
+    /// and noting how many older ones were left out.
+    pub fn draw(&self, backend: &mut dyn Backend, width: usize, theme: &Theme) {
+        const SEPARATOR: &str = " | ";
+
+        let mut shown: Vec<&Notification> = Vec::new();
+        let mut used: usize = 0;
+        for notification in self.queue.iter().rev() {
+            let len = notification.text.chars().count();
+            let needed = if shown.is_empty() {
+                len
+            } else {
+                len.saturating_add(SEPARATOR.len())
+            };
+
+            // The newest notification is always shown, truncated if need be
+            if !shown.is_empty() && used.saturating_add(needed) > width {
+                break;
+            }
+            used = used.saturating_add(needed);
+            shown.push(notification);
+        }
+
+        let hidden = self.queue.len().saturating_sub(shown.len());
+        let mut line = Vec::new();
+        if hidden > 0 {
+            line.push((format!("(+{hidden}) "), theme.message_bar_fg));
+        }
+        for (index, notification) in shown.iter().rev().enumerate() {
+            if index > 0 {
+                line.push((SEPARATOR.to_string(), theme.message_bar_fg));
+            }
+            let color = match notification.severity {
+                Severity::Info => theme.message_bar_fg,
+                Severity::Warning => theme.warning,
+                Severity::Error => theme.error,
+            };
+            line.push((notification.text.clone(), color));
+        }
+
+        let mut remaining = width;
+        for (text, color) in line {
+            let text: String = text.chars().take(remaining).collect();
+            remaining = remaining.saturating_sub(text.chars().count());
+            backend.print(&style(text).with(Terminal::color(color)).to_string());
+        }
+    }
+}
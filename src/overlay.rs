@@ -0,0 +1,216 @@
+use crossterm::style::{style, Stylize};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    backend::{Backend, Size},
+    theme::Theme,
+    Position, Terminal,
+};
+
+/// Where an overlay is placed on the screen.
+pub enum Anchor {
+    /// Centered over the text area, for dialogs and lists.
+    Center,
+    /// Right below a screen position (or above it when there's no room), for popups
+    /// that belong to the text under the cursor.
+    Below(Position),
+    /// In the top right corner of the text area, for panels that stay open while
+    /// editing.
+    TopRight,
+}
+
+/// A bordered box drawn above the text area, used by every popup in the editor.
+///
+/// Overlays only hold what to show; the `Editor` decides when one is open and
+/// `refresh_screen` draws it after the rows so it covers them.
+pub struct Overlay {
+    title: String,
+    lines: Vec<String>,
+    anchor: Anchor,
+    selected: Option<usize>,
+    highlight: Option<(usize, Range<usize>)>,
+    offset: usize,
+}
+
+impl Overlay {
+    pub fn new(title: &str, lines: Vec<String>, anchor: Anchor) -> Self {
+        Self {
+            title: title.to_string(),
+            lines,
+            anchor,
+            selected: None,
+            highlight: None,
+            offset: 0,
+        }
+    }
+
+    /// Makes the overlay a list where one line is highlighted as selected.
+    #[must_use]
+    pub fn with_selection(mut self, selected: usize) -> Self {
+        self.selected = Some(selected.min(self.lines.len().saturating_sub(1)));
+        self
+    }
+
+    /// Highlights the graphemes in `range` of one line, such as the parameter of a
+    /// signature the cursor is at.
+    #[must_use]
+    pub fn with_highlight(mut self, line: usize, range: Range<usize>) -> Self {
+        self.highlight = Some((line, range));
+        self
+    }
+
+    /// Starts with the last lines visible, useful for logs.
+    #[must_use]
+    pub fn scrolled_to_bottom(mut self) -> Self {
+        self.offset = self.lines.len();
+        self
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Moves the selection, or scrolls when the overlay has no selection.
+    pub fn move_by(&mut self, delta: isize) {
+        if let Some(selected) = self.selected {
+            let last = self.lines.len().saturating_sub(1);
+            self.selected = Some(selected.saturating_add_signed(delta).min(last));
+        } else {
+            self.offset = self.offset.saturating_add_signed(delta);
+        }
+    }
+
+    /// Returns the column, row, width and height of the box including its border.
+    fn frame(&self, screen: &Size) -> (usize, usize, usize, usize) {
+        let screen_width = usize::from(screen.width);
+        let screen_height = usize::from(screen.height);
+
+        let content_width = self
+            .lines
+            .iter()
+            .map(|line| line.graphemes(true).count())
+            .chain([self.title.graphemes(true).count().saturating_add(2)])
+            .max()
+            .unwrap_or(0);
+        let width = content_width
+            .saturating_add(4)
+            .min(screen_width.saturating_sub(2));
+        let height = self
+            .lines
+            .len()
+            .saturating_add(2)
+            .min(screen_height.saturating_sub(1))
+            .max(3);
+
+        match &self.anchor {
+            Anchor::Center => (
+                screen_width
+                    .saturating_sub(width)
+                    .checked_div(2)
+                    .unwrap_or_default(),
+                screen_height
+                    .saturating_sub(height)
+                    .checked_div(2)
+                    .unwrap_or_default(),
+                width,
+                height,
+            ),
+            Anchor::Below(position) => {
+                let x = position.x.min(screen_width.saturating_sub(width));
+                // A box that fits on neither side shrinks to the larger one, so it
+                // never covers the position it belongs to
+                let below = screen_height.saturating_sub(position.y.saturating_add(1));
+                let above = position.y;
+                if height <= below || below >= above {
+                    (x, position.y.saturating_add(1), width, height.min(below))
+                } else {
+                    let height = height.min(above);
+                    (x, position.y.saturating_sub(height), width, height)
+                }
+            }
+            Anchor::TopRight => (screen_width.saturating_sub(width), 0, width, height),
+        }
+    }
+
+    /// Draws the overlay over whatever is on the screen.
+    pub fn draw(&mut self, backend: &mut dyn Backend, theme: &Theme) {
+        let (x, y, width, height) = self.frame(backend.size());
+        let inner_width = width.saturating_sub(4);
+        let visible = height.saturating_sub(2);
+
+        // Keep the selection, or the scroll position, inside the visible lines
+        let max_offset = self.lines.len().saturating_sub(visible);
+        if let Some(selected) = self.selected {
+            if selected < self.offset {
+                self.offset = selected;
+            } else if selected >= self.offset.saturating_add(visible) {
+                self.offset = selected.saturating_sub(visible).saturating_add(1);
+            }
+        }
+        self.offset = self.offset.min(max_offset);
+
+        let title: String = self.title.graphemes(true).take(inner_width).collect();
+        let top = if title.is_empty() {
+            "─".repeat(width.saturating_sub(2))
+        } else {
+            format!(
+                "─ {title} {}",
+                "─".repeat(
+                    width
+                        .saturating_sub(5)
+                        .saturating_sub(title.graphemes(true).count())
+                )
+            )
+        };
+
+        backend.move_cursor(&Position { x, y });
+        backend.print(&format!("┌{top}┐"));
+
+        for row in 0..visible {
+            let index = self.offset.saturating_add(row);
+            let text: String = self
+                .lines
+                .get(index)
+                .map(|line| line.graphemes(true).take(inner_width).collect())
+                .unwrap_or_default();
+            let padding = inner_width.saturating_sub(text.graphemes(true).count());
+            let content = format!(" {text}{} ", " ".repeat(padding));
+
+            backend.move_cursor(&Position {
+                x,
+                y: y.saturating_add(1).saturating_add(row),
+            });
+            if self.selected == Some(index) {
+                backend.print(&format!(
+                    "│{}│",
+                    style(content).on(Terminal::color(theme.selection_bg))
+                ));
+            } else if let Some((_, range)) =
+                self.highlight.as_ref().filter(|(line, _)| *line == index)
+            {
+                let part = |range: Range<usize>| -> String {
+                    text.graphemes(true)
+                        .skip(range.start)
+                        .take(range.end.saturating_sub(range.start))
+                        .collect()
+                };
+                backend.print(&format!(
+                    "│ {}{}{}{} │",
+                    part(0..range.start),
+                    style(part(range.clone())).on(Terminal::color(theme.selection_bg)),
+                    part(range.end..usize::MAX),
+                    " ".repeat(padding)
+                ));
+            } else {
+                backend.print(&format!("│{content}│"));
+            }
+        }
+
+        backend.move_cursor(&Position {
+            x,
+            y: y.saturating_add(height).saturating_sub(1),
+        });
+        backend.print(&format!("└{}┘", "─".repeat(width.saturating_sub(2))));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fs, path::Path};
+
+/// Completes file paths typed into a prompt, cycling through the candidates when
+/// Tab is pressed repeatedly.
+#[derive(Default)]
+pub struct PathCompleter {
+    candidates: Vec<String>,
+    index: usize,
+    last_completion: Option<String>,
+}
+
+impl PathCompleter {
+    /// Completes `input` in place and returns the candidates to show to the user.
+    pub fn complete(&mut self, input: &mut String) -> Vec<String> {
+        let (dir, prefix) = match input.rfind('/') {
+            Some(index) => input.split_at(index.saturating_add(1)),
+            None => ("", input.as_str()),
+        };
+
+        // Pressing Tab again right after a completion moves on to the next candidate
+        if self.last_completion.as_deref() == Some(input.as_str()) && self.candidates.len() > 1 {
+            self.index = self
+                .index
+                .saturating_add(1)
+                .checked_rem(self.candidates.len())
+                .unwrap_or_default();
+            if let Some(candidate) = self.candidates.get(self.index) {
+                *input = format!("{dir}{candidate}");
+            }
+            self.last_completion = Some(input.clone());
+            return self.candidates.clone();
+        }
+
+        self.candidates = Self::candidates(dir, prefix);
+        self.index = 0;
+        let dir = dir.to_string();
+
+        match self.candidates.as_slice() {
+            [] => (),
+            [candidate] => *input = format!("{dir}{candidate}"),
+            [first, ..] => {
+                let common = self
+                    .candidates
+                    .iter()
+                    .fold(first.clone(), |common, candidate| {
+                        common
+                            .chars()
+                            .zip(candidate.chars())
+                            .take_while(|(a, b)| a == b)
+                            .map(|(a, _)| a)
+                            .collect()
+                    });
+
+                if common.len() > prefix.len() {
+                    // Start cycling from the first candidate on the next Tab
+                    self.index = self.candidates.len().saturating_sub(1);
+                    *input = format!("{dir}{common}");
+                } else {
+                    *input = format!("{dir}{first}");
+                }
+            }
+        }
+
+        self.last_completion = Some(input.clone());
+        self.candidates.clone()
+    }
+
+    /// Lists the entries of `dir` starting with `prefix`, directories with a trailing `/`.
+    fn candidates(dir: &str, prefix: &str) -> Vec<String> {
+        let path = if dir.is_empty() {
+            Path::new(".")
+        } else {
+            Path::new(dir)
+        };
+        let Ok(entries) = fs::read_dir(path) else {
+            return Vec::new();
+        };
+
+        let mut candidates: Vec<String> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+
+                if entry.path().is_dir() {
+                    Some(format!("{name}/"))
+                } else {
+                    Some(name)
+                }
+            })
+            .collect();
+
+        candidates.sort();
+        candidates
+    }
+}
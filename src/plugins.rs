@@ -0,0 +1,440 @@
+//! WebAssembly plugins. Every `*.wasm` module in the `plugins` folder of the config
+//! directory is loaded at startup into a sandbox of its own: it can only reach the
+//! editor through the functions below and runs with a fuel limit, so a plugin stuck
+//! in a loop is stopped instead of freezing the editor.
+//!
+//! # ABI, version 1
+//!
+//! A plugin exports its `memory` and `voider_api_version() -> i32`, which must return
+//! `API_VERSION`. Modules built for another version are refused rather than
+//! misread. It may also export:
+//!
+//! - `init()`, called once after loading, to register commands and bind keys.
+//! - `run_command(index: i32)`, called when one of its commands runs, with the
+//!   index `register_command` returned for it.
+//! - `status()`, called whenever the status bar is drawn, to update its segment.
+//!
+//! Strings are passed as a pointer and a length in bytes into the plugin's memory,
+//! and must be UTF-8. Lines and columns count from 0, and cursor columns count
+//! graphemes, the characters as the user sees them, so a letter with an accent
+//! typed as two code points is one column. The host functions are imported from
+//! the `voider` module:
+//!
+//! - `line_count() -> i32`, `line_len(line) -> i32` in bytes, or -1 past the end.
+//! - `read_line(line, ptr, len) -> i32` copies up to `len` bytes and returns how many.
+//! - `set_line(line, ptr, len) -> i32` and `insert(ptr, len) -> i32` edit the buffer,
+//!   returning 0, or -1 when the buffer is read-only or the arguments are wrong.
+//! - `cursor_line() -> i32`, `cursor_column() -> i32` and `set_cursor(line, column)`.
+//! - `message(ptr, len)` shows a message in the message bar.
+//! - `register_command(ptr, len) -> i32` adds a command, returning its index.
+//! - `bind(key_ptr, key_len, command_ptr, command_len) -> i32` binds a key such as
+//!   `alt+d` to a command, returning -1 for keys that can't be parsed.
+//! - `set_status(ptr, len)` sets the plugin's segment of the status bar.
+//!
+//! Every call into a plugin sees the active buffer. `status` runs while the screen
+//! is drawn and gets to read it only: editing and moving the cursor return -1 or
+//! do nothing there.
+use std::{fs, path::Path};
+
+use voider_core::{Document, Position, Row};
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store};
+
+use crate::config::KeyBinding;
+
+/// The version of the plugin ABI this editor implements.
+const API_VERSION: i32 = 1;
+
+/// How much work a plugin may do in a single call before it's stopped.
+const FUEL: u64 = 50_000_000;
+
+/// What a plugin works on during a call, and what it registered so far.
+#[derive(Default)]
+struct State {
+    document: Document,
+    cursor: Position,
+    /// Whether the call may change the buffer and the cursor.
+    writable: bool,
+    messages: Vec<String>,
+    commands: Vec<String>,
+    bindings: Vec<(String, String)>,
+    status: String,
+}
+
+struct Plugin {
+    name: String,
+    store: Store<State>,
+    instance: Instance,
+}
+
+impl Plugin {
+    fn load(engine: &Engine, linker: &Linker<State>, path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| err.to_string())?;
+        let module = Module::new(engine, &bytes).map_err(|err| err.to_string())?;
+        let mut store = Store::new(engine, State::default());
+        store.set_fuel(FUEL).map_err(|err| err.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| err.to_string())?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&store, "voider_api_version")
+            .and_then(|version| version.call(&mut store, ()))
+            .map_err(|err| format!("missing voider_api_version export: {err}"))?;
+        if version != API_VERSION {
+            return Err(format!(
+                "built for plugin API {version}, this editor supports {API_VERSION}"
+            ));
+        }
+
+        let mut plugin = Self {
+            name: path
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned()),
+            store,
+            instance,
+        };
+        plugin.call("init", ())?;
+        plugin.store.data_mut().messages.clear();
+        Ok(plugin)
+    }
+
+    /// Calls an export of the plugin if it has one, with a fresh fuel allowance.
+    fn call(&mut self, export: &str, arguments: impl wasmi::WasmParams) -> Result<(), String> {
+        let Ok(function) = self.instance.get_typed_func::<_, ()>(&self.store, export) else {
+            return Ok(());
+        };
+
+        self.store.set_fuel(FUEL).map_err(|err| err.to_string())?;
+        function
+            .call(&mut self.store, arguments)
+            .map_err(|err| format!("{}: {err}", self.name))
+    }
+
+    /// Calls an export with the active buffer lent to the plugin for the duration,
+    /// to change as it likes.
+    fn call_with(
+        &mut self,
+        document: &mut Document,
+        cursor: &mut Position,
+        export: &str,
+        arguments: impl wasmi::WasmParams,
+    ) -> Result<(), String> {
+        let state = self.store.data_mut();
+        std::mem::swap(&mut state.document, document);
+        state.cursor = cursor.clone();
+        state.writable = true;
+
+        let result = self.call(export, arguments);
+
+        let state = self.store.data_mut();
+        std::mem::swap(&mut state.document, document);
+        *cursor = state.cursor.clone();
+        state.writable = false;
+        result
+    }
+
+    /// Like `call_with`, but the plugin can only read the buffer and the cursor.
+    fn call_reading(
+        &mut self,
+        document: &mut Document,
+        cursor: &Position,
+        export: &str,
+    ) -> Result<(), String> {
+        let state = self.store.data_mut();
+        std::mem::swap(&mut state.document, document);
+        state.cursor = cursor.clone();
+
+        let result = self.call(export, ());
+
+        std::mem::swap(&mut self.store.data_mut().document, document);
+        result
+    }
+}
+
+/// The loaded plugins.
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Loads the modules in the `plugins` folder of `dir`, reporting the ones that
+    /// can't be loaded.
+    pub fn load(dir: Option<&Path>) -> (Self, Vec<String>) {
+        let mut plugins = Self::default();
+        let mut errors = Vec::new();
+
+        let Some(entries) = dir.and_then(|dir| fs::read_dir(dir.join("plugins")).ok()) else {
+            return (plugins, errors);
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "wasm")
+            })
+            .collect();
+        paths.sort();
+
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let linker = match linker(&engine) {
+            Ok(linker) => linker,
+            Err(err) => {
+                errors.push(format!("could not set up plugins: {err}"));
+                return (plugins, errors);
+            }
+        };
+
+        for path in paths {
+            match Plugin::load(&engine, &linker, &path) {
+                Ok(plugin) => plugins.plugins.push(plugin),
+                Err(err) => errors.push(format!("{}: {err}", path.display())),
+            }
+        }
+
+        (plugins, errors)
+    }
+
+    /// The keys bound by the plugins, as written and parsed, with the names of their
+    /// commands.
+    pub fn bindings(&self) -> Vec<(String, KeyBinding, String)> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.store.data().bindings.iter())
+            .filter_map(|(key, name)| Some((key.clone(), KeyBinding::parse(key)?, name.clone())))
+            .collect()
+    }
+
+    /// Runs the command registered under `name` on the active buffer, returning the
+    /// messages the plugin showed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error message when there's no such command or the plugin failed.
+    pub fn run(
+        &mut self,
+        name: &str,
+        document: &mut Document,
+        cursor: &mut Position,
+    ) -> Result<Vec<String>, String> {
+        let (plugin, index) = self
+            .plugins
+            .iter_mut()
+            .find_map(|plugin| {
+                let index = plugin
+                    .store
+                    .data()
+                    .commands
+                    .iter()
+                    .position(|command| command == name)?;
+                Some((plugin, i32::try_from(index).ok()?))
+            })
+            .ok_or_else(|| format!("no plugin command named `{name}`"))?;
+
+        let result = plugin.call_with(document, cursor, "run_command", index);
+        let messages = std::mem::take(&mut plugin.store.data_mut().messages);
+        result.map(|()| messages)
+    }
+
+    /// The status bar segments of the plugins, updated for the active buffer, which
+    /// they only get to read. It's lent to them rather than copied, which is why it
+    /// is taken mutably. Plugins that fail keep their previous segment.
+    pub fn status(&mut self, document: &mut Document, cursor: &Position) -> Vec<String> {
+        self.plugins
+            .iter_mut()
+            .filter_map(|plugin| {
+                plugin.call_reading(document, cursor, "status").ok();
+                let status = &plugin.store.data().status;
+                (!status.is_empty()).then(|| status.clone())
+            })
+            .collect()
+    }
+}
+
+fn memory(caller: &Caller<'_, State>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+/// Reads a UTF-8 string out of the plugin's memory.
+fn read_string(caller: &Caller<'_, State>, ptr: i32, len: i32) -> Option<String> {
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    let bytes = memory(caller)?.data(caller).get(start..end)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn count(value: usize) -> i32 {
+    i32::try_from(value).unwrap_or(i32::MAX)
+}
+
+/// The host functions every plugin can import.
+#[allow(clippy::too_many_lines)]
+fn linker(engine: &Engine) -> Result<Linker<State>, wasmi::errors::LinkerError> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap("voider", "line_count", |caller: Caller<'_, State>| {
+        count(caller.data().document.len())
+    })?;
+    linker.func_wrap(
+        "voider",
+        "line_len",
+        |caller: Caller<'_, State>, line: i32| {
+            usize::try_from(line)
+                .ok()
+                .and_then(|line| caller.data().document.row(line))
+                .map_or(-1, |row| count(row.as_str().len()))
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "read_line",
+        |mut caller: Caller<'_, State>, line: i32, ptr: i32, len: i32| {
+            let Some(text) = usize::try_from(line)
+                .ok()
+                .and_then(|line| caller.data().document.row(line))
+                .map(|row| row.as_str().to_string())
+            else {
+                return -1;
+            };
+            let Some(memory) = memory(&caller) else {
+                return -1;
+            };
+            let len = usize::try_from(len).unwrap_or(0).min(text.len());
+            let written = usize::try_from(ptr).is_ok_and(|ptr| {
+                memory
+                    .write(
+                        &mut caller,
+                        ptr,
+                        text.as_bytes().get(..len).unwrap_or_default(),
+                    )
+                    .is_ok()
+            });
+            if written {
+                count(len)
+            } else {
+                -1
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "set_line",
+        |mut caller: Caller<'_, State>, line: i32, ptr: i32, len: i32| {
+            let Some(text) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            let state = caller.data_mut();
+            if !state.writable {
+                return -1;
+            }
+            let document = &mut state.document;
+            let Some((line, row_len)) = usize::try_from(line)
+                .ok()
+                .and_then(|line| Some((line, document.row(line).map(Row::len)?)))
+            else {
+                return -1;
+            };
+            if document.is_read_only() {
+                return -1;
+            }
+
+            let start = Position { x: 0, y: line };
+            document.delete_range(
+                &start,
+                &Position {
+                    x: row_len,
+                    y: line,
+                },
+            );
+            document.insert_str(&start, &text);
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "insert",
+        |mut caller: Caller<'_, State>, ptr: i32, len: i32| {
+            let Some(text) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            let state = caller.data_mut();
+            if !state.writable || state.document.is_read_only() {
+                return -1;
+            }
+            state.cursor = state.document.insert_str(&state.cursor, &text);
+            0
+        },
+    )?;
+    linker.func_wrap("voider", "cursor_line", |caller: Caller<'_, State>| {
+        count(caller.data().cursor.y)
+    })?;
+    linker.func_wrap("voider", "cursor_column", |caller: Caller<'_, State>| {
+        count(caller.data().cursor.x)
+    })?;
+    linker.func_wrap(
+        "voider",
+        "set_cursor",
+        |mut caller: Caller<'_, State>, line: i32, column: i32| {
+            // The editor keeps the cursor inside the text once the call returns
+            let state = caller.data_mut();
+            if state.writable {
+                state.cursor = Position {
+                    x: usize::try_from(column).unwrap_or(0),
+                    y: usize::try_from(line).unwrap_or(0),
+                };
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "message",
+        |mut caller: Caller<'_, State>, ptr: i32, len: i32| {
+            if let Some(text) = read_string(&caller, ptr, len) {
+                caller.data_mut().messages.push(text);
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "register_command",
+        |mut caller: Caller<'_, State>, ptr: i32, len: i32| {
+            let Some(name) = read_string(&caller, ptr, len) else {
+                return -1;
+            };
+            let commands = &mut caller.data_mut().commands;
+            commands.push(name);
+            count(commands.len().saturating_sub(1))
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "bind",
+        |mut caller: Caller<'_, State>, key_ptr: i32, key_len: i32, ptr: i32, len: i32| {
+            let key = read_string(&caller, key_ptr, key_len);
+            let name = read_string(&caller, ptr, len);
+            let (Some(key), Some(name)) = (key, name) else {
+                return -1;
+            };
+            if KeyBinding::parse(&key).is_none() {
+                return -1;
+            }
+            caller.data_mut().bindings.push((key, name));
+            0
+        },
+    )?;
+    linker.func_wrap(
+        "voider",
+        "set_status",
+        |mut caller: Caller<'_, State>, ptr: i32, len: i32| {
+            if let Some(text) = read_string(&caller, ptr, len) {
+                caller.data_mut().status = text;
+            }
+        },
+    )?;
+
+    Ok(linker)
+}
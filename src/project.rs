@@ -0,0 +1,106 @@
+use std::{env, fs, io, path::Path};
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::workspace::Workspace;
+
+/// A line of a project file that matches a search.
+pub struct Match {
+    pub file: String,
+    /// The index of the line in the file.
+    pub line: usize,
+    /// The index of the grapheme the first match on the line starts at.
+    pub column: usize,
+    pub text: String,
+}
+
+/// Finds the lines matching `regex` in the files of the workspace. File names are
+/// given the way they would be typed to open them.
+pub fn search(workspace: &mut Workspace, regex: &Regex) -> Vec<Match> {
+    let root = workspace.root().to_path_buf();
+    let mut matches = Vec::new();
+    for path in workspace.files() {
+        let file = file_name(&root, path);
+        let Ok(contents) = fs::read_to_string(&file) else {
+            continue;
+        };
+
+        matches.extend(
+            lines(&contents)
+                .enumerate()
+                .filter_map(|(line, (text, _))| {
+                    let found = regex.find(text)?;
+                    Some(Match {
+                        file: file.clone(),
+                        line,
+                        column: text
+                            .get(..found.start())
+                            .map_or(0, |before| before.graphemes(true).count()),
+                        text: text.to_string(),
+                    })
+                }),
+        );
+    }
+
+    matches
+}
+
+/// Replaces the matches of `regex` on the given lines of a file and writes it back,
+/// keeping its line endings. Returns how many matches were replaced.
+///
+/// # Errors
+///
+/// Returns an error when the file can't be read or written.
+pub fn replace_in_file(
+    file: &str,
+    regex: &Regex,
+    replacement: &str,
+    selected_lines: &[usize],
+) -> Result<usize, io::Error> {
+    let contents = fs::read_to_string(file)?;
+    let mut count = 0_usize;
+    let mut replaced = String::with_capacity(contents.len());
+
+    for (index, (text, ending)) in lines(&contents).enumerate() {
+        if selected_lines.contains(&index) {
+            count = count.saturating_add(regex.find_iter(text).count());
+            replaced.push_str(&regex.replace_all(text, replacement));
+        } else {
+            replaced.push_str(text);
+        }
+        replaced.push_str(ending);
+    }
+
+    if count > 0 {
+        fs::write(file, replaced)?;
+    }
+    Ok(count)
+}
+
+/// Splits text into lines and the line ending after each, so the text can be put
+/// back together exactly.
+fn lines(contents: &str) -> impl Iterator<Item = (&str, &str)> {
+    contents.split_inclusive('\n').map(|line| {
+        let text = line
+            .strip_suffix("\r\n")
+            .or_else(|| line.strip_suffix('\n'))
+            .unwrap_or(line);
+        line.split_at(text.len())
+    })
+}
+
+/// The path of a workspace file relative to the working directory when it's inside
+/// it, so it reads the way it would be typed to open it.
+pub fn file_name(root: &Path, path: &Path) -> String {
+    let path = root.join(path);
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|dir| Some(path.strip_prefix(dir).ok()?.to_path_buf()))
+        .unwrap_or(path);
+    relative
+        .strip_prefix(".")
+        .unwrap_or(&relative)
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,210 @@
+//! Locations picked out of the output of project commands, such as compiler errors
+//! or grep matches, that can be stepped through one at a time.
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::project::{self, Match};
+
+/// A place in a project file that a command pointed at.
+pub struct Location {
+    pub file: String,
+    /// The index of the line in the file.
+    pub line: usize,
+    /// The index of the column in the line.
+    pub column: usize,
+    pub message: String,
+}
+
+/// The locations of the last command and which of them the editor is at.
+#[derive(Default)]
+pub struct Quickfix {
+    locations: Vec<Location>,
+    current: Option<usize>,
+}
+
+impl Quickfix {
+    /// Picks the `file:line:column: message` locations out of the lines a command
+    /// printed in `root`. Column and message are optional, and only files that exist
+    /// are kept. A location on a line of its own, like cargo's `--> file:line:column`,
+    /// takes its message from the line before.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>, root: &Path) -> Self {
+        let Ok(pattern) = Regex::new(r"^\s*(?:--> )?([^\s:][^:]*):(\d+)(?::(\d+))?(?::\s*(.*))?$")
+        else {
+            return Self::default();
+        };
+
+        let mut locations = Vec::new();
+        let mut previous = "";
+        for line in lines {
+            let Some(captures) = pattern.captures(line) else {
+                if !line.trim().is_empty() {
+                    previous = line.trim();
+                }
+                continue;
+            };
+
+            let path = Path::new(&captures[1]);
+            if !root.join(path).is_file() {
+                previous = line.trim();
+                continue;
+            }
+            let number = |index| {
+                captures
+                    .get(index)
+                    .and_then(|number| number.as_str().parse::<usize>().ok())
+                    .map_or(0, |number| number.saturating_sub(1))
+            };
+            let message = captures
+                .get(4)
+                .map_or("", |message| message.as_str())
+                .trim();
+
+            locations.push(Location {
+                file: project::file_name(root, path),
+                line: number(2),
+                column: number(3),
+                message: if message.is_empty() {
+                    previous
+                } else {
+                    message
+                }
+                .to_string(),
+            });
+        }
+
+        Self {
+            locations,
+            current: None,
+        }
+    }
+
+    /// The matches of a project search, with the matching lines as their messages.
+    pub fn from_matches(matches: &[Match]) -> Self {
+        Self {
+            locations: matches
+                .iter()
+                .map(|found| Location {
+                    file: found.file.clone(),
+                    line: found.line,
+                    column: found.column,
+                    message: found.text.trim().to_string(),
+                })
+                .collect(),
+            current: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Moves to the next location, wrapping around after the last one. Returns it with
+    /// its index.
+    pub fn next(&mut self) -> Option<(usize, &Location)> {
+        let index = match self.current {
+            Some(index) if index.saturating_add(1) < self.locations.len() => {
+                index.saturating_add(1)
+            }
+            _ => 0,
+        };
+        self.select(index)
+    }
+
+    /// Moves to the previous location, wrapping around before the first one. Returns
+    /// it with its index.
+    pub fn previous(&mut self) -> Option<(usize, &Location)> {
+        let index = match self.current {
+            Some(index) if index > 0 => index.saturating_sub(1),
+            _ => self.locations.len().saturating_sub(1),
+        };
+        self.select(index)
+    }
+
+    fn select(&mut self, index: usize) -> Option<(usize, &Location)> {
+        let location = self.locations.get(index)?;
+        self.current = Some(index);
+        Some((index, location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::*;
+
+    /// A project root holding `src/main.rs`, with the name the locations give it.
+    fn project(name: &str) -> (PathBuf, String) {
+        let root = env::temp_dir().join(format!("voider-{}-{name}", process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        let file = project::file_name(&root, Path::new("src/main.rs"));
+        (root, file)
+    }
+
+    fn parse(output: &str, root: &Path) -> Vec<(String, usize, usize, String)> {
+        let mut quickfix = Quickfix::parse(output.lines(), root);
+        let mut locations = Vec::new();
+        for _ in 0..quickfix.len() {
+            if let Some((_, location)) = quickfix.next() {
+                locations.push((
+                    location.file.clone(),
+                    location.line,
+                    location.column,
+                    location.message.clone(),
+                ));
+            }
+        }
+        locations
+    }
+
+    #[test]
+    fn takes_rustc_messages_from_the_line_before() {
+        let (root, file) = project("quickfix-rustc");
+        let output = "error[E0308]: mismatched types\n  --> src/main.rs:3:5\n   |\n";
+        assert_eq!(
+            parse(output, &root),
+            [(file, 2, 4, "error[E0308]: mismatched types".to_string())]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn reads_gcc_messages_after_the_location() {
+        let (root, file) = project("quickfix-gcc");
+        let output = "src/main.rs:10:2: error: expected ';' before '}' token\n";
+        assert_eq!(
+            parse(output, &root),
+            [(
+                file,
+                9,
+                1,
+                "error: expected ';' before '}' token".to_string()
+            )]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn column_and_message_are_optional() {
+        let (root, file) = project("quickfix-bare");
+        let output = "src/main.rs:7:12\nsrc/main.rs:1\n";
+        assert_eq!(
+            parse(output, &root),
+            [
+                (file.clone(), 6, 11, String::new()),
+                (file, 0, 0, String::new()),
+            ]
+        );
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn skips_files_that_do_not_exist() {
+        let (root, _) = project("quickfix-missing");
+        let output = "src/gone.rs:1:1: error: nothing here\nhttp://example.com:80\n";
+        assert!(parse(output, &root).is_empty());
+        fs::remove_dir_all(root).ok();
+    }
+}
@@ -0,0 +1,126 @@
+//! Turning rows into styled text for the terminal.
+use crossterm::style::{style, Color, Stylize};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use voider_core::{ansi, highlighting, Row};
+
+use crate::{theme::Theme, Terminal};
+
+/// Renders the screen columns from `start` to `end` of a row. Control characters
+/// are shown as placeholders like `^M` or `<0x1B>`, so they can't act on the
+/// terminal. With `ansi` set, ANSI color sequences are hidden and color the text
+/// after them instead of the syntax highlighting.
+///
+/// The result is cached in the row, so the row has to be told with
+/// `invalidate_rendering` when the theme changes.
+pub fn row(
+    row: &Row,
+    start: usize,
+    end: usize,
+    selection: &Range<usize>,
+    theme: &Theme,
+    ansi: bool,
+) -> String {
+    row.render_cached(start, end, selection, ansi, || {
+        styled(row, start, end, selection, theme, ansi)
+    })
+}
+
+fn styled(
+    row: &Row,
+    start: usize,
+    end: usize,
+    selection: &Range<usize>,
+    theme: &Theme,
+    ansi: bool,
+) -> String {
+    let mut result = String::new();
+    let mut column: usize = 0;
+    let ansi_styles = ansi.then(|| row.ansi_styles());
+
+    for (index, grapheme) in row.as_str().graphemes(true).enumerate() {
+        if column >= end {
+            break;
+        }
+
+        let ansi_style = match &ansi_styles {
+            Some(styles) => match styles.get(index) {
+                Some(Some(style)) => Some(*style),
+                _ => continue,
+            },
+            None => None,
+        };
+
+        let highlighting_type = row
+            .highlighting()
+            .get(index)
+            .unwrap_or(&highlighting::Type::None);
+        let placeholder = Row::placeholder(grapheme);
+        let text = match (&placeholder, grapheme.chars().next()) {
+            (Some(placeholder), _) => placeholder.clone(),
+            (None, Some('\t')) => " ".to_string(),
+            (None, Some(c)) => c.to_string(),
+            (None, None) => String::new(),
+        };
+
+        for c in text.chars() {
+            if column >= start && column < end {
+                let mut styled = if placeholder.is_some() {
+                    style(c).with(Terminal::color(theme.control)).reverse()
+                } else if let Some(ansi_style) = ansi_style {
+                    let mut styled =
+                        style(c).with(Terminal::color(ansi_style.fg.map_or(theme.text, color)));
+                    if let Some(bg) = ansi_style.bg {
+                        styled = styled.on(Terminal::color(color(bg)));
+                    }
+                    if ansi_style.bold {
+                        styled = styled.bold();
+                    }
+                    styled
+                } else if *highlighting_type == highlighting::Type::Url {
+                    style(c)
+                        .with(Terminal::color(theme.highlight(*highlighting_type)))
+                        .underlined()
+                } else {
+                    style(c).with(Terminal::color(theme.highlight(*highlighting_type)))
+                };
+
+                if selection.contains(&index) {
+                    styled = styled.on(Terminal::color(theme.selection_bg));
+                }
+
+                result.push_str(&styled.to_string());
+            }
+            column = column.saturating_add(1);
+        }
+    }
+    result
+}
+
+/// The terminal color for a color set by an ANSI escape sequence.
+fn color(color: ansi::Color) -> Color {
+    let (index, bright) = match color {
+        ansi::Color::Basic(index, bright) => (index, bright),
+        ansi::Color::Indexed(value) => return Color::AnsiValue(value),
+        ansi::Color::Rgb(r, g, b) => return Color::Rgb { r, g, b },
+    };
+
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::DarkRed,
+        (2, false) => Color::DarkGreen,
+        (3, false) => Color::DarkYellow,
+        (4, false) => Color::DarkBlue,
+        (5, false) => Color::DarkMagenta,
+        (6, false) => Color::DarkCyan,
+        (7, false) => Color::Grey,
+        (0, true) => Color::DarkGrey,
+        (1, true) => Color::Red,
+        (2, true) => Color::Green,
+        (3, true) => Color::Yellow,
+        (4, true) => Color::Blue,
+        (5, true) => Color::Magenta,
+        (6, true) => Color::Cyan,
+        _ => Color::White,
+    }
+}
@@ -0,0 +1,84 @@
+//! An interactive interpreter such as `python3 -i`, `ghci` or `node -i`, running in
+//! the background for the editor to send code to. What it prints comes back as task
+//! events. It's fed through pipes, so it has to be told to be interactive, as most
+//! interpreters only are in a terminal.
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Child, ChildStdin, Stdio},
+    thread,
+};
+
+use crate::{
+    hooks,
+    tasks::{self, Tasks},
+};
+
+/// A running REPL and the pipe code is sent to it through.
+pub struct Repl {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl Repl {
+    /// Starts `command` in `dir`, with a thread passing on what it prints as events
+    /// of `tasks`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of starting the command.
+    pub fn start(command: &str, dir: &Path, tasks: &mut Tasks) -> Result<Self, io::Error> {
+        let mut child = hooks::shell(command)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (Some(stdin), Some(stdout), Some(stderr)) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            return Err(io::Error::other("could not talk to the REPL"));
+        };
+
+        // The REPL runs as long as the editor, so it isn't a task the main loop keeps
+        // checking back for, like the file watcher
+        let sender = tasks.sender();
+        thread::spawn(move || tasks::stream_repl(stdout, stderr, &sender));
+        Ok(Self { child, stdin })
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Sends `code` as if typed, leaving out blank lines, which would end an indented
+    /// block early in REPLs such as Python's, and adding one after a last line that
+    /// is indented so such a block runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of writing to the REPL, such as when it exited.
+    pub fn send(&mut self, code: &str) -> Result<(), io::Error> {
+        let lines: Vec<&str> = code
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let mut text = lines.join("\n");
+        text.push('\n');
+        if lines
+            .last()
+            .is_some_and(|line| line.starts_with([' ', '\t']))
+        {
+            text.push('\n');
+        }
+        self.stdin.write_all(text.as_bytes())?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
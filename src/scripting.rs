@@ -0,0 +1,215 @@
+//! Lua scripts that extend the editor. Every `*.lua` file in the `scripts` folder of
+//! the config directory runs at startup, in name order, and may register commands
+//! and bind keys to them through the global `voider` table:
+//!
+//! ```lua
+//! voider.command("date", function()
+//!     voider.insert(os.date("%Y-%m-%d"))
+//! end)
+//! voider.bind("alt+d", "date")
+//! ```
+//!
+//! Inside a command, the script can also read and change the active buffer. Lines
+//! and columns count from 1, like everything else in Lua:
+//!
+//! - `voider.line_count()` and `voider.line(n)` read the buffer.
+//! - `voider.set_line(n, text)` replaces a line and `voider.insert(text)` types at the cursor.
+//!   Both raise an error in a read-only buffer.
+//! - `voider.cursor()` returns the line and column, `voider.set_cursor(line, column)` moves it.
+//! - `voider.file_name()` is the name of the file, or nil.
+//! - `voider.message(text)` shows a message in the message bar.
+use std::{cell::RefCell, fs, path::Path};
+
+use mlua::{Function, Lua, Table};
+use voider_core::Position;
+
+use crate::config::KeyBinding;
+
+const COMMANDS: &str = "voider.commands";
+const BINDINGS: &str = "voider.bindings";
+
+/// What a script may do to the editor while one of its commands runs.
+pub trait Host {
+    fn line_count(&self) -> usize;
+    fn line(&self, index: usize) -> Option<String>;
+    /// Replaces the text of a row, failing when the buffer can't be changed.
+    fn set_line(&mut self, index: usize, text: &str) -> Result<(), String>;
+    /// Inserts text at the cursor and moves the cursor after it, failing when the
+    /// buffer can't be changed.
+    fn insert(&mut self, text: &str) -> Result<(), String>;
+    fn cursor(&self) -> Position;
+    fn set_cursor(&mut self, position: Position);
+    fn file_name(&self) -> Option<String>;
+    fn message(&mut self, text: String);
+}
+
+/// The Lua state holding what the user scripts registered.
+pub struct Scripts {
+    lua: Lua,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        let lua = Lua::new();
+        // Only fails when Lua runs out of memory, and then nothing will work anyway
+        Self::install_api(&lua).ok();
+        Self { lua }
+    }
+}
+
+impl Scripts {
+    /// Runs the scripts in the `scripts` folder of `dir`. A script that fails is
+    /// reported and skipped, keeping whatever it registered before failing.
+    pub fn load(dir: Option<&Path>) -> (Self, Vec<String>) {
+        let scripts = Self::default();
+        let mut errors = Vec::new();
+
+        let Some(entries) = dir.and_then(|dir| fs::read_dir(dir.join("scripts")).ok()) else {
+            return (scripts, errors);
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let result = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|source| {
+                    scripts
+                        .lua
+                        .load(source)
+                        .set_name(format!("@{}", path.display()))
+                        .exec()
+                        .map_err(|err| err.to_string())
+                });
+
+            if let Err(err) = result {
+                errors.push(format!("{}: {err}", path.display()));
+            }
+        }
+
+        (scripts, errors)
+    }
+
+    fn install_api(lua: &Lua) -> mlua::Result<()> {
+        lua.set_named_registry_value(COMMANDS, lua.create_table()?)?;
+        lua.set_named_registry_value(BINDINGS, lua.create_table()?)?;
+
+        let api = lua.create_table()?;
+        api.set(
+            "command",
+            lua.create_function(|lua, (name, function): (String, Function)| {
+                let commands: Table = lua.named_registry_value(COMMANDS)?;
+                commands.set(name, function)
+            })?,
+        )?;
+        api.set(
+            "bind",
+            lua.create_function(|lua, (key, name): (String, String)| {
+                if KeyBinding::parse(&key).is_none() {
+                    return Err(mlua::Error::runtime(format!("invalid key `{key}`")));
+                }
+                let bindings: Table = lua.named_registry_value(BINDINGS)?;
+                bindings.set(key, name)
+            })?,
+        )?;
+        lua.globals().set("voider", api)
+    }
+
+    /// The keys bound by the scripts, as written and parsed, with the names of their
+    /// commands.
+    pub fn bindings(&self) -> Vec<(String, KeyBinding, String)> {
+        let Ok(bindings) = self.lua.named_registry_value::<Table>(BINDINGS) else {
+            return Vec::new();
+        };
+
+        let mut bindings: Vec<_> = bindings
+            .pairs::<String, String>()
+            .filter_map(Result::ok)
+            .filter_map(|(key, name)| Some((key.clone(), KeyBinding::parse(&key)?, name)))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// Runs the command a script registered under `name`, giving it access to `host`
+    /// until it returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error message when there's no such command or it raised an error.
+    pub fn run(&self, name: &str, host: &mut dyn Host) -> Result<(), String> {
+        let commands: Table = self
+            .lua
+            .named_registry_value(COMMANDS)
+            .map_err(|err| err.to_string())?;
+        let function: Option<Function> = commands.get(name).map_err(|err| err.to_string())?;
+        let function = function.ok_or_else(|| format!("no script command named `{name}`"))?;
+
+        let host = RefCell::new(host);
+        let lua = &self.lua;
+        lua.scope(|scope| {
+            let api: Table = lua.globals().get("voider")?;
+            api.set(
+                "line_count",
+                scope.create_function(|_, ()| Ok(host.borrow().line_count()))?,
+            )?;
+            api.set(
+                "line",
+                scope.create_function(|_, line: usize| {
+                    Ok(host.borrow().line(line.saturating_sub(1)))
+                })?,
+            )?;
+            api.set(
+                "set_line",
+                scope.create_function(|_, (line, text): (usize, String)| {
+                    host.borrow_mut()
+                        .set_line(line.saturating_sub(1), &text)
+                        .map_err(mlua::Error::runtime)
+                })?,
+            )?;
+            api.set(
+                "insert",
+                scope.create_function(|_, text: String| {
+                    host.borrow_mut()
+                        .insert(&text)
+                        .map_err(mlua::Error::runtime)
+                })?,
+            )?;
+            api.set(
+                "cursor",
+                scope.create_function(|_, ()| {
+                    let cursor = host.borrow().cursor();
+                    Ok((cursor.y.saturating_add(1), cursor.x.saturating_add(1)))
+                })?,
+            )?;
+            api.set(
+                "set_cursor",
+                scope.create_function(|_, (line, column): (usize, usize)| {
+                    host.borrow_mut().set_cursor(Position {
+                        x: column.saturating_sub(1),
+                        y: line.saturating_sub(1),
+                    });
+                    Ok(())
+                })?,
+            )?;
+            api.set(
+                "file_name",
+                scope.create_function(|_, ()| Ok(host.borrow().file_name()))?,
+            )?;
+            api.set(
+                "message",
+                scope.create_function(|_, text: String| {
+                    host.borrow_mut().message(text);
+                    Ok(())
+                })?,
+            )?;
+
+            function.call::<_, ()>(())
+        })
+        .map_err(|err| err.to_string())
+    }
+}
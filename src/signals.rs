@@ -0,0 +1,31 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, OnceLock,
+};
+
+static TERMINATING: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+fn flag() -> &'static Arc<AtomicBool> {
+    TERMINATING.get_or_init(|| Arc::new(AtomicBool::new(false)))
+}
+
+/// Installs handlers for SIGTERM and SIGHUP. The handlers only raise a flag, which
+/// the editor checks between keys so it can save its buffers before exiting.
+pub fn install() {
+    #[cfg(unix)]
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP] {
+        signal_hook::flag::register(signal, Arc::clone(flag())).ok();
+    }
+}
+
+/// Raises the flag as the handlers do, such as when the terminal can't be read
+/// anymore or to test the emergency save.
+pub fn terminate() {
+    flag().store(true, Ordering::Relaxed);
+}
+
+/// Whether the editor was asked to terminate.
+#[must_use]
+pub fn terminating() -> bool {
+    flag().load(Ordering::Relaxed)
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "headless")]
+use crate::headless;
+
+/// How many entries are kept in each prompt history.
+const HISTORY_SIZE: usize = 100;
+
+/// Things remembered between sessions, kept in `state.toml` in the voider state directory.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct State {
+    /// The input submitted to each kind of prompt, oldest first.
+    pub history: HashMap<String, Vec<String>>,
+    /// The project roots whose config may run commands, with the settings that
+    /// run them as they were when the user trusted them.
+    pub trusted: HashMap<String, String>,
+}
+
+impl State {
+    /// The voider state directory, or `None` while headless, so scripted runs
+    /// neither see nor change what real sessions remember, unless they were given
+    /// one of their own.
+    pub fn dir() -> Option<PathBuf> {
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            return headless::state_dir();
+        }
+        if let Ok(dir) = env::var("XDG_STATE_HOME") {
+            return Some(PathBuf::from(dir).join("voider"));
+        }
+
+        env::var("HOME").ok().map(|home| {
+            PathBuf::from(home)
+                .join(".local")
+                .join("state")
+                .join("voider")
+        })
+    }
+
+    /// Loads the saved state. A missing or unreadable file just means starting fresh.
+    pub fn load() -> Self {
+        Self::dir()
+            .and_then(|dir| fs::read_to_string(dir.join("state.toml")).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the state file, creating the state directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the directory or the file can't be written.
+    pub fn save(&self) -> Result<(), io::Error> {
+        let Some(dir) = Self::dir() else {
+            return Ok(());
+        };
+
+        let contents =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("state.toml"), contents)
+    }
+
+    /// Whether the user trusts the project at `root` to run `commands`, the way
+    /// `Config::load` writes them. A project whose commands changed is asked about again.
+    pub fn trusts(&self, root: &Path, commands: &str) -> bool {
+        self.trusted
+            .get(&root.to_string_lossy().into_owned())
+            .is_some_and(|trusted| trusted == commands)
+    }
+
+    pub fn trust(&mut self, root: &Path, commands: &str) {
+        self.trusted
+            .insert(root.to_string_lossy().into_owned(), commands.to_string());
+    }
+
+    pub fn history(&self, name: &str) -> &[String] {
+        self.history.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds an entry to a history, moving it to the end if it was already there.
+    pub fn push_history(&mut self, name: &str, entry: &str) {
+        let history = self.history.entry(name.to_string()).or_default();
+        history.retain(|old| old != entry);
+        history.push(entry.to_string());
+        if history.len() > HISTORY_SIZE {
+            history.drain(..history.len().saturating_sub(HISTORY_SIZE));
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! The segments of the status bar. Each subsystem that has something to say there,
+//! like git or the language servers, registers a `Segment` once, and the status bar
+//! asks every segment for its text whenever it's drawn, leaving out the ones that
+//! have nothing to show at the moment.
+use crate::Editor;
+
+/// The end of the status bar a segment goes to. Segments keep the order they were
+/// registered in on both sides.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Works out the text of a segment, or `None` to hide it.
+pub type Provider = fn(&mut Editor) -> Option<String>;
+
+/// A named piece of the status bar.
+///
+/// # Fields
+///
+/// - `name`: Tells segments apart, so one can be replaced or taken out.
+/// - `side`: Where it's shown.
+/// - `text`: What it shows.
+#[derive(Clone, Copy)]
+pub struct Segment {
+    pub name: &'static str,
+    pub side: Side,
+    pub text: Provider,
+}
+
+/// The segments registered so far.
+#[derive(Default)]
+pub struct StatusLine {
+    segments: Vec<Segment>,
+}
+
+impl StatusLine {
+    /// Adds a segment at the end of its side, or puts it in the place of the one
+    /// with the same name.
+    pub fn register(&mut self, segment: Segment) {
+        match self
+            .segments
+            .iter_mut()
+            .find(|registered| registered.name == segment.name)
+        {
+            Some(registered) => *registered = segment,
+            None => self.segments.push(segment),
+        }
+    }
+
+    /// A copy of the segments, so they can be asked for their text while the
+    /// editor they belong to is borrowed.
+    pub fn segments(&self) -> Vec<Segment> {
+        self.segments.clone()
+    }
+}
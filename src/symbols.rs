@@ -0,0 +1,174 @@
+//! The symbols of a document, such as functions, types and headings, recognized
+//! with a pattern per file type rather than by parsing, and nested by indentation.
+use std::sync::OnceLock;
+
+use regex::Regex;
+use voider_core::{Document, Row};
+
+static RUST: OnceLock<Option<Regex>> = OnceLock::new();
+static SHELL: OnceLock<Option<Regex>> = OnceLock::new();
+static OTHER: OnceLock<Option<Regex>> = OnceLock::new();
+
+/// How the symbols of a document are recognized.
+pub enum Syntax {
+    /// Headings, nested by their level.
+    Markdown,
+    /// Lines matching a pattern, nested by their indentation.
+    Code(Option<&'static Regex>),
+}
+
+impl Syntax {
+    pub fn of(document: &Document) -> Self {
+        let file_name = document.file_name.as_deref().unwrap_or_default();
+        if file_name.ends_with(".md") || file_name.ends_with(".markdown") {
+            return Self::Markdown;
+        }
+
+        let (pattern, source) = match document.file_type().as_str() {
+            "Rust" => (
+                &RUST,
+                r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default|extern\s+"[^"]*")\s+)*(?:fn|struct|enum|union|trait|impl|mod|macro_rules!)\W"#,
+            ),
+            "Shell" => (&SHELL, r"^\s*(?:function\s+[\w.:-]+|[\w.:-]+\s*\(\s*\))"),
+            _ => (
+                &OTHER,
+                r"^\s*(?:(?:export|public|private|protected|static|abstract|async|pub)\s+)*(?:class|def|function|func|fn|interface|struct|enum|impl|trait|module|namespace)\b",
+            ),
+        };
+        Self::Code(pattern.get_or_init(|| Regex::new(source).ok()).as_ref())
+    }
+
+    /// How deeply a line is nested, or `None` when it's blank and so has no say in
+    /// the nesting.
+    fn depth(&self, line: &str) -> Option<usize> {
+        match self {
+            Self::Markdown => Some(heading_level(line).unwrap_or(usize::MAX)),
+            Self::Code(_) => {
+                let text = line.trim_start();
+                (!text.is_empty()).then(|| line.len().saturating_sub(text.len()))
+            }
+        }
+    }
+
+    fn is_symbol(&self, line: &str) -> bool {
+        match self {
+            Self::Markdown => heading_level(line).is_some(),
+            Self::Code(pattern) => pattern.is_some_and(|pattern| pattern.is_match(line)),
+        }
+    }
+}
+
+/// A line that starts a function, type or heading.
+///
+/// # Fields
+///
+/// - `row`: The index of the line in the document.
+/// - `level`: How many other symbols it's nested in.
+/// - `text`: The line, trimmed.
+pub struct Symbol {
+    pub row: usize,
+    pub level: usize,
+    pub text: String,
+}
+
+/// The symbols of a document, found again only when it changes.
+#[derive(Default)]
+pub struct Outline {
+    seen: Option<(Option<String>, usize)>,
+    symbols: Vec<Symbol>,
+}
+
+impl Outline {
+    /// Brings the symbols up to date with `document`.
+    pub fn update(&mut self, document: &Document) {
+        let seen = (document.file_name.clone(), document.revision());
+        if self.seen.as_ref() != Some(&seen) {
+            self.seen = Some(seen);
+            self.symbols = list(document, &Syntax::of(document));
+        }
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// The index of the last symbol starting at or above `row`.
+    pub fn current(&self, row: usize) -> Option<usize> {
+        self.symbols.iter().rposition(|symbol| symbol.row <= row)
+    }
+}
+
+/// Every symbol of a document, in order.
+pub fn list(document: &Document, syntax: &Syntax) -> Vec<Symbol> {
+    // The depths of the symbols the current line is nested in
+    let mut open: Vec<usize> = Vec::new();
+    let mut symbols = Vec::new();
+
+    for index in 0..document.len() {
+        let Some(line) = document.row(index).map(Row::as_str) else {
+            continue;
+        };
+        let Some(depth) = syntax.depth(line) else {
+            continue;
+        };
+        if matches!(syntax, Syntax::Markdown) && depth == usize::MAX {
+            continue;
+        }
+        while open.last().is_some_and(|open| *open >= depth) {
+            open.pop();
+        }
+
+        if syntax.is_symbol(line) {
+            symbols.push(Symbol {
+                row: index,
+                level: open.len(),
+                text: trim(line).to_string(),
+            });
+            open.push(depth);
+        }
+    }
+
+    symbols
+}
+
+/// The rows of the symbols that `row` is inside, outermost first.
+pub fn enclosing(document: &Document, syntax: &Syntax, row: usize) -> Vec<usize> {
+    let mut limit = document
+        .row(row)
+        .and_then(|line| syntax.depth(line.as_str()))
+        .unwrap_or(usize::MAX);
+    let mut rows = Vec::new();
+
+    for index in (0..row.min(document.len())).rev() {
+        if limit == 0 {
+            break;
+        }
+        let Some(line) = document.row(index).map(Row::as_str) else {
+            continue;
+        };
+        // Only a line nested less deeply than everything since can contain them
+        match syntax.depth(line) {
+            Some(depth) if depth < limit => {
+                if syntax.is_symbol(line) {
+                    rows.push(index);
+                }
+                limit = depth;
+            }
+            _ => {}
+        }
+    }
+
+    rows.reverse();
+    rows
+}
+
+/// A symbol line without its indentation and opening brace, for showing it.
+pub fn trim(line: &str) -> &str {
+    line.trim().trim_end_matches('{').trim_end()
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    ((1..=6).contains(&level) && line.chars().nth(level).is_none_or(char::is_whitespace))
+        .then_some(level)
+}
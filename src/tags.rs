@@ -0,0 +1,125 @@
+//! The symbol index `ctags` writes to a `tags` file, for finding definitions
+//! without a language server.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use voider_core::Document;
+
+/// The name of the index file, looked for in the project root.
+pub const FILE_NAME: &str = "tags";
+
+/// Where a tag's definition is in its file.
+pub enum Address {
+    /// The index of its line.
+    Line(usize),
+    /// The text of its line, as ctags searches for it.
+    Pattern(String),
+}
+
+/// A definition listed in a tags file.
+///
+/// # Fields
+///
+/// - `name`: The name it defines.
+/// - `file`: The file it's in.
+/// - `kind`: What it defines, such as `function` or just `f`, when ctags says.
+/// - `address`: How to find it in the file.
+pub struct Tag {
+    pub name: String,
+    pub file: PathBuf,
+    pub kind: Option<String>,
+    pub address: Address,
+}
+
+impl Tag {
+    /// The row of `document`, the tag's file, where the definition is.
+    pub fn row(&self, document: &Document) -> usize {
+        match &self.address {
+            Address::Line(line) => *line,
+            Address::Pattern(text) => {
+                let rows = || (0..document.len()).filter_map(|index| document.row(index));
+                rows()
+                    .position(|row| row.as_str() == text)
+                    .or_else(|| rows().position(|row| row.as_str().contains(text.as_str())))
+                    .unwrap_or(0)
+            }
+        }
+    }
+}
+
+/// Reads the tags in the tags file of `root`, with their files relative to it.
+///
+/// # Errors
+///
+/// Returns a description of the problem when the file can't be read.
+pub fn read(root: &Path) -> Result<Vec<Tag>, String> {
+    let path = root.join(FILE_NAME);
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(text
+        .lines()
+        .filter(|line| !line.starts_with("!_TAG_"))
+        .filter_map(|line| parse(line, root))
+        .collect())
+}
+
+/// Parses a line like `name<TAB>file<TAB>/^fn name() {$/;"<TAB>f`, where the
+/// address may also be a line number and the fields after `;"` are optional.
+fn parse(line: &str, root: &Path) -> Option<Tag> {
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?.to_string();
+    let file = root.join(fields.next()?);
+    let rest = fields.next()?;
+    let (address, extensions) = rest.split_once(";\"").unwrap_or((rest, ""));
+
+    let address = match address.trim().parse::<usize>() {
+        Ok(line) => Address::Line(line.saturating_sub(1)),
+        Err(_) => Address::Pattern(pattern(address.trim())?),
+    };
+    // The kind is the one extension field without a name, or the one named `kind`
+    let kind = extensions
+        .split('\t')
+        .map(str::trim)
+        .find_map(|field| match field.split_once(':') {
+            Some(("kind", kind)) => Some(kind),
+            Some(_) => None,
+            None => Some(field).filter(|field| !field.is_empty()),
+        })
+        .map(str::to_string);
+
+    Some(Tag {
+        name,
+        file,
+        kind,
+        address,
+    })
+}
+
+/// The text of a search address such as `/^fn main() {$/`, without its delimiters,
+/// anchors and escapes.
+fn pattern(address: &str) -> Option<String> {
+    let delimiter = address.chars().next().filter(|c| *c == '/' || *c == '?')?;
+    let body = address
+        .get(1..)?
+        .strip_suffix(delimiter)
+        .unwrap_or(address.get(1..)?);
+    let body = body.strip_prefix('^').unwrap_or(body);
+    let body = match body.strip_suffix('$') {
+        Some(body) if !body.ends_with('\\') => body,
+        _ => body,
+    };
+
+    let mut text = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next)) if next == delimiter || next == '\\' || next == '$' => {
+                text.push(next);
+                chars.next();
+            }
+            _ => text.push(c),
+        }
+    }
+    Some(text)
+}
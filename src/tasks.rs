@@ -0,0 +1,253 @@
+//! Work that runs off the UI thread, such as shell commands. Each task runs on a
+//! thread of its own and sends its result back as an `Event`, which the main loop
+//! picks up between keys instead of waiting for it.
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde_json::Value;
+use voider_core::command::Job;
+
+use crate::{
+    diff::Hunk,
+    git::{Repository, Staged},
+    hooks::{self, HookOutput},
+    lsp::Request,
+    watcher::Change,
+};
+
+/// What a task reports back to the main loop.
+pub enum Event {
+    /// The on-save hooks of `file_name` ran, with each command and how it went.
+    Hooks {
+        file_name: String,
+        timeout: Duration,
+        runs: Vec<(String, Result<HookOutput, io::Error>)>,
+    },
+    /// A project command printed a line.
+    Output { job: Job, line: String },
+    /// A commit was made, with the summary git printed, or failed.
+    Committed { result: Result<String, String> },
+    /// A project command exited, or couldn't be started.
+    Finished {
+        job: Job,
+        status: Result<ExitStatus, io::Error>,
+    },
+    /// The REPL printed a line.
+    ReplOutput { line: String },
+    /// The REPL closed its output, usually because it exited.
+    ReplClosed,
+    /// Another program changed a file in the directory of an open one.
+    Watched(Change),
+    /// The staged version of `file_name` was read, or git turned out not to track it.
+    Staged {
+        file_name: String,
+        staged: Option<(Repository, Staged)>,
+    },
+    /// A language server answered a request, or it failed.
+    Answered {
+        request: Request,
+        result: Result<Value, String>,
+    },
+    /// The rows of `file_name` at `revision` were compared with its staged version.
+    Diffed {
+        file_name: String,
+        revision: usize,
+        hunks: Vec<Hunk>,
+    },
+}
+
+/// The tasks started by the editor and the channel their results arrive on.
+pub struct Tasks {
+    sender: Sender<Event>,
+    receiver: Receiver<Event>,
+    running: Vec<JoinHandle<()>>,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            running: Vec::new(),
+        }
+    }
+}
+
+impl Tasks {
+    /// Runs `work` on a background thread and delivers its event to `poll`.
+    pub fn spawn(&mut self, work: impl FnOnce() -> Event + Send + 'static) {
+        self.spawn_streaming(move |sender| {
+            sender.send(work()).ok();
+        });
+    }
+
+    /// Runs `work` on a background thread, delivering every event it sends along the
+    /// way rather than a single one at the end.
+    pub fn spawn_streaming(&mut self, work: impl FnOnce(&Sender<Event>) + Send + 'static) {
+        let sender = self.sender.clone();
+        self.running.push(thread::spawn(move || work(&sender)));
+    }
+
+    /// Takes the events the tasks sent since the last call.
+    pub fn poll(&mut self) -> Vec<Event> {
+        // A task that panicked never sends an event, so finished threads are counted
+        // separately from the events
+        self.running.retain(|task| !task.is_finished());
+        self.receiver.try_iter().collect()
+    }
+
+    /// A sender for events that come from elsewhere than a task, such as the file
+    /// watcher.
+    pub fn sender(&self) -> Sender<Event> {
+        self.sender.clone()
+    }
+
+    /// Whether a task is still running, so the main loop should check back soon.
+    pub fn is_busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+}
+
+/// How often a project command that closed its output is checked for having exited.
+const EXIT_POLL: Duration = Duration::from_millis(10);
+
+/// A project command started by `run_job`, kept so it can be stopped. Dropping it
+/// kills the command if it's still running, so none outlives the editor.
+pub struct RunningJob {
+    pub job: Job,
+    child: Arc<Mutex<Child>>,
+}
+
+impl RunningJob {
+    /// Kills the command along with the processes it started. Its task still
+    /// reports how it exited.
+    pub fn stop(&self) {
+        if let Ok(mut child) = self.child.lock() {
+            // Only while it wasn't waited for, so its id can't be another process's yet
+            if matches!(child.try_wait(), Ok(None)) {
+                kill_tree(child.id());
+            }
+            child.kill().ok();
+        }
+    }
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts a project command in `dir`, with a task of `tasks` sending what it prints
+/// line by line as it goes, with stdout and stderr interleaved, and then how it exited.
+///
+/// # Errors
+///
+/// Returns the error of starting the command.
+pub fn run_job(
+    job: Job,
+    command: &str,
+    dir: &Path,
+    tasks: &mut Tasks,
+) -> Result<RunningJob, io::Error> {
+    let mut shell = hooks::shell(command);
+    // A group of its own, so what the shell starts can be killed with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+    let mut child = shell
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let child = Arc::new(Mutex::new(child));
+
+    let waited = Arc::clone(&child);
+    tasks.spawn_streaming(move |sender| {
+        let output = move |line| Event::Output { job, line };
+        let stderr = stderr.map(|pipe| {
+            let sender = sender.clone();
+            thread::spawn(move || send_lines(pipe, &sender, output))
+        });
+        if let Some(stdout) = stdout {
+            send_lines(stdout, sender, output);
+        }
+        if let Some(reader) = stderr {
+            reader.join().ok();
+        }
+        let status = wait(&waited);
+        sender.send(Event::Finished { job, status }).ok();
+    });
+    Ok(RunningJob { job, child })
+}
+
+/// Kills the process `id` and the ones it started, which would otherwise keep
+/// running, and keep its output open, after the shell of a command is gone.
+fn kill_tree(id: u32) {
+    let mut kill = if cfg!(windows) {
+        let mut kill = Command::new("taskkill");
+        kill.args(["/T", "/F", "/PID", &id.to_string()]);
+        kill
+    } else {
+        let mut kill = Command::new("kill");
+        kill.args(["-KILL", "--", &format!("-{id}")]);
+        kill
+    };
+    kill.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+}
+
+/// Waits for `child` to exit without holding on to it, so it can still be killed.
+fn wait(child: &Mutex<Child>) -> Result<ExitStatus, io::Error> {
+    loop {
+        let Ok(mut child) = child.lock() else {
+            return Err(io::Error::other("lost the command's process"));
+        };
+        let status = child.try_wait()?;
+        drop(child);
+        if let Some(status) = status {
+            return Ok(status);
+        }
+        thread::sleep(EXIT_POLL);
+    }
+}
+
+/// Sends what a REPL prints line by line as it goes, with stdout and stderr
+/// interleaved, and then that it closed them.
+pub fn stream_repl(
+    stdout: impl Read + Send + 'static,
+    stderr: impl Read + Send + 'static,
+    sender: &Sender<Event>,
+) {
+    let output = |line| Event::ReplOutput { line };
+    let stderr = {
+        let sender = sender.clone();
+        thread::spawn(move || send_lines(stderr, &sender, output))
+    };
+    send_lines(stdout, sender, output);
+    stderr.join().ok();
+    sender.send(Event::ReplClosed).ok();
+}
+
+fn send_lines(pipe: impl Read, sender: &Sender<Event>, event: impl Fn(String) -> Event) {
+    for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+        let line = String::from_utf8_lossy(&line);
+        let line = line.strip_suffix('\r').unwrap_or(&line).to_string();
+        if sender.send(event(line)).is_err() {
+            break;
+        }
+    }
+}
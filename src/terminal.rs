@@ -1,18 +1,157 @@
-use std::io::{stdout, Write};
+use std::{
+    cell::RefCell,
+    env,
+    io::{stdout, Write},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
 use crossterm::{
-    cursor::{self, MoveTo},
-    event::{read, Event, KeyCode, KeyEventKind},
-    execute,
-    style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
-    terminal::{self, Clear, ClearType},
+    cursor::{self, MoveTo, SetCursorStyle},
+    event::{
+        poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
+        KeyEventKind, MouseEventKind,
+    },
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate, SetTitle},
+};
+
+#[cfg(feature = "headless")]
+use crate::headless;
+use crate::{
+    backend::{Backend, Input, Size},
+    clipboard, signals, Position,
 };
 
-use crate::Position;
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorSupport {
+    /// Guesses the color support from `COLORTERM` and `TERM`, since terminals have
+    /// no portable way to be asked.
+    fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return Self::TrueColor;
+        }
+
+        let term = env::var("TERM").unwrap_or_default().to_lowercase();
+        if term.contains("truecolor") || term.contains("direct") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else if cfg!(windows) && term.is_empty() {
+            // Windows Terminal and conhost don't set TERM, and both handle RGB
+            Self::TrueColor
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Converts a color to the closest one this level of support can show.
+    pub fn adapt(self, color: Color) -> Color {
+        let Color::Rgb { r, g, b } = color else {
+            return color;
+        };
+
+        match self {
+            Self::TrueColor => color,
+            Self::Ansi256 => Color::AnsiValue(ansi256(r, g, b)),
+            Self::Ansi16 => ansi16(r, g, b),
+        }
+    }
+}
+
+static COLOR_SUPPORT: OnceLock<ColorSupport> = OnceLock::new();
+
+thread_local! {
+    /// Everything queued since the last flush, written out in one go.
+    static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The squared distance between two colors, good enough to pick the nearest one.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [(r1, r2), (g1, g2), (b1, b2)]
+        .iter()
+        .map(|(a, b)| u32::from(a.abs_diff(*b)).pow(2))
+        .sum()
+}
+
+/// Picks the nearest color of the 6x6x6 cube or the gray ramp of the 256-color palette.
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |value: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|index| {
+                LEVELS
+                    .get(*index)
+                    .map_or(u8::MAX, |level| level.abs_diff(value))
+            })
+            .unwrap_or(0)
+    };
 
-pub struct Size {
-    pub width: u16,
-    pub height: u16,
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube_color = |index: usize| LEVELS.get(index).copied().unwrap_or(0);
+    let cube = (cube_color(ri), cube_color(gi), cube_color(bi));
+    let cube_index = ri
+        .saturating_mul(36)
+        .saturating_add(gi.saturating_mul(6))
+        .saturating_add(bi)
+        .saturating_add(16);
+    let cube_index = u8::try_from(cube_index).unwrap_or(u8::MAX);
+
+    // The gray ramp goes from 8 to 238 in steps of 10
+    let average = u16::from(r)
+        .saturating_add(u16::from(g))
+        .saturating_add(u16::from(b))
+        .checked_div(3)
+        .unwrap_or_default();
+    let gray_step = average
+        .saturating_sub(3)
+        .checked_div(10)
+        .unwrap_or_default()
+        .min(23);
+    let gray_step = u8::try_from(gray_step).unwrap_or(23);
+    let gray = 8_u8.saturating_add(gray_step.saturating_mul(10));
+
+    if distance((r, g, b), (gray, gray, gray)) < distance((r, g, b), cube) {
+        232_u8.saturating_add(gray_step)
+    } else {
+        cube_index
+    }
+}
+
+/// Picks the nearest of the 16 basic terminal colors.
+fn ansi16(r: u8, g: u8, b: u8) -> Color {
+    const PALETTE: [(Color, (u8, u8, u8)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::DarkRed, (128, 0, 0)),
+        (Color::DarkGreen, (0, 128, 0)),
+        (Color::DarkYellow, (128, 128, 0)),
+        (Color::DarkBlue, (0, 0, 128)),
+        (Color::DarkMagenta, (128, 0, 128)),
+        (Color::DarkCyan, (0, 128, 128)),
+        (Color::Grey, (192, 192, 192)),
+        (Color::DarkGrey, (128, 128, 128)),
+        (Color::Red, (255, 0, 0)),
+        (Color::Green, (0, 255, 0)),
+        (Color::Yellow, (255, 255, 0)),
+        (Color::Blue, (0, 0, 255)),
+        (Color::Magenta, (255, 0, 255)),
+        (Color::Cyan, (0, 255, 255)),
+        (Color::White, (255, 255, 255)),
+    ];
+
+    PALETTE
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map_or(Color::Reset, |(color, _)| *color)
 }
 
 pub struct Terminal {
@@ -20,85 +159,262 @@ pub struct Terminal {
 }
 
 impl Terminal {
-    pub fn default() -> Result<Self, std::io::Error> {
+    /// Sets up the terminal for the editor: raw mode, mouse capture and the size.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the terminal when its size can't be read, such as when
+    /// the editor isn't run in one.
+    pub fn new() -> Result<Self, std::io::Error> {
+        #[cfg(feature = "headless")]
+        if let Some((width, height)) = headless::size() {
+            let mut terminal = Self {
+                size: Size {
+                    width: 0,
+                    height: 0,
+                },
+            };
+            terminal.resize(width, height);
+            return Ok(terminal);
+        }
+
         let size = terminal::size()?;
         terminal::enable_raw_mode().ok();
-        Ok(Self {
+        Self::save_title();
+        Self::queue_action(EnableMouseCapture);
+        let mut terminal = Self {
             size: Size {
-                width: size.0,
-                height: size.1.saturating_sub(2),
+                width: 0,
+                height: 0,
             },
-        })
+        };
+        terminal.resize(size.0, size.1);
+        Ok(terminal)
+    }
+
+    /// Whether a key event should be handled. Windows reports releases as well as
+    /// presses, and held keys repeat as presses or, with some terminals, as repeats.
+    fn is_key_press(event: &KeyEvent) -> bool {
+        matches!(event.kind, KeyEventKind::Press | KeyEventKind::Repeat)
     }
 
+    /// Writes everything queued so far to the terminal.
+    fn flush() -> Result<(), std::io::Error> {
+        let output = OUTPUT.with_borrow_mut(std::mem::take);
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            headless::draw(&output);
+            return Ok(());
+        }
+        let mut stdout = stdout().lock();
+        stdout.write_all(&output)?;
+        stdout.flush()
+    }
+
+    /// The color support of the terminal, detected the first time it's needed.
+    pub fn color_support() -> ColorSupport {
+        *COLOR_SUPPORT.get_or_init(ColorSupport::detect)
+    }
+
+    /// Converts a color to the closest one the terminal can show.
     #[must_use]
-    pub fn size(&self) -> &Size {
+    pub fn color(color: Color) -> Color {
+        Self::color_support().adapt(color)
+    }
+
+    /// Queues a command to be written with the rest of the frame, on the next
+    /// `flush`, so drawing a frame takes a single write instead of one per command.
+    fn queue_action(action: impl crossterm::Command) {
+        if let Err(err) = OUTPUT.with_borrow_mut(|output| queue!(output, action)) {
+            eprintln!("Error al ejecutar la acción: {err}");
+        }
+    }
+
+    /// Pushes the current window title on the terminal's title stack. Terminals
+    /// without one simply ignore it.
+    fn save_title() {
+        Self::queue_action(Print("\x1b[22;0t"));
+    }
+
+    /// Brings back the title saved when the editor started.
+    pub fn restore_title() {
+        Self::queue_action(Print("\x1b[23;0t"));
+        Self::flush().ok();
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&self) -> &Size {
         &self.size
     }
 
-    pub fn flush() -> Result<(), std::io::Error> {
-        stdout().flush()
+    /// Updates the size after the terminal was resized, keeping two lines for the
+    /// status and message bars.
+    fn resize(&mut self, width: u16, height: u16) {
+        self.size = Size {
+            width,
+            height: height.saturating_sub(2),
+        };
     }
 
-    #[must_use]
-    pub fn read_key() -> KeyCode {
+    /// Waits for a key press. Returns Esc when the editor is asked to terminate, so
+    /// an open prompt gets out of the way of the emergency save.
+    fn read_key(&mut self) -> KeyEvent {
+        // A prompt still open when the script runs out is cancelled
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            return headless::next_key().unwrap_or_else(|| KeyEvent::from(KeyCode::Esc));
+        }
+
         loop {
+            if signals::terminating() {
+                return KeyEvent::from(KeyCode::Esc);
+            }
+            if !poll(Duration::from_millis(100)).unwrap_or(false) {
+                continue;
+            }
+
             match read() {
-                Ok(Event::Key(event)) => {
-                    //This is to make sure that crossterm will only read when the key is pressed
-                    if let KeyEventKind::Press = event.kind {
-                        return event.code;
-                    }
+                //This is to make sure that crossterm will only read when the key is pressed
+                Ok(Event::Key(event)) if Terminal::is_key_press(&event) => return event,
+                // A terminal that can't be read is gone, as when it hung up
+                Err(_) => {
+                    signals::terminate();
+                    return KeyEvent::from(KeyCode::Esc);
                 }
-                Err(err) => panic!("{err:?}"),
                 _ => (),
             }
         }
     }
 
-    fn execute_action(action: impl crossterm::Command) {
-        if let Err(err) = execute!(std::io::stdout(), action) {
-            eprintln!("Error al ejecutar la acción: {}", err);
+    /// Waits up to `timeout` for a key press. Returns Esc when the editor is asked to
+    /// terminate, like `read_key`.
+    fn read_key_within(&mut self, timeout: Duration) -> Option<KeyEvent> {
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            return Some(self.read_key());
+        }
+
+        match self.poll_input(timeout) {
+            Some(Input::Key(event)) => Some(event),
+            _ if signals::terminating() => Some(KeyEvent::from(KeyCode::Esc)),
+            _ => None,
+        }
+    }
+
+    /// Waits up to `timeout` for a key press, a mouse event or a resize, returning
+    /// `None` when none happened in time or the editor is asked to terminate. When
+    /// the terminal can't be read anymore, the editor is asked to.
+    fn poll_input(&mut self, timeout: Duration) -> Option<Input> {
+        #[cfg(feature = "headless")]
+        if headless::is_active() {
+            return headless::next_key().map(Input::Key);
+        }
+
+        let start = Instant::now();
+        loop {
+            // The terminal may have hung up, so it isn't read from again
+            if signals::terminating() {
+                return None;
+            }
+            let remaining = timeout.saturating_sub(start.elapsed());
+            match poll(remaining) {
+                Ok(true) => match read() {
+                    Ok(Event::Key(event)) if Terminal::is_key_press(&event) => {
+                        return Some(Input::Key(event))
+                    }
+                    Ok(Event::Resize(width, height)) => return Some(Input::Resize(width, height)),
+                    // Moving the mouse without a button held reports nothing worth waking for
+                    Ok(Event::Mouse(event)) if event.kind != MouseEventKind::Moved => {
+                        return Some(Input::Mouse(event))
+                    }
+                    // A terminal that can't be read is gone, as when it hung up
+                    Err(_) => {
+                        signals::terminate();
+                        return None;
+                    }
+                    _ => (),
+                },
+                Ok(false) => return None,
+                Err(_) => {
+                    signals::terminate();
+                    return None;
+                }
+            }
         }
     }
 
-    pub fn clear_screen() {
-        Self::execute_action(Clear(ClearType::All));
+    /// Asks the terminal to hold off showing what's drawn until `end_frame`, so a
+    /// frame appears all at once. Terminals that don't support it ignore the request.
+    fn begin_frame(&mut self) {
+        Self::queue_action(BeginSynchronizedUpdate);
+    }
+
+    fn end_frame(&mut self) -> Result<(), std::io::Error> {
+        Self::queue_action(EndSynchronizedUpdate);
+        Self::flush()
     }
 
-    pub fn cursor_position(position: &Position) {
+    fn move_cursor(&mut self, position: &Position) {
         let Position { x, y } = position;
-        let x = *x as u16;
-        let y = *y as u16;
+        let x = u16::try_from(*x).unwrap_or(u16::MAX);
+        let y = u16::try_from(*y).unwrap_or(u16::MAX);
+
+        Self::queue_action(MoveTo(x, y));
+    }
+
+    fn show_cursor(&mut self) {
+        Self::queue_action(cursor::Show);
+    }
+
+    fn hide_cursor(&mut self) {
+        Self::queue_action(cursor::Hide);
+    }
+
+    fn set_cursor_style(&mut self, style: SetCursorStyle) {
+        Self::queue_action(style);
+    }
+
+    fn clear_screen(&mut self) {
+        Self::queue_action(Clear(ClearType::All));
+    }
+
+    fn clear_line(&mut self) {
+        Self::queue_action(Clear(ClearType::CurrentLine));
+    }
 
-        Self::execute_action(MoveTo(x, y));
+    fn print(&mut self, text: &str) {
+        Self::queue_action(Print(text));
     }
 
-    pub fn cursor_hide() {
-        Self::execute_action(cursor::Hide);
+    fn set_fg_color(&mut self, color: Color) {
+        Self::queue_action(SetForegroundColor(Self::color(color)));
     }
 
-    pub fn cursor_show() {
-        Self::execute_action(cursor::Show);
+    fn set_bg_color(&mut self, color: Color) {
+        Self::queue_action(SetBackgroundColor(Self::color(color)));
     }
 
-    pub fn clear_current_line() {
-        Self::execute_action(Clear(ClearType::CurrentLine));
+    fn reset_colors(&mut self) {
+        Self::queue_action(ResetColor);
     }
 
-    pub fn set_bg_color(color: Color) {
-        Self::execute_action(SetBackgroundColor(color));
+    fn set_title(&mut self, title: &str) {
+        Self::queue_action(SetTitle(title));
     }
 
-    pub fn reset_bg_color() {
-        Self::execute_action(ResetColor);
+    fn set_clipboard(&mut self, text: &str) {
+        clipboard::copy(text);
     }
 
-    pub fn set_fg_color(color: Color) {
-        Self::execute_action(SetForegroundColor(color));
+    fn clipboard(&mut self) -> Option<String> {
+        clipboard::paste()
     }
 
-    pub fn reset_fg_color() {
-        Self::execute_action(ResetColor);
+    fn restore(&mut self) {
+        Self::queue_action(SetCursorStyle::DefaultUserShape);
+        Self::queue_action(DisableMouseCapture);
+        Self::restore_title();
+        terminal::disable_raw_mode().ok();
     }
 }
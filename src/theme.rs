@@ -0,0 +1,420 @@
+use crossterm::style::Color;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use voider_core::highlighting::Type;
+
+use crate::config::Config;
+
+/// The colors of every part of the screen, both UI elements and highlighting types.
+///
+/// A base16 scheme can be picked with `theme` in the `[editor]` config table, and
+/// single colors overridden from the `[theme]` table, where each key is one of the
+/// names accepted by `Theme::set` and each value is a color such as `"#268bd2"` or
+/// `"dark_grey"`.
+#[derive(Clone)]
+pub struct Theme {
+    pub status_bar_fg: Color,
+    pub status_bar_bg: Color,
+    pub message_bar_fg: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub tilde: Color,
+    pub welcome: Color,
+    pub line_number: Color,
+    pub modified: Color,
+    pub added: Color,
+    pub removed: Color,
+    pub conflict: Color,
+    pub selection_bg: Color,
+    pub control: Color,
+    pub text: Color,
+    pub number: Color,
+    pub search_match: Color,
+    pub string: Color,
+    pub character: Color,
+    pub comment: Color,
+    pub variable: Color,
+    pub lifetime: Color,
+    pub attribute: Color,
+    pub primary_keywords: Color,
+    pub secondary_keywords: Color,
+    pub type_name: Color,
+    pub function: Color,
+    pub macro_call: Color,
+    pub parameter: Color,
+    pub local: Color,
+    pub url: Color,
+}
+
+impl Default for Theme {
+    #[allow(clippy::too_many_lines)]
+    fn default() -> Self {
+        Self {
+            status_bar_fg: Color::Rgb {
+                r: 239,
+                g: 239,
+                b: 239,
+            },
+            status_bar_bg: Color::Rgb {
+                r: 63,
+                g: 63,
+                b: 63,
+            },
+            message_bar_fg: Color::Reset,
+            warning: Color::Rgb {
+                r: 181,
+                g: 137,
+                b: 0,
+            },
+            error: Color::Rgb {
+                r: 220,
+                g: 50,
+                b: 47,
+            },
+            tilde: Color::Reset,
+            welcome: Color::Reset,
+            line_number: Color::Rgb {
+                r: 101,
+                g: 123,
+                b: 131,
+            },
+            modified: Color::Rgb {
+                r: 38,
+                g: 139,
+                b: 210,
+            },
+            added: Color::Rgb {
+                r: 133,
+                g: 153,
+                b: 0,
+            },
+            removed: Color::Rgb {
+                r: 220,
+                g: 50,
+                b: 47,
+            },
+            conflict: Color::Rgb {
+                r: 211,
+                g: 54,
+                b: 130,
+            },
+            selection_bg: Color::Rgb { r: 7, g: 54, b: 66 },
+            control: Color::Rgb {
+                r: 220,
+                g: 50,
+                b: 47,
+            },
+            text: Color::Rgb {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            number: Color::Rgb {
+                r: 180,
+                g: 126,
+                b: 141,
+            },
+            search_match: Color::Rgb {
+                r: 38,
+                g: 139,
+                b: 210,
+            },
+            string: Color::Rgb {
+                r: 205,
+                g: 92,
+                b: 8,
+            },
+            character: Color::Rgb {
+                r: 108,
+                g: 113,
+                b: 196,
+            },
+            comment: Color::Rgb {
+                r: 245,
+                g: 232,
+                b: 183,
+            },
+            variable: Color::Rgb {
+                r: 42,
+                g: 161,
+                b: 152,
+            },
+            lifetime: Color::Rgb {
+                r: 181,
+                g: 137,
+                b: 0,
+            },
+            attribute: Color::Rgb {
+                r: 133,
+                g: 153,
+                b: 0,
+            },
+            primary_keywords: Color::Red,
+            secondary_keywords: Color::Rgb {
+                r: 255,
+                g: 161,
+                b: 152,
+            },
+            type_name: Color::Rgb {
+                r: 181,
+                g: 137,
+                b: 0,
+            },
+            function: Color::Rgb {
+                r: 38,
+                g: 139,
+                b: 210,
+            },
+            macro_call: Color::Rgb {
+                r: 211,
+                g: 54,
+                b: 130,
+            },
+            parameter: Color::Rgb {
+                r: 203,
+                g: 75,
+                b: 22,
+            },
+            local: Color::Rgb {
+                r: 147,
+                g: 161,
+                b: 161,
+            },
+            url: Color::Rgb {
+                r: 38,
+                g: 139,
+                b: 210,
+            },
+        }
+    }
+}
+
+impl Theme {
+    /// Builds the theme from the scheme named in the config, or the defaults, with
+    /// the colors of the `[theme]` table on top.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of a scheme that can't be loaded, or of every unknown
+    /// element and invalid color.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut theme = match &config.editor.theme {
+            Some(name) => Self::load_base16(name)?,
+            None => Self::default(),
+        };
+        let mut errors = Vec::new();
+
+        for (name, value) in &config.theme {
+            match parse_color(value) {
+                Some(color) => {
+                    if !theme.set(name, color) {
+                        errors.push(format!("unknown theme element {name}"));
+                    }
+                }
+                None => errors.push(format!("invalid color {value} for {name}")),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(theme)
+        } else {
+            errors.sort();
+            Err(errors.join(", "))
+        }
+    }
+
+    /// Loads a base16 scheme from `themes/<name>.yaml` in the config directory, or
+    /// from `name` itself when it's a path.
+    fn load_base16(name: &str) -> Result<Self, String> {
+        let path = if name.contains('/') || name.ends_with(".yaml") || name.ends_with(".yml") {
+            PathBuf::from(name)
+        } else {
+            Config::dir()
+                .ok_or_else(|| format!("no config directory to find theme {name} in"))?
+                .join("themes")
+                .join(format!("{name}.yaml"))
+        };
+
+        let contents =
+            fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Self::from_base16(&contents).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Maps the 16 colors of a base16 scheme onto the theme, following the base16
+    /// styling guidelines. Both the classic flat format and the newer one with a
+    /// `palette` table are read, since only the `baseXX` keys matter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when one of the 16 colors is missing or invalid.
+    pub fn from_base16(contents: &str) -> Result<Self, String> {
+        let mut palette: HashMap<String, Color> = HashMap::new();
+
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().trim_matches(['"', '\'']).to_lowercase();
+            if !key.starts_with("base") || key.len() != 6 {
+                continue;
+            }
+
+            // Values look like "181818", '#181818' or 181818 # a comment
+            let value = value.split(" #").next().unwrap_or_default();
+            let value = value.trim().trim_matches(['"', '\'']);
+            let hex = value.strip_prefix('#').unwrap_or(value);
+            let color = parse_color(&format!("#{hex}"))
+                .ok_or_else(|| format!("invalid color {value} for {key}"))?;
+            palette.insert(key, color);
+        }
+
+        let base = |index: u8| {
+            palette
+                .get(&format!("base{index:02X}").to_lowercase())
+                .copied()
+                .ok_or_else(|| format!("missing base{index:02X}"))
+        };
+
+        Ok(Self {
+            status_bar_fg: base(0x04)?,
+            status_bar_bg: base(0x01)?,
+            message_bar_fg: base(0x05)?,
+            warning: base(0x0A)?,
+            error: base(0x08)?,
+            tilde: base(0x03)?,
+            welcome: base(0x04)?,
+            line_number: base(0x03)?,
+            modified: base(0x0D)?,
+            added: base(0x0B)?,
+            removed: base(0x08)?,
+            conflict: base(0x0E)?,
+            selection_bg: base(0x02)?,
+            control: base(0x08)?,
+            text: base(0x05)?,
+            number: base(0x09)?,
+            search_match: base(0x0A)?,
+            string: base(0x0B)?,
+            character: base(0x0C)?,
+            comment: base(0x03)?,
+            variable: base(0x08)?,
+            lifetime: base(0x0F)?,
+            attribute: base(0x0D)?,
+            primary_keywords: base(0x0E)?,
+            secondary_keywords: base(0x0A)?,
+            type_name: base(0x0A)?,
+            function: base(0x0D)?,
+            macro_call: base(0x0F)?,
+            parameter: base(0x0C)?,
+            local: base(0x08)?,
+            url: base(0x0D)?,
+        })
+    }
+
+    /// Changes the color of an element by name, returning whether the name is known.
+    pub fn set(&mut self, name: &str, color: Color) -> bool {
+        let slot = match name {
+            "status_bar_fg" => &mut self.status_bar_fg,
+            "status_bar_bg" => &mut self.status_bar_bg,
+            "message_bar_fg" => &mut self.message_bar_fg,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            "tilde" => &mut self.tilde,
+            "welcome" => &mut self.welcome,
+            "line_number" => &mut self.line_number,
+            "modified" => &mut self.modified,
+            "added" => &mut self.added,
+            "removed" => &mut self.removed,
+            "conflict" => &mut self.conflict,
+            "selection_bg" => &mut self.selection_bg,
+            "control" => &mut self.control,
+            "text" => &mut self.text,
+            "number" => &mut self.number,
+            "search_match" => &mut self.search_match,
+            "string" => &mut self.string,
+            "character" => &mut self.character,
+            "comment" => &mut self.comment,
+            "variable" => &mut self.variable,
+            "lifetime" => &mut self.lifetime,
+            "attribute" => &mut self.attribute,
+            "primary_keywords" => &mut self.primary_keywords,
+            "secondary_keywords" => &mut self.secondary_keywords,
+            "type_name" => &mut self.type_name,
+            "function" => &mut self.function,
+            "macro_call" => &mut self.macro_call,
+            "parameter" => &mut self.parameter,
+            "local" => &mut self.local,
+            "url" => &mut self.url,
+            _ => return false,
+        };
+
+        *slot = color;
+        true
+    }
+
+    /// The color text of a highlighting type is drawn with.
+    #[must_use]
+    pub fn highlight(&self, highlighting_type: Type) -> Color {
+        match highlighting_type {
+            Type::None => self.text,
+            Type::Number => self.number,
+            Type::Match => self.search_match,
+            Type::String => self.string,
+            Type::Character => self.character,
+            Type::Comment | Type::MultilineComment => self.comment,
+            Type::Variable => self.variable,
+            Type::Lifetime => self.lifetime,
+            Type::Attribute => self.attribute,
+            Type::PrimaryKeywords => self.primary_keywords,
+            Type::SecondaryKeywords => self.secondary_keywords,
+            Type::TypeName => self.type_name,
+            Type::Function => self.function,
+            Type::Macro => self.macro_call,
+            Type::Parameter => self.parameter,
+            Type::Local => self.local,
+            Type::Url => self.url,
+        }
+    }
+}
+
+/// The `#rrggbb` colors written in `text`, in order. Longer runs of hex digits, as in
+/// `#rrggbbaa`, don't count.
+pub fn hex_colors(text: &str) -> Vec<Color> {
+    let bytes = text.as_bytes();
+    text.match_indices('#')
+        .filter_map(|(index, _)| {
+            let end = index.saturating_add(7);
+            let followed = bytes.get(end).is_some_and(u8::is_ascii_alphanumeric);
+            let digits = text.get(index.saturating_add(1)..end)?;
+            if followed || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return None;
+            }
+            parse_color(text.get(index..end)?)
+        })
+        .collect()
+}
+
+/// Parses `#rrggbb` colors and the color names crossterm knows, like `dark_grey`.
+pub fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |index: usize| {
+            hex.get(index..index.saturating_add(2))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    if text.eq_ignore_ascii_case("reset") || text.eq_ignore_ascii_case("default") {
+        return Some(Color::Reset);
+    }
+
+    Color::try_from(text).ok()
+}
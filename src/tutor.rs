@@ -0,0 +1,84 @@
+use crate::config::KeysConfig;
+
+/// Builds the practice document opened by `--tutor`, naming the keys as they are
+/// currently bound so the lessons stay right after remapping.
+pub fn text(keys: &KeysConfig) -> String {
+    format!(
+        "\
+Welcome to the Voider tutor
+===========================
+
+This is a scratch copy, so practice freely: nothing here touches a file until
+you save it somewhere yourself.
+
+
+Lesson 1: moving around
+-----------------------
+
+Use the arrow keys to move the cursor one character or one line at a time.
+Home and End jump to the start and end of a line, PageUp and PageDown move a
+whole screen at once.
+
+    Move the cursor onto the X at the end of this line ->  X
+
+Now go down to the next lesson with PageDown.
+
+
+Lesson 2: editing
+-----------------
+
+Type to insert text at the cursor. Backspace deletes the character before the
+cursor and Delete the one under it. Insert switches to overwrite mode, where
+typing replaces characters instead; the status bar shows OVR while it's on.
+
+    Fix this line so it reads the same as the one below it:
+    Th quick brwn fox jumpps over the lazy dog.
+    The quick brown fox jumps over the lazy dog.
+
+
+Lesson 3: selecting, copying and pasting
+----------------------------------------
+
+Hold Shift while moving to select text. With a selection:
+
+    {copy:<12} copies it
+    {cut:<12} cuts it
+    {paste:<12} pastes at the cursor
+    {select_all:<12} selects the whole document
+
+    Copy this line and paste it right below.
+
+
+Lesson 4: searching
+-------------------
+
+Press F3 and type to search; the cursor jumps to the first match as you type.
+Tab moves to the next match and Shift+Tab to the previous one. Enter keeps the
+cursor where it is, Esc brings it back to where the search started. Up and
+Down recall earlier searches.
+
+    Search for the word needle. It hides here once: haystack needle haystack.
+
+{replace} replaces every match of a regular expression, and the replacement
+can use the groups it captured as $1, $2 and so on.
+
+
+Lesson 5: saving and quitting
+-----------------------------
+
+F5 saves. This tutor has no file name yet, so you'll be asked for one; Tab
+completes file names while you type it.
+
+F8 quits. With unsaved changes you'll be asked whether to save them first:
+y saves, n throws them away and Esc keeps you in the editor.
+
+
+That's all you need to get going. F1 shows every key at any time.
+",
+        copy = keys.copy,
+        cut = keys.cut,
+        paste = keys.paste,
+        select_all = keys.select_all,
+        replace = keys.replace,
+    )
+}
@@ -0,0 +1,100 @@
+//! Watches the directories of the open files, so files created, changed, removed
+//! or renamed by other programs are noticed as it happens instead of on the next
+//! save. What the watcher sees arrives in the main loop as an `Event::Watched`.
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode,
+};
+
+use crate::tasks::Event;
+
+/// Something that happened to a file in a watched directory, by absolute path.
+pub enum Change {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// The directories being watched, none when the system offers no way to watch them.
+pub struct Watcher {
+    inner: Option<RecommendedWatcher>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Watcher {
+    /// Starts a watcher that sends the changes it sees to `sender`.
+    pub fn new(sender: Sender<Event>) -> Self {
+        let inner = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            if let Ok(event) = result {
+                for change in changes(event) {
+                    sender.send(Event::Watched(change)).ok();
+                }
+            }
+        })
+        .ok();
+        Self {
+            inner,
+            dirs: HashSet::new(),
+        }
+    }
+
+    /// Starts watching the directory of `file`, unless it's watched already.
+    pub fn watch(&mut self, file: &str) {
+        let Some(dir) =
+            resolve(Path::new(file)).and_then(|path| Some(path.parent()?.to_path_buf()))
+        else {
+            return;
+        };
+        let Some(inner) = self.inner.as_mut() else {
+            return;
+        };
+        if !self.dirs.contains(&dir)
+            && notify::Watcher::watch(inner, &dir, RecursiveMode::NonRecursive).is_ok()
+        {
+            self.dirs.insert(dir);
+        }
+    }
+}
+
+/// The absolute path of `file` with the symlinks of its directory resolved, the
+/// way the watcher names it, whether the file exists or not.
+pub fn resolve(file: &Path) -> Option<PathBuf> {
+    let name = file.file_name()?;
+    let dir = match file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => dir.canonicalize().ok()?,
+        None => env::current_dir().ok()?,
+    };
+    Some(dir.join(name))
+}
+
+/// What a notify event means for the files it names. Renames come as one event
+/// with both paths where the system can tell, and otherwise as a removal and a
+/// creation.
+fn changes(event: notify::Event) -> Vec<Change> {
+    let mut paths = event.paths.into_iter();
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(Change::Created).collect()
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(Change::Removed).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            match (paths.next(), paths.next()) {
+                (Some(from), Some(to)) => vec![Change::Renamed { from, to }],
+                _ => Vec::new(),
+            }
+        }
+        EventKind::Modify(ModifyKind::Metadata(_)) | EventKind::Access(_) => Vec::new(),
+        EventKind::Modify(_) => paths.map(Change::Modified).collect(),
+        _ => Vec::new(),
+    }
+}
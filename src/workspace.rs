@@ -0,0 +1,302 @@
+//! The files of the project being edited, for everything that looks across files
+//! rather than at a single one. Files matched by `.gitignore` or `.ignore` rules,
+//! hidden files and binary files are left out, and the list is kept between scans
+//! until a directory or ignore file in it changes.
+use std::{
+    env,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::hooks;
+
+/// The files that hold ignore rules, read in every directory.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// The files or directories that mark the root of a project.
+const PROJECT_MARKERS: [&str; 2] = [".git", "Cargo.toml"];
+
+/// How much of a file is checked for NUL bytes to tell whether it's binary.
+const BINARY_CHECK: u64 = 8000;
+
+/// A line of an ignore file.
+///
+/// # Fields
+///
+/// - `base`: The directory of the ignore file, relative to the root.
+/// - `pattern`: The glob, without the `!`, leading `/` and trailing `/` markers.
+/// - `anchored`: Whether the glob matches the path below `base` rather than any file name.
+/// - `negated`: Whether matching files are included again.
+/// - `dir_only`: Whether the rule only applies to directories.
+struct Rule {
+    base: PathBuf,
+    pattern: String,
+    anchored: bool,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    fn parse(line: &str, base: &Path) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+
+        (!pattern.is_empty()).then(|| Self {
+            base: base.to_path_buf(),
+            pattern: pattern.to_string(),
+            anchored,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(path) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+
+        let path = path.to_string_lossy().replace('\\', "/");
+        if self.anchored {
+            hooks::matches_path(&self.pattern, &path)
+        } else {
+            hooks::matches(&self.pattern, &path)
+        }
+    }
+}
+
+/// What a scan found, and the modification times that tell when it's out of date.
+struct Scan {
+    files: Vec<PathBuf>,
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Scan {
+    fn is_stale(&self) -> bool {
+        self.stamps
+            .iter()
+            .any(|(path, modified)| modified_time(path) != *modified)
+    }
+}
+
+/// The project files under a root directory.
+pub struct Workspace {
+    root: PathBuf,
+    scan: Option<Scan>,
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            scan: None,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The text files of the project, relative to the root and sorted. The last scan
+    /// is reused unless files were added, removed or renamed, or ignore rules changed.
+    pub fn files(&mut self) -> &[PathBuf] {
+        if self.scan.as_ref().is_none_or(Scan::is_stale) {
+            let mut scan = Scan {
+                files: Vec::new(),
+                stamps: Vec::new(),
+            };
+            walk(&self.root, Path::new(""), &mut Vec::new(), &mut scan);
+            self.scan = Some(scan);
+        }
+
+        self.scan.as_ref().map_or(&[], |scan| scan.files.as_slice())
+    }
+
+    /// Forgets the last scan, so the next one reads the directories again.
+    pub fn invalidate(&mut self) {
+        self.scan = None;
+    }
+}
+
+/// The nearest directory holding `file`, or the working directory when there's no
+/// file, that looks like the root of a project.
+pub fn project_root(file: Option<&str>) -> Option<PathBuf> {
+    let start = env::current_dir().ok()?;
+    let start = match file {
+        Some(file) => start.join(file).parent()?.to_path_buf(),
+        None => start,
+    };
+
+    start
+        .ancestors()
+        .find(|dir| {
+            PROJECT_MARKERS
+                .iter()
+                .any(|marker| dir.join(marker).exists())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Collects the files of `dir`, a path relative to `root`, and of the directories in
+/// it, applying the ignore rules of the directories above it and its own.
+fn walk(root: &Path, dir: &Path, rules: &mut Vec<Rule>, scan: &mut Scan) {
+    let full = root.join(dir);
+    scan.stamps.push((full.clone(), modified_time(&full)));
+
+    let inherited = rules.len();
+    for name in IGNORE_FILES {
+        let path = full.join(name);
+        if let Ok(text) = fs::read_to_string(&path) {
+            rules.extend(text.lines().filter_map(|line| Rule::parse(line, dir)));
+            scan.stamps.push((path.clone(), modified_time(&path)));
+        }
+    }
+
+    let Ok(entries) = fs::read_dir(&full) else {
+        rules.truncate(inherited);
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = dir.join(entry.file_name());
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if is_ignored(rules, &path, kind.is_dir()) {
+            continue;
+        }
+
+        if kind.is_dir() {
+            walk(root, &path, rules, scan);
+        } else if kind.is_file() && !is_binary(&root.join(&path)) {
+            scan.files.push(path);
+        }
+    }
+
+    rules.truncate(inherited);
+}
+
+/// Whether the last rule matching the path ignores it.
+fn is_ignored(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+fn is_binary(path: &Path) -> bool {
+    let mut start = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(BINARY_CHECK).read_to_end(&mut start))
+        .is_ok_and(|_| start.contains(&0))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(lines: &[&str]) -> Vec<Rule> {
+        lines
+            .iter()
+            .filter_map(|line| Rule::parse(line, Path::new("")))
+            .collect()
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        assert!(rules(&["", "# target", "   "]).is_empty());
+        let escaped = rules(&["\\#notes"]);
+        assert_eq!(
+            escaped.first().map(|rule| rule.pattern.as_str()),
+            Some("#notes")
+        );
+    }
+
+    #[test]
+    fn negation_includes_files_again() {
+        let rules = rules(&["*.log", "!keep.log"]);
+        assert!(is_ignored(&rules, Path::new("debug.log"), false));
+        assert!(!is_ignored(&rules, Path::new("keep.log"), false));
+        assert!(!is_ignored(&rules, Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn anchored_patterns_match_below_their_directory() {
+        let rules = rules(&["/build", "docs/*.html"]);
+        assert!(is_ignored(&rules, Path::new("build"), true));
+        assert!(!is_ignored(&rules, Path::new("src/build"), true));
+        assert!(is_ignored(&rules, Path::new("docs/index.html"), false));
+        assert!(!is_ignored(&rules, Path::new("docs/api/index.html"), false));
+    }
+
+    #[test]
+    fn unanchored_patterns_match_names_anywhere() {
+        let rules = rules(&["target"]);
+        assert!(is_ignored(&rules, Path::new("target"), true));
+        assert!(is_ignored(&rules, Path::new("core/target"), true));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let rules = rules(&["out/"]);
+        assert!(rules
+            .first()
+            .is_some_and(|rule| rule.dir_only && !rule.anchored));
+        assert!(is_ignored(&rules, Path::new("out"), true));
+        assert!(!is_ignored(&rules, Path::new("out"), false));
+    }
+
+    #[test]
+    fn double_star_matches_any_depth() {
+        let rules = rules(&["**/generated/*.rs", "logs/**"]);
+        assert!(is_ignored(&rules, Path::new("generated/a.rs"), false));
+        assert!(is_ignored(
+            &rules,
+            Path::new("src/deep/generated/a.rs"),
+            false
+        ));
+        assert!(is_ignored(
+            &rules,
+            Path::new("logs/2024/01/today.txt"),
+            false
+        ));
+        assert!(!is_ignored(&rules, Path::new("src/logs.rs"), false));
+    }
+
+    #[test]
+    fn rules_only_apply_below_their_ignore_file() {
+        let rules: Vec<Rule> = Rule::parse("*.tmp", Path::new("sub")).into_iter().collect();
+        assert!(is_ignored(&rules, Path::new("sub/a.tmp"), false));
+        assert!(!is_ignored(&rules, Path::new("a.tmp"), false));
+    }
+}
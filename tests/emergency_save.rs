@@ -0,0 +1,41 @@
+//! The emergency save when the editor is asked to terminate, such as when its SSH
+//! connection drops. Raising the flag affects the whole process, so this runs in a
+//! test binary of its own.
+//!
+//! Only unix has the signals that ask the editor to terminate, and recovered buffers
+//! are named after unix paths.
+#![cfg(unix)]
+use std::{env, fs, process};
+
+use text_editor::{cli::Args, headless, signals, Editor};
+
+#[test]
+fn saves_modified_buffers_when_terminating() {
+    let dir = env::temp_dir().join(format!("voider-{}-terminating", process::id()));
+    let state = dir.join("state");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("notes.txt");
+    fs::write(&path, "first line\n").unwrap();
+    headless::start(80, 24, headless::keys("unsaved "));
+    headless::use_state_dir(&state);
+
+    let mut editor = Editor::new(Args {
+        files: vec![path.to_string_lossy().into_owned()],
+        ..Args::default()
+    });
+    editor.run_script().unwrap();
+    signals::terminate();
+    editor.run();
+
+    let name = fs::canonicalize(&path)
+        .unwrap()
+        .display()
+        .to_string()
+        .replace('/', "%");
+    assert_eq!(
+        fs::read_to_string(state.join("recovery").join(name)).unwrap(),
+        "unsaved first line\n"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first line\n");
+    fs::remove_dir_all(dir).ok();
+}
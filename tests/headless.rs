@@ -0,0 +1,188 @@
+//! Scenarios that drive the editor with scripted keys and check what it drew and
+//! wrote, without a terminal.
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use text_editor::{cli::Args, headless, Editor};
+
+/// A file in the temporary directory holding `text`, named after the test so
+/// tests running at the same time don't share one.
+fn file(name: &str, text: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("voider-{}-{name}.txt", process::id()));
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn open(path: &Path) -> Editor {
+    Editor::new(Args {
+        files: vec![path.to_string_lossy().into_owned()],
+        ..Args::default()
+    })
+}
+
+#[test]
+fn types_searches_and_saves() {
+    let path = file("save", "first line\nsecond line\n");
+    let mut keys = headless::keys("Hello, ");
+    keys.push(KeyEvent::from(KeyCode::F(3)));
+    keys.extend(headless::keys("second\n"));
+    keys.extend(headless::keys("the "));
+    keys.push(KeyEvent::from(KeyCode::F(5)));
+    headless::start(80, 24, keys);
+
+    open(&path).run_script().unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "Hello, first line\nthe second line\n"
+    );
+    assert!(headless::screen().line(1).contains("the second line"));
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn leaves_the_user_config_and_state_alone() {
+    let home = env::temp_dir().join(format!("voider-{}-home", process::id()));
+    fs::create_dir_all(home.join("voider")).unwrap();
+    fs::write(
+        home.join("voider").join("config.toml"),
+        "[editor]\nline_numbers = true\n",
+    )
+    .unwrap();
+    env::set_var("XDG_CONFIG_HOME", &home);
+    env::set_var("XDG_STATE_HOME", &home);
+
+    let path = file("defaults", "some text\n");
+    let mut keys = vec![KeyEvent::from(KeyCode::F(3))];
+    keys.extend(headless::keys("text\n"));
+    headless::start(80, 24, keys);
+
+    open(&path).run_script().unwrap();
+
+    // The rows stay without numbers, and the search isn't remembered for real sessions
+    assert_eq!(headless::screen().line(0).trim_start(), "some text");
+    assert!(!home.join("voider").join("state.toml").exists());
+    fs::remove_file(path).ok();
+    fs::remove_dir_all(home).ok();
+}
+
+#[test]
+fn continues_and_ends_markdown_lists() {
+    let path = env::temp_dir().join(format!("voider-{}-list.md", process::id()));
+    fs::write(&path, "").unwrap();
+    let mut keys = headless::keys("- [x] milk\neggs\n\n9. nine\nten\n\nbread");
+    keys.push(KeyEvent::from(KeyCode::F(5)));
+    headless::start(80, 24, keys);
+
+    open(&path).run_script().unwrap();
+
+    // Items are continued with an unchecked box or the next number, and Enter on an
+    // empty one ends the list
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "- [x] milk\n- [ ] eggs\n9. nine\n10. ten\nbread\n"
+    );
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn records_and_plays_macros() {
+    let path = file("macro", "one\ntwo\nthree\n");
+    let record = KeyEvent::new(KeyCode::Char('Q'), KeyModifiers::ALT | KeyModifiers::SHIFT);
+    let play = KeyEvent::new(KeyCode::Char('M'), KeyModifiers::ALT | KeyModifiers::SHIFT);
+    let mut keys = vec![record];
+    keys.extend(headless::keys("- "));
+    keys.push(KeyEvent::from(KeyCode::Home));
+    keys.push(KeyEvent::from(KeyCode::Down));
+    headless::start(80, 24, keys);
+
+    let mut editor = open(&path);
+    editor.run_script().unwrap();
+    // The status bar shows that a macro is being recorded
+    assert!(headless::screen().line(22).contains("REC"));
+
+    headless::press([record, play, play, KeyEvent::from(KeyCode::F(5))]);
+    editor.run_script().unwrap();
+
+    assert!(!headless::screen().line(22).contains("REC"));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "- one\n- two\n- three\n"
+    );
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn undoes_a_typed_run_in_one_step() {
+    let path = file("typing", "first\n");
+    let undo = KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    let save = KeyEvent::from(KeyCode::F(5));
+    let mut keys = headless::keys("ab");
+    // Moving the cursor ends the run
+    keys.push(KeyEvent::from(KeyCode::End));
+    keys.extend(headless::keys("cd"));
+    headless::start(80, 24, keys);
+
+    let mut editor = open(&path);
+    editor.run_script().unwrap();
+    // So does a pause in typing
+    thread::sleep(Duration::from_millis(1100));
+    headless::press(headless::keys("ef"));
+    editor.run_script().unwrap();
+
+    headless::press([undo, save]);
+    editor.run_script().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "abfirstcd\n");
+
+    headless::press([undo, save]);
+    editor.run_script().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "abfirst\n");
+
+    headless::press([undo, save]);
+    editor.run_script().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first\n");
+    fs::remove_file(path).ok();
+}
+
+// Recovered buffers are named after unix paths
+#[cfg(unix)]
+#[test]
+fn restores_recovered_buffers_as_unsaved_changes() {
+    let path = file("recovered", "saved text\n");
+    let state = env::temp_dir().join(format!("voider-{}-recovery-state", process::id()));
+    let recovery = state.join("recovery");
+    fs::create_dir_all(&recovery).unwrap();
+    let name = |path: &Path| {
+        fs::canonicalize(path)
+            .unwrap()
+            .display()
+            .to_string()
+            .replace('/', "%")
+    };
+    fs::write(recovery.join(name(&path)), "recovered text\n").unwrap();
+    // A directory can't be opened, so what was recovered for it is kept for later
+    let directory = state.join("directory");
+    fs::create_dir_all(&directory).unwrap();
+    let kept = recovery.join(name(&directory));
+    fs::write(&kept, "lost text\n").unwrap();
+
+    // Both are restored, then quitting asks to save the buffer, which is modified
+    let mut keys = vec![KeyEvent::from(KeyCode::Enter); 2];
+    keys.push(KeyEvent::from(KeyCode::F(8)));
+    keys.extend(headless::keys("y"));
+    headless::start(80, 24, keys);
+    headless::use_state_dir(&state);
+
+    open(&path).run();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "recovered text\n");
+    assert!(!recovery.join(name(&path)).exists());
+    assert_eq!(fs::read_to_string(kept).unwrap(), "lost text\n");
+    fs::remove_file(path).ok();
+    fs::remove_dir_all(state).ok();
+}